@@ -0,0 +1,397 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A minimal MQTT 3.1.1 broker for systems under test that consume events.
+//!
+//! Clients can connect, subscribe and publish as with a real broker (all deliveries are
+//! QoS 0). On top of that, the `broker.topics` rules publish templated messages on a
+//! schedule or in reaction to client publishes, rendered with the same template engine
+//! as HTTP responses.
+
+pub mod packet;
+
+use crate::config::{BrokerConfig, TopicRule};
+use crate::rules::executor::ResponseExecutor;
+use crate::rules::state::StateManager;
+use crate::rules::ExecutionContext;
+use packet::Packet;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+struct Subscriber {
+    filters: Vec<String>,
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+#[derive(Clone)]
+pub struct Broker {
+    rules: Arc<Vec<TopicRule>>,
+    subscribers: Arc<Mutex<HashMap<u64, Subscriber>>>,
+    next_client: Arc<AtomicU64>,
+    templates: ResponseExecutor,
+}
+
+impl Broker {
+    pub fn new(config: &BrokerConfig) -> Self {
+        Self {
+            rules: Arc::new(config.topics.clone()),
+            subscribers: Arc::new(Mutex::new(HashMap::new())),
+            next_client: Arc::new(AtomicU64::new(0)),
+            templates: ResponseExecutor::new(Arc::new(StateManager::new())),
+        }
+    }
+
+    /// Binds the listener and starts serving clients and scheduled topics in the background.
+    pub async fn start(config: &BrokerConfig) -> anyhow::Result<Self> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let listener = TcpListener::bind(&addr).await?;
+        info!("Starting MQTT broker mock on {}", addr);
+
+        let broker = Self::new(config);
+        broker.spawn_schedules();
+
+        let accepting = broker.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let broker = accepting.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = broker.serve(stream, peer.ip().to_string()).await {
+                                debug!(error = %e, "MQTT client connection ended");
+                            }
+                        });
+                    }
+                    Err(e) => warn!(error = %e, "Failed to accept MQTT connection"),
+                }
+            }
+        });
+
+        Ok(broker)
+    }
+
+    fn spawn_schedules(&self) {
+        for rule in self.rules.iter() {
+            let Some(every) = &rule.every else { continue };
+            let interval = match crate::config::types::Delay::Fixed(every.clone()).parse_duration()
+            {
+                Ok(interval) if !interval.is_zero() => interval,
+                _ => {
+                    warn!(topic = %rule.topic, every = %every, "Skipping invalid broker schedule");
+                    continue;
+                }
+            };
+
+            let broker = self.clone();
+            let rule = rule.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let message = broker.render(&rule, None);
+                    broker.publish(&rule.topic, message.as_bytes());
+                }
+            });
+        }
+    }
+
+    /// Delivers a message to every client subscribed to a matching filter.
+    pub fn publish(&self, topic: &str, payload: &[u8]) {
+        let bytes = packet::publish(topic, payload);
+        let subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        for subscriber in subscribers.values() {
+            if subscriber
+                .filters
+                .iter()
+                .any(|filter| packet::topic_matches(filter, topic))
+            {
+                let _ = subscriber.sender.send(bytes.clone());
+            }
+        }
+        debug!(topic = %topic, "Published broker message");
+    }
+
+    fn render(&self, rule: &TopicRule, trigger: Option<(&str, &[u8])>) -> String {
+        let mut path_params = HashMap::new();
+        if let Some((topic, payload)) = trigger {
+            path_params.insert("topic".to_string(), topic.to_string());
+            path_params.insert(
+                "payload".to_string(),
+                String::from_utf8_lossy(payload).into_owned(),
+            );
+        }
+
+        let context = ExecutionContext {
+            method: "PUBLISH".to_string(),
+            path: rule.topic.clone(),
+            query: String::new(),
+            headers: HashMap::new(),
+            client_ip: String::new(),
+            path_params,
+            body: None,
+        };
+        self.templates.render_template(&rule.message, &context, 0)
+    }
+
+    fn on_client_publish(&self, topic: &str, payload: &[u8]) {
+        self.publish(topic, payload);
+
+        for rule in self.rules.iter() {
+            if let Some(filter) = &rule.on_publish {
+                if packet::topic_matches(filter, topic) {
+                    let message = self.render(rule, Some((topic, payload)));
+                    self.publish(&rule.topic, message.as_bytes());
+                }
+            }
+        }
+    }
+
+    async fn serve(&self, stream: TcpStream, peer: String) -> anyhow::Result<()> {
+        let (mut reader, mut writer) = stream.into_split();
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Vec<u8>>();
+
+        let client_id = self.next_client.fetch_add(1, Ordering::Relaxed);
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                client_id,
+                Subscriber {
+                    filters: Vec::new(),
+                    sender: sender.clone(),
+                },
+            );
+        debug!(client = client_id, peer = %peer, "MQTT client connected");
+
+        tokio::spawn(async move {
+            while let Some(bytes) = receiver.recv().await {
+                if writer.write_all(&bytes).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let result = self.client_loop(client_id, &mut reader, &sender).await;
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&client_id);
+        result
+    }
+
+    async fn client_loop(
+        &self,
+        client_id: u64,
+        reader: &mut tokio::net::tcp::OwnedReadHalf,
+        sender: &mpsc::UnboundedSender<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        while let Some(packet) = packet::read_packet(reader).await? {
+            match packet {
+                Packet::Connect => sender.send(packet::connack())?,
+                Packet::Publish {
+                    topic,
+                    packet_id,
+                    qos,
+                    payload,
+                } => {
+                    match (qos, packet_id) {
+                        (1, Some(id)) => sender.send(packet::puback(id))?,
+                        (2, Some(id)) => sender.send(packet::pubrec(id))?,
+                        _ => {}
+                    }
+                    self.on_client_publish(&topic, &payload);
+                }
+                Packet::PubRel { packet_id } => sender.send(packet::pubcomp(packet_id))?,
+                Packet::Subscribe { packet_id, filters } => {
+                    let count = filters.len();
+                    if let Some(subscriber) = self
+                        .subscribers
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .get_mut(&client_id)
+                    {
+                        subscriber.filters.extend(filters);
+                    }
+                    sender.send(packet::suback(packet_id, count))?;
+                }
+                Packet::Unsubscribe { packet_id, filters } => {
+                    if let Some(subscriber) = self
+                        .subscribers
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .get_mut(&client_id)
+                    {
+                        subscriber
+                            .filters
+                            .retain(|filter| !filters.contains(filter));
+                    }
+                    sender.send(packet::unsuback(packet_id))?;
+                }
+                Packet::PingReq => sender.send(packet::pingresp())?,
+                Packet::Disconnect => break,
+                Packet::Other => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn connect_packet() -> Vec<u8> {
+        // Protocol name, level 4, clean session, keep-alive 60, empty client id.
+        let body = [
+            0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3c, 0x00, 0x00,
+        ];
+        let mut bytes = vec![0x10, body.len() as u8];
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    fn subscribe_packet(filter: &str) -> Vec<u8> {
+        let mut body = vec![0x00, 0x01];
+        body.extend_from_slice(&(filter.len() as u16).to_be_bytes());
+        body.extend_from_slice(filter.as_bytes());
+        body.push(0x00);
+        let mut bytes = vec![0x82, body.len() as u8];
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    fn unsubscribe_packet(filter: &str) -> Vec<u8> {
+        let mut body = vec![0x00, 0x02];
+        body.extend_from_slice(&(filter.len() as u16).to_be_bytes());
+        body.extend_from_slice(filter.as_bytes());
+        let mut bytes = vec![0xa2, body.len() as u8];
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_on_publish_rule() {
+        let config = BrokerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            topics: vec![TopicRule {
+                topic: "orders/ack".to_string(),
+                message: r#"{"topic": "{{topic}}", "echo": {{payload}}}"#.to_string(),
+                every: None,
+                on_publish: Some("orders/+".to_string()),
+            }],
+        };
+        let broker = Broker::new(&config);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let serving = broker.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let broker = serving.clone();
+                tokio::spawn(async move { broker.serve(stream, "test".to_string()).await });
+            }
+        });
+
+        let mut subscriber = TcpStream::connect(addr).await.unwrap();
+        subscriber.write_all(&connect_packet()).await.unwrap();
+        // CONNACK is not a packet the broker decodes for itself.
+        assert_eq!(
+            packet::read_packet(&mut subscriber).await.unwrap(),
+            Some(Packet::Other)
+        );
+        subscriber
+            .write_all(&subscribe_packet("orders/ack"))
+            .await
+            .unwrap();
+        let mut suback = [0u8; 5];
+        subscriber.read_exact(&mut suback).await.unwrap();
+        assert_eq!(suback, [0x90, 0x03, 0x00, 0x01, 0x00]);
+
+        let mut publisher = TcpStream::connect(addr).await.unwrap();
+        publisher.write_all(&connect_packet()).await.unwrap();
+        publisher
+            .write_all(&packet::publish("orders/new", b"42"))
+            .await
+            .unwrap();
+
+        let delivered = packet::read_packet(&mut subscriber).await.unwrap().unwrap();
+        assert_eq!(
+            delivered,
+            Packet::Publish {
+                topic: "orders/ack".to_string(),
+                packet_id: None,
+                qos: 0,
+                payload: br#"{"topic": "orders/new", "echo": 42}"#.to_vec(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_stops_delivery() {
+        let broker = Broker::new(&BrokerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            topics: vec![],
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = broker.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serving.serve(stream, "test".to_string()).await
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(&connect_packet()).await.unwrap();
+        packet::read_packet(&mut client).await.unwrap();
+        for filter in ["news/a", "news/b"] {
+            client.write_all(&subscribe_packet(filter)).await.unwrap();
+            let mut suback = [0u8; 5];
+            client.read_exact(&mut suback).await.unwrap();
+        }
+        client
+            .write_all(&unsubscribe_packet("news/a"))
+            .await
+            .unwrap();
+        let mut unsuback = [0u8; 4];
+        client.read_exact(&mut unsuback).await.unwrap();
+        assert_eq!(unsuback, [0xb0, 0x02, 0x00, 0x02]);
+
+        broker.publish("news/a", b"dropped");
+        broker.publish("news/b", b"kept");
+
+        let delivered = packet::read_packet(&mut client).await.unwrap().unwrap();
+        assert_eq!(
+            delivered,
+            Packet::Publish {
+                topic: "news/b".to_string(),
+                packet_id: None,
+                qos: 0,
+                payload: b"kept".to_vec(),
+            }
+        );
+    }
+}
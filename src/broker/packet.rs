@@ -0,0 +1,299 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The subset of MQTT 3.1.1 packets the broker mock speaks.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    Connect,
+    Publish {
+        topic: String,
+        packet_id: Option<u16>,
+        qos: u8,
+        payload: Vec<u8>,
+    },
+    PubRel {
+        packet_id: u16,
+    },
+    Subscribe {
+        packet_id: u16,
+        filters: Vec<String>,
+    },
+    Unsubscribe {
+        packet_id: u16,
+        filters: Vec<String>,
+    },
+    PingReq,
+    Disconnect,
+    /// Any packet the mock does not act on, such as PUBACK from a client.
+    Other,
+}
+
+/// Reads one packet, returning `None` when the connection was closed cleanly.
+pub async fn read_packet<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Option<Packet>> {
+    let header = match reader.read_u8().await {
+        Ok(header) => header,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut length = 0usize;
+    for shift in 0..4 {
+        let byte = reader.read_u8().await?;
+        length |= ((byte & 0x7f) as usize) << (7 * shift);
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if shift == 3 {
+            anyhow::bail!("Malformed remaining length");
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+
+    decode(header, &body).map(Some)
+}
+
+fn decode(header: u8, body: &[u8]) -> anyhow::Result<Packet> {
+    let mut cursor = Cursor { data: body, pos: 0 };
+
+    let packet = match header >> 4 {
+        1 => Packet::Connect,
+        3 => {
+            let qos = (header >> 1) & 0x03;
+            let topic = cursor.string()?;
+            let packet_id = if qos > 0 { Some(cursor.u16()?) } else { None };
+            Packet::Publish {
+                topic,
+                packet_id,
+                qos,
+                payload: cursor.rest().to_vec(),
+            }
+        }
+        6 => Packet::PubRel {
+            packet_id: cursor.u16()?,
+        },
+        8 => {
+            let packet_id = cursor.u16()?;
+            let mut filters = Vec::new();
+            while !cursor.rest().is_empty() {
+                filters.push(cursor.string()?);
+                cursor.u8()?; // requested QoS, always granted as 0
+            }
+            Packet::Subscribe { packet_id, filters }
+        }
+        10 => {
+            let packet_id = cursor.u16()?;
+            let mut filters = Vec::new();
+            while !cursor.rest().is_empty() {
+                filters.push(cursor.string()?);
+            }
+            Packet::Unsubscribe { packet_id, filters }
+        }
+        12 => Packet::PingReq,
+        14 => Packet::Disconnect,
+        _ => Packet::Other,
+    };
+
+    Ok(packet)
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| anyhow::anyhow!("Truncated packet"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let length = self.u16()? as usize;
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + length)
+            .ok_or_else(|| anyhow::anyhow!("Truncated packet"))?;
+        self.pos += length;
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    fn rest(&self) -> &[u8] {
+        &self.data[self.pos..]
+    }
+}
+
+fn frame(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+pub fn connack() -> Vec<u8> {
+    frame(0x20, &[0x00, 0x00])
+}
+
+/// A QoS 0 PUBLISH, which is how the broker delivers every message.
+pub fn publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+    body.extend_from_slice(topic.as_bytes());
+    body.extend_from_slice(payload);
+    frame(0x30, &body)
+}
+
+pub fn puback(packet_id: u16) -> Vec<u8> {
+    frame(0x40, &packet_id.to_be_bytes())
+}
+
+pub fn pubrec(packet_id: u16) -> Vec<u8> {
+    frame(0x50, &packet_id.to_be_bytes())
+}
+
+pub fn pubcomp(packet_id: u16) -> Vec<u8> {
+    frame(0x70, &packet_id.to_be_bytes())
+}
+
+pub fn suback(packet_id: u16, count: usize) -> Vec<u8> {
+    let mut body = packet_id.to_be_bytes().to_vec();
+    body.extend(std::iter::repeat_n(0x00, count));
+    frame(0x90, &body)
+}
+
+pub fn unsuback(packet_id: u16) -> Vec<u8> {
+    frame(0xb0, &packet_id.to_be_bytes())
+}
+
+pub fn pingresp() -> Vec<u8> {
+    frame(0xd0, &[])
+}
+
+/// Matches a topic name against a subscription filter with `+` and `#` wildcards.
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("orders/created", "orders/created"));
+        assert!(topic_matches("orders/+", "orders/created"));
+        assert!(topic_matches("orders/#", "orders/eu/created"));
+        assert!(topic_matches("#", "anything"));
+        assert!(!topic_matches("orders/+", "orders/eu/created"));
+        assert!(!topic_matches("orders/created", "orders"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_round_trip() {
+        let payload = vec![b'x'; 300];
+        let bytes = publish("a/b", &payload);
+        assert_eq!(bytes[1], 0xb1); // 305 encoded over two length bytes
+
+        let packet = read_packet(&mut bytes.as_slice()).await.unwrap().unwrap();
+        assert_eq!(
+            packet,
+            Packet::Publish {
+                topic: "a/b".to_string(),
+                packet_id: None,
+                qos: 0,
+                payload,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_subscribe() {
+        let mut body = vec![0x00, 0x07];
+        for filter in ["a/+", "b/#"] {
+            body.extend_from_slice(&(filter.len() as u16).to_be_bytes());
+            body.extend_from_slice(filter.as_bytes());
+            body.push(0x01);
+        }
+        let bytes = frame(0x82, &body);
+
+        let packet = read_packet(&mut bytes.as_slice()).await.unwrap().unwrap();
+        assert_eq!(
+            packet,
+            Packet::Subscribe {
+                packet_id: 7,
+                filters: vec!["a/+".to_string(), "b/#".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_unsubscribe() {
+        let mut body = vec![0x00, 0x09];
+        for filter in ["a/+", "b/#"] {
+            body.extend_from_slice(&(filter.len() as u16).to_be_bytes());
+            body.extend_from_slice(filter.as_bytes());
+        }
+        let bytes = frame(0xa2, &body);
+
+        let packet = read_packet(&mut bytes.as_slice()).await.unwrap().unwrap();
+        assert_eq!(
+            packet,
+            Packet::Unsubscribe {
+                packet_id: 9,
+                filters: vec!["a/+".to_string(), "b/#".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_packet_eof() {
+        let mut empty: &[u8] = &[];
+        assert!(read_packet(&mut empty).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,289 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Typed client for the `/__admin` API, for test harnesses that drive a running
+//! Molock instance from other repositories.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use molock::client::MolockClient;
+//! use molock::rules::inbox::InboxQuery;
+//!
+//! let client = MolockClient::new("http://localhost:8080");
+//! let received = client.inbox("payments", &InboxQuery::default()).await?;
+//! client.release_gate("orders", None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::rules::failpoints::FailpointSpec;
+use crate::rules::inbox::{InboxQuery, InboxRequest};
+use crate::server::openapi::{
+    FailpointsResponse, GateReleaseResponse, HealthResponse, InboxVerification, UsageResponse,
+};
+use crate::smtp::{CapturedEmail, EmailQuery};
+use anyhow::Context;
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, Clone)]
+pub struct MolockClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl MolockClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_client(base_url, reqwest::Client::new())
+    }
+
+    /// Uses a preconfigured `reqwest` client, e.g. one with timeouts or proxies.
+    pub fn with_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http,
+        }
+    }
+
+    pub async fn health(&self) -> anyhow::Result<HealthResponse> {
+        self.json(self.request(Method::GET, "/health")).await
+    }
+
+    pub async fn usage(&self) -> anyhow::Result<UsageResponse> {
+        self.json(self.request(Method::GET, "/__admin/usage")).await
+    }
+
+    /// The effective configuration of the instance, with secrets masked.
+    pub async fn config(&self) -> anyhow::Result<serde_json::Value> {
+        self.json(self.request(Method::GET, "/__admin/config"))
+            .await
+    }
+
+    pub async fn emails(&self, query: &EmailQuery) -> anyhow::Result<Vec<CapturedEmail>> {
+        self.json(self.request(Method::GET, "/__admin/emails").query(query))
+            .await
+    }
+
+    pub async fn clear_emails(&self) -> anyhow::Result<()> {
+        self.send(self.request(Method::DELETE, "/__admin/emails"))
+            .await
+            .map(drop)
+    }
+
+    pub async fn inbox(&self, name: &str, query: &InboxQuery) -> anyhow::Result<Vec<InboxRequest>> {
+        let path = format!("/__admin/inbox/{}", name);
+        self.json(self.request(Method::GET, &path).query(query))
+            .await
+    }
+
+    pub async fn clear_inbox(&self, name: &str) -> anyhow::Result<()> {
+        let path = format!("/__admin/inbox/{}", name);
+        self.send(self.request(Method::DELETE, &path))
+            .await
+            .map(drop)
+    }
+
+    /// Checks that the inbox received exactly `count` matching requests (at least one
+    /// when `None`). A failed expectation is reported in the result, not as an error.
+    pub async fn verify_inbox(
+        &self,
+        name: &str,
+        query: &InboxQuery,
+        count: Option<usize>,
+    ) -> anyhow::Result<InboxVerification> {
+        let path = format!("/__admin/inbox/{}/verify", name);
+        let mut request = self.request(Method::GET, &path).query(query);
+        if let Some(count) = count {
+            request = request.query(&[("count", count)]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", path))?;
+        if response.status() == StatusCode::EXPECTATION_FAILED {
+            return Ok(response.json().await?);
+        }
+        Self::decode(Self::check(response).await?).await
+    }
+
+    /// Releases `count` requests held by a gate, or all of them when `None`.
+    pub async fn release_gate(
+        &self,
+        name: &str,
+        count: Option<usize>,
+    ) -> anyhow::Result<GateReleaseResponse> {
+        let path = format!("/__admin/gates/{}/release", name);
+        let mut request = self.request(Method::POST, &path);
+        if let Some(count) = count {
+            request = request.query(&[("count", count)]);
+        }
+        self.json(request).await
+    }
+
+    pub async fn failpoints(&self) -> anyhow::Result<FailpointsResponse> {
+        self.json(self.request(Method::GET, "/__admin/failpoints"))
+            .await
+    }
+
+    /// Makes a Molock subsystem fail as `spec` says, e.g. `state_store`.
+    pub async fn arm_failpoint(&self, name: &str, spec: &FailpointSpec) -> anyhow::Result<()> {
+        let path = format!("/__admin/failpoints/{}", name);
+        self.send(self.request(Method::PUT, &path).json(spec))
+            .await
+            .map(drop)
+    }
+
+    pub async fn disarm_failpoint(&self, name: &str) -> anyhow::Result<()> {
+        let path = format!("/__admin/failpoints/{}", name);
+        self.send(self.request(Method::DELETE, &path))
+            .await
+            .map(drop)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.base_url, path))
+    }
+
+    async fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let response = request
+            .send()
+            .await
+            .context("Failed to reach the Molock admin API")?;
+        Self::check(response).await
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder) -> anyhow::Result<T> {
+        Self::decode(self.send(request).await?).await
+    }
+
+    async fn check(response: Response) -> anyhow::Result<Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let url = response.url().clone();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} answered {}: {}", url, status, body)
+    }
+
+    async fn decode<T: DeserializeOwned>(response: Response) -> anyhow::Result<T> {
+        let url = response.url().clone();
+        response
+            .json()
+            .await
+            .with_context(|| format!("Unexpected response from {}", url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_release_gate() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/__admin/gates/orders/release"))
+            .and(query_param("count", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"released": 2, "held": 1})),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = MolockClient::new(format!("{}/", server.uri()));
+        let released = client.release_gate("orders", Some(2)).await.unwrap();
+        assert_eq!(
+            released,
+            GateReleaseResponse {
+                released: 2,
+                held: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_inbox_reports_failed_expectation() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/__admin/inbox/payments/verify"))
+            .and(query_param("method", "POST"))
+            .respond_with(ResponseTemplate::new(417).set_body_json(serde_json::json!({
+                "matched": false,
+                "expected": null,
+                "actual": 0
+            })))
+            .mount(&server)
+            .await;
+
+        let client = MolockClient::new(server.uri());
+        let query = InboxQuery {
+            method: Some("POST".to_string()),
+            ..Default::default()
+        };
+        let verification = client.verify_inbox("payments", &query, None).await.unwrap();
+        assert!(!verification.matched);
+        assert_eq!(verification.actual, 0);
+    }
+
+    #[tokio::test]
+    async fn test_arm_failpoint() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/__admin/failpoints/state_store"))
+            .and(wiremock::matchers::body_json(serde_json::json!({
+                "count": 2,
+                "message": null
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = MolockClient::new(server.uri());
+        let spec = FailpointSpec {
+            count: Some(2),
+            message: None,
+        };
+        client.arm_failpoint("state_store", &spec).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_errors_carry_status_and_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/__admin/inbox/payments"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .mount(&server)
+            .await;
+
+        let client = MolockClient::new(server.uri());
+        let error = client
+            .clear_inbox("payments")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("500"));
+        assert!(error.contains("boom"));
+    }
+}
@@ -15,30 +15,210 @@
  */
 
 use crate::config::types::Config;
+use crate::config::{merge, templates};
+use crate::rules::matcher::PATH_REGEX_PREFIX;
 use anyhow::Context;
 use serde_yaml;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct ConfigLoader;
 
 impl ConfigLoader {
     pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Config> {
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read config file: {:?}", path.as_ref()))?;
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {:?}", path))?;
+
+        let override_path = Self::override_path(path);
+        let overlay = match fs::read_to_string(&override_path) {
+            Ok(overlay) => Some(overlay),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read config override: {:?}", override_path)
+                })
+            }
+        };
+
+        let mut mounting = vec![path.canonicalize()?];
+        let parsed = Self::parse(
+            &content,
+            overlay.as_deref(),
+            path.parent().unwrap_or(Path::new("")),
+            &mut mounting,
+        );
+        if overlay.is_none() {
+            return parsed;
+        }
+        let mut config =
+            parsed.with_context(|| format!("Invalid config with override {:?}", override_path))?;
+        config.override_file = Some(override_path.display().to_string());
+        Ok(config)
+    }
 
-        Self::parse_str(&content)
+    /// The override file merged onto the config at `path` when it exists:
+    /// `molock-config.override.yaml` for `molock-config.yaml`.
+    pub fn override_path(path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path
+            .extension()
+            .map_or_else(|| "yaml".into(), |e| e.to_string_lossy());
+        path.with_file_name(format!("{}.override.{}", stem, extension))
     }
 
+    /// Parses a config, resolving `mounts` against the current directory.
     pub fn parse_str(content: &str) -> anyhow::Result<Config> {
-        let config: Config =
-            serde_yaml::from_str(content).with_context(|| "Failed to parse YAML configuration")?;
+        Self::parse(content, None, Path::new(""), &mut Vec::new())
+    }
+
+    /// `overlay` is an override file's content, merged onto `content` before anything
+    /// else. `mounting` holds the files being mounted around this one, to reject cycles;
+    /// it holds more than this file's own path when this file is mounted.
+    fn parse(
+        content: &str,
+        overlay: Option<&str>,
+        base: &Path,
+        mounting: &mut Vec<PathBuf>,
+    ) -> anyhow::Result<Config> {
+        let mut document: serde_yaml::Value =
+            serde_yaml::from_str(content).map_err(Self::parse_error)?;
+        let overridden = match overlay {
+            Some(overlay) => {
+                let overlay = serde_yaml::from_str(overlay).map_err(Self::parse_error)?;
+                merge::apply_override(&mut document, overlay)?;
+                true
+            }
+            None => false,
+        };
+        // Parsing the text directly keeps line numbers in errors, so the expanded
+        // document is only used when an override, templates or `defaults`/`extends`
+        // rewrote it.
+        let templated = templates::expand_templates(&mut document)?;
+        let extended = merge::resolve_extends(&mut document)?;
+        let mut config: Config = if overridden || templated || extended {
+            serde_yaml::from_value(document)
+        } else {
+            serde_yaml::from_str(content)
+        }
+        .map_err(Self::parse_error)?;
 
+        // The top-level config reads data files relative to the working directory,
+        // a mounted one relative to itself.
+        if mounting.len() > 1 {
+            Self::resolve_data_paths(&mut config, base);
+        }
         Self::validate(&config)?;
+        let mut hasher = Sha256::new();
+        hasher.update(content.as_bytes());
+        if let Some(overlay) = overlay {
+            hasher.update(overlay.as_bytes());
+        }
+
+        for mount in config.mounts.clone() {
+            let path = base.join(&mount.config);
+            let content = fs::read_to_string(&path).with_context(|| {
+                format!(
+                    "Failed to read config mounted at {}: {:?}",
+                    mount.prefix, path
+                )
+            })?;
+            let canonical = path.canonicalize()?;
+            if mounting.contains(&canonical) {
+                anyhow::bail!("Config {:?} is mounted inside itself", path);
+            }
+            mounting.push(canonical.clone());
+            let mut mounted = Self::parse(&content, None, path.parent().unwrap_or(base), mounting)
+                .with_context(|| format!("Invalid config mounted at {}", mount.prefix))?;
+            mounting.pop();
+            // The hash covers every mounted file, so editing one changes it.
+            hasher.update(mounted.sha256.take().unwrap_or_default().as_bytes());
+            config.mounted_files.push(canonical);
+            config.mounted_files.append(&mut mounted.mounted_files);
+            Self::mount(&mut config, &mount.prefix, mounted)?;
+        }
 
+        config.sha256 = Some(
+            hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        );
         Ok(config)
     }
 
+    /// Resolves the relative dataset and `csv_row` files of a mounted config's
+    /// endpoints against `base`, the directory of its file.
+    fn resolve_data_paths(config: &mut Config, base: &Path) {
+        for endpoint in &mut config.endpoints {
+            for path in endpoint.datasets.values_mut() {
+                *path = base.join(&*path).display().to_string();
+            }
+            for response in &mut endpoint.responses {
+                let templates = response
+                    .body
+                    .iter_mut()
+                    .chain(response.headers.values_mut());
+                for template in templates {
+                    *template = crate::rules::fixtures::resolve_csv_paths(template, base);
+                }
+            }
+        }
+    }
+
+    /// Adds the endpoints and latency profiles of `mounted` under `prefix`, with
+    /// names prefixed so they cannot collide with those of other files.
+    fn mount(config: &mut Config, prefix: &str, mounted: Config) -> anyhow::Result<()> {
+        let prefix = prefix.trim_end_matches('/');
+        if !prefix.starts_with('/') || prefix.contains([':', '*', '~']) {
+            anyhow::bail!(
+                "Invalid mount prefix `{}`: use a literal path such as /payments",
+                prefix
+            );
+        }
+
+        let name = |name: &str| format!("{} {}", prefix, name);
+        for (profile, latency) in mounted.latency_profiles {
+            config.latency_profiles.insert(name(&profile), latency);
+        }
+        for mut endpoint in mounted.endpoints {
+            endpoint.path = match endpoint.path.strip_prefix(PATH_REGEX_PREFIX) {
+                Some(regex) => match regex.strip_prefix('^') {
+                    Some(rest) => {
+                        format!("{}^{}{}", PATH_REGEX_PREFIX, regex::escape(prefix), rest)
+                    }
+                    None => anyhow::bail!(
+                        "Regex path `{}` must start with ^ to be mounted at {}",
+                        endpoint.path,
+                        prefix
+                    ),
+                },
+                None => match endpoint.path.trim_start_matches('/') {
+                    "" => prefix.to_string(),
+                    path => format!("{}/{}", prefix, path),
+                },
+            };
+            endpoint.name = name(&endpoint.name);
+            endpoint.delay_profile = endpoint.delay_profile.as_deref().map(name);
+            config.endpoints.push(endpoint);
+        }
+
+        Ok(())
+    }
+
+    fn parse_error(e: serde_yaml::Error) -> anyhow::Error {
+        let message = match Self::suggest_unknown_field(&e.to_string()) {
+            Some((field, suggestion)) => format!(
+                "Failed to parse YAML configuration: unknown field `{}`, did you mean `{}`?",
+                field, suggestion
+            ),
+            None => "Failed to parse YAML configuration".to_string(),
+        };
+        anyhow::Error::new(e).context(message)
+    }
+
     fn validate(config: &Config) -> anyhow::Result<()> {
         if config.server.port == 0 {
             anyhow::bail!("Server port cannot be 0");
@@ -48,6 +228,30 @@ impl ConfigLoader {
             anyhow::bail!("Number of workers cannot be 0");
         }
 
+        if let Some(jitter) = &config.server.jitter {
+            crate::config::types::parse_duration_range(jitter)
+                .with_context(|| format!("Invalid server jitter: {}", jitter))?;
+        }
+
+        crate::rules::transform::TransformChain::new(&config.server.transform)
+            .context("Server has an invalid transform")?;
+
+        let strict = &config.server.strict;
+        if !(100..=599).contains(&strict.status) {
+            anyhow::bail!("Invalid strict mode status code: {}", strict.status);
+        }
+        if strict.exit_code == Some(0) {
+            anyhow::bail!("Strict mode exit_code must be non-zero");
+        }
+
+        let mut region_names = std::collections::HashSet::new();
+        for region in &config.regions {
+            Self::validate_region(region)?;
+            if !region_names.insert(region.name.as_str()) {
+                anyhow::bail!("Duplicate region name '{}'", region.name);
+            }
+        }
+
         if config.telemetry.sampling_rate < 0.0 || config.telemetry.sampling_rate > 1.0 {
             anyhow::bail!("Sampling rate must be between 0.0 and 1.0");
         }
@@ -57,8 +261,39 @@ impl ConfigLoader {
             Self::validate_telemetry_config(&config.telemetry)?;
         }
 
+        if let Some(statsd) = &config.telemetry.statsd {
+            // Hostnames are resolved at startup, when the agent may not be up yet.
+            let port = statsd.address.rsplit_once(':').map(|(_, port)| port);
+            if port.and_then(|port| port.parse::<u16>().ok()).is_none() {
+                anyhow::bail!(
+                    "Invalid StatsD address '{}', expected host:port",
+                    statsd.address
+                );
+            }
+        }
+
+        if let Some(broker) = &config.broker {
+            Self::validate_broker(broker)?;
+        }
+
+        // Hit counts, validators, transforms, overrides and reload diffs all go
+        // by endpoint name.
+        let mut endpoint_names = std::collections::HashSet::new();
         for endpoint in &config.endpoints {
             Self::validate_endpoint(endpoint)?;
+            if !endpoint_names.insert(endpoint.name.as_str()) {
+                anyhow::bail!("Duplicate endpoint name '{}'", endpoint.name);
+            }
+
+            if let Some(profile) = &endpoint.delay_profile {
+                if !config.latency_profiles.contains_key(profile) {
+                    anyhow::bail!(
+                        "Endpoint '{}' references unknown latency profile '{}'",
+                        endpoint.name,
+                        profile
+                    );
+                }
+            }
         }
 
         Ok(())
@@ -117,6 +352,78 @@ impl ConfigLoader {
             anyhow::bail!("Telemetry export timeout must be greater than 0");
         }
 
+        if config.export_breaker.failure_threshold == 0 {
+            anyhow::bail!("Telemetry export breaker failure threshold must be greater than 0");
+        }
+
+        if config.export_breaker.probe_interval_seconds == 0 {
+            anyhow::bail!("Telemetry export breaker probe interval must be greater than 0");
+        }
+
+        Ok(())
+    }
+
+    fn validate_region(region: &crate::config::types::Region) -> anyhow::Result<()> {
+        if region.name.is_empty() {
+            anyhow::bail!("Region name cannot be empty");
+        }
+
+        if region.header.is_empty() && region.client_ip.is_empty() {
+            anyhow::bail!(
+                "Region '{}' needs `header` or `client_ip` to select its clients",
+                region.name
+            );
+        }
+
+        for range in &region.client_ip {
+            crate::rules::matcher::parse_cidr(range)
+                .with_context(|| format!("Region '{}' has an invalid client_ip", region.name))?;
+        }
+
+        if let Some(jitter) = &region.jitter {
+            crate::config::types::parse_duration_range(jitter).with_context(|| {
+                format!("Invalid jitter for region '{}': {}", region.name, jitter)
+            })?;
+        }
+
+        if let Some(fault) = &region.fault {
+            if !(0.0..=1.0).contains(&fault.rate) {
+                anyhow::bail!(
+                    "Region '{}' fault rate must be between 0.0 and 1.0",
+                    region.name
+                );
+            }
+            if !(100..=599).contains(&fault.status) {
+                anyhow::bail!("Invalid HTTP status code: {}", fault.status);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_broker(broker: &crate::config::types::BrokerConfig) -> anyhow::Result<()> {
+        for rule in &broker.topics {
+            if rule.topic.is_empty() || rule.topic.contains(['+', '#']) {
+                anyhow::bail!(
+                    "Broker topic must be a concrete topic name: '{}'",
+                    rule.topic
+                );
+            }
+
+            if rule.every.is_none() && rule.on_publish.is_none() {
+                anyhow::bail!(
+                    "Broker topic '{}' needs `every` or `on_publish`",
+                    rule.topic
+                );
+            }
+
+            if let Some(every) = &rule.every {
+                crate::config::types::Delay::Fixed(every.clone())
+                    .parse_duration()
+                    .with_context(|| format!("Invalid broker schedule: {}", every))?;
+            }
+        }
+
         Ok(())
     }
 
@@ -133,7 +440,10 @@ impl ConfigLoader {
             anyhow::bail!("Endpoint path cannot be empty");
         }
 
-        if endpoint.responses.is_empty() {
+        crate::rules::matcher::validate_path(&endpoint.path)
+            .with_context(|| format!("Endpoint '{}' has an invalid path regex", endpoint.name))?;
+
+        if endpoint.responses.is_empty() && endpoint.inbox.is_none() {
             anyhow::bail!("Endpoint must have at least one response");
         }
 
@@ -147,6 +457,156 @@ impl ConfigLoader {
             Self::validate_response(response)?;
         }
 
+        if let Some(pagination) = &endpoint.pagination {
+            if pagination.page_size == 0 {
+                anyhow::bail!("Pagination page_size must be greater than 0");
+            }
+        }
+
+        if endpoint.serialize && !endpoint.stateful {
+            anyhow::bail!(
+                "Endpoint '{}' sets `serialize` but is not `stateful`",
+                endpoint.name
+            );
+        }
+
+        if let Some(burn) = &endpoint.cpu_burn {
+            crate::config::types::parse_duration_str(burn)
+                .with_context(|| format!("Invalid cpu_burn: {}", burn))?;
+        }
+
+        if let Some(interval) = &endpoint.min_interval {
+            if !endpoint.stateful {
+                anyhow::bail!(
+                    "Endpoint '{}' sets `min_interval` but is not `stateful`",
+                    endpoint.name
+                );
+            }
+            crate::config::types::parse_duration_str(interval)
+                .with_context(|| format!("Invalid min_interval: {}", interval))?;
+        }
+
+        if let Some(window) = &endpoint.retry_window {
+            crate::config::types::parse_duration_str(window)
+                .with_context(|| format!("Invalid retry_window: {}", window))?;
+        }
+
+        if let Some(validation) = &endpoint.validation {
+            crate::rules::validation::BodyValidator::new(validation).with_context(|| {
+                format!("Endpoint '{}' has an invalid validation", endpoint.name)
+            })?;
+        }
+
+        if let Some(body_match) = &endpoint.body_match {
+            if body_match.exact.is_some() == body_match.partial.is_some() {
+                anyhow::bail!(
+                    "Endpoint '{}' body_match needs exactly one of `exact` or `partial`",
+                    endpoint.name
+                );
+            }
+        }
+
+        for matcher in &endpoint.matchers {
+            crate::rules::matcher::validate_matcher(matcher)
+                .with_context(|| format!("Endpoint '{}' has an invalid matcher", endpoint.name))?;
+        }
+
+        for capture in &endpoint.capture {
+            crate::rules::captures::validate(capture)
+                .with_context(|| format!("Endpoint '{}' has an invalid capture", endpoint.name))?;
+        }
+
+        for (name, path) in &endpoint.datasets {
+            crate::rules::datasets::load(path).with_context(|| {
+                format!(
+                    "Endpoint '{}' has an invalid dataset `{}`",
+                    endpoint.name, name
+                )
+            })?;
+        }
+
+        if let Some(cache) = &endpoint.cache {
+            let ttl = crate::config::types::parse_duration_str(&cache.ttl)
+                .with_context(|| format!("Invalid cache ttl: {}", cache.ttl))?;
+            if ttl.as_secs() == 0 {
+                anyhow::bail!("Cache ttl must be at least 1s, got {}", cache.ttl);
+            }
+        }
+
+        if !endpoint.instances.is_empty() {
+            let mut names = std::collections::HashSet::new();
+            for instance in &endpoint.instances {
+                if instance.name.is_empty() || !names.insert(instance.name.as_str()) {
+                    anyhow::bail!(
+                        "Endpoint '{}' instance names must be unique and non-empty",
+                        endpoint.name
+                    );
+                }
+            }
+            if endpoint
+                .instances
+                .iter()
+                .all(|instance| instance.weight == 0)
+            {
+                anyhow::bail!(
+                    "Endpoint '{}' needs an instance with a weight above 0",
+                    endpoint.name
+                );
+            }
+        }
+
+        if let Some(auth) = &endpoint.basic_auth {
+            if auth.username.contains(':') {
+                anyhow::bail!(
+                    "Endpoint '{}' basic_auth username cannot contain ':'",
+                    endpoint.name
+                );
+            }
+            if !(100..=599).contains(&auth.status) {
+                anyhow::bail!("Invalid HTTP status code: {}", auth.status);
+            }
+        }
+
+        if let Some(timeout) = &endpoint.timeout {
+            if timeout.after.as_duration().is_zero() {
+                anyhow::bail!(
+                    "Endpoint '{}' timeout must be greater than 0",
+                    endpoint.name
+                );
+            }
+            if !(100..=599).contains(&timeout.status) {
+                anyhow::bail!("Invalid HTTP status code: {}", timeout.status);
+            }
+        }
+
+        crate::rules::transform::TransformChain::new(&endpoint.transform)
+            .with_context(|| format!("Endpoint '{}' has an invalid transform", endpoint.name))?;
+
+        if let Some(hmac) = endpoint.signing.as_ref().and_then(|s| s.hmac.as_ref()) {
+            if http::HeaderName::from_bytes(hmac.header.as_bytes()).is_err() {
+                anyhow::bail!(
+                    "Endpoint '{}' has an invalid HMAC signature header '{}'",
+                    endpoint.name,
+                    hmac.header
+                );
+            }
+            if hmac.secret.is_empty() {
+                anyhow::bail!("Endpoint '{}' HMAC signature needs a secret", endpoint.name);
+            }
+        }
+
+        for metric in &endpoint.metrics {
+            if metric.name.is_empty() {
+                anyhow::bail!("Endpoint '{}' has a metric without a name", endpoint.name);
+            }
+            if metric.kind == crate::config::types::MetricKind::Counter && metric.increment < 0.0 {
+                anyhow::bail!(
+                    "Counter '{}' cannot have a negative increment; use `type: gauge`",
+                    metric.name
+                );
+            }
+        }
+
         Ok(())
     }
 
@@ -167,8 +627,82 @@ impl ConfigLoader {
             }
         }
 
+        if let Some(expr) = &response.delay_expr {
+            if response.delay.is_some() {
+                anyhow::bail!("A response cannot set both delay and delay_expr");
+            }
+            if let Err(e) = crate::rules::delay_expr::DelayExpr::parse(expr) {
+                anyhow::bail!("Invalid delay_expr `{}`: {}", expr, e);
+            }
+        }
+
+        if let Some(charset) = response.headers.content_charset() {
+            if encoding_rs::Encoding::for_label(charset.as_bytes()).is_none() {
+                anyhow::bail!("Unsupported response charset: {}", charset);
+            }
+        }
+
+        for matcher in &response.matchers {
+            crate::rules::matcher::validate_matcher(matcher)
+                .context("Response has an invalid matcher")?;
+        }
+
+        if let Some(schema) = &response.body_schema {
+            if response.body.is_some() {
+                anyhow::bail!("A response cannot set both body and body_schema");
+            }
+            crate::rules::schema_body::SchemaDocuments::default()
+                .check(schema)
+                .context("Invalid body_schema")?;
+        }
+
+        let templates = response
+            .body
+            .iter()
+            .chain(response.headers.iter().map(|(_, value)| value));
+        for template in templates {
+            for path in crate::rules::fixtures::csv_paths(template) {
+                crate::rules::fixtures::CsvTable::load(path)?;
+            }
+        }
+
         Ok(())
     }
+
+    /// Extracts the offending field from a serde "unknown field" error and returns it
+    /// together with the closest expected field name, if one is close enough to be a typo.
+    fn suggest_unknown_field(error: &str) -> Option<(String, String)> {
+        let rest = &error[error.find("unknown field `")? + "unknown field `".len()..];
+        let end = rest.find('`')?;
+        let field = &rest[..end];
+        let expected = &rest[rest.find("expected")?..];
+
+        let max_distance = (field.len() / 3).max(1);
+        expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(|candidate| (candidate, Self::edit_distance(field, candidate)))
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(candidate, _)| (field.to_string(), candidate.to_string()))
+    }
+
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+        for (i, ca) in a.chars().enumerate() {
+            let mut current = vec![i + 1; b.len() + 1];
+            for (j, cb) in b.iter().enumerate() {
+                let substitution = previous[j] + usize::from(ca != *cb);
+                current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            }
+            previous = current;
+        }
+
+        previous[b.len()]
+    }
 }
 
 #[cfg(test)]
@@ -186,9 +720,6 @@ telemetry:
   enabled: true
   service_name: "test"
 
-logging:
-  level: "info"
-
 endpoints:
   - name: "Test"
     method: GET
@@ -214,9 +745,6 @@ server:
 telemetry:
   enabled: true
 
-logging:
-  level: "info"
-
 endpoints: []
         "#;
 
@@ -236,9 +764,6 @@ telemetry:
   enabled: true
   sampling_rate: 1.5
 
-logging:
-  level: "info"
-
 endpoints: []
         "#;
 
@@ -260,9 +785,6 @@ server:
 telemetry:
   enabled: true
 
-logging:
-  level: "info"
-
 endpoints:
   - name: ""
     method: GET
@@ -279,6 +801,32 @@ endpoints:
             .contains("Endpoint name cannot be empty"));
     }
 
+    #[test]
+    fn test_duplicate_endpoint_name() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Users"
+    method: GET
+    path: "/users"
+    responses:
+      - status: 200
+  - name: "Users"
+    method: POST
+    path: "/users"
+    responses:
+      - status: 201
+        "#;
+
+        let err = ConfigLoader::parse_str(config_str).unwrap_err();
+        assert!(err.to_string().contains("Duplicate endpoint name 'Users'"));
+    }
+
     #[test]
     fn test_multiple_default_responses() {
         let config_str = r#"
@@ -289,9 +837,6 @@ server:
 telemetry:
   enabled: true
 
-logging:
-  level: "info"
-
 endpoints:
   - name: "Test"
     method: GET
@@ -321,9 +866,6 @@ server:
 telemetry:
   enabled: true
 
-logging:
-  level: "info"
-
 endpoints:
   - name: "Test"
     method: GET
@@ -341,6 +883,46 @@ endpoints:
             .contains("Invalid delay format"));
     }
 
+    #[test]
+    fn test_delay_expr_validation() {
+        let config = |response: &str| {
+            format!(
+                r#"
+server:
+  port: 8080
+  workers: 4
+
+telemetry:
+  enabled: true
+
+endpoints:
+  - name: "Test"
+    method: POST
+    path: "/test"
+    responses:
+      - status: 200
+{}
+"#,
+                response
+            )
+        };
+
+        assert!(ConfigLoader::parse_str(&config(
+            r#"        delay_expr: "body.items.length * 10ms""#
+        ))
+        .is_ok());
+
+        let err = ConfigLoader::parse_str(&config(r#"        delay_expr: "body.items.length *""#))
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid delay_expr"));
+
+        let err = ConfigLoader::parse_str(&config(
+            "        delay: 10ms\n        delay_expr: header.x-delay",
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("both delay and delay_expr"));
+    }
+
     #[test]
     fn test_invalid_telemetry_endpoint() {
         let config_str = r#"
@@ -387,6 +969,1355 @@ endpoints: []
             .contains("Telemetry protocol must be 'http' or 'grpc'"));
     }
 
+    #[test]
+    fn test_invalid_export_breaker() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: true
+  endpoint: "http://localhost:4318"
+  protocol: http
+  export_breaker:
+    failure_threshold: 0
+
+endpoints: []
+        "#;
+
+        assert!(ConfigLoader::parse_str(config_str)
+            .unwrap_err()
+            .to_string()
+            .contains("Telemetry export breaker failure threshold must be greater than 0"));
+
+        let defaults = config_str.replace("    failure_threshold: 0\n", "    buffered_spans: 0\n");
+        let config = ConfigLoader::parse_str(&defaults).unwrap();
+        assert_eq!(config.telemetry.export_breaker.failure_threshold, 3);
+        assert_eq!(config.telemetry.export_breaker.buffered_spans, 0);
+    }
+
+    #[test]
+    fn test_invalid_retry_window() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Charge"
+    method: POST
+    path: "/charge"
+    retry_window: soon
+    responses:
+      - status: 200
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid retry_window: soon"));
+        assert!(ConfigLoader::parse_str(&config_str.replace("soon", "10s")).is_ok());
+    }
+
+    #[test]
+    fn test_inbox_endpoint_without_responses() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Webhooks"
+    method: POST
+    path: "/hooks/*"
+    inbox: payments
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(config.endpoints[0].inbox.as_deref(), Some("payments"));
+        assert!(config.endpoints[0].responses.is_empty());
+
+        let without_inbox = config_str.replace("inbox: payments", "");
+        let result = ConfigLoader::parse_str(&without_inbox);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint must have at least one response"));
+    }
+
+    #[test]
+    fn test_response_extends_defaults() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+defaults:
+  responses:
+    json_error:
+      status: 500
+      headers:
+        Content-Type: application/json
+      body: '{"error": "internal"}'
+
+endpoints:
+  - name: "Users"
+    method: GET
+    path: "/users/:id"
+    responses:
+      - extends: json_error
+        status: 404
+        headers:
+          X-Reason: missing
+        default: true
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let response = &config.endpoints[0].responses[0];
+        assert_eq!(response.status, 404);
+        assert_eq!(response.body.as_deref(), Some(r#"{"error": "internal"}"#));
+        assert_eq!(
+            response.headers.get("Content-Type").map(String::as_str),
+            Some("application/json")
+        );
+        assert_eq!(
+            response.headers.get("X-Reason").map(String::as_str),
+            Some("missing")
+        );
+        assert!(response.default);
+
+        let result =
+            ConfigLoader::parse_str(&config_str.replace("extends: json_error", "extends: nope"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown response definition 'nope'"));
+    }
+
+    #[test]
+    fn test_endpoint_templates() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+defaults:
+  responses:
+    not_found:
+      status: 404
+      body: '{"error": "not found"}'
+
+endpoint_templates:
+  rest_resource:
+    params: [resource, base_path]
+    endpoints:
+      - name: "List $resource"
+        method: GET
+        path: "$base_path/$resource"
+        responses:
+          - status: 200
+            body: '{"$resource": []}'
+      - name: "Get $resource"
+        method: GET
+        path: "$base_path/$resource/:id"
+        responses:
+          - extends: not_found
+            default: true
+
+endpoints:
+  - template: rest_resource
+    params: &v1 {resource: users, base_path: /api/v1}
+  - template: rest_resource
+    params: {<<: *v1, resource: orders}
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let routes: Vec<(&str, &str)> = config
+            .endpoints
+            .iter()
+            .map(|e| (e.name.as_str(), e.path.as_str()))
+            .collect();
+        assert_eq!(
+            routes,
+            [
+                ("List users", "/api/v1/users"),
+                ("Get users", "/api/v1/users/:id"),
+                ("List orders", "/api/v1/orders"),
+                ("Get orders", "/api/v1/orders/:id"),
+            ]
+        );
+        assert_eq!(
+            config.endpoints[2].responses[0].body.as_deref(),
+            Some(r#"{"orders": []}"#)
+        );
+        assert_eq!(config.endpoints[3].responses[0].status, 404);
+    }
+
+    #[test]
+    fn test_override_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("molock-config.yaml");
+        std::fs::write(
+            &main,
+            r#"
+server:
+  port: 8080
+  workers: 2
+endpoints:
+  - name: Users
+    method: GET
+    path: /users
+    responses:
+      - status: 200
+        body: "[]"
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::from_file(&main).unwrap();
+        assert_eq!(config.override_file, None);
+        let plain_sha = config.sha256.clone();
+
+        let override_path = ConfigLoader::override_path(&main);
+        assert_eq!(
+            override_path.file_name().unwrap(),
+            "molock-config.override.yaml"
+        );
+        std::fs::write(
+            &override_path,
+            r#"
+server:
+  port: 9090
+endpoints:
+  - name: Users
+    responses:
+      - status: 503
+  - name: Debug
+    method: GET
+    path: /debug
+    responses:
+      - status: 200
+"#,
+        )
+        .unwrap();
+
+        let config = ConfigLoader::from_file(&main).unwrap();
+        assert_eq!(config.server.port, 9090);
+        assert_eq!(config.server.workers, 2);
+        assert_eq!(config.endpoints.len(), 2);
+        assert_eq!(config.endpoints[0].path, "/users");
+        assert_eq!(config.endpoints[0].responses[0].status, 503);
+        assert_eq!(config.endpoints[0].responses[0].body, None);
+        assert_eq!(config.endpoints[1].name, "Debug");
+        assert_ne!(config.sha256, plain_sha);
+        assert_eq!(
+            serde_json::to_value(&config).unwrap()["override_file"],
+            serde_json::json!(override_path.display().to_string())
+        );
+
+        std::fs::write(
+            &override_path,
+            "server:
+  port: not-a-port
+",
+        )
+        .unwrap();
+        let error = format!("{:#}", ConfigLoader::from_file(&main).unwrap_err());
+        assert!(error.contains("Invalid config with override"), "{}", error);
+    }
+
+    #[test]
+    fn test_mounted_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        write(
+            "teams/payments.yaml",
+            r#"
+latency_profiles:
+  psp: "p50 30ms, p99 200ms"
+endpoints:
+  - name: Charge
+    method: POST
+    path: /charges
+    delay_profile: psp
+    responses: [{status: 201}]
+  - name: Refund
+    method: POST
+    path: '~^/refunds/(?P<id>\d+)$'
+    responses: [{status: 202}]
+mounts:
+  - {prefix: /v2, config: v2.yaml}
+"#,
+        );
+        write(
+            "teams/v2.yaml",
+            "endpoints: [{name: Root, method: GET, path: /, responses: [{status: 200}]}]",
+        );
+        let main = write(
+            "molock.yaml",
+            r#"
+server:
+  port: 8080
+endpoints:
+  - name: Charge
+    method: GET
+    path: /health
+    responses: [{status: 200}]
+mounts:
+  - {prefix: /payments/, config: teams/payments.yaml}
+"#,
+        );
+
+        let config = ConfigLoader::from_file(&main).unwrap();
+        let routes: Vec<(&str, &str)> = config
+            .endpoints
+            .iter()
+            .map(|e| (e.name.as_str(), e.path.as_str()))
+            .collect();
+        assert_eq!(
+            routes,
+            [
+                ("Charge", "/health"),
+                ("/payments Charge", "/payments/charges"),
+                ("/payments Refund", r"~^/payments/refunds/(?P<id>\d+)$"),
+                ("/payments /v2 Root", "/payments/v2"),
+            ]
+        );
+        assert_eq!(
+            config.endpoints[1].delay_profile.as_deref(),
+            Some("/payments psp")
+        );
+        assert!(config.latency_profiles.contains_key("/payments psp"));
+
+        write(
+            "teams/v2.yaml",
+            "mounts: [{prefix: /again, config: payments.yaml}]",
+        );
+        let err = format!("{:#}", ConfigLoader::from_file(&main).unwrap_err());
+        assert!(err.contains("mounted inside itself"), "{}", err);
+
+        write("teams/v2.yaml", "endpoints: []");
+        let bad_prefix = write(
+            "bad.yaml",
+            "mounts: [{prefix: /users/:id, config: teams/v2.yaml}]",
+        );
+        let err = format!("{:#}", ConfigLoader::from_file(&bad_prefix).unwrap_err());
+        assert!(err.contains("Invalid mount prefix"), "{}", err);
+    }
+
+    #[test]
+    fn test_mounted_config_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+        write("teams/users.csv", "id,name\n1,Ann\n");
+        let mounted = write(
+            "teams/users.yaml",
+            r#"
+endpoints:
+  - name: User
+    method: GET
+    path: /users/:id
+    datasets: {users: users.csv}
+    responses:
+      - status: 200
+        body: '{{csv_row "users.csv" request_count "name"}}'
+"#,
+        );
+        let main = write(
+            "molock.yaml",
+            "mounts: [{prefix: /team, config: teams/users.yaml}]",
+        );
+
+        // Data files resolve against the mounted file, not the working directory.
+        let config = ConfigLoader::from_file(&main).unwrap();
+        let csv = dir.path().join("teams").join("users.csv");
+        let endpoint = &config.endpoints[0];
+        assert_eq!(endpoint.datasets["users"], csv.display().to_string());
+        assert_eq!(
+            endpoint.responses[0].body.as_deref(),
+            Some(
+                format!(
+                    r#"{{{{csv_row "{}" request_count "name"}}}}"#,
+                    csv.display()
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(config.mounted_files, [mounted.canonicalize().unwrap()]);
+
+        // Editing only the mounted file changes the config's hash.
+        write(
+            "teams/users.yaml",
+            "endpoints: [{name: User, method: GET, path: /users, responses: [{status: 204}]}]",
+        );
+        let edited = ConfigLoader::from_file(&main).unwrap();
+        assert_ne!(edited.sha256, config.sha256);
+    }
+
+    #[test]
+    fn test_endpoint_captures() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Create Order"
+    method: POST
+    path: "/orders"
+    capture:
+      - slot: "order-{{body.id}}"
+      - slot: tenant
+        header: X-Tenant
+    responses:
+      - status: 201
+  - name: "Get Order"
+    method: GET
+    path: "/orders/:id"
+    matchers:
+      - captured: "order-{{id}}"
+    responses:
+      - status: 200
+        body: '{{captured "order-" id}}'
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let capture = &config.endpoints[0].capture;
+        assert_eq!(capture[0].slot, "order-{{body.id}}");
+        assert_eq!(capture[1].header.as_deref(), Some("X-Tenant"));
+        assert_eq!(
+            config.endpoints[1].matchers[0].captured.as_deref(),
+            Some("order-{{id}}")
+        );
+
+        let both = config_str.replace(
+            "        header: X-Tenant\n",
+            "        header: X-Tenant\n        query: tenant\n",
+        );
+        let error = format!("{:#}", ConfigLoader::parse_str(&both).unwrap_err());
+        assert!(error.contains("has an invalid capture"), "{}", error);
+        assert!(
+            error.contains("only one of: json_path, header"),
+            "{}",
+            error
+        );
+
+        let absent = config_str.replace(
+            "      - captured: \"order-{{id}}\"\n",
+            "      - captured: \"order-{{id}}\"\n        absent: true\n",
+        );
+        assert!(ConfigLoader::parse_str(&absent).is_ok());
+    }
+
+    #[test]
+    fn test_endpoint_metrics() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Create Order"
+    method: POST
+    path: "/orders"
+    metrics:
+      - name: orders_created_total
+        labels:
+          region: "{{query.region}}"
+      - name: orders_open
+        type: gauge
+        increment: 1
+    responses:
+      - status: 201
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let metrics = &config.endpoints[0].metrics;
+        assert_eq!(metrics[0].kind, crate::config::types::MetricKind::Counter);
+        assert_eq!(metrics[0].increment, 1.0);
+        assert_eq!(metrics[0].labels["region"], "{{query.region}}");
+        assert_eq!(metrics[1].kind, crate::config::types::MetricKind::Gauge);
+
+        let negative = config_str.replace(
+            "      - name: orders_open\n        type: gauge\n",
+            "      - name: orders_open\n",
+        );
+        let negative = negative.replace("increment: 1", "increment: -1");
+        let result = ConfigLoader::parse_str(&negative);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("cannot have a negative increment"));
+    }
+
+    #[test]
+    fn test_body_match_needs_one_mode() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Card Payment"
+    method: POST
+    path: "/payments"
+    body_match:
+      partial:
+        method: card
+    responses:
+      - status: 201
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(
+            config.endpoints[0].body_match.as_ref().unwrap().partial,
+            Some(serde_json::json!({"method": "card"}))
+        );
+
+        let result =
+            ConfigLoader::parse_str(&config_str.replace("partial:\n        method: card", "{}"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("needs exactly one of `exact` or `partial`"));
+    }
+
+    #[test]
+    fn test_invalid_json_path_matcher() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Express Orders"
+    method: POST
+    path: "/orders"
+    matchers:
+      - json_path: '$.order.type == "express"'
+    responses:
+      - status: 201
+        "#;
+
+        assert!(ConfigLoader::parse_str(config_str).is_ok());
+
+        let result = ConfigLoader::parse_str(&config_str.replace("$.order", "order"));
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("invalid matcher"));
+        assert!(error.contains("must start with `$`"));
+    }
+
+    #[test]
+    fn test_broker_config() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+broker:
+  port: 1884
+  topics:
+    - topic: "orders/created"
+      every: 5s
+      message: '{"id": "{{uuid}}"}'
+
+endpoints: []
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let broker = config.broker.unwrap();
+        assert_eq!(broker.port, 1884);
+        assert_eq!(broker.host, "0.0.0.0");
+        assert_eq!(broker.topics[0].every.as_deref(), Some("5s"));
+
+        let unscheduled = config_str.replace("every: 5s", "");
+        let result = ConfigLoader::parse_str(&unscheduled);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("needs `every` or `on_publish`"));
+
+        let wildcard = config_str.replace("orders/created", "orders/#");
+        assert!(ConfigLoader::parse_str(&wildcard).is_err());
+    }
+
+    #[test]
+    fn test_body_validation() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Signup"
+    method: POST
+    path: "/signup"
+    validation:
+      schema:
+        type: object
+        required: [email]
+      status: 400
+    responses:
+      - status: 201
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let validation = config.endpoints[0].validation.as_ref().unwrap();
+        assert_eq!(validation.status, 400);
+        assert_eq!(validation.envelope, r#"{"errors": {{errors}}}"#);
+
+        let invalid = config_str.replace("type: object", "type: 12");
+        let result = ConfigLoader::parse_str(&invalid);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint 'Signup' has an invalid validation"));
+    }
+
+    #[test]
+    fn test_unsupported_charset() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Test"
+    method: GET
+    path: "/test"
+    responses:
+      - status: 200
+        headers:
+          Content-Type: "text/plain; charset=klingon"
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unsupported response charset: klingon"));
+
+        let latin1 = config_str.replace("klingon", "latin1");
+        assert!(ConfigLoader::parse_str(&latin1).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_path_regex() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Files"
+    method: GET
+    path: '~/files/(\d{4}'
+    responses:
+      - status: 200
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint 'Files' has an invalid path regex"));
+
+        let valid = config_str.replace(r"(\d{4}'", r"(?P<year>\d{4})'");
+        assert!(ConfigLoader::parse_str(&valid).is_ok());
+    }
+
+    #[test]
+    fn test_response_signing() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Webhook"
+    method: POST
+    path: "/webhook"
+    signing:
+      content_md5: true
+      digest: sha-512
+      hmac:
+        header: X-Hub-Signature-256
+        secret: "s3cret"
+        prefix: "sha256="
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let signing = config.endpoints[0].signing.as_ref().unwrap();
+        assert!(signing.content_md5);
+        assert_eq!(signing.digest, Some(crate::config::DigestAlgorithm::Sha512));
+        let hmac = signing.hmac.as_ref().unwrap();
+        assert_eq!(hmac.algorithm, crate::config::HmacAlgorithm::Sha256);
+        assert_eq!(hmac.encoding, crate::config::SignatureEncoding::Hex);
+
+        let no_secret = config_str.replace(r#"secret: "s3cret""#, r#"secret: """#);
+        assert!(ConfigLoader::parse_str(&no_secret)
+            .unwrap_err()
+            .to_string()
+            .contains("HMAC signature needs a secret"));
+
+        let bad_header = config_str.replace("X-Hub-Signature-256", "X Signature");
+        assert!(ConfigLoader::parse_str(&bad_header)
+            .unwrap_err()
+            .to_string()
+            .contains("invalid HMAC signature header 'X Signature'"));
+    }
+
+    #[test]
+    fn test_serialize_requires_stateful() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Retry"
+    method: GET
+    path: "/retry"
+    stateful: true
+    serialize: true
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert!(config.endpoints[0].serialize);
+
+        let stateless = config_str.replace("stateful: true", "stateful: false");
+        assert!(ConfigLoader::parse_str(&stateless)
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint 'Retry' sets `serialize` but is not `stateful`"));
+    }
+
+    #[test]
+    fn test_cpu_burn() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Report"
+    method: GET
+    path: "/report"
+    cpu_burn: 5ms
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(config.endpoints[0].cpu_burn.as_deref(), Some("5ms"));
+
+        let invalid = config_str.replace("cpu_burn: 5ms", "cpu_burn: hot");
+        assert!(
+            format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err())
+                .contains("Invalid cpu_burn: hot")
+        );
+    }
+
+    #[test]
+    fn test_endpoint_datasets() {
+        let dir = tempfile::tempdir().unwrap();
+        let users = dir.path().join("users.csv");
+        std::fs::write(&users, "id,name\n1,Ann\n").unwrap();
+        let config_str = format!(
+            r#"
+endpoints:
+  - name: "User"
+    method: GET
+    path: "/users/:id"
+    datasets:
+      users: {}
+    responses:
+      - status: 200
+        body: '{{{{#with (find_row datasets.users "id" id)}}}}{{{{name}}}}{{{{/with}}}}'
+"#,
+            users.display()
+        );
+
+        let config = ConfigLoader::parse_str(&config_str).unwrap();
+        assert_eq!(
+            config.endpoints[0].datasets["users"],
+            users.display().to_string()
+        );
+
+        let missing = config_str.replace("users.csv", "missing.csv");
+        let error = format!("{:#}", ConfigLoader::parse_str(&missing).unwrap_err());
+        assert!(
+            error.contains("Endpoint 'User' has an invalid dataset `users`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_body_schema() {
+        let config_str = r##"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "User"
+    method: GET
+    path: "/users/:id"
+    responses:
+      - status: 200
+        body_schema:
+          type: object
+          required: [id]
+          properties:
+            id: {$ref: "#/$defs/Id"}
+          $defs:
+            Id: {type: string, format: uuid}
+        "##;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert!(config.endpoints[0].responses[0].body_schema.is_some());
+
+        let broken = config_str.replace("#/$defs/Id", "#/$defs/Missing");
+        let error = format!("{:#}", ConfigLoader::parse_str(&broken).unwrap_err());
+        assert!(error.contains("Invalid body_schema"), "{}", error);
+        assert!(
+            error.contains("Unresolved $ref `#/$defs/Missing`"),
+            "{}",
+            error
+        );
+
+        let both = config_str.replace(
+            "      - status: 200\n",
+            "      - status: 200\n        body: \"{}\"\n",
+        );
+        assert!(format!("{:#}", ConfigLoader::parse_str(&both).unwrap_err())
+            .contains("cannot set both body and body_schema"));
+    }
+
+    #[test]
+    fn test_min_interval() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Checkout"
+    method: POST
+    path: "/checkout"
+    stateful: true
+    min_interval: 2s
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(config.endpoints[0].min_interval.as_deref(), Some("2s"));
+
+        let stateless = config_str.replace("stateful: true", "stateful: false");
+        assert!(ConfigLoader::parse_str(&stateless)
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint 'Checkout' sets `min_interval` but is not `stateful`"));
+
+        let invalid = config_str.replace("min_interval: 2s", "min_interval: soon");
+        assert!(
+            format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err())
+                .contains("Invalid min_interval: soon")
+        );
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Admin"
+    method: GET
+    path: "/admin"
+    basic_auth:
+      username: admin
+      password: secret
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let auth = config.endpoints[0].basic_auth.as_ref().unwrap();
+        assert_eq!(auth.realm, "molock");
+        assert_eq!(auth.status, 401);
+
+        let colon = config_str.replace("username: admin", "username: 'ad:min'");
+        assert!(ConfigLoader::parse_str(&colon)
+            .unwrap_err()
+            .to_string()
+            .contains("basic_auth username cannot contain ':'"));
+    }
+
+    #[test]
+    fn test_endpoint_timeout() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Slow"
+    method: GET
+    path: "/slow"
+    timeout:
+      after: 2s
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let timeout = config.endpoints[0].timeout.as_ref().unwrap();
+        assert_eq!(timeout.after.to_string(), "2s");
+        assert_eq!(
+            timeout.after.as_duration(),
+            std::time::Duration::from_secs(2)
+        );
+        assert_eq!(timeout.status, 504);
+        assert_eq!(timeout.body, None);
+
+        let zero = config_str.replace("after: 2s", "after: 0ms");
+        assert!(ConfigLoader::parse_str(&zero)
+            .unwrap_err()
+            .to_string()
+            .contains("Endpoint 'Slow' timeout must be greater than 0"));
+        let invalid = config_str.replace("after: 2s", "after: later");
+        assert!(
+            format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err())
+                .contains("Invalid duration format: later")
+        );
+        let bad_status = config_str.replace("after: 2s", "after: 2s\n      status: 42");
+        assert!(ConfigLoader::parse_str(&bad_status)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid HTTP status code: 42"));
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Catalog"
+    method: GET
+    path: "/catalog"
+    cache:
+      ttl: 60s
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let cache = config.endpoints[0].cache.as_ref().unwrap();
+        assert_eq!(cache.ttl, "60s");
+        assert!(!cache.private);
+
+        let too_short = config_str.replace("ttl: 60s", "ttl: 500ms");
+        assert!(ConfigLoader::parse_str(&too_short)
+            .unwrap_err()
+            .to_string()
+            .contains("Cache ttl must be at least 1s"));
+        let invalid = config_str.replace("ttl: 60s", "ttl: soon");
+        assert!(ConfigLoader::parse_str(&invalid)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid cache ttl: soon"));
+    }
+
+    #[test]
+    fn test_missing_csv_fixture() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Users"
+    method: GET
+    path: "/users/next"
+    responses:
+      - status: 200
+        body: '{{csv_row "fixtures/does-not-exist.csv" request_count}}'
+        "#;
+
+        let error = ConfigLoader::parse_str(config_str).unwrap_err().to_string();
+        assert!(error.contains("Failed to open CSV fixture"), "{}", error);
+    }
+
+    #[test]
+    fn test_statsd_config() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+  statsd:
+    address: "statsd.internal:8125"
+    dogstatsd: true
+
+endpoints: []
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let statsd = config.telemetry.statsd.unwrap();
+        assert_eq!(statsd.prefix, "molock");
+        assert!(statsd.dogstatsd);
+
+        let invalid = config_str.replace("statsd.internal:8125", "statsd.internal");
+        assert!(ConfigLoader::parse_str(&invalid)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid StatsD address 'statsd.internal'"));
+    }
+
+    #[test]
+    fn test_invalid_jitter() {
+        let config_str = r#"
+server:
+  port: 8080
+  jitter: "20ms"
+
+telemetry:
+  enabled: false
+
+endpoints: []
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid server jitter: 20ms"));
+
+        let config =
+            ConfigLoader::parse_str(&config_str.replace("\"20ms\"", "\"0-20ms\"")).unwrap();
+        assert_eq!(config.server.jitter.as_deref(), Some("0-20ms"));
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let config = |strict: &str| {
+            ConfigLoader::parse_str(&format!(
+                "server:\n  port: 8080\n  strict: {}\ntelemetry:\n  enabled: false\nendpoints: []\n",
+                strict
+            ))
+        };
+
+        let strict = config("true").unwrap().server.strict;
+        assert!(strict.enabled);
+        assert_eq!(strict.status, 501);
+        assert_eq!(strict.exit_code, None);
+
+        assert!(!config("false").unwrap().server.strict.enabled);
+
+        let strict = config("{status: 418, exit_code: 3}").unwrap().server.strict;
+        assert!(strict.enabled);
+        assert_eq!((strict.status, strict.exit_code), (418, Some(3)));
+
+        assert!(config("{status: 42}")
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid strict mode status code"));
+        assert!(config("{exit_code: 0}")
+            .unwrap_err()
+            .to_string()
+            .contains("exit_code must be non-zero"));
+    }
+
+    #[test]
+    fn test_response_transform() {
+        let config_str = r#"
+server:
+  port: 8080
+  transform:
+    - headers: {X-Served-By: molock}
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Report"
+    method: GET
+    path: "/report"
+    transform:
+      - replace: {pattern: "secret", with: "***"}
+      - compress: [br, gzip]
+    responses:
+      - status: 200
+        body: "secret"
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(config.server.transform.len(), 1);
+        assert_eq!(
+            config.endpoints[0].transform[1].compress,
+            Some(vec![
+                crate::config::ContentCoding::Br,
+                crate::config::ContentCoding::Gzip
+            ])
+        );
+
+        let invalid = config_str.replace("pattern: \"secret\"", "pattern: \"(\"");
+        let err = format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err());
+        assert!(
+            err.contains("Endpoint 'Report' has an invalid transform: Invalid transform step 0")
+        );
+
+        let invalid = config_str.replace("- headers: {X-Served-By: molock}", "- compress: []");
+        let err = format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err());
+        assert!(err.contains("Server has an invalid transform"));
+    }
+
+    #[test]
+    fn test_regions() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+regions:
+  - name: eu-west
+    header: {X-Region: eu-west}
+    latency: "p50 80ms, p99 300ms"
+    jitter: "0-20ms"
+  - name: us-east
+    client_ip: [10.1.0.0/16]
+    fault: {rate: 0.25}
+
+endpoints: []
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert_eq!(config.regions.len(), 2);
+        assert_eq!(config.regions[1].fault.as_ref().unwrap().status, 503);
+
+        let invalid = |from: &str, to: &str| {
+            format!(
+                "{:#}",
+                ConfigLoader::parse_str(&config_str.replace(from, to)).unwrap_err()
+            )
+        };
+        assert!(invalid("rate: 0.25", "rate: 1.5")
+            .contains("Region 'us-east' fault rate must be between 0.0 and 1.0"));
+        assert!(invalid("10.1.0.0/16", "10.1.0.0/40")
+            .contains("Region 'us-east' has an invalid client_ip"));
+        assert!(invalid("    header: {X-Region: eu-west}\n", "")
+            .contains("Region 'eu-west' needs `header` or `client_ip` to select its clients"));
+        assert!(invalid("us-east", "eu-west").contains("Duplicate region name 'eu-west'"));
+    }
+
+    #[test]
+    fn test_endpoint_instances() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Users"
+    method: GET
+    path: "/users"
+    instances:
+      - {name: node-a, weight: 2}
+      - {name: node-b, healthy: false}
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        let instances = &config.endpoints[0].instances;
+        assert_eq!((instances[0].weight, instances[0].healthy), (2, true));
+        assert_eq!((instances[1].weight, instances[1].healthy), (1, false));
+
+        let duplicate = config_str.replace("name: node-b", "name: node-a");
+        assert!(
+            format!("{:#}", ConfigLoader::parse_str(&duplicate).unwrap_err())
+                .contains("Endpoint 'Users' instance names must be unique and non-empty")
+        );
+
+        let weightless = config_str
+            .replace("weight: 2", "weight: 0")
+            .replace("healthy: false", "weight: 0");
+        assert!(
+            format!("{:#}", ConfigLoader::parse_str(&weightless).unwrap_err())
+                .contains("Endpoint 'Users' needs an instance with a weight above 0")
+        );
+    }
+
+    #[test]
+    fn test_latency_profiles() {
+        let config_str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+latency_profiles:
+  slow-db: "p50 30ms, p95 200ms, p99 1s"
+
+endpoints:
+  - name: "Test"
+    method: GET
+    path: "/test"
+    delay_profile: slow-db
+    responses:
+      - status: 200
+        "#;
+
+        let config = ConfigLoader::parse_str(config_str).unwrap();
+        assert!(config.latency_profiles.contains_key("slow-db"));
+        assert_eq!(
+            config.endpoints[0].delay_profile.as_deref(),
+            Some("slow-db")
+        );
+
+        let unknown = config_str.replace("delay_profile: slow-db", "delay_profile: fast-db");
+        let result = ConfigLoader::parse_str(&unknown);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("references unknown latency profile 'fast-db'"));
+    }
+
+    #[test]
+    fn test_unknown_field_with_suggestion() {
+        let config_str = r#"
+server:
+  port: 8080
+  workers: 4
+
+telemetry:
+  enabled: true
+
+endpoints:
+  - name: "Test"
+    method: GET
+    path: "/test"
+    responses:
+      - status: 200
+        probabilty: 0.5
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result.is_err());
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("unknown field `probabilty`"));
+        assert!(message.contains("did you mean `probability`?"));
+    }
+
+    #[test]
+    fn test_unknown_field_without_suggestion() {
+        let config_str = r#"
+server:
+  port: 8080
+  workers: 4
+
+telemetry:
+  enabled: true
+
+logging:
+  level: "info"
+
+endpoints: []
+        "#;
+
+        let result = ConfigLoader::parse_str(config_str);
+        assert!(result.is_err());
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(message.contains("unknown field `logging`"));
+        assert!(!message.contains("did you mean"));
+    }
+
+    #[test]
+    fn test_suggest_unknown_field() {
+        let error = "endpoints[0]: unknown field `stateful_key`, expected one of `name`, \
+                     `method`, `path`, `stateful`, `state_key`, `responses` at line 3";
+        assert_eq!(
+            ConfigLoader::suggest_unknown_field(error),
+            Some(("stateful_key".to_string(), "state_key".to_string()))
+        );
+
+        let error = "unknown field `xyz`, expected `port` or `host`";
+        assert_eq!(ConfigLoader::suggest_unknown_field(error), None);
+
+        assert_eq!(ConfigLoader::suggest_unknown_field("invalid type"), None);
+    }
+
     #[test]
     fn test_valid_telemetry_config() {
         let config_str = r#"
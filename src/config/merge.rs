@@ -0,0 +1,269 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Response inheritance: `extends: <name>` on a response deep-merges a named
+//! definition from `defaults.responses` underneath it before the config is typed.
+//! Override files are merged onto the config they sit next to the same way, with
+//! endpoints paired up by name.
+
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+
+/// Merges `overlay` onto `base`: mappings merge key by key, anything else in
+/// `overlay` replaces what `base` had.
+pub fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Merges an override file onto `config`. Endpoints are merged onto the endpoint
+/// with the same `name`, or appended when there is none; everything else follows
+/// [`deep_merge`], so lists such as an endpoint's `responses` are replaced whole.
+pub fn apply_override(config: &mut Value, overlay: Value) -> anyhow::Result<()> {
+    let mut overlay = match overlay {
+        Value::Mapping(overlay) => overlay,
+        Value::Null => return Ok(()),
+        _ => anyhow::bail!("The override must be a mapping"),
+    };
+
+    if let Some(endpoints) = overlay.remove("endpoints") {
+        let Value::Sequence(endpoints) = endpoints else {
+            anyhow::bail!("`endpoints` in the override must be a list");
+        };
+        let root = config
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("The config must be a mapping"))?;
+        let base = root
+            .entry(Value::from("endpoints"))
+            .or_insert_with(|| Value::Sequence(Vec::new()));
+        let Value::Sequence(base) = base else {
+            anyhow::bail!("`endpoints` must be a list");
+        };
+        for endpoint in endpoints {
+            let name = endpoint.get("name").and_then(Value::as_str);
+            let existing = name.and_then(|name| {
+                base.iter_mut()
+                    .find(|e| e.get("name").and_then(Value::as_str) == Some(name))
+            });
+            match existing {
+                Some(existing) => deep_merge(existing, endpoint),
+                None => base.push(endpoint),
+            }
+        }
+    }
+
+    deep_merge(config, Value::Mapping(overlay));
+    Ok(())
+}
+
+/// Removes the `defaults` section from a raw config and expands every `extends` in
+/// endpoint responses against it. Returns whether the document was changed.
+pub fn resolve_extends(config: &mut Value) -> anyhow::Result<bool> {
+    let Some(root) = config.as_mapping_mut() else {
+        return Ok(false);
+    };
+
+    let defaults = root.remove("defaults");
+    let library = match &defaults {
+        Some(Value::Mapping(defaults)) => match defaults.get("responses") {
+            Some(Value::Mapping(responses)) => responses.clone(),
+            Some(_) => anyhow::bail!("`defaults.responses` must be a mapping of named responses"),
+            None => Mapping::new(),
+        },
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => anyhow::bail!("`defaults` must be a mapping"),
+    };
+
+    let mut changed = defaults.is_some();
+    let endpoints = root
+        .get_mut("endpoints")
+        .and_then(Value::as_sequence_mut)
+        .into_iter()
+        .flatten();
+    for endpoint in endpoints {
+        let responses = endpoint
+            .get_mut("responses")
+            .and_then(Value::as_sequence_mut)
+            .into_iter()
+            .flatten();
+        for response in responses {
+            if let Value::Mapping(mapping) = response {
+                if let Some(parent) = mapping.remove("extends") {
+                    let name = parent
+                        .as_str()
+                        .ok_or_else(|| anyhow::anyhow!("`extends` must name a response"))?;
+                    let mut merged = lookup(&library, name, &mut HashSet::new())?;
+                    deep_merge(&mut merged, Value::Mapping(std::mem::take(mapping)));
+                    *response = merged;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Returns the fully expanded definition `name`, following its own `extends` chain.
+fn lookup(library: &Mapping, name: &str, seen: &mut HashSet<String>) -> anyhow::Result<Value> {
+    if !seen.insert(name.to_string()) {
+        anyhow::bail!("Response definition '{}' extends itself", name);
+    }
+
+    let mut definition = match library.get(name) {
+        Some(Value::Mapping(definition)) => definition.clone(),
+        Some(_) => anyhow::bail!("Response definition '{}' must be a mapping", name),
+        None => anyhow::bail!("Unknown response definition '{}' in `extends`", name),
+    };
+
+    match definition.remove("extends") {
+        Some(Value::String(parent)) => {
+            let mut merged = lookup(library, &parent, seen)?;
+            deep_merge(&mut merged, Value::Mapping(definition));
+            Ok(merged)
+        }
+        Some(_) => anyhow::bail!("`extends` must name a response"),
+        None => Ok(Value::Mapping(definition)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_deep_merge() {
+        let mut base = yaml("{status: 500, headers: {A: a, B: b}, body: base}");
+        deep_merge(&mut base, yaml("{status: 503, headers: {B: c}}"));
+        assert_eq!(
+            base,
+            yaml("{status: 503, headers: {A: a, B: c}, body: base}")
+        );
+    }
+
+    #[test]
+    fn test_apply_override() {
+        let mut config = yaml(
+            r#"
+server: {port: 8080, workers: 4}
+endpoints:
+  - name: Users
+    path: /users
+    responses: [{status: 200}, {status: 500}]
+  - name: Orders
+    path: /orders
+"#,
+        );
+        apply_override(
+            &mut config,
+            yaml(
+                r#"
+server: {port: 9090}
+endpoints:
+  - name: Users
+    responses: [{status: 503}]
+  - name: Debug
+    path: /debug
+"#,
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            yaml(
+                r#"
+server: {port: 9090, workers: 4}
+endpoints:
+  - name: Users
+    path: /users
+    responses: [{status: 503}]
+  - name: Orders
+    path: /orders
+  - name: Debug
+    path: /debug
+"#
+            )
+        );
+
+        let mut empty = yaml("{}");
+        apply_override(&mut empty, yaml("endpoints: [{name: A}]")).unwrap();
+        assert_eq!(empty, yaml("endpoints: [{name: A}]"));
+        assert!(apply_override(&mut empty, yaml("[1]")).is_err());
+        assert!(apply_override(&mut empty, yaml("endpoints: {A: 1}")).is_err());
+    }
+
+    #[test]
+    fn test_resolve_extends_chain() {
+        let mut config = yaml(
+            r#"
+defaults:
+  responses:
+    error:
+      status: 500
+      headers: {Content-Type: application/json}
+    not_found:
+      extends: error
+      status: 404
+endpoints:
+  - responses:
+      - extends: not_found
+        headers: {X-Reason: missing}
+"#,
+        );
+
+        assert!(resolve_extends(&mut config).unwrap());
+        assert!(config.get("defaults").is_none());
+        assert_eq!(
+            config["endpoints"][0]["responses"][0],
+            yaml("{status: 404, headers: {Content-Type: application/json, X-Reason: missing}}")
+        );
+    }
+
+    #[test]
+    fn test_resolve_extends_errors() {
+        let mut config = yaml("endpoints: [{responses: [{extends: missing}]}]");
+        let error = resolve_extends(&mut config).unwrap_err().to_string();
+        assert!(error.contains("Unknown response definition 'missing'"));
+
+        let mut config = yaml(
+            "{defaults: {responses: {a: {extends: b}, b: {extends: a}}}, endpoints: [{responses: [{extends: a}]}]}",
+        );
+        let error = resolve_extends(&mut config).unwrap_err().to_string();
+        assert!(error.contains("extends itself"));
+    }
+
+    #[test]
+    fn test_resolve_without_defaults_is_untouched() {
+        let mut config = yaml("endpoints: [{responses: [{status: 200}]}]");
+        assert!(!resolve_extends(&mut config).unwrap());
+    }
+}
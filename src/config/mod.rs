@@ -15,7 +15,19 @@
  */
 
 pub mod loader;
+pub mod merge;
+pub mod redact;
+pub mod templates;
 pub mod types;
 
 pub use loader::ConfigLoader;
-pub use types::{Config, Endpoint, Response, TelemetryConfig};
+pub use types::{
+    BasicAuth, BodyMatch, BodyRewrite, BodySize, BodyValidation, BrokerConfig, ByteSize,
+    CacheSimulation, CanonicalJson, Capture, Config, ConnectionOptions, ContentCoding,
+    CustomMatcher, CustomTransform, DigestAlgorithm, DurationValue, Endpoint, EndpointInstance,
+    EndpointMetric, EndpointTimeout, ExportBreakerConfig, Fallthrough, Fault, GraphqlMatcher,
+    GraphqlOperationType, HeaderList, HmacAlgorithm, HmacSignature, JwtMatcher, LatencyProfile,
+    MetricKind, Mount, MultipartMatcher, Oversize, Pagination, Region, RegionFault, RequestMatcher,
+    Response, ResponseLimits, ResponseSigning, ResponseTransform, SelectionStrategy,
+    SignatureEncoding, SmtpConfig, StatsdConfig, StrictMode, TelemetryConfig, TopicRule,
+};
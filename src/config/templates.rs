@@ -0,0 +1,327 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Endpoint templates: `endpoint_templates:` defines groups of endpoints with `$param`
+//! placeholders, and a `template:` entry in `endpoints` stamps a group out with
+//! concrete values before the config is typed.
+//!
+//! ```yaml
+//! endpoint_templates:
+//!   rest_resource:
+//!     params: [resource, base_path]
+//!     endpoints:
+//!       - name: "List $resource"
+//!         method: GET
+//!         path: "$base_path/$resource"
+//!         responses: [{status: 200, body: '[]'}]
+//!       - name: "Get $resource"
+//!         method: GET
+//!         path: "$base_path/$resource/:id"
+//!         responses: [{status: 200, body: '{"id": "{{id}}"}'}]
+//!
+//! endpoints:
+//!   - template: rest_resource
+//!     params: {resource: users, base_path: /api/v1}
+//!   - template: rest_resource
+//!     params: {resource: orders, base_path: /api/v1}
+//! ```
+//!
+//! Placeholders are written `$name`, or `${name}` when followed by more letters, and
+//! are replaced in every string of the template, mapping keys included. Only declared
+//! parameters are substituted, so `$.order` in a JSONPath or `$ref` in a schema is left
+//! alone. YAML merge keys (`<<: *anchor`) are applied first, so templates and their
+//! uses can share parameters through anchors.
+
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+
+/// Removes `endpoint_templates` from a raw config and replaces every `template:`
+/// entry in `endpoints` with the template's endpoints. Returns whether the document
+/// was changed.
+pub fn expand_templates(config: &mut Value) -> anyhow::Result<bool> {
+    let Some(templates) = config
+        .as_mapping_mut()
+        .and_then(|root| root.remove("endpoint_templates"))
+    else {
+        return Ok(false);
+    };
+    config.apply_merge()?;
+
+    let templates = match templates {
+        Value::Mapping(templates) => templates,
+        Value::Null => Mapping::new(),
+        _ => anyhow::bail!("`endpoint_templates` must be a mapping of named templates"),
+    };
+
+    let Some(endpoints) = config.get_mut("endpoints").and_then(Value::as_sequence_mut) else {
+        return Ok(true);
+    };
+    let mut expanded = Vec::with_capacity(endpoints.len());
+    for endpoint in std::mem::take(endpoints) {
+        match endpoint.get("template") {
+            Some(_) => expanded.extend(instantiate(&templates, endpoint)?),
+            None => expanded.push(endpoint),
+        }
+    }
+    *endpoints = expanded;
+
+    Ok(true)
+}
+
+/// The endpoints of the template named by a `template:` entry, with its params filled in.
+fn instantiate(templates: &Mapping, mut usage: Value) -> anyhow::Result<Vec<Value>> {
+    let usage = usage
+        .as_mapping_mut()
+        .ok_or_else(|| anyhow::anyhow!("An endpoint must be a mapping"))?;
+    let name = match usage.remove("template") {
+        Some(Value::String(name)) => name,
+        _ => anyhow::bail!("`template` must name an endpoint template"),
+    };
+    let values = match usage.remove("params") {
+        Some(Value::Mapping(values)) => values,
+        Some(Value::Null) | None => Mapping::new(),
+        Some(_) => anyhow::bail!("`params` of template '{}' must be a mapping", name),
+    };
+    if let Some(key) = usage.keys().next() {
+        anyhow::bail!(
+            "Unexpected `{}` next to `template: {}` (only `params` is allowed)",
+            key.as_str().unwrap_or("?"),
+            name
+        );
+    }
+
+    let template = match templates.get(name.as_str()) {
+        Some(Value::Mapping(template)) => template,
+        Some(_) => anyhow::bail!("Endpoint template '{}' must be a mapping", name),
+        None => anyhow::bail!("Unknown endpoint template '{}'", name),
+    };
+    let declared: Vec<&str> = match template.get("params") {
+        Some(Value::Sequence(params)) => params
+            .iter()
+            .map(|param| {
+                param.as_str().ok_or_else(|| {
+                    anyhow::anyhow!("Params of endpoint template '{}' must be names", name)
+                })
+            })
+            .collect::<anyhow::Result<_>>()?,
+        Some(Value::Null) | None => Vec::new(),
+        Some(_) => anyhow::bail!("`params` of endpoint template '{}' must be a list", name),
+    };
+
+    let mut params = HashMap::new();
+    for (key, value) in values {
+        let key = key
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Param names of template '{}' must be strings", name))?
+            .to_string();
+        if !declared.contains(&key.as_str()) {
+            anyhow::bail!("Endpoint template '{}' has no param `{}`", name, key);
+        }
+        let value = match value {
+            Value::String(value) => value,
+            Value::Number(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            _ => anyhow::bail!("Param `{}` of template '{}' must be a scalar", key, name),
+        };
+        params.insert(key, value);
+    }
+    if let Some(missing) = declared.iter().find(|param| !params.contains_key(**param)) {
+        anyhow::bail!("Template '{}' needs a value for `{}`", name, missing);
+    }
+
+    let endpoints = match template.get("endpoints") {
+        Some(Value::Sequence(endpoints)) if !endpoints.is_empty() => endpoints,
+        _ => anyhow::bail!("Endpoint template '{}' must list its endpoints", name),
+    };
+    Ok(endpoints
+        .iter()
+        .map(|endpoint| substitute(endpoint.clone(), &params))
+        .collect())
+}
+
+fn substitute(value: Value, params: &HashMap<String, String>) -> Value {
+    match value {
+        Value::String(s) => Value::String(substitute_str(&s, params)),
+        Value::Sequence(items) => Value::Sequence(
+            items
+                .into_iter()
+                .map(|item| substitute(item, params))
+                .collect(),
+        ),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (substitute(key, params), substitute(value, params)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Replaces `$name` and `${name}` for the given params, leaving any other `$` as is.
+fn substitute_str(s: &str, params: &HashMap<String, String>) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after.find(|c| !is_ident(c)).unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match params.get(name) {
+            Some(value) => {
+                out.push_str(value);
+                rest = &after[consumed..];
+            }
+            None => {
+                out.push('$');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_expand_templates() {
+        let mut config = yaml(
+            r#"
+endpoint_templates:
+  rest: &rest
+    params: [resource, base_path]
+    endpoints:
+      - name: "List $resource"
+        method: GET
+        path: "$base_path/$resource"
+        matchers: [{json_path: '$.kind == "${resource}_page"'}]
+      - name: "Get $resource"
+        method: GET
+        path: "$base_path/$resource/:id"
+        responses: [{status: 200, headers: {X-$resource-Id: "{{id}}"}}]
+endpoints:
+  - name: Health
+    method: GET
+    path: /health
+  - template: rest
+    params: {resource: users, base_path: /api/v1}
+  - template: rest
+    params: {resource: orders, base_path: /api/v2}
+"#,
+        );
+
+        assert!(expand_templates(&mut config).unwrap());
+        assert!(config.get("endpoint_templates").is_none());
+        let endpoints = config["endpoints"].as_sequence().unwrap();
+        let names: Vec<&str> = endpoints
+            .iter()
+            .map(|e| e["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Health",
+                "List users",
+                "Get users",
+                "List orders",
+                "Get orders"
+            ]
+        );
+        assert_eq!(endpoints[3]["path"], yaml("/api/v2/orders"));
+        assert_eq!(
+            endpoints[1]["matchers"][0]["json_path"],
+            yaml(r#"'$.kind == "users_page"'"#)
+        );
+        assert_eq!(
+            endpoints[2]["responses"][0]["headers"],
+            yaml(r#"{X-users-Id: "{{id}}"}"#)
+        );
+    }
+
+    #[test]
+    fn test_params_shared_through_merge_keys() {
+        let mut config = yaml(
+            r#"
+endpoint_templates:
+  item:
+    params: [resource, base_path]
+    endpoints: [{name: "$resource", method: GET, path: "$base_path/$resource"}]
+common: &v1 {base_path: /api/v1}
+endpoints:
+  - template: item
+    params: {<<: *v1, resource: carts}
+"#,
+        );
+
+        expand_templates(&mut config).unwrap();
+        assert_eq!(config["endpoints"][0]["path"], yaml("/api/v1/carts"));
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let expand = |endpoints: &str| {
+            let mut config = yaml(&format!(
+                "{{endpoint_templates: {{rest: {{params: [resource], endpoints: [{{path: /$resource}}]}}}}, endpoints: {}}}",
+                endpoints
+            ));
+            expand_templates(&mut config).unwrap_err().to_string()
+        };
+
+        assert!(expand("[{template: nope}]").contains("Unknown endpoint template 'nope'"));
+        assert!(expand("[{template: rest}]").contains("needs a value for `resource`"));
+        assert!(
+            expand("[{template: rest, params: {resource: a, extra: b}}]")
+                .contains("has no param `extra`")
+        );
+        assert!(
+            expand("[{template: rest, params: {resource: a}, path: /x}]")
+                .contains("Unexpected `path`")
+        );
+    }
+
+    #[test]
+    fn test_substitute_leaves_other_dollars() {
+        let params = HashMap::from([("id".to_string(), "42".to_string())]);
+        assert_eq!(substitute_str("$id-${id}x", &params), "42-42x");
+        assert_eq!(
+            substitute_str("$.items[$idx] $ref ${id", &params),
+            "$.items[$idx] $ref ${id"
+        );
+        assert_eq!(substitute_str("^/a$", &params), "^/a$");
+    }
+
+    #[test]
+    fn test_without_templates_is_untouched() {
+        let mut config = yaml("endpoints: [{name: a}]");
+        assert!(!expand_templates(&mut config).unwrap());
+    }
+}
@@ -14,27 +14,309 @@
  * limitations under the License.
  */
 
-use serde::{Deserialize, Serialize};
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde(default)]
     pub server: ServerConfig,
+    #[serde(default)]
     pub telemetry: TelemetryConfig,
+    /// Named latency distributions that endpoints reference through `delay_profile`.
+    #[serde(default)]
+    pub latency_profiles: HashMap<String, LatencyProfile>,
+    /// Client groups, each seeing its own network latency and outages; a client
+    /// belongs to the first region it matches.
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    #[serde(default)]
     pub endpoints: Vec<Endpoint>,
+    /// Other config files served under path prefixes; the loader adds their
+    /// endpoints to `endpoints`.
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    /// Optional MQTT broker mock, served when built with the `broker` feature.
+    #[serde(default)]
+    pub broker: Option<BrokerConfig>,
+    /// Optional SMTP listener capturing mail, served when built with the `smtp` feature.
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+    /// SHA-256 of the YAML this config was parsed from, set by the loader.
+    #[serde(skip)]
+    pub sha256: Option<String>,
+    /// The override file merged onto this config, set by the loader.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub override_file: Option<String>,
+    /// Every file mounted into this config, nested mounts included, set by the
+    /// loader so hot reload can watch them.
+    #[serde(skip)]
+    pub mounted_files: Vec<std::path::PathBuf>,
+}
+
+/// A config file whose endpoints are served under `prefix`, so per-team mock
+/// definitions can run in one instance without merging files. A relative `config`
+/// is resolved against the directory of the file that mounts it, and so are the
+/// datasets and CSV fixtures of the mounted file.
+///
+/// ```yaml
+/// mounts:
+///   - {prefix: /payments, config: payments.yaml}
+///   - {prefix: /users, config: users.yaml}
+/// ```
+///
+/// Mounted endpoints keep their own paths below the prefix, and their names and
+/// latency profiles are prefixed (`/payments Create charge`) so hits and state stay
+/// apart from other files. Server, telemetry and region settings come from the
+/// mounting file only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mount {
+    pub prefix: String,
+    pub config: String,
+}
+
+/// A simulated region: clients sending one of the `header` values or calling from
+/// one of the `client_ip` ranges get its latency and failures on top of whatever the
+/// endpoint does, so clients can exercise region failover against one instance.
+///
+/// ```yaml
+/// regions:
+///   - name: eu-west
+///     header: {X-Region: eu-west}
+///     client_ip: [10.1.0.0/16]
+///     latency: "p50 80ms, p99 300ms"
+///     jitter: "0-20ms"
+///     fault: {rate: 0.2, status: 503}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub name: String,
+    /// Request headers (by name) and the values that place a client in the region.
+    #[serde(default)]
+    pub header: HashMap<String, String>,
+    /// CIDR ranges or single addresses of clients in the region.
+    #[serde(default)]
+    pub client_ip: Vec<String>,
+    /// Latency added to every response, sampled from a percentile profile.
+    #[serde(default)]
+    pub latency: Option<LatencyProfile>,
+    /// Random extra delay range, e.g. `"0-20ms"`.
+    #[serde(default)]
+    pub jitter: Option<String>,
+    #[serde(default)]
+    pub fault: Option<RegionFault>,
+}
+
+/// Fails a share of the region's requests, as an outage would, instead of running
+/// the endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegionFault {
+    /// Share of requests that fail, from 0.0 to 1.0.
+    pub rate: f64,
+    #[serde(default = "default_region_fault_status")]
+    pub status: u16,
+    /// Response body template; defaults to a JSON error naming the region.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+fn default_region_fault_status() -> u16 {
+    503
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+}
+
+fn default_smtp_port() -> u16 {
+    2525
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BrokerConfig {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_broker_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub topics: Vec<TopicRule>,
+}
+
+fn default_broker_port() -> u16 {
+    1883
+}
+
+/// A templated message published to `topic` on a schedule (`every`) and/or whenever a
+/// client publishes to a topic matching `on_publish`.
+///
+/// `message` uses the response template syntax; `{{path}}` is the target topic and,
+/// for `on_publish` rules, `{{topic}}` and `{{payload}}` describe the triggering message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopicRule {
+    pub topic: String,
+    pub message: String,
+    #[serde(default)]
+    pub every: Option<String>,
+    #[serde(default)]
+    pub on_publish: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ServerConfig {
     #[serde(default = "default_port")]
     pub port: u16,
-    #[serde(default = "default_workers")]
+    /// A number of workers, or `auto` for one per CPU.
+    #[serde(default = "default_workers", deserialize_with = "deserialize_workers")]
     pub workers: usize,
     #[serde(default = "default_host")]
     pub host: String,
     #[serde(default = "default_max_request_size")]
     pub max_request_size: usize,
+    #[serde(default)]
+    pub debug_headers: bool,
+    #[serde(default)]
+    pub journal: Option<String>,
+    /// Random extra delay range added to every response, e.g. `"0-20ms"`.
+    #[serde(default)]
+    pub jitter: Option<String>,
+    /// Transform steps run on every endpoint's responses, after the endpoint's own.
+    #[serde(default)]
+    pub transform: Vec<ResponseTransform>,
+    /// Directory crash reports of panicking handlers are written to; defaults to
+    /// `molock-crashes` in the system temp directory.
+    #[serde(default)]
+    pub crash_dir: Option<String>,
+    /// Answer requests no endpoint matches with a distinct status, so tests hitting
+    /// endpoints nobody mocked fail loudly.
+    #[serde(default)]
+    pub strict: StrictMode,
+    /// Legacy connection semantics, for testing clients' connection pooling.
+    #[serde(default)]
+    pub connection: ConnectionOptions,
+    /// Caps on every endpoint's rendered responses; an endpoint's own `limits`
+    /// take precedence field by field.
+    #[serde(default = "default_response_limits")]
+    pub limits: ResponseLimits,
+    /// Longest delay a `delay_expr` may compute from request values.
+    #[serde(default = "default_max_computed_delay")]
+    pub max_computed_delay: DurationValue,
+    /// Set by `--dry-run`: explain match decisions instead of serving responses.
+    #[serde(skip)]
+    pub dry_run: bool,
+}
+
+/// `server.connection`: how the listener treats connections, e.g.
+///
+/// ```yaml
+/// server:
+///   connection: {keep_alive: false}
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionOptions {
+    /// Whether connections stay open for further requests; `false` closes each one
+    /// after its response.
+    #[serde(default = "default_keep_alive")]
+    pub keep_alive: bool,
+    /// Answers every request with `Connection: close`, then closes the connection.
+    #[serde(default)]
+    pub force_close: bool,
+    /// HTTP/1.0 connection semantics: connections close after each response unless
+    /// the request sends `Connection: keep-alive`, and bodies are never chunked. The
+    /// status line still carries the request's HTTP version.
+    #[serde(default)]
+    pub http10: bool,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            keep_alive: default_keep_alive(),
+            force_close: false,
+            http10: false,
+        }
+    }
+}
+
+fn default_keep_alive() -> bool {
+    true
+}
+
+/// `server.strict`: `true`, or a mapping overriding the status unmatched requests get
+/// and an exit code for the process to end with if any request went unmatched.
+///
+/// ```yaml
+/// server:
+///   strict: {status: 501, exit_code: 3}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StrictValue")]
+pub struct StrictMode {
+    pub enabled: bool,
+    pub status: u16,
+    pub exit_code: Option<i32>,
+}
+
+impl Default for StrictMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            status: default_strict_status(),
+            exit_code: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StrictValue {
+    Enabled(bool),
+    Options(StrictOptions),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictOptions {
+    #[serde(default = "default_strict_status")]
+    status: u16,
+    #[serde(default)]
+    exit_code: Option<i32>,
+}
+
+impl From<StrictValue> for StrictMode {
+    fn from(value: StrictValue) -> Self {
+        match value {
+            StrictValue::Enabled(enabled) => Self {
+                enabled,
+                ..Self::default()
+            },
+            StrictValue::Options(options) => Self {
+                enabled: true,
+                status: options.status,
+                exit_code: options.exit_code,
+            },
+        }
+    }
+}
+
+fn default_strict_status() -> u16 {
+    501
 }
 
 fn default_port() -> u16 {
@@ -45,6 +327,27 @@ fn default_workers() -> usize {
     4
 }
 
+fn deserialize_workers<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Workers {
+        Count(usize),
+        Name(String),
+    }
+
+    match Workers::deserialize(deserializer)? {
+        Workers::Count(count) => Ok(count),
+        Workers::Name(name) if name == "auto" => Ok(num_cpus::get()),
+        Workers::Name(name) => Err(serde::de::Error::custom(format!(
+            "invalid workers `{}`, expected a number or `auto`",
+            name
+        ))),
+    }
+}
+
 fn default_host() -> String {
     "0.0.0.0".to_string()
 }
@@ -53,7 +356,20 @@ fn default_max_request_size() -> usize {
     10 * 1024 * 1024 // 10MB
 }
 
+fn default_max_computed_delay() -> DurationValue {
+    DurationValue::try_from("60s".to_string()).expect("valid default duration")
+}
+
+fn default_response_limits() -> ResponseLimits {
+    ResponseLimits {
+        max_body_size: Some(ByteSize(10 * 1024 * 1024)),
+        max_header_size: Some(ByteSize(64 * 1024)),
+        on_exceed: Oversize::Fail,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TelemetryConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
@@ -67,6 +383,8 @@ pub struct TelemetryConfig {
     pub protocol: String,
     #[serde(default = "default_sampling_rate")]
     pub sampling_rate: f64,
+    /// An `EnvFilter` directive, e.g. `info` or `info,molock::rules=debug` to also
+    /// record every matcher and condition evaluation.
     #[serde(default = "default_log_level")]
     pub log_level: String,
     #[serde(default = "default_log_format")]
@@ -77,6 +395,50 @@ pub struct TelemetryConfig {
     pub export_batch_size: usize,
     #[serde(default = "default_export_timeout_millis")]
     pub export_timeout_millis: u64,
+    /// Distinct route patterns labelled in metrics and spans before further routes
+    /// are bucketed as `other`.
+    #[serde(default = "default_max_route_labels")]
+    pub max_route_labels: usize,
+    /// Also emits request, error, latency and fault metrics over StatsD, independent
+    /// of `enabled`.
+    #[serde(default)]
+    pub statsd: Option<StatsdConfig>,
+    #[serde(default)]
+    pub export_breaker: ExportBreakerConfig,
+}
+
+/// Stops exporting to a collector that keeps failing until a probe gets through, so
+/// a dead collector costs neither startup time nor request latency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportBreakerConfig {
+    /// Failed exports in a row that open the breaker.
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before an export probes the collector again.
+    #[serde(default = "default_breaker_probe_interval_seconds")]
+    pub probe_interval_seconds: u64,
+    /// Spans kept while the breaker is open, dropping the oldest, and sent once the
+    /// collector is back.
+    #[serde(default = "default_breaker_buffered_spans")]
+    pub buffered_spans: usize,
+}
+
+/// A StatsD (or DogStatsD) agent to send metrics to over UDP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdConfig {
+    /// Agent address, e.g. `127.0.0.1:8125`.
+    pub address: String,
+    #[serde(default = "default_statsd_prefix")]
+    pub prefix: String,
+    /// Sends labels as DogStatsD tags; plain StatsD folds them into the metric name.
+    #[serde(default)]
+    pub dogstatsd: bool,
+}
+
+fn default_statsd_prefix() -> String {
+    "molock".to_string()
 }
 
 fn default_enabled() -> bool {
@@ -123,7 +485,24 @@ fn default_export_timeout_millis() -> u64 {
     30000
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_breaker_failure_threshold() -> u32 {
+    3
+}
+
+fn default_breaker_probe_interval_seconds() -> u64 {
+    30
+}
+
+fn default_breaker_buffered_spans() -> usize {
+    2048
+}
+
+fn default_max_route_labels() -> usize {
+    crate::telemetry::routes::DEFAULT_MAX_ROUTE_LABELS
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Endpoint {
     pub name: String,
     pub method: String,
@@ -132,24 +511,994 @@ pub struct Endpoint {
     pub stateful: bool,
     #[serde(default)]
     pub state_key: Option<String>,
+    /// Serves concurrent requests that share a state key one at a time, in arrival
+    /// order, so `request_count` sequences hold when clients fire in parallel.
+    #[serde(default)]
+    pub serialize: bool,
+    /// The least time between two changes of the per-client state, e.g. `2s`; requests
+    /// arriving sooner, such as client retries, see the state as it is.
+    #[serde(default)]
+    pub min_interval: Option<String>,
+    /// Adds an `X-Endpoint-Hits` header with the endpoint's hit count, this request
+    /// included, which templates also see as `{{endpoint_hits}}`.
+    #[serde(default)]
+    pub hit_header: bool,
+    /// May be omitted for `inbox` endpoints, which then answer `202 Accepted`.
+    #[serde(default)]
     pub responses: Vec<Response>,
+    #[serde(default)]
+    pub fallthrough: Fallthrough,
+    #[serde(default)]
+    pub selection: SelectionStrategy,
+    /// Name of a `latency_profiles` entry used for responses without their own `delay`.
+    #[serde(default)]
+    pub delay_profile: Option<String>,
+    /// Keeps a CPU busy this long per request, e.g. `5ms`, on top of any delay, to
+    /// mimic a compute-bound upstream rather than one that idles.
+    #[serde(default)]
+    pub cpu_burn: Option<String>,
+    #[serde(default)]
+    pub validation: Option<BodyValidation>,
+    /// Only match requests whose JSON body matches; otherwise the next endpoint is tried.
+    #[serde(default)]
+    pub body_match: Option<BodyMatch>,
+    /// Further request predicates that must all hold; otherwise the next endpoint is tried.
+    #[serde(default)]
+    pub matchers: Vec<RequestMatcher>,
+    /// Rejects matched requests that lack these Basic credentials.
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuth>,
+    #[serde(default)]
+    pub pagination: Option<Pagination>,
+    /// Records every matched request under this inbox name for `/__admin/inbox/{name}`.
+    #[serde(default)]
+    pub inbox: Option<String>,
+    /// Holds matched requests until `/__admin/gates/{name}/release` is called.
+    #[serde(default)]
+    pub gate: Option<String>,
+    /// Simulated upstream nodes requests rotate over by weight; a request routed to
+    /// an unhealthy one fails with 503.
+    #[serde(default)]
+    pub instances: Vec<EndpointInstance>,
+    /// How long an identical request from the same client still counts as a retry
+    /// for the `attempt` condition variable (30s when unset).
+    #[serde(default)]
+    pub retry_window: Option<String>,
+    /// Skips the transparent decoding of compressed request bodies, passing them on
+    /// as received.
+    #[serde(default)]
+    pub raw_body: bool,
+    #[serde(default)]
+    pub limits: Option<ResponseLimits>,
+    /// Simulated caching headers whose `ETag` rotates every `ttl`.
+    #[serde(default)]
+    pub cache: Option<CacheSimulation>,
+    /// Mutations of the encoded response, applied in order before `signing`.
+    #[serde(default)]
+    pub transform: Vec<ResponseTransform>,
+    /// Checksum and signature headers computed over each response body.
+    #[serde(default)]
+    pub signing: Option<ResponseSigning>,
+    /// Gives up on producing a response after this long; a `gate` hold does not count.
+    #[serde(default)]
+    pub timeout: Option<EndpointTimeout>,
+    /// Seeds the random stream behind probability selection and delay ranges once,
+    /// so a run replays the same sequence; an `X-Molock-Seed` request header takes
+    /// precedence for its request.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Custom metrics updated each time the endpoint serves a request.
+    #[serde(default)]
+    pub metrics: Vec<EndpointMetric>,
+    /// Values kept from each served request for later requests to match on and render.
+    #[serde(default)]
+    pub capture: Vec<Capture>,
+    /// Data files by name, CSV or a JSON/YAML array of objects, that templates read
+    /// as `{{datasets.<name>}}`; see [`crate::rules::datasets`].
+    #[serde(default)]
+    pub datasets: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// One step of a response `transform` chain; exactly one kind (`headers`, `replace`,
+/// `canonical_json`, `compress` or `custom`) is set.
+///
+/// ```yaml
+/// transform:
+///   - headers: {X-Served-By: molock}
+///   - replace: {pattern: '"internal_id":\s*\d+,?', with: ""}
+///   - canonical_json: {sort_arrays: true}
+///   - compress: [br, gzip]
+///   - custom: {kind: stamp, config: {key: k1}}
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseTransform {
+    /// Headers set on the response, replacing any of the same name.
+    #[serde(default)]
+    pub headers: Option<HeaderList>,
+    /// Rewrites the text body with a regex replacement.
+    #[serde(default)]
+    pub replace: Option<BodyRewrite>,
+    /// Rewrites a JSON body with sorted keys and fixed formatting.
+    #[serde(default)]
+    pub canonical_json: Option<CanonicalJson>,
+    /// Compresses the body with the first of these codings the client accepts.
+    #[serde(default)]
+    pub compress: Option<Vec<ContentCoding>>,
+    /// A transformer kind registered through `rules::transform::register`.
+    #[serde(default)]
+    pub custom: Option<CustomTransform>,
+}
+
+/// Replaces every match of `pattern` in the body; `with` may refer to capture
+/// groups as `$1` or `${name}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyRewrite {
+    pub pattern: String,
+    #[serde(default)]
+    pub with: String,
+}
+
+/// How a `canonical_json` step writes JSON bodies, so clients that snapshot responses
+/// see the same bytes for the same data. Object keys are always sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CanonicalJson {
+    /// Also orders array elements, by their canonical JSON text.
+    #[serde(default)]
+    pub sort_arrays: bool,
+    /// Indents by two spaces, one value per line, instead of writing compact JSON.
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentCoding {
+    Gzip,
+    Deflate,
+    Br,
+}
+
+impl ContentCoding {
+    /// The coding's `Content-Encoding` token.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+            ContentCoding::Br => "br",
+        }
+    }
+}
+
+/// A use of a custom transformer kind, with the configuration it is built from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomTransform {
+    pub kind: String,
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+/// One simulated node behind an endpoint. Clients can address it directly with an
+/// `X-Molock-Instance` header; its health is toggled through `/__admin/instances`.
+///
+/// ```yaml
+/// instances:
+///   - {name: node-a, weight: 2}
+///   - {name: node-b}
+///   - {name: node-c, healthy: false}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointInstance {
+    pub name: String,
+    /// Share of the rotation relative to the other instances; 0 only serves
+    /// requests addressed to it.
+    #[serde(default = "default_instance_weight")]
+    pub weight: u32,
+    /// Health at startup.
+    #[serde(default = "default_enabled")]
+    pub healthy: bool,
+}
+
+fn default_instance_weight() -> u32 {
+    1
+}
+
+/// Serves a list of `total` items in pages of `page_size`, addressed by opaque cursors
+/// passed back in the `cursor_param` query parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pagination {
+    #[serde(default = "default_page_size")]
+    pub page_size: u64,
+    pub total: u64,
+    #[serde(default = "default_cursor_param")]
+    pub cursor_param: String,
+}
+
+fn default_page_size() -> u64 {
+    20
+}
+
+fn default_cursor_param() -> String {
+    "cursor".to_string()
+}
+
+/// A business-level metric an endpoint updates on every hit, e.g.
+///
+/// ```yaml
+/// metrics:
+///   - name: orders_created_total
+///     increment: 1
+///     labels: {region: "{{query.region}}"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointMetric {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub kind: MetricKind,
+    #[serde(default = "default_increment")]
+    pub increment: f64,
+    /// Label values are templates rendered against the request, plus `{{status}}`.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+fn default_increment() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricKind {
+    /// Only ever goes up.
+    #[default]
+    Counter,
+    /// Goes up or down by `increment`, e.g. `-1` for a cancellation endpoint.
+    Gauge,
+}
+
+/// Keeps a value from the request in a named slot, e.g.
+///
+/// ```yaml
+/// capture:
+///   - slot: "order-{{body.id}}"
+///   - slot: last_customer
+///     json_path: $.customer.id
+/// ```
+///
+/// At most one source is set; without one the whole body is kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Capture {
+    /// The slot name, a template rendered against the request.
+    pub slot: String,
+    /// A JSONPath into the JSON request body; the first node it selects is kept.
+    #[serde(default)]
+    pub json_path: Option<String>,
+    /// A request header, by case-insensitive name.
+    #[serde(default)]
+    pub header: Option<String>,
+    /// A parameter of the endpoint path.
+    #[serde(default)]
+    pub path_param: Option<String>,
+    /// A query string parameter, kept after percent-decoding.
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+/// Caps on what an endpoint's templates may render, so a runaway template cannot
+/// exhaust a shared instance.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseLimits {
+    #[serde(default)]
+    pub max_body_size: Option<ByteSize>,
+    /// Applies to each rendered header value.
+    #[serde(default)]
+    pub max_header_size: Option<ByteSize>,
+    #[serde(default)]
+    pub on_exceed: Oversize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Oversize {
+    /// Fail the request with an error naming the limit.
+    #[default]
+    Fail,
+    /// Cut the rendered value down to the limit.
+    Truncate,
+}
+
+/// Adds `Cache-Control`, `Age` and an `ETag` that only changes every `ttl`, and
+/// answers `304 Not Modified` to an `If-None-Match` naming the current `ETag`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheSimulation {
+    /// How long a representation stays fresh, e.g. `60s`.
+    pub ttl: String,
+    /// Marks responses `private` instead of `public`.
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// Credentials an endpoint requires as an `Authorization: Basic` header. Requests
+/// without them, or with others, get `status` and a `WWW-Authenticate` challenge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+    #[serde(default = "default_realm")]
+    pub realm: String,
+    #[serde(default = "default_unauthorized_status")]
+    pub status: u16,
+    /// JSON body of the rejection; a generic error when unset.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+fn default_realm() -> String {
+    "molock".to_string()
+}
+
+fn default_unauthorized_status() -> u16 {
+    401
+}
+
+/// Bounds how long an endpoint may take to produce a response, answering with
+/// `status` (`504` by default) once `after` has passed.
+///
+/// The timeout can only interrupt the endpoint while it is waiting, i.e. during
+/// delays, latency profiles and proxying; template rendering runs to completion
+/// and is bounded by the response size limits instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointTimeout {
+    /// e.g. `2s` or `500ms`.
+    pub after: DurationValue,
+    #[serde(default = "default_timeout_status")]
+    pub status: u16,
+    /// JSON body of the timeout response; a generic error when unset.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+fn default_timeout_status() -> u16 {
+    504
+}
+
+/// Integrity headers computed over the response body as sent (after charset
+/// encoding), so clients that verify payloads can be tested without hardcoding
+/// hashes in the config.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseSigning {
+    /// Adds `Content-MD5` with the base64 MD5 of the body.
+    #[serde(default)]
+    pub content_md5: bool,
+    /// Adds an RFC 3230 `Digest` header, e.g. `Digest: SHA-256=<base64>`.
+    #[serde(default)]
+    pub digest: Option<DigestAlgorithm>,
+    #[serde(default)]
+    pub hmac: Option<HmacSignature>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DigestAlgorithm {
+    #[serde(rename = "sha-256")]
+    Sha256,
+    #[serde(rename = "sha-512")]
+    Sha512,
+}
+
+/// An HMAC of the body keyed with `secret`, sent in `header` as
+/// `<prefix><signature>`, e.g. `X-Hub-Signature-256: sha256=<hex>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HmacSignature {
+    pub header: String,
+    pub secret: String,
+    #[serde(default)]
+    pub algorithm: HmacAlgorithm,
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+    #[serde(default)]
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HmacAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// A size in bytes, given as a number or with a `KB`/`MB`/`GB` suffix (powers of 1024).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "ByteSizeValue", into = "u64")]
+pub struct ByteSize(pub u64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ByteSizeValue {
+    Bytes(u64),
+    Text(String),
+}
+
+impl TryFrom<ByteSizeValue> for ByteSize {
+    type Error = String;
+
+    fn try_from(value: ByteSizeValue) -> Result<Self, Self::Error> {
+        match value {
+            ByteSizeValue::Bytes(bytes) => Ok(ByteSize(bytes)),
+            ByteSizeValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl std::str::FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let lower = value.trim().to_ascii_lowercase();
+        let (number, multiplier) = match lower.len().checked_sub(2).map(|i| lower.split_at(i)) {
+            Some((number, "kb")) => (number, 1024),
+            Some((number, "mb")) => (number, 1024 * 1024),
+            Some((number, "gb")) => (number, 1024 * 1024 * 1024),
+            _ => (lower.as_str(), 1),
+        };
+        number
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(multiplier))
+            .map(ByteSize)
+            .ok_or_else(|| format!("invalid size `{}`", value))
+    }
+}
+
+impl From<ByteSize> for u64 {
+    fn from(value: ByteSize) -> Self {
+        value.0
+    }
+}
+
+/// Restricts an endpoint to requests with a given JSON body. Exactly one of the
+/// fields is set:
+///
+/// ```yaml
+/// body_match:
+///   partial: {"payment": {"method": "card"}}
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyMatch {
+    /// The body must equal this JSON value; key order and whitespace are ignored.
+    #[serde(default)]
+    pub exact: Option<serde_json::Value>,
+    /// Every field given must be present with an equal value; extra fields in the
+    /// body are ignored, at any depth.
+    #[serde(default)]
+    pub partial: Option<serde_json::Value>,
+}
+
+/// Validates request bodies against a JSON Schema before any response is selected,
+/// answering invalid requests with field-level errors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyValidation {
+    pub schema: serde_json::Value,
+    #[serde(default = "default_validation_status")]
+    pub status: u16,
+    /// Body template for invalid requests. `{{errors}}` expands to a JSON array of
+    /// `{"field", "message"}` objects and `{{error_count}}` to its length.
+    #[serde(default = "default_validation_envelope")]
+    pub envelope: String,
+}
+
+fn default_validation_status() -> u16 {
+    422
+}
+
+fn default_validation_envelope() -> String {
+    r#"{"errors": {{errors}}}"#.to_string()
+}
+
+/// How one response is picked when several non-default responses apply to a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// The first applicable response in configuration order.
+    FirstMatch,
+    /// A random pick weighted by `probability`. Responses without a `probability`
+    /// share whatever weight the others leave unclaimed equally, or weigh the same
+    /// when none of them sets one.
+    #[default]
+    Weighted,
+    /// Cycle through the applicable responses on successive requests, separately for
+    /// each state key (see `state_key`).
+    RoundRobin,
+}
+
+/// What happens when an endpoint matches but none of its responses' conditions pass
+/// and it has no default response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "FallthroughValue", into = "String")]
+pub enum Fallthrough {
+    /// Fail the request (500), as an endpoint without a usable response always did.
+    #[default]
+    Default,
+    /// Continue matching with the next endpoint whose method and path match.
+    NextEndpoint,
+    /// Answer `404 Not Found`.
+    NotFound,
+}
+
+/// Accepts both `fallthrough: next_endpoint` and an unquoted `fallthrough: 404`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FallthroughValue {
+    Status(u16),
+    Name(String),
+}
+
+impl TryFrom<FallthroughValue> for Fallthrough {
+    type Error = String;
+
+    fn try_from(value: FallthroughValue) -> Result<Self, Self::Error> {
+        match value {
+            FallthroughValue::Status(404) => Ok(Fallthrough::NotFound),
+            FallthroughValue::Name(name) => match name.as_str() {
+                "default" => Ok(Fallthrough::Default),
+                "next_endpoint" => Ok(Fallthrough::NextEndpoint),
+                "404" => Ok(Fallthrough::NotFound),
+                other => Err(format!(
+                    "invalid fallthrough `{}`, expected `next_endpoint`, `404` or `default`",
+                    other
+                )),
+            },
+            FallthroughValue::Status(status) => Err(format!(
+                "invalid fallthrough `{}`, expected `next_endpoint`, `404` or `default`",
+                status
+            )),
+        }
+    }
+}
+
+impl From<Fallthrough> for String {
+    fn from(value: Fallthrough) -> Self {
+        match value {
+            Fallthrough::Default => "default",
+            Fallthrough::NextEndpoint => "next_endpoint",
+            Fallthrough::NotFound => "404",
+        }
+        .to_string()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Response {
     pub status: u16,
     #[serde(default)]
     pub delay: Option<Delay>,
+    /// A delay computed from the request, e.g. `"body.items.length * 10ms"`; see
+    /// [`crate::rules::delay_expr`]. Cannot be combined with `delay`.
+    #[serde(default)]
+    pub delay_expr: Option<String>,
     #[serde(default)]
     pub body: Option<String>,
+    /// A JSON Schema a random body is generated from on every request, in place of
+    /// `body`; see [`crate::rules::schema_body`].
+    #[serde(default)]
+    pub body_schema: Option<serde_json::Value>,
     #[serde(default)]
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderList,
     #[serde(default)]
     pub condition: Option<String>,
     #[serde(default)]
     pub probability: Option<f64>,
     #[serde(default)]
     pub default: bool,
+    /// Deliberately malformed output, for testing client error handling.
+    #[serde(default)]
+    pub fault: Option<Fault>,
+    /// Request predicates that must all hold, alongside `condition`, for this
+    /// response to be a candidate.
+    #[serde(default)]
+    pub matchers: Vec<RequestMatcher>,
+}
+
+/// A single request predicate; exactly one kind (`json_path`, `content_type`,
+/// `xpath`, `form`, `multipart`, `body_size`, `header`, `query`, `path`, `client_ip`,
+/// `jwt`, `graphql`, `custom`, `all_of`, `any_of` or `not`) is set. `header` and `query`
+/// take at most one of `equals`, `not_equals` or `absent`, and otherwise require presence.
+/// `all_of`, `any_of` and `not` combine other matchers into a tree.
+///
+/// ```yaml
+/// matchers:
+///   - json_path: '$.order.type == "express"'
+///   - content_type: application/json
+///   - xpath: "//*[local-name()='GetUser']/id = '42'"
+///   - form: {grant_type: client_credentials, scope: "~read( write)?"}
+///   - multipart: {field: avatar, filename: "~.*\\.png", content_type: image/png}
+///   - body_size: {min: 1MB}
+///   - {header: X-Debug, absent: true}
+///   - {query: version, not_equals: "2"}
+///   - client_ip: [10.0.0.0/8, 192.168.1.20, "fd00::/8"]
+///   - jwt: {claims: {sub: alice, scope: "~.*\\badmin\\b.*"}, secret: s3cr3t}
+///   - graphql: {operation_type: query, operation_name: GetUser, variables: {id: "42"}}
+///   - custom: {kind: tenant, config: {id: acme}}
+///   - any_of:
+///       - {header: X-Beta, equals: "1"}
+///       - all_of: [{path: "~/v2/.*"}, {not: {query: legacy}}]
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequestMatcher {
+    /// A JSONPath into the JSON request body, optionally compared with a JSON
+    /// literal using `==`, `!=`, `<`, `<=`, `>` or `>=`. A bare path matches when it
+    /// selects anything.
+    #[serde(default)]
+    pub json_path: Option<String>,
+    /// The request's media type, e.g. `application/xml` or `text/*`, ignoring
+    /// parameters such as `charset`.
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// An XPath 1.0 expression over the XML request body. Node-sets match when not
+    /// empty, other results by XPath's `boolean()` rules.
+    #[serde(default)]
+    pub xpath: Option<String>,
+    /// Fields of a form-encoded request body and the value each must have; a value
+    /// starting with `~` is a regex the whole field value has to match.
+    #[serde(default)]
+    pub form: Option<HashMap<String, String>>,
+    /// A part of a `multipart/form-data` body.
+    #[serde(default)]
+    pub multipart: Option<MultipartMatcher>,
+    /// Bounds on the request body's size.
+    #[serde(default)]
+    pub body_size: Option<BodySize>,
+    /// A request header, by case-insensitive name.
+    #[serde(default)]
+    pub header: Option<String>,
+    /// A query string parameter, compared after percent-decoding.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// A slot filled by an endpoint's `capture`, named by a template rendered against
+    /// the request, e.g. `order-{{id}}`.
+    #[serde(default)]
+    pub captured: Option<String>,
+    /// The `header`, `query` or `captured` value must be this.
+    #[serde(default)]
+    pub equals: Option<String>,
+    /// The `header`, `query` or `captured` value must be missing or another one.
+    #[serde(default)]
+    pub not_equals: Option<String>,
+    /// The `header`, `query` or `captured` value must be missing.
+    #[serde(default)]
+    pub absent: bool,
+    /// CIDR ranges or single addresses, one of which the client address must fall in.
+    #[serde(default)]
+    pub client_ip: Option<Vec<String>>,
+    /// Claims of the `Authorization: Bearer` JWT.
+    #[serde(default)]
+    pub jwt: Option<JwtMatcher>,
+    /// The operation of a GraphQL request.
+    #[serde(default)]
+    pub graphql: Option<GraphqlMatcher>,
+    /// A matcher kind registered through `rules::custom_matchers::register`.
+    #[serde(default)]
+    pub custom: Option<CustomMatcher>,
+    /// The request path, literally or, prefixed with `~`, as an anchored regex.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Matches when every nested matcher does.
+    #[serde(default)]
+    pub all_of: Option<Vec<RequestMatcher>>,
+    /// Matches when at least one nested matcher does.
+    #[serde(default)]
+    pub any_of: Option<Vec<RequestMatcher>>,
+    /// Matches when the nested matcher does not.
+    #[serde(default)]
+    pub not: Option<Box<RequestMatcher>>,
+}
+
+/// Inclusive bounds on a request body's size, measured after decoding a compressed
+/// body rather than taken from `Content-Length`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodySize {
+    #[serde(default)]
+    pub min: Option<ByteSize>,
+    #[serde(default)]
+    pub max: Option<ByteSize>,
+}
+
+/// Expectations of a `multipart/form-data` part named `field`; each value set is
+/// compared literally or, prefixed with `~`, as an anchored regex.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MultipartMatcher {
+    pub field: String,
+    /// The part's content, e.g. a text field's value.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// The uploaded file's name; requires the part to be a file upload.
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+/// Expectations of a request's `Authorization: Bearer` JSON Web Token. Without `secret`
+/// or `jwks` the signature is not checked.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtMatcher {
+    /// Expected claim values by name (dotted for nested claims), compared literally or,
+    /// prefixed with `~`, as an anchored regex. Array claims match when any element does.
+    #[serde(default)]
+    pub claims: HashMap<String, String>,
+    /// Shared secret verifying HS256, HS384 and HS512 signatures.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Public keys verifying asymmetric signatures, picked by the token's `kid`.
+    #[serde(default)]
+    pub jwks: Option<jsonwebtoken::jwk::JwkSet>,
+    /// Whether the token must (`true`) or must not (`false`) be past its `exp`.
+    #[serde(default)]
+    pub expired: Option<bool>,
+}
+
+/// Expectations of a GraphQL request, read from its JSON body (or an
+/// `application/graphql` one) or from the `query`, `operationName` and `variables`
+/// parameters of a `GET`. Names and values compare literally or, prefixed with `~`,
+/// as an anchored regex.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GraphqlMatcher {
+    /// The name of the operation executed: the one `operationName` picks, or the only
+    /// one in the document.
+    #[serde(default)]
+    pub operation_name: Option<String>,
+    #[serde(default)]
+    pub operation_type: Option<GraphqlOperationType>,
+    /// A top-level field the operation selects, such as `createOrder`.
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Expected variable values by name (dotted for nested ones). Array variables
+    /// match when any element does.
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphqlOperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+/// A use of a custom matcher kind, with the configuration it is evaluated against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomMatcher {
+    pub kind: String,
+    #[serde(default)]
+    pub config: serde_json::Value,
+}
+
+/// Response headers in configuration order, allowing a name to repeat.
+///
+/// In YAML a header maps to either a single value or a list of values, each sent as
+/// its own header line:
+///
+/// ```yaml
+/// headers:
+///   Content-Type: application/json
+///   Set-Cookie: ["session=abc; Path=/", "theme=dark"]
+/// ```
+///
+/// Names keep their configured casing here, but actix lowercases header names on
+/// the wire, so responses cannot reproduce unusual capitalizations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderList {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the first value of the header, compared case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns every value of the header in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the header, replacing any existing values.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.entries
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+        self.entries.push((name, value.into()));
+    }
+
+    /// Adds another value for the header, keeping existing ones.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// The `charset` parameter of the `Content-Type` header, if any.
+    pub fn content_charset(&self) -> Option<&str> {
+        self.get("Content-Type")?
+            .split(';')
+            .skip(1)
+            .find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.entries.iter_mut().map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl IntoIterator for HeaderList {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for HeaderList {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Self::new();
+        for (key, value) in iter {
+            headers.append(key, value);
+        }
+        headers
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl<'de> Deserialize<'de> for HeaderList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HeaderListVisitor;
+
+        impl<'de> Visitor<'de> for HeaderListVisitor {
+            type Value = HeaderList;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of header names to a value or a list of values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<HeaderList, A::Error> {
+                let mut headers = HeaderList::new();
+                while let Some((name, values)) = map.next_entry::<String, HeaderValues>()? {
+                    match values {
+                        HeaderValues::One(value) => headers.append(name, value),
+                        HeaderValues::Many(values) => {
+                            for value in values {
+                                headers.append(name.clone(), value);
+                            }
+                        }
+                    }
+                }
+                Ok(headers)
+            }
+        }
+
+        deserializer.deserialize_map(HeaderListVisitor)
+    }
+}
+
+impl Serialize for HeaderList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut names: Vec<&String> = Vec::new();
+        for (name, _) in &self.entries {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(names.len()))?;
+        for name in names {
+            let values: Vec<&String> = self
+                .entries
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .collect();
+            match values.as_slice() {
+                [value] => map.serialize_entry(name, value)?,
+                _ => map.serialize_entry(name, &values)?,
+            }
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// Declare `Content-Encoding: gzip` but send the body uncompressed.
+    GzipPlain,
+    /// Declare `Content-Encoding: gzip` and send only the first half of the gzip stream.
+    GzipTruncated,
+    /// Declare a `Content-Length` of half the body and send all of it.
+    ContentLengthShort,
+    /// Declare a `Content-Length` beyond the body and close the connection after it.
+    ContentLengthLong,
+    /// Declare both `Transfer-Encoding: chunked` and a `Content-Length`, sending a chunked body.
+    ChunkedWithLength,
+    /// Send neither `Content-Length` nor chunked encoding, ending the body by closing.
+    Unframed,
+}
+
+impl Fault {
+    /// The name used in config, metrics and logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::GzipPlain => "gzip_plain",
+            Fault::GzipTruncated => "gzip_truncated",
+            Fault::ContentLengthShort => "content_length_short",
+            Fault::ContentLengthLong => "content_length_long",
+            Fault::ChunkedWithLength => "chunked_with_length",
+            Fault::Unframed => "unframed",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -163,18 +1512,7 @@ impl Delay {
     pub fn parse_duration(&self) -> anyhow::Result<Duration> {
         match self {
             Delay::Fixed(delay_str) => parse_duration_str(delay_str),
-            Delay::Range(range_str) => {
-                let parts: Vec<&str> = range_str.split('-').collect();
-                if parts.len() != 2 {
-                    anyhow::bail!("Invalid delay range format: {}", range_str);
-                }
-                let min = parse_duration_str(parts[0])?;
-                let max = parse_duration_str(parts[1])?;
-                if min > max {
-                    anyhow::bail!("Min delay cannot be greater than max delay");
-                }
-                Ok(min)
-            }
+            Delay::Range(range_str) => Ok(parse_duration_range(range_str)?.0),
         }
     }
 
@@ -184,23 +1522,142 @@ impl Delay {
                 let duration = parse_duration_str(delay_str)?;
                 Ok((duration, duration))
             }
-            Delay::Range(range_str) => {
-                let parts: Vec<&str> = range_str.split('-').collect();
-                if parts.len() != 2 {
-                    anyhow::bail!("Invalid delay range format: {}", range_str);
-                }
-                let min = parse_duration_str(parts[0])?;
-                let max = parse_duration_str(parts[1])?;
-                if min > max {
-                    anyhow::bail!("Min delay cannot be greater than max delay");
+            Delay::Range(range_str) => parse_duration_range(range_str),
+        }
+    }
+}
+
+/// Parses a `"min-max"` duration range such as `"100ms-500ms"`. A bare number as the
+/// lower bound takes the unit of the upper one, so `"0-20ms"` is accepted.
+pub fn parse_duration_range(range_str: &str) -> anyhow::Result<(Duration, Duration)> {
+    let parts: Vec<&str> = range_str.split('-').collect();
+    if parts.len() != 2 {
+        anyhow::bail!("Invalid delay range format: {}", range_str);
+    }
+    let max = parse_duration_str(parts[1])?;
+    let min = match parts[0].trim().parse::<u64>() {
+        Ok(value) if parts[1].trim().ends_with("ms") => Duration::from_millis(value),
+        Ok(value) => Duration::from_secs(value),
+        Err(_) => parse_duration_str(parts[0])?,
+    };
+    if min > max {
+        anyhow::bail!("Min delay cannot be greater than max delay");
+    }
+    Ok((min, max))
+}
+
+/// A latency distribution given as percentiles, e.g. `"p50 30ms, p95 200ms, p99 1s"`.
+///
+/// Samples are interpolated linearly between consecutive percentiles, starting from
+/// zero at p0. Anything above the highest percentile gets its value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LatencyProfile {
+    points: Vec<(f64, Duration)>,
+}
+
+impl LatencyProfile {
+    /// Returns the latency at quantile `q` (between 0.0 and 1.0).
+    pub fn sample(&self, q: f64) -> Duration {
+        let percentile = q.clamp(0.0, 1.0) * 100.0;
+        let (mut lower_p, mut lower_d) = (0.0, Duration::ZERO);
+
+        for &(p, d) in &self.points {
+            if percentile <= p {
+                let fraction = (percentile - lower_p) / (p - lower_p);
+                return lower_d + (d - lower_d).mul_f64(fraction);
+            }
+            (lower_p, lower_d) = (p, d);
+        }
+
+        lower_d
+    }
+}
+
+impl TryFrom<String> for LatencyProfile {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut points: Vec<(f64, Duration)> = Vec::new();
+
+        for entry in value.split(',') {
+            let (percentile, duration) = entry
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| format!("invalid latency profile entry `{}`", entry.trim()))?;
+            let percentile: f64 = percentile
+                .strip_prefix('p')
+                .and_then(|p| p.parse().ok())
+                .filter(|p| *p > 0.0 && *p <= 100.0)
+                .ok_or_else(|| format!("invalid percentile `{}`", percentile))?;
+            let duration = parse_duration_str(duration).map_err(|e| e.to_string())?;
+
+            if let Some(&(last_p, last_d)) = points.last() {
+                if percentile <= last_p || duration < last_d {
+                    return Err(format!(
+                        "latency profile `{}` must list increasing percentiles with non-decreasing latencies",
+                        value
+                    ));
                 }
-                Ok((min, max))
             }
+            points.push((percentile, duration));
         }
+
+        Ok(Self { points })
     }
 }
 
-fn parse_duration_str(duration_str: &str) -> anyhow::Result<Duration> {
+impl From<LatencyProfile> for String {
+    fn from(value: LatencyProfile) -> Self {
+        value
+            .points
+            .iter()
+            .map(|(p, d)| format!("p{} {}ms", p, d.as_millis()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A duration parsed once when the config is loaded, keeping its original
+/// spelling (e.g. `500ms`) for messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DurationValue {
+    text: String,
+    duration: Duration,
+}
+
+impl DurationValue {
+    pub fn as_duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl TryFrom<String> for DurationValue {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let duration = parse_duration_str(&value).map_err(|e| e.to_string())?;
+        Ok(Self {
+            text: value,
+            duration,
+        })
+    }
+}
+
+impl From<DurationValue> for String {
+    fn from(value: DurationValue) -> Self {
+        value.text
+    }
+}
+
+impl fmt::Display for DurationValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+pub fn parse_duration_str(duration_str: &str) -> anyhow::Result<Duration> {
     let duration_str = duration_str.trim();
     if let Some(stripped) = duration_str.strip_suffix("ms") {
         let ms = stripped
@@ -224,6 +1681,16 @@ impl Default for ServerConfig {
             workers: default_workers(),
             host: default_host(),
             max_request_size: default_max_request_size(),
+            debug_headers: false,
+            journal: None,
+            jitter: None,
+            transform: Vec::new(),
+            crash_dir: None,
+            strict: StrictMode::default(),
+            connection: ConnectionOptions::default(),
+            limits: default_response_limits(),
+            max_computed_delay: default_max_computed_delay(),
+            dry_run: false,
         }
     }
 }
@@ -242,6 +1709,19 @@ impl Default for TelemetryConfig {
             timeout_seconds: default_timeout_seconds(),
             export_batch_size: default_export_batch_size(),
             export_timeout_millis: default_export_timeout_millis(),
+            max_route_labels: default_max_route_labels(),
+            statsd: None,
+            export_breaker: ExportBreakerConfig::default(),
+        }
+    }
+}
+
+impl Default for ExportBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_breaker_failure_threshold(),
+            probe_interval_seconds: default_breaker_probe_interval_seconds(),
+            buffered_spans: default_breaker_buffered_spans(),
         }
     }
 }
@@ -276,12 +1756,162 @@ mod tests {
         assert!(delay.parse_range().is_err());
     }
 
+    #[test]
+    fn test_parse_duration_range() {
+        assert_eq!(
+            parse_duration_range("0-20ms").unwrap(),
+            (Duration::ZERO, Duration::from_millis(20))
+        );
+        assert_eq!(
+            parse_duration_range("1-2s").unwrap(),
+            (Duration::from_secs(1), Duration::from_secs(2))
+        );
+        assert_eq!(
+            parse_duration_range("500ms-1s").unwrap(),
+            (Duration::from_millis(500), Duration::from_secs(1))
+        );
+        assert!(parse_duration_range("20ms").is_err());
+        assert!(parse_duration_range("30ms-20ms").is_err());
+    }
+
+    #[test]
+    fn test_latency_profile_sample() {
+        let profile = LatencyProfile::try_from("p50 30ms, p95 200ms, p99 1s".to_string()).unwrap();
+
+        assert_eq!(profile.sample(0.0), Duration::ZERO);
+        assert_eq!(profile.sample(0.25), Duration::from_millis(15));
+        assert_eq!(profile.sample(0.5), Duration::from_millis(30));
+        assert_eq!(profile.sample(0.95), Duration::from_millis(200));
+        assert_eq!(profile.sample(0.97), Duration::from_millis(600));
+        assert_eq!(profile.sample(1.0), Duration::from_secs(1));
+        assert_eq!(String::from(profile), "p50 30ms, p95 200ms, p99 1000ms");
+    }
+
+    #[test]
+    fn test_latency_profile_invalid() {
+        let parse = |value: &str| LatencyProfile::try_from(value.to_string());
+
+        assert!(parse("p50").is_err());
+        assert!(parse("50 30ms").is_err());
+        assert!(parse("p150 30ms").is_err());
+        assert!(parse("p50 30").is_err());
+        assert!(parse("p95 200ms, p50 30ms").is_err());
+        assert!(parse("p50 300ms, p95 200ms").is_err());
+    }
+
+    #[test]
+    fn test_header_list_yaml() {
+        let yaml = "Content-Type: application/json\nSet-Cookie: [\"a=1\", \"b=2\"]\nVary: Accept\n";
+        let headers: HeaderList = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers.get("content-type").unwrap(), "application/json");
+        let cookies: Vec<&String> = headers.get_all("Set-Cookie").collect();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+
+        let serialized = serde_yaml::to_string(&headers).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<HeaderList>(&serialized).unwrap(),
+            headers
+        );
+    }
+
+    #[test]
+    fn test_header_list_insert_and_append() {
+        let mut headers = HeaderList::new();
+        headers.append("Vary", "Accept");
+        headers.append("vary", "Origin");
+        assert_eq!(headers.get_all("VARY").count(), 2);
+
+        headers.insert("VARY", "*");
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("Vary").unwrap(), "*");
+    }
+
+    #[test]
+    fn test_header_list_content_charset() {
+        let mut headers = HeaderList::new();
+        assert_eq!(headers.content_charset(), None);
+
+        headers.insert("Content-Type", "text/plain");
+        assert_eq!(headers.content_charset(), None);
+
+        headers.insert("content-type", "text/plain; Charset=\"Shift_JIS\"");
+        assert_eq!(headers.content_charset(), Some("Shift_JIS"));
+    }
+
+    #[test]
+    fn test_fallthrough_values() {
+        let parse = |value: &str| serde_yaml::from_str::<Fallthrough>(value);
+
+        assert_eq!(parse("next_endpoint").unwrap(), Fallthrough::NextEndpoint);
+        assert_eq!(parse("404").unwrap(), Fallthrough::NotFound);
+        assert_eq!(parse("'404'").unwrap(), Fallthrough::NotFound);
+        assert_eq!(parse("default").unwrap(), Fallthrough::Default);
+        assert!(parse("500").is_err());
+        assert!(parse("next").is_err());
+    }
+
+    #[test]
+    fn test_selection_strategy_values() {
+        let parse = |value: &str| serde_yaml::from_str::<SelectionStrategy>(value);
+
+        assert_eq!(parse("first_match").unwrap(), SelectionStrategy::FirstMatch);
+        assert_eq!(parse("weighted").unwrap(), SelectionStrategy::Weighted);
+        assert_eq!(parse("round_robin").unwrap(), SelectionStrategy::RoundRobin);
+        assert!(parse("random").is_err());
+        assert_eq!(SelectionStrategy::default(), SelectionStrategy::Weighted);
+    }
+
+    #[test]
+    fn test_workers_auto() {
+        let parse = |value: &str| serde_yaml::from_str::<ServerConfig>(value);
+
+        assert_eq!(parse("workers: 2").unwrap().workers, 2);
+        assert_eq!(parse("workers: auto").unwrap().workers, num_cpus::get());
+        assert!(parse("workers: many").is_err());
+        assert_eq!(parse("port: 9000").unwrap().workers, 4);
+    }
+
+    #[test]
+    fn test_connection_options() {
+        let parse = |value: &str| serde_yaml::from_str::<ServerConfig>(value);
+
+        assert_eq!(
+            parse("port: 9000").unwrap().connection,
+            ConnectionOptions::default()
+        );
+        assert!(parse("port: 9000").unwrap().connection.keep_alive);
+        assert_eq!(
+            parse("connection: {keep_alive: false, http10: true}")
+                .unwrap()
+                .connection,
+            ConnectionOptions {
+                keep_alive: false,
+                force_close: false,
+                http10: true,
+            }
+        );
+        assert!(parse("connection: {pipelining: false}").is_err());
+    }
+
+    #[test]
+    fn test_byte_size_values() {
+        let parse = |value: &str| serde_yaml::from_str::<ByteSize>(value);
+
+        assert_eq!(parse("512").unwrap(), ByteSize(512));
+        assert_eq!(parse("64KB").unwrap(), ByteSize(64 * 1024));
+        assert_eq!(parse("'1 mb'").unwrap(), ByteSize(1024 * 1024));
+        assert!(parse("lots").is_err());
+        assert!(parse("-1").is_err());
+    }
+
     #[test]
     fn test_default_config() {
         let config = Config::default();
         assert_eq!(config.server.port, 8080);
         assert_eq!(config.server.workers, 4);
-        assert_eq!(config.telemetry.enabled, true);
+        assert!(config.telemetry.enabled);
         assert_eq!(config.telemetry.log_level, "info");
     }
 }
@@ -0,0 +1,220 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Append-only JSON Lines journal of served requests.
+//!
+//! When `server.journal` is set, every request handled by the mock is written as one
+//! JSON object per line. The journal can be fed back into the `usage` and `coverage`
+//! subcommands after a test run.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    /// Name of the endpoint that served the request, if any matched.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    pub status: u16,
+    pub latency_ms: f64,
+}
+
+/// How many of the latest entries are kept in memory for crash reports.
+const RECENT_ENTRIES: usize = 20;
+
+enum Message {
+    Entry(JournalEntry),
+    /// Acknowledged once every entry sent before it is on disk.
+    Flush(Sender<()>),
+}
+
+/// Entries are handed to a background writer thread, so recording a request never
+/// waits on the file.
+pub struct Journal {
+    sender: Sender<Message>,
+    recent: Arc<Mutex<VecDeque<JournalEntry>>>,
+}
+
+impl Journal {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open journal file: {:?}", path.as_ref()))?;
+
+        let (sender, receiver) = mpsc::channel();
+        let recent = Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_ENTRIES)));
+        let writer_recent = recent.clone();
+        std::thread::Builder::new()
+            .name("molock-journal".to_string())
+            .spawn(move || write_entries(receiver, BufWriter::new(file), &writer_recent))
+            .context("Failed to start the journal writer")?;
+
+        Ok(Self { sender, recent })
+    }
+
+    pub fn record(&self, entry: JournalEntry) {
+        // The writer only goes away with the journal itself.
+        let _ = self.sender.send(Message::Entry(entry));
+    }
+
+    /// Blocks until every entry recorded so far has been written.
+    pub fn flush(&self) {
+        let (ack, done) = mpsc::channel();
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+
+    /// The latest entries written by this process, oldest first.
+    pub fn recent(&self) -> Vec<JournalEntry> {
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().cloned().collect()
+    }
+
+    pub fn read_entries<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<JournalEntry>> {
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open journal file: {:?}", path.as_ref()))?;
+
+        let mut entries = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.context("Failed to read journal file")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid journal entry on line {}", index + 1))?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Writes entries until the journal is dropped, flushing whenever the queue runs
+/// dry so a burst of requests costs one write.
+fn write_entries(
+    receiver: Receiver<Message>,
+    mut file: BufWriter<File>,
+    recent: &Mutex<VecDeque<JournalEntry>>,
+) {
+    while let Ok(message) = receiver.recv() {
+        let mut acks = Vec::new();
+        for message in std::iter::once(message).chain(receiver.try_iter()) {
+            match message {
+                Message::Entry(entry) => write_entry(&mut file, recent, entry),
+                Message::Flush(ack) => acks.push(ack),
+            }
+        }
+        if let Err(e) = file.flush() {
+            tracing::warn!(error = %e, "Failed to write journal entry");
+        }
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn write_entry(
+    file: &mut BufWriter<File>,
+    recent: &Mutex<VecDeque<JournalEntry>>,
+    entry: JournalEntry,
+) {
+    match serde_json::to_string(&entry) {
+        Ok(line) => {
+            if let Err(e) = writeln!(file, "{}", line) {
+                tracing::warn!(error = %e, "Failed to write journal entry");
+            }
+        }
+        Err(e) => tracing::warn!(error = %e, "Failed to serialize journal entry"),
+    }
+
+    let mut recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_ENTRIES {
+        recent.pop_front();
+    }
+    recent.push_back(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(endpoint: Option<&str>, status: u16) -> JournalEntry {
+        JournalEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            method: "GET".to_string(),
+            path: "/test".to_string(),
+            endpoint: endpoint.map(str::to_string),
+            status,
+            latency_ms: 1.5,
+        }
+    }
+
+    #[test]
+    fn test_record_and_read_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let journal = Journal::open(&path).unwrap();
+        journal.record(entry(Some("Test"), 200));
+        journal.record(entry(None, 500));
+        journal.flush();
+
+        let entries = Journal::read_entries(&path).unwrap();
+        assert_eq!(entries, vec![entry(Some("Test"), 200), entry(None, 500)]);
+    }
+
+    #[test]
+    fn test_recent_keeps_latest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::open(dir.path().join("journal.jsonl")).unwrap();
+
+        for status in 0..RECENT_ENTRIES as u16 + 5 {
+            journal.record(entry(None, status));
+        }
+        journal.flush();
+
+        let recent = journal.recent();
+        assert_eq!(recent.len(), RECENT_ENTRIES);
+        assert_eq!(recent.first().unwrap().status, 5);
+        assert_eq!(recent.last().unwrap().status, RECENT_ENTRIES as u16 + 4);
+    }
+
+    #[test]
+    fn test_read_entries_invalid_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        std::fs::write(&path, "{\"not\": \"an entry\"}\n").unwrap();
+
+        let result = Journal::read_entries(&path);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid journal entry on line 1"));
+    }
+}
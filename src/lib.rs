@@ -14,8 +14,14 @@
  * limitations under the License.
  */
 
+#[cfg(feature = "broker")]
+pub mod broker;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod config;
+pub mod journal;
 pub mod rules;
 pub mod server;
+pub mod smtp;
 pub mod telemetry;
 pub mod utils;
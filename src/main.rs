@@ -16,48 +16,133 @@
 
 use anyhow::Context;
 use arc_swap::ArcSwap;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use molock::config::ConfigLoader;
+use molock::journal::Journal;
+use molock::rules::usage::UsageReport;
 use molock::rules::RuleEngine;
-use molock::server::run_server;
+use molock::server::reload::Reloader;
+use molock::server::strict;
+use molock::server::summary::{self, RunSummary};
+use molock::server::{run_server, run_server_with_reload};
 use molock::telemetry::{init_telemetry, shutdown_telemetry};
 use molock::utils::shutdown_signal;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tracing::info;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(short, long, default_value = "config/molock-config.yaml")]
+    #[arg(
+        short,
+        long,
+        global = true,
+        default_value = "config/molock-config.yaml"
+    )]
     config: PathBuf,
 
     #[arg(long, default_value = "false")]
     hot_reload: bool,
+
+    /// Answer every request with an explanation of the match decision instead of the
+    /// configured response, without touching state, inboxes or delays
+    #[arg(long, default_value = "false")]
+    dry_run: bool,
+
+    /// After binding, answer one synthetic request per endpoint without side effects
+    /// and exit with an error if any fails to render
+    #[arg(long, default_value = "false")]
+    self_check: bool,
+
+    /// On shutdown, write a JSON summary of the run (requests, per-endpoint hits,
+    /// unmatched requests, injected faults, latency percentiles) to this file
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report per-endpoint hits from a request journal, listing endpoints never hit
+    Usage {
+        /// Journal file written by a previous run (see `server.journal`)
+        #[arg(long)]
+        from: PathBuf,
+    },
+    /// Compute the share of configured endpoints exercised by a run and fail below a threshold
+    Coverage {
+        /// Journal file written by the run (see `server.journal`)
+        #[arg(long)]
+        journal: PathBuf,
+
+        /// Minimum required coverage in percent
+        #[arg(long, default_value = "0")]
+        min: f64,
+    },
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let config = ConfigLoader::from_file(&args.config)
+    match &args.command {
+        Some(Command::Usage { from }) => return print_usage(&args.config, from),
+        Some(Command::Coverage { journal, min }) => {
+            return check_coverage(&args.config, journal, *min)
+        }
+        None => {}
+    }
+
+    let mut config = ConfigLoader::from_file(&args.config)
         .with_context(|| format!("Failed to load config from {:?}", args.config))?;
+    config.server.dry_run = args.dry_run;
 
     init_telemetry(&config.telemetry).await?;
 
-    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
-    let rule_engine_swap = Arc::new(ArcSwap::from(rule_engine.clone()));
+    let rule_engine = Arc::new(RuleEngine::from_config(&config));
+    let check_engine = rule_engine.clone();
+
+    if let Some(broker) = &config.broker {
+        start_broker(broker).await?;
+    }
 
-    if args.hot_reload {
-        start_hot_reload(&args.config, rule_engine_swap.clone()).await?;
+    let strict_mode = config.server.strict.clone();
+    if args.report.is_some() {
+        summary::enable();
     }
 
-    let server = run_server(config, rule_engine).await?;
+    let (server, serving) = if args.hot_reload {
+        let reloader = Arc::new(Reloader::new(
+            args.config.clone(),
+            config.clone(),
+            rule_engine,
+        ));
+        start_hot_reload(&args.config, reloader.clone())?;
+        let serving = reloader.engine();
+        (run_server_with_reload(config, reloader).await?, serving)
+    } else {
+        let serving = Arc::new(ArcSwap::from(rule_engine.clone()));
+        (run_server(config, rule_engine).await?, serving)
+    };
+
+    let server_handle = server.handle();
+    let server = tokio::spawn(server);
+
+    if args.self_check {
+        let report = check_engine.self_check().await;
+        println!("{}", report);
+        if !report.passed() {
+            server_handle.stop(false).await;
+            anyhow::bail!("Self-check failed for {} endpoint(s)", report.failures());
+        }
+    }
 
     info!("Molock server is running");
     info!("Press Ctrl+C to shutdown");
 
-    let server_handle = server.handle();
     tokio::select! {
         _ = server => {
             info!("Server stopped");
@@ -69,49 +154,158 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    if let Some(path) = &args.report {
+        match RunSummary::collect(&serving.load()).write(path) {
+            Ok(()) => info!(path = %path.display(), "Run summary written"),
+            Err(e) => {
+                tracing::error!(error = %e, path = %path.display(), "Failed to write run summary")
+            }
+        }
+    }
+
+    if strict_mode.enabled && strict::unmatched_count() > 0 {
+        tracing::error!(
+            unmatched = strict::unmatched_count(),
+            "Strict mode: requests hit endpoints nobody mocked"
+        );
+    }
+
     shutdown_telemetry().await;
 
+    if let Some(code) = strict::exit_code(&strict_mode) {
+        std::process::exit(code);
+    }
+
     Ok(())
 }
 
-#[cfg(feature = "hot-reload")]
-async fn start_hot_reload(
+fn load_journal_usage(
+    config_path: &PathBuf,
+    journal_path: &PathBuf,
+) -> anyhow::Result<UsageReport> {
+    let config = ConfigLoader::from_file(config_path)
+        .with_context(|| format!("Failed to load config from {:?}", config_path))?;
+    let entries = Journal::read_entries(journal_path)?;
+
+    Ok(UsageReport::from_journal(&config.endpoints, &entries))
+}
+
+fn print_usage(config_path: &PathBuf, journal_path: &PathBuf) -> anyhow::Result<()> {
+    let report = load_journal_usage(config_path, journal_path)?;
+
+    println!("{:>8}  {:<7} {:<40} NAME", "HITS", "METHOD", "PATH");
+    for endpoint in &report.endpoints {
+        println!(
+            "{:>8}  {:<7} {:<40} {}",
+            endpoint.hits, endpoint.method, endpoint.path, endpoint.name
+        );
+    }
+
+    let unused = report.unused();
+    println!();
+    println!(
+        "{} of {} endpoints were never hit",
+        unused.len(),
+        report.endpoints.len()
+    );
+    for endpoint in unused {
+        println!(
+            "  - {} ({} {})",
+            endpoint.name, endpoint.method, endpoint.path
+        );
+    }
+
+    Ok(())
+}
+
+fn check_coverage(
     config_path: &PathBuf,
-    rule_engine_swap: Arc<ArcSwap<RuleEngine>>,
+    journal_path: &PathBuf,
+    min_percent: f64,
 ) -> anyhow::Result<()> {
-    use notify::{RecommendedWatcher, RecursiveMode, Watcher};
-    use std::sync::mpsc;
-    use std::time::Duration;
+    let report = load_journal_usage(config_path, journal_path)?;
+    let coverage = report.coverage_percent();
+    let unused = report.unused();
 
-    let (tx, rx) = mpsc::channel();
-    let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1))?;
+    println!(
+        "Endpoint coverage: {:.1}% ({} of {} endpoints hit)",
+        coverage,
+        report.endpoints.len() - unused.len(),
+        report.endpoints.len()
+    );
+    for endpoint in &unused {
+        println!(
+            "  not hit: {} ({} {})",
+            endpoint.name, endpoint.method, endpoint.path
+        );
+    }
 
-    watcher.watch(config_path, RecursiveMode::NonRecursive)?;
+    if coverage < min_percent {
+        anyhow::bail!(
+            "Endpoint coverage {:.1}% is below the required minimum of {:.1}%",
+            coverage,
+            min_percent
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "hot-reload")]
+fn start_hot_reload(config_path: &Path, reloader: Arc<Reloader>) -> anyhow::Result<()> {
+    use notify::EventKind;
+    use std::collections::HashSet;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = tx.send(event);
+        }
+    })?;
+    // Editors save by replacing the file, which ends a watch on the file itself, so
+    // the directories of the config and of every file it mounts are watched.
+    let config_path = std::fs::canonicalize(config_path)?;
+    let override_path = ConfigLoader::override_path(&config_path);
+    let watched_files = move |config: &molock::config::Config| {
+        let mut files = vec![config_path.clone(), override_path.clone()];
+        files.extend(config.mounted_files.iter().cloned());
+        files
+    };
+    let mut watched_dirs = HashSet::new();
+    watch_dirs(
+        &mut watcher,
+        &mut watched_dirs,
+        &watched_files(&reloader.config()),
+    )?;
 
-    let config_path = config_path.clone();
     tokio::spawn(async move {
-        while let Ok(event) = rx.recv() {
-            match event {
-                notify::Event {
-                    kind: notify::EventKind::Modify(_),
-                    paths,
-                    ..
-                } => {
-                    if paths.iter().any(|p| p == &config_path) {
-                        info!("Configuration file modified, reloading...");
-                        match ConfigLoader::from_file(&config_path) {
-                            Ok(new_config) => {
-                                let new_engine = Arc::new(RuleEngine::new(new_config.endpoints));
-                                rule_engine_swap.store(new_engine);
-                                info!("Configuration reloaded successfully");
-                            }
-                            Err(e) => {
-                                tracing::error!("Failed to reload configuration: {}", e);
-                            }
-                        }
-                    }
-                }
-                _ => {}
+        // The watcher stops when dropped, so it lives as long as this task.
+        let mut watcher = watcher;
+        while let Some(event) = rx.recv().await {
+            let files = watched_files(&reloader.config());
+            if !matches!(
+                event.kind,
+                EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+            ) || !event.paths.iter().any(|path| files.contains(path))
+            {
+                continue;
+            }
+            // Editors emit several events per save; one reload covers them all.
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            while rx.try_recv().is_ok() {}
+
+            info!("Configuration file modified, reloading...");
+            match reloader.reload().await {
+                Ok(()) => info!("Configuration reloaded successfully"),
+                Err(e) => tracing::error!(
+                    "Failed to reload configuration, keeping the previous one: {:#}",
+                    e
+                ),
+            }
+            // The reloaded config may mount files from new directories.
+            let files = watched_files(&reloader.config());
+            if let Err(e) = watch_dirs(&mut watcher, &mut watched_dirs, &files) {
+                tracing::warn!("Failed to watch mounted config files: {}", e);
             }
         }
     });
@@ -119,11 +313,37 @@ async fn start_hot_reload(
     Ok(())
 }
 
+/// Watches the directories holding `files` that are not watched yet.
+#[cfg(feature = "hot-reload")]
+fn watch_dirs(
+    watcher: &mut impl notify::Watcher,
+    watched: &mut std::collections::HashSet<PathBuf>,
+    files: &[PathBuf],
+) -> notify::Result<()> {
+    for file in files {
+        let dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        if !watched.contains(&dir) {
+            watcher.watch(&dir, notify::RecursiveMode::NonRecursive)?;
+            watched.insert(dir);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "broker")]
+async fn start_broker(config: &molock::config::BrokerConfig) -> anyhow::Result<()> {
+    molock::broker::Broker::start(config).await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "broker"))]
+async fn start_broker(_config: &molock::config::BrokerConfig) -> anyhow::Result<()> {
+    tracing::warn!("`broker` is configured but the broker feature is not enabled");
+    Ok(())
+}
+
 #[cfg(not(feature = "hot-reload"))]
-async fn start_hot_reload(
-    _config_path: &PathBuf,
-    _rule_engine_swap: Arc<ArcSwap<RuleEngine>>,
-) -> anyhow::Result<()> {
+fn start_hot_reload(_config_path: &Path, _reloader: Arc<Reloader>) -> anyhow::Result<()> {
     info!("Hot reload feature is not enabled");
     Ok(())
 }
@@ -0,0 +1,161 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Retry detection for the `attempt` condition variable.
+
+use dashmap::DashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Headers through which a client can state its attempt number directly.
+const ATTEMPT_HEADERS: [&str; 2] = ["retry-attempt", "x-attempt"];
+
+/// How long an identical request still counts as a retry when the endpoint sets no
+/// `retry_window`.
+pub const DEFAULT_RETRY_WINDOW: Duration = Duration::from_secs(30);
+
+struct LastRequest {
+    fingerprint: u64,
+    attempt: u64,
+    seen: Instant,
+}
+
+/// Counts consecutive identical requests per client so that stateless clients
+/// retrying the same call can be told apart from new calls.
+#[derive(Clone, Default)]
+pub struct AttemptTracker {
+    last: Arc<DashMap<String, LastRequest>>,
+}
+
+impl AttemptTracker {
+    /// The attempt number of a request, starting at 1.
+    ///
+    /// A `Retry-Attempt` or `X-Attempt` header wins. Otherwise a request identical
+    /// to the client's previous one (method, path, query and body) within `window`
+    /// is the next attempt, and anything else starts again at 1.
+    pub fn attempt(
+        &self,
+        client: &str,
+        headers: &HashMap<String, String>,
+        request: (&str, &str, &str, Option<&str>),
+        window: Duration,
+    ) -> u64 {
+        self.next(client, headers, request, window, true)
+    }
+
+    /// Like [`attempt`](Self::attempt), without remembering the request.
+    pub fn peek(
+        &self,
+        client: &str,
+        headers: &HashMap<String, String>,
+        request: (&str, &str, &str, Option<&str>),
+        window: Duration,
+    ) -> u64 {
+        self.next(client, headers, request, window, false)
+    }
+
+    fn next(
+        &self,
+        client: &str,
+        headers: &HashMap<String, String>,
+        request: (&str, &str, &str, Option<&str>),
+        window: Duration,
+        record: bool,
+    ) -> u64 {
+        if let Some(attempt) = ATTEMPT_HEADERS
+            .iter()
+            .find_map(|name| headers.get(*name)?.trim().parse().ok())
+        {
+            return attempt;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        request.hash(&mut hasher);
+        let fingerprint = hasher.finish();
+        let now = Instant::now();
+
+        if !record {
+            return match self.last.get(client) {
+                Some(last)
+                    if last.fingerprint == fingerprint
+                        && now.duration_since(last.seen) <= window =>
+                {
+                    last.attempt + 1
+                }
+                _ => 1,
+            };
+        }
+
+        let mut last = self.last.entry(client.to_string()).or_insert(LastRequest {
+            fingerprint,
+            attempt: 0,
+            seen: now,
+        });
+
+        if last.fingerprint == fingerprint && now.duration_since(last.seen) <= window {
+            last.attempt += 1;
+        } else {
+            last.fingerprint = fingerprint;
+            last.attempt = 1;
+        }
+        last.seen = now;
+        last.attempt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_requests_count_as_retries() {
+        let tracker = AttemptTracker::default();
+        let headers = HashMap::new();
+        let charge = ("POST", "/charge", "", Some(r#"{"amount":10}"#));
+        let other = ("POST", "/charge", "", Some(r#"{"amount":20}"#));
+
+        assert_eq!(
+            tracker.attempt("a", &headers, charge, DEFAULT_RETRY_WINDOW),
+            1
+        );
+        assert_eq!(
+            tracker.attempt("a", &headers, charge, DEFAULT_RETRY_WINDOW),
+            2
+        );
+        assert_eq!(
+            tracker.attempt("b", &headers, charge, DEFAULT_RETRY_WINDOW),
+            1
+        );
+        assert_eq!(
+            tracker.attempt("a", &headers, other, DEFAULT_RETRY_WINDOW),
+            1
+        );
+    }
+
+    #[test]
+    fn test_attempt_header_wins() {
+        let tracker = AttemptTracker::default();
+        let headers = HashMap::from([("x-attempt".to_string(), "3".to_string())]);
+
+        assert_eq!(
+            tracker.attempt("a", &headers, ("GET", "/", "", None), DEFAULT_RETRY_WINDOW),
+            3
+        );
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Endpoint selection on request body content (`body_match:`).
+
+use crate::config::BodyMatch;
+use serde_json::Value;
+
+/// Whether `body` satisfies the endpoint's `body_match`. Bodies that are missing or
+/// not JSON never match.
+pub fn matches(body_match: &BodyMatch, body: Option<&str>) -> bool {
+    let Some(actual) = body.and_then(|body| serde_json::from_str::<Value>(body).ok()) else {
+        return false;
+    };
+
+    match (&body_match.exact, &body_match.partial) {
+        (Some(expected), _) => *expected == actual,
+        (None, Some(expected)) => contains(&actual, expected),
+        (None, None) => true,
+    }
+}
+
+/// Whether `actual` contains everything in `expected`: objects may carry extra keys,
+/// arrays must have the same length with each element contained in turn.
+fn contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => expected
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|a| contains(a, value))),
+        (Value::Array(actual), Value::Array(expected)) => {
+            actual.len() == expected.len()
+                && actual.iter().zip(expected).all(|(a, e)| contains(a, e))
+        }
+        (actual, expected) => actual == expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn exact(value: Value) -> BodyMatch {
+        BodyMatch {
+            exact: Some(value),
+            partial: None,
+        }
+    }
+
+    fn partial(value: Value) -> BodyMatch {
+        BodyMatch {
+            exact: None,
+            partial: Some(value),
+        }
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let spec = exact(json!({"a": 1, "b": [1, 2]}));
+        assert!(matches(&spec, Some(r#"{"b": [1, 2], "a": 1}"#)));
+        assert!(!matches(&spec, Some(r#"{"a": 1, "b": [1, 2], "c": 3}"#)));
+        assert!(!matches(&spec, Some(r#"{"a": 1}"#)));
+        assert!(!matches(&spec, Some("not json")));
+        assert!(!matches(&spec, None));
+    }
+
+    #[test]
+    fn test_partial_match() {
+        let spec = partial(json!({"payment": {"method": "card"}, "items": [{"sku": "A"}]}));
+        assert!(matches(
+            &spec,
+            Some(
+                r#"{"id": 7, "payment": {"method": "card", "last4": "4242"}, "items": [{"sku": "A", "qty": 2}]}"#
+            )
+        ));
+        assert!(!matches(
+            &spec,
+            Some(r#"{"payment": {"method": "paypal"}, "items": [{"sku": "A"}]}"#)
+        ));
+        assert!(!matches(
+            &spec,
+            Some(r#"{"payment": {"method": "card"}, "items": [{"sku": "A"}, {"sku": "B"}]}"#)
+        ));
+        assert!(!matches(&spec, Some(r#"{"items": [{"sku": "A"}]}"#)));
+    }
+}
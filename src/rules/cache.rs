@@ -0,0 +1,126 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Simulated HTTP caching for endpoints with `cache:`. Time is cut into `ttl`-long
+//! windows aligned to the Unix epoch; the `ETag` only changes when a new window
+//! starts and `Age` counts the seconds into the current one, so caches in front of
+//! the system under test see a resource that is regenerated every `ttl`.
+
+use crate::config::types::parse_duration_str;
+use crate::config::{CacheSimulation, HeaderList};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The caching headers of one `ttl` window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheWindow {
+    pub etag: String,
+    pub age: u64,
+    pub max_age: u64,
+    pub private: bool,
+}
+
+impl CacheWindow {
+    /// The window `now` falls into for `endpoint`. Sub-second TTLs count as one second.
+    pub fn at(cache: &CacheSimulation, endpoint: &str, now: SystemTime) -> Self {
+        let ttl = parse_duration_str(&cache.ttl)
+            .map(|ttl| ttl.as_secs().max(1))
+            .unwrap_or(1);
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let window = now / ttl;
+
+        let hash = Sha256::digest(format!("{}:{}", endpoint, window).as_bytes());
+        let etag: String = hash[..8].iter().map(|b| format!("{:02x}", b)).collect();
+
+        Self {
+            etag: format!("\"{}\"", etag),
+            age: now - window * ttl,
+            max_age: ttl,
+            private: cache.private,
+        }
+    }
+
+    pub fn now(cache: &CacheSimulation, endpoint: &str) -> Self {
+        Self::at(cache, endpoint, SystemTime::now())
+    }
+
+    /// Whether an `If-None-Match` request header names the current `ETag`.
+    pub fn matches(&self, if_none_match: &str) -> bool {
+        if_none_match.trim() == "*"
+            || if_none_match
+                .split(',')
+                .any(|tag| tag.trim().trim_start_matches("W/") == self.etag)
+    }
+
+    pub fn apply(&self, headers: &mut HeaderList) {
+        let scope = if self.private { "private" } else { "public" };
+        headers.insert(
+            "Cache-Control",
+            format!("{}, max-age={}", scope, self.max_age),
+        );
+        headers.insert("Age", self.age.to_string());
+        headers.insert("ETag", self.etag.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(cache: &CacheSimulation, secs: u64) -> CacheWindow {
+        CacheWindow::at(cache, "Catalog", UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn test_etag_rotates_every_ttl() {
+        let cache = CacheSimulation {
+            ttl: "60s".to_string(),
+            private: false,
+        };
+
+        let first = at(&cache, 120);
+        let later = at(&cache, 179);
+        assert_eq!(first.etag, later.etag);
+        assert_eq!(first.age, 0);
+        assert_eq!(later.age, 59);
+        assert_ne!(at(&cache, 180).etag, first.etag);
+        assert_ne!(
+            CacheWindow::at(&cache, "Other", UNIX_EPOCH + Duration::from_secs(120)).etag,
+            first.etag
+        );
+
+        let mut headers = HeaderList::new();
+        later.apply(&mut headers);
+        assert_eq!(headers.get("Cache-Control").unwrap(), "public, max-age=60");
+        assert_eq!(headers.get("Age").unwrap(), "59");
+        assert_eq!(headers.get("ETag").unwrap(), &first.etag);
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let cache = CacheSimulation {
+            ttl: "300s".to_string(),
+            private: true,
+        };
+        let window = at(&cache, 1000);
+
+        assert!(window.matches(&window.etag));
+        assert!(window.matches(&format!("\"stale\", W/{}", window.etag)));
+        assert!(window.matches("*"));
+        assert!(!window.matches("\"stale\""));
+    }
+}
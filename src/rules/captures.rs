@@ -0,0 +1,250 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Values endpoints with `capture:` keep from the requests they serve, in named slots
+//! that later requests match on with `captured` matchers and templates read as
+//! `{{captured.<slot>}}`, or `{{captured "order-" id}}` for a slot named at render time.
+
+use crate::config::Capture;
+use crate::rules::executor::ResponseExecutor;
+use crate::rules::matcher::JsonPathCondition;
+use crate::rules::{template, ExecutionContext};
+use dashmap::DashMap;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+pub struct Captures {
+    slots: Arc<DashMap<String, Value>>,
+}
+
+impl Captures {
+    /// Stores what each capture takes from the request. A capture whose source the
+    /// request lacks leaves its slot as it was.
+    pub fn record(&self, captures: &[Capture], context: &ExecutionContext) {
+        for capture in captures {
+            let Some(value) = extract(capture, context) else {
+                continue;
+            };
+            let slot = slot_name(&capture.slot, context);
+            tracing::debug!(slot = %slot, "Captured request value");
+            self.slots.insert(slot, value);
+        }
+    }
+
+    pub fn get(&self, slot: &str) -> Option<Value> {
+        self.slots.get(slot).map(|value| value.clone())
+    }
+
+    /// The slot's value as text: strings as they are, anything else as JSON.
+    pub fn text(&self, slot: &str) -> Option<String> {
+        self.get(slot).map(|value| match value {
+            Value::String(text) => text,
+            value => value.to_string(),
+        })
+    }
+
+    /// Every filled slot, for templates.
+    pub fn slots(&self) -> Map<String, Value> {
+        self.slots
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+}
+
+/// Renders a slot name template against the request; a name that is not valid
+/// Handlebars is used as written.
+pub fn slot_name(template: &str, context: &ExecutionContext) -> String {
+    if !template.contains("{{") {
+        return template.to_string();
+    }
+    let data = ResponseExecutor::template_data(template, context, 0);
+    template::render(template, &data).unwrap_or_else(|_| template.to_string())
+}
+
+/// Checks a capture's slot and source, for config validation.
+pub fn validate(capture: &Capture) -> anyhow::Result<()> {
+    validate_slot(&capture.slot)?;
+    let sources = [
+        capture.json_path.is_some(),
+        capture.header.is_some(),
+        capture.path_param.is_some(),
+        capture.query.is_some(),
+    ];
+    if sources.iter().filter(|set| **set).count() > 1 {
+        anyhow::bail!("Capture can set only one of: json_path, header, path_param, query");
+    }
+    if let Some(expression) = &capture.json_path {
+        if JsonPathCondition::parse(expression)?.has_comparison() {
+            anyhow::bail!("Capture json_path `{}` cannot compare", expression);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a slot name is a non-empty, valid template.
+pub fn validate_slot(slot: &str) -> anyhow::Result<()> {
+    if slot.trim().is_empty() {
+        anyhow::bail!("Capture slot name cannot be empty");
+    }
+    template::render(slot, &Value::Null)
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("Invalid capture slot `{}`: {}", slot, e))
+}
+
+fn extract(capture: &Capture, context: &ExecutionContext) -> Option<Value> {
+    if let Some(expression) = &capture.json_path {
+        let body: Value = serde_json::from_str(context.body.as_deref()?).ok()?;
+        let path = JsonPathCondition::parse(expression).ok()?;
+        return path.select(&body).first().map(|node| (*node).clone());
+    }
+    if let Some(name) = &capture.header {
+        return context
+            .headers
+            .get(&name.to_ascii_lowercase())
+            .map(|value| Value::from(value.as_str()));
+    }
+    if let Some(name) = &capture.path_param {
+        return context
+            .path_params
+            .get(name)
+            .map(|value| Value::from(value.as_str()));
+    }
+    if let Some(name) = &capture.query {
+        return url::form_urlencoded::parse(context.query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| Value::from(value.into_owned()));
+    }
+
+    context.json_body()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn context(body: &str) -> ExecutionContext {
+        ExecutionContext {
+            method: "POST".to_string(),
+            path: "/orders/o-1".to_string(),
+            query: "source=web%20shop".to_string(),
+            headers: HashMap::from([("x-tenant".to_string(), "acme".to_string())]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::from([("id".to_string(), "o-1".to_string())]),
+            body: Some(body.to_string()),
+        }
+    }
+
+    fn capture(slot: &str) -> Capture {
+        Capture {
+            slot: slot.to_string(),
+            json_path: None,
+            header: None,
+            path_param: None,
+            query: None,
+        }
+    }
+
+    #[test]
+    fn test_record_from_each_source() {
+        let captures = Captures::default();
+        captures.record(
+            &[
+                capture("order-{{body.id}}"),
+                Capture {
+                    json_path: Some("$.items[*].sku".to_string()),
+                    ..capture("first_sku")
+                },
+                Capture {
+                    header: Some("X-Tenant".to_string()),
+                    ..capture("tenant")
+                },
+                Capture {
+                    path_param: Some("id".to_string()),
+                    ..capture("order_id")
+                },
+                Capture {
+                    query: Some("source".to_string()),
+                    ..capture("source")
+                },
+                Capture {
+                    header: Some("X-Missing".to_string()),
+                    ..capture("missing")
+                },
+            ],
+            &context(r#"{"id": 7, "items": [{"sku": "A1"}, {"sku": "B2"}]}"#),
+        );
+
+        assert_eq!(
+            captures.get("order-7"),
+            Some(json!({"id": 7, "items": [{"sku": "A1"}, {"sku": "B2"}]}))
+        );
+        assert_eq!(captures.text("first_sku").as_deref(), Some("A1"));
+        assert_eq!(captures.text("tenant").as_deref(), Some("acme"));
+        assert_eq!(captures.text("order_id").as_deref(), Some("o-1"));
+        assert_eq!(captures.text("source").as_deref(), Some("web shop"));
+        assert_eq!(captures.get("missing"), None);
+        assert_eq!(captures.slots().len(), 5);
+    }
+
+    #[test]
+    fn test_non_json_body_is_kept_as_text() {
+        let captures = Captures::default();
+        captures.record(&[capture("raw")], &context("plain text"));
+        assert_eq!(captures.get("raw"), Some(json!("plain text")));
+
+        captures.record(
+            &[Capture {
+                json_path: Some("$.id".to_string()),
+                ..capture("raw")
+            }],
+            &context("still not json"),
+        );
+        assert_eq!(captures.get("raw"), Some(json!("plain text")));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&capture("order-{{body.id}}")).is_ok());
+        assert!(validate(&Capture {
+            json_path: Some("$.id".to_string()),
+            ..capture("id")
+        })
+        .is_ok());
+
+        let error = |capture: Capture| validate(&capture).unwrap_err().to_string();
+        assert_eq!(error(capture(" ")), "Capture slot name cannot be empty");
+        assert!(error(capture("order-{{#if id}}")).starts_with("Invalid capture slot"));
+        assert_eq!(
+            error(Capture {
+                header: Some("X-Id".to_string()),
+                query: Some("id".to_string()),
+                ..capture("id")
+            }),
+            "Capture can set only one of: json_path, header, path_param, query"
+        );
+        assert_eq!(
+            error(Capture {
+                json_path: Some("$.id == 1".to_string()),
+                ..capture("id")
+            }),
+            "Capture json_path `$.id == 1` cannot compare"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Matcher kinds added by embedders of the library, e.g. protobuf field or
+//! company-specific token matchers, without forking the crate.
+//!
+//! A kind registered under a name is configured like any built-in matcher and takes
+//! part in endpoint and response selection the same way:
+//!
+//! ```yaml
+//! matchers:
+//!   - custom: {kind: tenant, config: {id: acme}}
+//! ```
+//!
+//! Kinds must be registered before the configuration is loaded, since the loader
+//! validates each use against its kind.
+
+use crate::rules::registry::Registry;
+use crate::rules::ExecutionContext;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A custom request predicate, configured per use with an arbitrary `config` value.
+pub trait Matcher: Send + Sync {
+    /// Checks one use's `config` while the configuration is loaded.
+    fn validate(&self, config: &Value) -> anyhow::Result<()> {
+        let _ = config;
+        Ok(())
+    }
+
+    /// Whether the request satisfies the matcher as configured by `config`.
+    fn matches(&self, config: &Value, context: &ExecutionContext) -> bool;
+}
+
+/// Plain functions and closures work as matchers that accept any `config`.
+impl<F> Matcher for F
+where
+    F: Fn(&Value, &ExecutionContext) -> bool + Send + Sync,
+{
+    fn matches(&self, config: &Value, context: &ExecutionContext) -> bool {
+        self(config, context)
+    }
+}
+
+static REGISTRY: Registry<dyn Matcher> = Registry::new();
+
+/// Makes `matcher` available as `custom: {kind: <kind>}`, replacing any matcher
+/// registered under the same kind.
+pub fn register(kind: impl Into<String>, matcher: impl Matcher + 'static) {
+    REGISTRY.insert(kind, Arc::new(matcher));
+}
+
+/// The matcher registered under `kind`, if any.
+pub fn get(kind: &str) -> Option<Arc<dyn Matcher>> {
+    REGISTRY.get(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    struct HeaderPrefix;
+
+    impl Matcher for HeaderPrefix {
+        fn validate(&self, config: &Value) -> anyhow::Result<()> {
+            if config.get("prefix").and_then(Value::as_str).is_none() {
+                anyhow::bail!("`prefix` is required");
+            }
+            Ok(())
+        }
+
+        fn matches(&self, config: &Value, context: &ExecutionContext) -> bool {
+            let prefix = config["prefix"].as_str().unwrap_or_default();
+            context
+                .headers
+                .get("x-token")
+                .is_some_and(|token| token.starts_with(prefix))
+        }
+    }
+
+    #[test]
+    fn test_register_and_get() {
+        register("custom_matchers_test_prefix", HeaderPrefix);
+        register(
+            "custom_matchers_test_fn",
+            |_: &Value, ctx: &ExecutionContext| ctx.method == "POST",
+        );
+
+        let context = ExecutionContext {
+            method: "POST".to_string(),
+            path: "/".to_string(),
+            query: String::new(),
+            headers: HashMap::from([("x-token".to_string(), "acme-123".to_string())]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+
+        let prefix = get("custom_matchers_test_prefix").unwrap();
+        assert!(prefix
+            .validate(&serde_json::json!({"prefix": "acme-"}))
+            .is_ok());
+        assert!(prefix.validate(&Value::Null).is_err());
+        assert!(prefix.matches(&serde_json::json!({"prefix": "acme-"}), &context));
+        assert!(!prefix.matches(&serde_json::json!({"prefix": "other-"}), &context));
+
+        let closure = get("custom_matchers_test_fn").unwrap();
+        assert!(closure.validate(&Value::Null).is_ok());
+        assert!(closure.matches(&Value::Null, &context));
+
+        assert!(get("custom_matchers_test_unknown").is_none());
+    }
+}
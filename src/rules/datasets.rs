@@ -0,0 +1,162 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Data files an endpoint reads its responses from, named under `datasets`:
+//!
+//! ```yaml
+//! datasets:
+//!   users: fixtures/users.csv
+//! responses:
+//!   - status: 200
+//!     body: '{{#with (find_row datasets.users "id" id)}}{"name": "{{name}}"}{{/with}}'
+//! ```
+//!
+//! A dataset is a CSV file, whose rows become objects keyed by the header row with
+//! text values, or a JSON or YAML file holding an array of objects. Templates read it
+//! as `{{datasets.<name>}}`, usually through the `find_row` and `filter_rows` helpers
+//! of [`crate::rules::template`] or an `{{#each}}` block.
+
+use crate::config::Endpoint;
+use crate::rules::fixtures::CsvTable;
+use anyhow::Context;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Reads the rows of the dataset at `path`.
+pub fn load(path: &str) -> anyhow::Result<Vec<Value>> {
+    let is_csv = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        return Ok(CsvTable::load(path)?.objects());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read dataset {:?}", path))?;
+    // YAML is a superset of JSON, so this reads both.
+    let rows: Vec<Map<String, Value>> = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid dataset {:?}: expected an array of objects", path))?;
+    Ok(rows.into_iter().map(Value::Object).collect())
+}
+
+/// Every endpoint's datasets, loaded once when the engine is built.
+#[derive(Clone, Default)]
+pub struct Datasets {
+    files: Arc<HashMap<String, Arc<Value>>>,
+}
+
+impl Datasets {
+    /// Loads the datasets of `endpoints`. The loader rejects a config with a
+    /// dataset that fails to load, so one failing here is only logged.
+    pub fn new(endpoints: &[Endpoint]) -> Self {
+        let mut files = HashMap::new();
+        for path in endpoints
+            .iter()
+            .flat_map(|endpoint| endpoint.datasets.values())
+        {
+            if files.contains_key(path) {
+                continue;
+            }
+            match load(path) {
+                Ok(rows) => {
+                    files.insert(path.clone(), Arc::new(Value::Array(rows)));
+                }
+                Err(e) => tracing::warn!(path = %path, error = %e, "Failed to load dataset"),
+            }
+        }
+        Self {
+            files: Arc::new(files),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Arc<Value>> {
+        self.files.get(path)
+    }
+
+    /// The template variable for the `datasets` that `template` reads: each name
+    /// mapped to its rows. A dataset that is not loaded is left out, so its
+    /// placeholders stay as is.
+    pub fn template_data(&self, datasets: &HashMap<String, String>, template: &str) -> Value {
+        let data = datasets
+            .iter()
+            .filter(|(name, _)| template.contains(&format!("datasets.{}", name)))
+            .filter_map(|(name, path)| Some((name.clone(), Value::clone(self.get(path)?))))
+            .collect();
+        Value::Object(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_csv_json_and_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("users.csv");
+        std::fs::write(&csv, "id,name\n1,Ann\n2,Bob\n").unwrap();
+        let json = dir.path().join("users.json");
+        std::fs::write(
+            &json,
+            r#"[{"id": 1, "name": "Ann"}, {"id": 2, "name": "Bob"}]"#,
+        )
+        .unwrap();
+        let yaml = dir.path().join("users.yaml");
+        std::fs::write(&yaml, "- {id: 1, name: Ann}\n- {id: 2, name: Bob}\n").unwrap();
+
+        let rows = load(csv.to_str().unwrap()).unwrap();
+        assert_eq!(rows[1], serde_json::json!({"id": "2", "name": "Bob"}));
+        for path in [&json, &yaml] {
+            let rows = load(path.to_str().unwrap()).unwrap();
+            assert_eq!(rows[1], serde_json::json!({"id": 2, "name": "Bob"}));
+        }
+
+        let scalar = dir.path().join("scalar.json");
+        std::fs::write(&scalar, "[1, 2]").unwrap();
+        let error = format!("{:#}", load(scalar.to_str().unwrap()).unwrap_err());
+        assert!(error.contains("expected an array of objects"), "{}", error);
+        assert!(load("missing.json").is_err());
+    }
+
+    #[test]
+    fn test_template_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("users.csv");
+        std::fs::write(&csv, "id,name\n1,Ann\n").unwrap();
+        let datasets: HashMap<String, String> = [
+            ("users".to_string(), csv.to_str().unwrap().to_string()),
+            ("orders".to_string(), "missing.csv".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let endpoint = Endpoint {
+            datasets: datasets.clone(),
+            ..Default::default()
+        };
+        let loaded = Datasets::new(&[endpoint]);
+
+        let data = loaded.template_data(&datasets, "{{datasets.users}} {{datasets.orders}}");
+        assert_eq!(data["users"][0]["name"], "Ann");
+        assert!(data.get("orders").is_none());
+        assert_eq!(
+            loaded.template_data(&datasets, "{{id}}"),
+            serde_json::json!({})
+        );
+    }
+}
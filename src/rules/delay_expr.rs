@@ -0,0 +1,445 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Response delays computed from the request, for `delay_expr:`.
+//!
+//! An expression combines numbers, durations (`10ms`, `2s`) and request references
+//! with `+`, `-`, `*`, `/` and parentheses, and evaluates to milliseconds:
+//!
+//! ```yaml
+//! delay_expr: "body.items.length * 10ms + 50ms"
+//! ```
+//!
+//! References are `body.<path>` (a dotted path into the JSON body, where a trailing
+//! `length` counts array items or string characters), `header.<name>` and
+//! `query.<name>` (a number of milliseconds or a duration such as `250ms`), and
+//! `body_size` (the body length in bytes). A reference that is missing or not a
+//! number counts as zero, so a malformed request never fails the response. Put
+//! spaces around `-`, since it is also allowed inside header names.
+//!
+//! Since clients control the values, results are capped at
+//! `server.max_computed_delay` (one minute by default).
+
+use super::ExecutionContext;
+use crate::config::types::parse_duration_str;
+use serde_json::Value;
+use std::time::Duration;
+
+/// A parsed `delay_expr`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelayExpr {
+    expr: Expr,
+    /// Whether any reference reads the JSON body, which is only parsed then.
+    uses_body: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Ref(Reference),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Reference {
+    Body(Vec<String>),
+    Header(String),
+    Query(String),
+    BodySize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ref(String),
+    Op(char),
+    Open,
+    Close,
+}
+
+impl DelayExpr {
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let parsed = parser.sum()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            anyhow::bail!("Unexpected {:?} in delay expression `{}`", token, expr);
+        }
+        Ok(Self {
+            uses_body: reads_body(&parsed),
+            expr: parsed,
+        })
+    }
+
+    /// The delay for this request, at most `max`; negative or NaN results become
+    /// zero.
+    pub fn evaluate(&self, context: &ExecutionContext, max: Duration) -> Duration {
+        let body = if self.uses_body {
+            context
+                .body
+                .as_deref()
+                .and_then(|body| serde_json::from_str::<Value>(body).ok())
+        } else {
+            None
+        };
+        let ms = eval(&self.expr, context, body.as_ref());
+        if ms.is_nan() || ms <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(ms / 1000.0)
+                .unwrap_or(max)
+                .min(max)
+        }
+    }
+}
+
+fn reads_body(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) => false,
+        Expr::Ref(reference) => matches!(reference, Reference::Body(_)),
+        Expr::Binary(lhs, _, rhs) => reads_body(lhs) || reads_body(rhs),
+    }
+}
+
+fn tokenize(expr: &str) -> anyhow::Result<Vec<Token>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '+' | '-' | '*' | '/' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let value: f64 = literal
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid number `{}`", literal))?;
+                let unit_start = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let unit: String = chars[unit_start..i].iter().collect();
+                let value = match unit.as_str() {
+                    "" | "ms" => value,
+                    "s" => value * 1000.0,
+                    _ => anyhow::bail!("Unknown duration unit `{}` (use ms or s)", unit),
+                };
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.' | '-'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ref(chars[start..i].iter().collect()));
+            }
+            _ => anyhow::bail!("Unexpected `{}` in delay expression", c),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_op(&self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> anyhow::Result<Expr> {
+        let mut lhs = self.product()?;
+        while let Some(op) = self.peek_op(&['+', '-']) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.product()?));
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> anyhow::Result<Expr> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = self.peek_op(&['*', '/']) {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Ref(name)) => Ok(Expr::Ref(parse_reference(&name)?)),
+            Some(Token::Op('-')) => Ok(Expr::Binary(
+                Box::new(Expr::Number(0.0)),
+                '-',
+                Box::new(self.factor()?),
+            )),
+            Some(Token::Open) => {
+                let inner = self.sum()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => anyhow::bail!("Missing `)` in delay expression"),
+                }
+            }
+            Some(token) => anyhow::bail!("Unexpected {:?} in delay expression", token),
+            None => anyhow::bail!("Delay expression ends unexpectedly"),
+        }
+    }
+}
+
+fn parse_reference(name: &str) -> anyhow::Result<Reference> {
+    if name == "body_size" {
+        return Ok(Reference::BodySize);
+    }
+    let (scope, rest) = name.split_once('.').unwrap_or((name, ""));
+    if rest.is_empty() {
+        anyhow::bail!(
+            "Unknown reference `{}` (use body.<path>, header.<name>, query.<name> or body_size)",
+            name
+        );
+    }
+    match scope {
+        "body" => Ok(Reference::Body(rest.split('.').map(String::from).collect())),
+        "header" => Ok(Reference::Header(rest.to_lowercase())),
+        "query" => Ok(Reference::Query(rest.to_string())),
+        _ => anyhow::bail!(
+            "Unknown reference `{}` (use body.<path>, header.<name>, query.<name> or body_size)",
+            name
+        ),
+    }
+}
+
+fn eval(expr: &Expr, context: &ExecutionContext, body: Option<&Value>) -> f64 {
+    match expr {
+        Expr::Number(value) => *value,
+        Expr::Ref(reference) => resolve(reference, context, body).unwrap_or(0.0),
+        Expr::Binary(lhs, op, rhs) => {
+            let (lhs, rhs) = (eval(lhs, context, body), eval(rhs, context, body));
+            match op {
+                '+' => lhs + rhs,
+                '-' => lhs - rhs,
+                '*' => lhs * rhs,
+                _ if rhs == 0.0 => 0.0,
+                _ => lhs / rhs,
+            }
+        }
+    }
+}
+
+fn resolve(reference: &Reference, context: &ExecutionContext, body: Option<&Value>) -> Option<f64> {
+    match reference {
+        Reference::Body(path) => body_value(body?, path),
+        Reference::Header(name) => context
+            .headers
+            .get(name)
+            .and_then(|v| number_or_duration(v)),
+        Reference::Query(name) => context.query_param(name).and_then(number_or_duration),
+        Reference::BodySize => Some(context.body.as_ref().map_or(0, String::len) as f64),
+    }
+}
+
+fn body_value(body: &Value, path: &[String]) -> Option<f64> {
+    let mut current = body;
+    for (i, segment) in path.iter().enumerate() {
+        let is_last = i + 1 == path.len();
+        current = match current {
+            Value::Object(map) if map.contains_key(segment) => &map[segment],
+            Value::Array(items) if segment.parse::<usize>().is_ok() => {
+                items.get(segment.parse::<usize>().ok()?)?
+            }
+            Value::Array(items) if segment == "length" && is_last => {
+                return Some(items.len() as f64)
+            }
+            Value::String(s) if segment == "length" && is_last => {
+                return Some(s.chars().count() as f64)
+            }
+            _ => return None,
+        };
+    }
+    match current {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => number_or_duration(s),
+        _ => None,
+    }
+}
+
+/// A bare number is taken as milliseconds; otherwise a duration like `250ms` or `2s`.
+fn number_or_duration(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value
+        .parse::<f64>()
+        .ok()
+        .or_else(|| parse_duration_str(value).ok().map(|d| d.as_millis() as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn context(body: Option<&str>, headers: &[(&str, &str)], query: &str) -> ExecutionContext {
+        ExecutionContext {
+            method: "POST".to_string(),
+            path: "/orders".to_string(),
+            query: query.to_string(),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: body.map(String::from),
+        }
+    }
+
+    fn delay(expr: &str, context: &ExecutionContext) -> Duration {
+        DelayExpr::parse(expr)
+            .unwrap()
+            .evaluate(context, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_scales_with_body_array_length() {
+        let ctx = context(Some(r#"{"items": [1, 2, 3]}"#), &[], "");
+        assert_eq!(
+            delay("body.items.length * 10ms", &ctx),
+            Duration::from_millis(30)
+        );
+        assert_eq!(
+            delay("50ms + body.items.length * 10ms", &ctx),
+            Duration::from_millis(80)
+        );
+        assert_eq!(
+            delay("(50ms + body.items.length) * 2", &ctx),
+            Duration::from_millis(106)
+        );
+        assert_eq!(delay("1s / 4", &ctx), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_body_paths() {
+        let ctx = context(
+            Some(r#"{"order": {"lines": [{"qty": 4}], "note": "abc", "wait": "2s"}}"#),
+            &[],
+            "",
+        );
+        assert_eq!(
+            delay("body.order.lines.0.qty", &ctx),
+            Duration::from_millis(4)
+        );
+        assert_eq!(
+            delay("body.order.note.length", &ctx),
+            Duration::from_millis(3)
+        );
+        assert_eq!(delay("body.order.wait", &ctx), Duration::from_secs(2));
+        assert_eq!(
+            delay("body_size", &ctx).as_millis() as usize,
+            ctx.body.unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_headers_and_query() {
+        let ctx = context(None, &[("x-delay", "250ms"), ("x-cost", "3")], "pages=2");
+        assert_eq!(delay("header.X-Delay", &ctx), Duration::from_millis(250));
+        assert_eq!(
+            delay("header.x-cost * query.pages * 1s", &ctx),
+            Duration::from_secs(6)
+        );
+    }
+
+    #[test]
+    fn test_missing_references_count_as_zero() {
+        let ctx = context(Some("not json"), &[("x-delay", "soon")], "");
+        assert_eq!(delay("body.items.length * 10ms", &ctx), Duration::ZERO);
+        assert_eq!(
+            delay("header.x-delay + 5ms", &ctx),
+            Duration::from_millis(5)
+        );
+        assert_eq!(delay("query.missing - 1s", &ctx), Duration::ZERO);
+        assert_eq!(delay("10ms / body.n", &ctx), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_client_values_are_capped() {
+        let ctx = context(None, &[("x-delay", "1e300"), ("x-neg", "-5")], "");
+        assert_eq!(delay("header.x-delay", &ctx), Duration::from_secs(60));
+        assert_eq!(
+            delay("header.x-delay * header.x-delay", &ctx),
+            Duration::from_secs(60)
+        );
+        assert_eq!(delay("header.x-neg", &ctx), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_only_body_references_read_the_body() {
+        assert!(
+            DelayExpr::parse("body.items.length * 10ms")
+                .unwrap()
+                .uses_body
+        );
+        assert!(DelayExpr::parse("10ms + (1 * body.n)").unwrap().uses_body);
+        assert!(
+            !DelayExpr::parse("body_size + header.x-delay")
+                .unwrap()
+                .uses_body
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expr in [
+            "",
+            "10ms +",
+            "(10ms",
+            "10min",
+            "items.length",
+            "body",
+            "10ms $ 2",
+        ] {
+            assert!(DelayExpr::parse(expr).is_err(), "{} should not parse", expr);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use thiserror::Error;
+
+/// Why the rule engine could not produce a response for a request.
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("No matching endpoint found for {method} {path}")]
+    NoMatch { method: String, path: String },
+    #[error("Method {method} not allowed for {path}")]
+    MethodNotAllowed {
+        method: String,
+        path: String,
+        /// Methods the path is configured for, answered in the `Allow` header.
+        allowed: Vec<String>,
+    },
+    #[error("No matching response and no default response found for endpoint '{endpoint}'")]
+    NoResponse { endpoint: String },
+    #[error("{0}")]
+    TemplateError(String),
+    #[error("State error: {0}")]
+    StateError(String),
+}
+
+impl RuleError {
+    /// The HTTP status a request failing this way is answered with.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            RuleError::NoMatch { .. } => 404,
+            RuleError::MethodNotAllowed { .. } => 405,
+            _ => 500,
+        }
+    }
+
+    /// Whether no endpoint matched the request at all, as opposed to one failing.
+    pub fn is_unmatched(&self) -> bool {
+        matches!(
+            self,
+            RuleError::NoMatch { .. } | RuleError::MethodNotAllowed { .. }
+        )
+    }
+
+    /// The `error.type` label used in metrics, logs and admin diagnostics.
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            RuleError::NoMatch { .. } => "no_match",
+            RuleError::MethodNotAllowed { .. } => "method_not_allowed",
+            RuleError::NoResponse { .. } => "no_response",
+            RuleError::TemplateError(_) => "template_error",
+            RuleError::StateError(_) => "state_error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_codes_and_types() {
+        let no_match = RuleError::NoMatch {
+            method: "GET".to_string(),
+            path: "/missing".to_string(),
+        };
+        assert_eq!(no_match.status_code(), 404);
+        assert_eq!(no_match.error_type(), "no_match");
+        assert_eq!(
+            no_match.to_string(),
+            "No matching endpoint found for GET /missing"
+        );
+
+        let no_response = RuleError::NoResponse {
+            endpoint: "Orders".to_string(),
+        };
+        assert_eq!(no_response.status_code(), 500);
+        assert_eq!(no_response.error_type(), "no_response");
+
+        let not_allowed = RuleError::MethodNotAllowed {
+            method: "PUT".to_string(),
+            path: "/orders".to_string(),
+            allowed: vec!["GET".to_string(), "POST".to_string()],
+        };
+        assert_eq!(not_allowed.status_code(), 405);
+        assert_eq!(not_allowed.error_type(), "method_not_allowed");
+        assert!(not_allowed.is_unmatched() && no_match.is_unmatched());
+        assert!(!no_response.is_unmatched());
+        assert_eq!(
+            not_allowed.to_string(),
+            "Method PUT not allowed for /orders"
+        );
+    }
+}
@@ -14,30 +14,204 @@
  * limitations under the License.
  */
 
-use crate::config::{Endpoint, Response};
+use crate::config::{
+    BasicAuth, ByteSize, Endpoint, EndpointInstance, EndpointTimeout, HeaderList, LatencyProfile,
+    Oversize, Region, RegionFault, Response, ResponseLimits, SelectionStrategy,
+};
+use crate::rules::attempts::{AttemptTracker, DEFAULT_RETRY_WINDOW};
+use crate::rules::cache::CacheWindow;
+use crate::rules::captures::Captures;
+use crate::rules::datasets::Datasets;
+use crate::rules::delay_expr::DelayExpr;
+use crate::rules::error::RuleError;
+use crate::rules::failpoints::{self, Failpoints};
+use crate::rules::fixtures::CsvFixtures;
+use crate::rules::matcher::{self, CompiledMatchers};
+use crate::rules::pagination::CursorStore;
+use crate::rules::schema_body::SchemaDocuments;
 use crate::rules::state::StateManager;
-use crate::rules::{ExecutionContext, RuleResponse};
-use anyhow::Context;
-use rand::Rng;
+use crate::rules::template::{self, CompiledTemplates};
+use crate::rules::validation::{BodyValidator, FieldError};
+use crate::rules::{ExecutionContext, ResponseSelection, RuleResponse, SelectionReason};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::info;
 
+/// `{{body}}` or a field of it such as `{{body.user.id}}` or `{{body.items.0}}`.
+static BODY_FIELD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*body((?:\.[\w-]+)*)\s*\}\}").unwrap());
+
+/// `{{header.<name>}}`, whose name is matched case-insensitively.
+static HEADER_VARIABLE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{header\.([\w-]+)\}\}").unwrap());
+
 #[derive(Clone)]
 pub struct ResponseExecutor {
     state_manager: Arc<StateManager>,
+    cursors: CursorStore,
+    attempts: AttemptTracker,
+    latency_profiles: Arc<HashMap<String, LatencyProfile>>,
+    regions: Arc<Vec<Region>>,
+    jitter: Option<(Duration, Duration)>,
+    failpoints: Failpoints,
+    fixtures: CsvFixtures,
+    schemas: SchemaDocuments,
+    datasets: Datasets,
+    hits: Arc<DashMap<String, u64>>,
+    captures: Captures,
+    limits: ResponseLimits,
+    /// The random stream of each endpoint with a `seed`, by endpoint name.
+    seeded: Arc<DashMap<String, StdRng>>,
+    templates: CompiledTemplates,
+    matchers: CompiledMatchers,
+    /// Every response's parsed `delay_expr`, by its source.
+    delay_exprs: Arc<HashMap<String, DelayExpr>>,
+    max_computed_delay: Duration,
+    dry_run: bool,
 }
 
 impl ResponseExecutor {
     pub fn new(state_manager: Arc<StateManager>) -> Self {
-        Self { state_manager }
+        Self {
+            state_manager,
+            cursors: CursorStore::new(),
+            attempts: AttemptTracker::default(),
+            latency_profiles: Arc::new(HashMap::new()),
+            regions: Arc::new(Vec::new()),
+            jitter: None,
+            failpoints: Failpoints::default(),
+            fixtures: CsvFixtures::default(),
+            schemas: SchemaDocuments::default(),
+            datasets: Datasets::default(),
+            hits: Arc::new(DashMap::new()),
+            captures: Captures::default(),
+            limits: ResponseLimits::default(),
+            seeded: Arc::new(DashMap::new()),
+            templates: CompiledTemplates::default(),
+            matchers: CompiledMatchers::default(),
+            delay_exprs: Arc::new(HashMap::new()),
+            max_computed_delay: Duration::from_secs(60),
+            dry_run: false,
+        }
+    }
+
+    /// Starts the random stream of every endpoint with a `seed`, which its
+    /// requests then draw from in turn.
+    pub fn with_endpoint_seeds(self, endpoints: &[Endpoint]) -> Self {
+        for endpoint in endpoints {
+            if let Some(seed) = endpoint.seed {
+                self.seeded
+                    .insert(endpoint.name.clone(), StdRng::seed_from_u64(seed));
+            }
+        }
+        self
+    }
+
+    /// Parses every response's `delay_expr` up front; invalid ones are rejected
+    /// by the loader and ignored here.
+    pub fn with_delay_exprs(mut self, endpoints: &[Endpoint]) -> Self {
+        let exprs = endpoints
+            .iter()
+            .flat_map(|endpoint| &endpoint.responses)
+            .filter_map(|response| response.delay_expr.as_ref())
+            .filter_map(|source| Some((source.clone(), DelayExpr::parse(source).ok()?)))
+            .collect();
+        self.delay_exprs = Arc::new(exprs);
+        self
+    }
+
+    /// Caps the delays `delay_expr` computes from request values.
+    pub fn with_max_computed_delay(mut self, max: Duration) -> Self {
+        self.max_computed_delay = max;
+        self
+    }
+
+    /// Renders endpoint responses from the templates compiled for them.
+    pub fn with_templates(mut self, templates: CompiledTemplates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Selects responses by the matchers compiled for them.
+    pub fn with_matchers(mut self, matchers: CompiledMatchers) -> Self {
+        self.matchers = matchers;
+        self
+    }
+
+    /// Gives endpoint responses the datasets they read, loaded up front.
+    pub fn with_datasets(mut self, datasets: Datasets) -> Self {
+        self.datasets = datasets;
+        self
+    }
+
+    /// Fills in `csv_row` helpers from the CSV files loaded up front.
+    pub fn with_fixtures(mut self, fixtures: CsvFixtures) -> Self {
+        self.fixtures = fixtures;
+        self
+    }
+
+    /// Caps the rendered responses of endpoints without `limits` of their own.
+    pub fn with_limits(mut self, limits: ResponseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Lets the admin-armed failpoints fail state and template access.
+    pub fn with_failpoints(mut self, failpoints: Failpoints) -> Self {
+        self.failpoints = failpoints;
+        self
+    }
+
+    /// Selects and renders responses without sleeping for delays or advancing any
+    /// per-client state.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Counts answered requests into the engine's per-endpoint hit counts, which
+    /// templates read as `{{endpoint_hits}}`.
+    pub fn with_hits(mut self, hits: Arc<DashMap<String, u64>>) -> Self {
+        self.hits = hits;
+        self
+    }
+
+    /// Gives templates and response matchers the slots endpoints `capture` into.
+    pub fn with_captures(mut self, captures: Captures) -> Self {
+        self.captures = captures;
+        self
+    }
+
+    /// Adds a random delay between `min` and `max` to every response.
+    pub fn with_jitter(mut self, min: Duration, max: Duration) -> Self {
+        self.jitter = Some((min, max));
+        self
+    }
+
+    pub fn with_latency_profiles(mut self, profiles: HashMap<String, LatencyProfile>) -> Self {
+        self.latency_profiles = Arc::new(profiles);
+        self
+    }
+
+    /// Adds each region's latency and failures to the responses of its clients.
+    pub fn with_regions(mut self, regions: Vec<Region>) -> Self {
+        self.regions = Arc::new(regions);
+        self
     }
 
     pub async fn execute(
         &self,
         endpoint: &Endpoint,
         context: &ExecutionContext,
-    ) -> anyhow::Result<RuleResponse> {
+    ) -> Result<RuleResponse, RuleError> {
         info!(
             endpoint = %endpoint.name,
             method = %context.method,
@@ -45,106 +219,663 @@ impl ResponseExecutor {
             "Executing endpoint"
         );
 
-        let state_key = if endpoint.stateful {
-            let key = endpoint
-                .state_key
-                .as_deref()
-                .unwrap_or("client_ip")
-                .to_string();
-
-            match key.as_str() {
-                "client_ip" => context.client_ip.clone(),
-                _ => {
-                    if let Some(value) = context.headers.get(&key) {
-                        value.clone()
-                    } else {
-                        context.client_ip.clone()
+        let mut rng = self.rng(endpoint, context);
+
+        let region = self.region(context);
+        if let Some(region) = region {
+            if let Some(fault) = &region.fault {
+                if rng.gen_bool(fault.rate.clamp(0.0, 1.0)) {
+                    return Ok(self.region_unavailable(endpoint, context, region, fault));
+                }
+            }
+        }
+
+        let page = match &endpoint.pagination {
+            Some(pagination) => {
+                let cursor = context.query_param(&pagination.cursor_param);
+                match self.cursors.page(&endpoint.name, pagination, cursor) {
+                    Some(page) => Some(page),
+                    None => {
+                        return Ok(self.json_response(
+                            endpoint,
+                            context,
+                            400,
+                            r#"{"error": "Invalid pagination cursor"}"#.to_string(),
+                            SelectionReason::InvalidCursor,
+                        ))
                     }
                 }
             }
+            None => None,
+        };
+
+        if endpoint.stateful || endpoint.selection == SelectionStrategy::RoundRobin {
+            self.failpoint(failpoints::STATE_STORE)
+                .map_err(RuleError::StateError)?;
+        }
+
+        let state_key = if endpoint.stateful {
+            Self::resolve_state_key(endpoint, context)
         } else {
             "".to_string()
         };
 
-        if endpoint.stateful && !state_key.is_empty() {
-            self.state_manager.increment_count(&state_key);
-        }
+        // Held until the response is ready, delay included, so a queued request
+        // only takes its count once the one before it has been answered.
+        let _serialized = if endpoint.serialize && !state_key.is_empty() && !self.dry_run {
+            Some(self.state_manager.lock(&state_key).await)
+        } else {
+            None
+        };
 
-        let request_count = if endpoint.stateful && !state_key.is_empty() {
-            self.state_manager.get_count(&state_key)
+        // The count is peeked for the conditions and only taken once a response is
+        // chosen, so requests the endpoint cannot answer leave it alone. Concurrent
+        // requests wait in between, unless the endpoint serializes them already.
+        let counts_requests = endpoint.stateful && !state_key.is_empty();
+        let counting = if counts_requests && _serialized.is_none() && !self.dry_run {
+            Some(self.state_manager.lock(&state_key).await)
+        } else {
+            None
+        };
+        // The loader has already validated the interval.
+        let min_interval = endpoint
+            .min_interval
+            .as_deref()
+            .and_then(|i| crate::config::types::parse_duration_str(i).ok());
+        let request_count = if counts_requests {
+            self.peek_count(&state_key, min_interval)
         } else {
             0
         };
 
+        // Only endpoints that branch on `attempt` pay for tracking it.
+        let attempt = if endpoint.responses.iter().any(|r| {
+            r.condition
+                .as_deref()
+                .is_some_and(|c| c.contains("attempt"))
+        }) {
+            self.attempt(endpoint, context)
+        } else {
+            1
+        };
+
+        // The default response is the fallback for when nothing else applies, never a
+        // candidate in its own right.
         let candidate_responses: Vec<&Response> = endpoint
             .responses
             .iter()
-            .filter(|r| self.evaluate_condition(r, context, request_count))
+            .enumerate()
+            .filter(|(_, r)| !r.default)
+            .filter(|(index, r)| {
+                let passed = self.evaluate_condition(r, context, request_count, attempt)
+                    && matcher::matches_all_with(
+                        &self.matchers.response(endpoint, *index),
+                        context,
+                        &self.captures,
+                    );
+                if let Some(condition) = &r.condition {
+                    tracing::debug!(
+                        endpoint = %endpoint.name,
+                        response = index,
+                        condition = %condition,
+                        passed,
+                        "Condition evaluated"
+                    );
+                }
+                passed
+            })
+            .map(|(_, r)| r)
             .collect();
 
-        let selected_response = if candidate_responses.is_empty() {
-            endpoint
+        let selected = match candidate_responses.as_slice() {
+            [] => None,
+            [only] => Some((*only, Self::match_reason(only))),
+            candidates => match endpoint.selection {
+                SelectionStrategy::FirstMatch => {
+                    Some((candidates[0], Self::match_reason(candidates[0])))
+                }
+                SelectionStrategy::Weighted => self
+                    .select_by_probability(candidates, &mut rng)
+                    .map(|r| (r, SelectionReason::Probability)),
+                SelectionStrategy::RoundRobin => {
+                    // Each state key (client IP by default) gets its own rotation, so
+                    // concurrent clients all see the configured sequence.
+                    let turn = self.next_count(&format!(
+                        "round_robin:{}:{}",
+                        endpoint.name,
+                        Self::resolve_state_key(endpoint, context)
+                    ));
+                    let index = (turn - 1) as usize % candidates.len();
+                    Some((candidates[index], SelectionReason::RoundRobin))
+                }
+            },
+        };
+
+        let (selected_response, reason) = match selected {
+            Some(selected) => selected,
+            None => {
+                let default_response =
+                    endpoint
+                        .responses
+                        .iter()
+                        .find(|r| r.default)
+                        .ok_or_else(|| RuleError::NoResponse {
+                            endpoint: endpoint.name.clone(),
+                        })?;
+                (default_response, SelectionReason::Default)
+            }
+        };
+
+        if counts_requests {
+            match min_interval {
+                Some(interval) => self.next_count_after(&state_key, interval),
+                None => self.next_count(&state_key),
+            };
+        }
+        drop(counting);
+
+        let selection = ResponseSelection {
+            endpoint: endpoint.name.clone(),
+            route: endpoint.path.clone(),
+            response_index: endpoint
                 .responses
                 .iter()
-                .find(|r| r.default)
-                .context("No matching response and no default response found")?
-        } else if candidate_responses.len() == 1 {
-            candidate_responses[0]
-        } else {
-            self.select_by_probability(&candidate_responses)?
+                .position(|r| std::ptr::eq(r, selected_response)),
+            reason,
         };
 
+        info!(
+            endpoint = %selection.endpoint,
+            response = ?selection.response_index,
+            reason = %selection.reason,
+            status = selected_response.status,
+            "Response selected"
+        );
+
         let delay = if let Some(delay_config) = &selected_response.delay {
-            let (min, max) = delay_config.parse_range()?;
-            if min == max {
-                min.as_millis() as u64
-            } else {
-                let mut rng = rand::thread_rng();
-                rng.gen_range(min.as_millis()..=max.as_millis()) as u64
+            match delay_config.parse_range() {
+                Ok((min, max)) if min == max => min.as_millis() as u64,
+                Ok((min, max)) => rng.gen_range(min.as_millis()..=max.as_millis()) as u64,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Ignoring invalid response delay");
+                    0
+                }
+            }
+        } else if let Some(source) = &selected_response.delay_expr {
+            let evaluate = |expr: &DelayExpr| {
+                expr.evaluate(context, self.max_computed_delay).as_millis() as u64
+            };
+            match self.delay_exprs.get(source) {
+                Some(expr) => evaluate(expr),
+                None => match DelayExpr::parse(source) {
+                    Ok(expr) => evaluate(&expr),
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Ignoring invalid response delay_expr");
+                        0
+                    }
+                },
+            }
+        } else if let Some(name) = &endpoint.delay_profile {
+            match self.latency_profiles.get(name) {
+                Some(profile) => profile.sample(rng.gen()).as_millis() as u64,
+                None => {
+                    tracing::warn!(profile = %name, "Unknown latency profile");
+                    0
+                }
             }
         } else {
             0
         };
 
-        if delay > 0 {
+        let delay = match self.jitter {
+            Some((min, max)) => {
+                delay.saturating_add(rng.gen_range(min.as_millis()..=max.as_millis()) as u64)
+            }
+            None => delay,
+        };
+
+        let delay = match region {
+            Some(region) => {
+                delay.saturating_add(Self::region_delay(region, &mut rng).as_millis() as u64)
+            }
+            None => delay,
+        };
+
+        if delay > 0 && !self.dry_run {
             info!(delay_ms = delay, "Adding delay to response");
             tokio::time::sleep(Duration::from_millis(delay)).await;
         }
 
-        let body = selected_response
-            .body
-            .as_ref()
-            .map(|body_template| self.render_template(body_template, context, request_count));
+        // The loader has already validated the duration.
+        if let (Some(burn), false) = (
+            endpoint
+                .cpu_burn
+                .as_deref()
+                .and_then(|b| crate::config::types::parse_duration_str(b).ok()),
+            self.dry_run,
+        ) {
+            info!(
+                cpu_burn_ms = burn.as_millis() as u64,
+                "Burning CPU for response"
+            );
+            burn_cpu(burn).await;
+        }
 
-        let mut headers = selected_response.headers.clone();
-        headers.insert(
-            "X-Request-ID".to_string(),
-            context
-                .headers
-                .get("x-request-id")
-                .cloned()
-                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
-        );
+        self.failpoint(failpoints::TEMPLATE_RENDER)
+            .map_err(RuleError::TemplateError)?;
+
+        let endpoint_hits = self.count_hit(endpoint);
+        let response_index = selection.response_index;
+
+        // Generated values come from the request's RNG too, so seeded requests
+        // repeat them.
+        let rng = RefCell::new(rng);
+        let render = |compiled: Option<String>, source: &str, limit: Option<ByteSize>| {
+            // One byte over the limit is enough for `enforce_limit` to see it passed.
+            let cap = limit.map_or(usize::MAX, |ByteSize(limit)| {
+                usize::try_from(limit)
+                    .unwrap_or(usize::MAX)
+                    .saturating_add(1)
+            });
+            let data = self.template_variables(
+                source,
+                context,
+                request_count,
+                Some(endpoint_hits),
+                &endpoint.datasets,
+            );
+            let rendered = template::with_rng(&mut rng.borrow_mut(), || {
+                self.templates
+                    .render(compiled.as_deref(), source, data, cap)
+            });
+            let rendered = self.finish_render(
+                rendered,
+                source,
+                context,
+                request_count,
+                Some(endpoint_hits),
+            );
+            match &page {
+                Some(page) => page.render(&rendered),
+                None => rendered,
+            }
+        };
+
+        let limits = self.limits(endpoint);
+        let body = match &selected_response.body_schema {
+            Some(schema) => Some(
+                self.schemas
+                    .generate(schema, &mut *rng.borrow_mut())
+                    .map_err(|e| {
+                        RuleError::TemplateError(format!(
+                            "Failed to generate body of endpoint '{}' from its body_schema: {:#}",
+                            endpoint.name, e
+                        ))
+                    })?
+                    .to_string(),
+            ),
+            None => selected_response.body.as_deref().map(|body| {
+                let compiled =
+                    response_index.map(|index| CompiledTemplates::body(&endpoint.name, index));
+                render(compiled, body, limits.max_body_size)
+            }),
+        };
+        let body = body
+            .map(|body| {
+                Self::enforce_limit(body, limits.max_body_size, limits.on_exceed, || {
+                    format!("Rendered body of endpoint '{}'", endpoint.name)
+                })
+            })
+            .transpose()?;
+
+        let mut headers = HeaderList::new();
+        for (header, (name, value)) in selected_response.headers.iter().enumerate() {
+            let compiled = response_index
+                .map(|index| CompiledTemplates::header(&endpoint.name, index, header));
+            let value = Self::enforce_limit(
+                render(compiled, value, limits.max_header_size),
+                limits.max_header_size,
+                limits.on_exceed,
+                || format!("Rendered header '{}' of endpoint '{}'", name, endpoint.name),
+            )?;
+            headers.append(name.clone(), value);
+        }
+        headers.insert("X-Request-ID", Self::request_id(context));
+        if selected_response.body_schema.is_some() && headers.get("Content-Type").is_none() {
+            headers.insert("Content-Type", "application/json");
+        }
 
         if endpoint.stateful {
-            headers.insert("X-Request-Count".to_string(), request_count.to_string());
+            headers.insert("X-Request-Count", request_count.to_string());
+        }
+        if endpoint.hit_header {
+            headers.insert("X-Endpoint-Hits", endpoint_hits.to_string());
+        }
+
+        let mut status = selected_response.status;
+        let mut body = body;
+        if let Some(cache) = &endpoint.cache {
+            let window = CacheWindow::now(cache, &endpoint.name);
+            window.apply(&mut headers);
+            if context
+                .headers
+                .get("if-none-match")
+                .is_some_and(|tags| window.matches(tags))
+            {
+                status = 304;
+                body = None;
+            }
         }
 
         Ok(RuleResponse {
-            status: selected_response.status,
+            status,
             body,
             headers,
+            fault: selected_response.fault,
+            transform: None,
+            signing: endpoint.signing.clone(),
+            selection,
+            delay: Duration::from_millis(delay),
+        })
+    }
+
+    /// Counts a request the endpoint answers and returns its number, this request
+    /// included. Concurrent requests each get their own number; dry runs only peek.
+    fn count_hit(&self, endpoint: &Endpoint) -> u64 {
+        if self.dry_run {
+            return self.hits.get(&endpoint.name).map_or(0, |hits| *hits) + 1;
+        }
+        let mut hits = self.hits.entry(endpoint.name.clone()).or_insert(0);
+        *hits += 1;
+        *hits
+    }
+
+    /// Checks an armed failpoint; dry runs never fail on one.
+    fn failpoint(&self, name: &'static str) -> Result<(), String> {
+        if self.dry_run {
+            return Ok(());
+        }
+        self.failpoints.check(name)
+    }
+
+    /// Builds the `404 Not Found` answer for endpoints with `fallthrough: 404`.
+    pub fn not_found(&self, endpoint: &Endpoint, context: &ExecutionContext) -> RuleResponse {
+        self.json_response(
+            endpoint,
+            context,
+            404,
+            r#"{"error": "No matching response"}"#.to_string(),
+            SelectionReason::Fallthrough,
+        )
+    }
+
+    /// Builds the challenge for a request without the endpoint's `basic_auth` credentials.
+    pub fn unauthorized(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        auth: &BasicAuth,
+    ) -> RuleResponse {
+        info!(endpoint = %endpoint.name, "Request lacks valid Basic credentials");
+
+        let body = match &auth.body {
+            Some(template) => self.render_template(template, context, 0),
+            None => r#"{"error": "Unauthorized"}"#.to_string(),
+        };
+        let mut response = self.json_response(
+            endpoint,
+            context,
+            auth.status,
+            body,
+            SelectionReason::Unauthorized,
+        );
+        response.headers.insert(
+            "WWW-Authenticate",
+            format!(r#"Basic realm="{}", charset="UTF-8""#, auth.realm),
+        );
+        response
+    }
+
+    /// Builds the answer of an endpoint whose `timeout` ran out after `after`.
+    pub fn timed_out(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        timeout: &EndpointTimeout,
+        after: Duration,
+    ) -> RuleResponse {
+        info!(
+            endpoint = %endpoint.name,
+            timeout_ms = after.as_millis() as u64,
+            "Endpoint timed out"
+        );
+
+        let body = match &timeout.body {
+            Some(template) => self.render_template(template, context, 0),
+            None => format!(
+                r#"{{"error": "Endpoint timed out after {}"}}"#,
+                timeout.after
+            ),
+        };
+        let mut response = self.json_response(
+            endpoint,
+            context,
+            timeout.status,
+            body,
+            SelectionReason::Timeout,
+        );
+        response.delay = after;
+        response
+    }
+
+    /// The first configured region the client belongs to.
+    fn region(&self, context: &ExecutionContext) -> Option<&Region> {
+        self.regions.iter().find(|region| {
+            region
+                .header
+                .iter()
+                .any(|(name, value)| context.headers.get(&name.to_ascii_lowercase()) == Some(value))
+                || matcher::client_ip_in(&context.client_ip, &region.client_ip)
         })
     }
 
+    /// The network latency a region adds to a response.
+    fn region_delay(region: &Region, rng: &mut impl Rng) -> Duration {
+        let latency = region
+            .latency
+            .as_ref()
+            .map_or(Duration::ZERO, |profile| profile.sample(rng.gen()));
+        // The loader has already validated the range.
+        let jitter = match region
+            .jitter
+            .as_deref()
+            .map(crate::config::types::parse_duration_range)
+        {
+            Some(Ok((min, max))) => {
+                Duration::from_millis(rng.gen_range(min.as_millis()..=max.as_millis()) as u64)
+            }
+            _ => Duration::ZERO,
+        };
+        latency + jitter
+    }
+
+    /// Builds the answer for a request failed by its region's `fault`.
+    fn region_unavailable(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        region: &Region,
+        fault: &RegionFault,
+    ) -> RuleResponse {
+        info!(
+            endpoint = %endpoint.name,
+            region = %region.name,
+            "Region fault failed the request"
+        );
+
+        let body = match &fault.body {
+            Some(template) => self.render_template(template, context, 0),
+            None => format!(r#"{{"error": "Region '{}' is unavailable"}}"#, region.name),
+        };
+        self.json_response(
+            endpoint,
+            context,
+            fault.status,
+            body,
+            SelectionReason::RegionFault,
+        )
+    }
+
+    /// Builds the `503 Service Unavailable` answer of an unhealthy instance.
+    pub fn instance_down(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        instance: &EndpointInstance,
+    ) -> RuleResponse {
+        info!(endpoint = %endpoint.name, instance = %instance.name, "Instance is down");
+
+        let mut response = self.json_response(
+            endpoint,
+            context,
+            503,
+            format!(r#"{{"error": "Instance '{}' is down"}}"#, instance.name),
+            SelectionReason::InstanceDown,
+        );
+        response
+            .headers
+            .insert("X-Molock-Instance", instance.name.clone());
+        response
+    }
+
+    /// Builds the `202 Accepted` answer of an `inbox` endpoint without responses.
+    pub fn accepted(&self, endpoint: &Endpoint, context: &ExecutionContext) -> RuleResponse {
+        self.json_response(
+            endpoint,
+            context,
+            202,
+            r#"{"status": "received"}"#.to_string(),
+            SelectionReason::Inbox,
+        )
+    }
+
+    /// Builds the answer for a request body rejected by the endpoint's `validation`.
+    pub fn invalid_body(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        validator: &BodyValidator,
+        errors: &[FieldError],
+    ) -> RuleResponse {
+        info!(
+            endpoint = %endpoint.name,
+            errors = errors.len(),
+            "Request body failed validation"
+        );
+
+        self.json_response(
+            endpoint,
+            context,
+            validator.status(),
+            validator.render_errors(errors),
+            SelectionReason::InvalidBody,
+        )
+    }
+
+    fn json_response(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        status: u16,
+        body: String,
+        reason: SelectionReason,
+    ) -> RuleResponse {
+        self.count_hit(endpoint);
+        let mut headers = HeaderList::new();
+        headers.insert("Content-Type", "application/json");
+        headers.insert("X-Request-ID", Self::request_id(context));
+
+        RuleResponse {
+            status,
+            body: Some(body),
+            headers,
+            fault: None,
+            transform: None,
+            signing: endpoint.signing.clone(),
+            selection: ResponseSelection {
+                endpoint: endpoint.name.clone(),
+                route: endpoint.path.clone(),
+                response_index: None,
+                reason,
+            },
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// The endpoint's `limits`, with the server-wide ones filling in what it leaves
+    /// unset.
+    fn limits(&self, endpoint: &Endpoint) -> ResponseLimits {
+        match &endpoint.limits {
+            Some(limits) => ResponseLimits {
+                max_body_size: limits.max_body_size.or(self.limits.max_body_size),
+                max_header_size: limits.max_header_size.or(self.limits.max_header_size),
+                on_exceed: limits.on_exceed,
+            },
+            None => self.limits.clone(),
+        }
+    }
+
+    /// Applies an endpoint's size `limit` to a rendered value, failing or truncating
+    /// as its `on_exceed` says. Templates stop rendering just past the limit, so the
+    /// full size of an oversized value is not known.
+    fn enforce_limit(
+        mut value: String,
+        limit: Option<ByteSize>,
+        on_exceed: Oversize,
+        what: impl FnOnce() -> String,
+    ) -> Result<String, RuleError> {
+        let Some(ByteSize(limit)) = limit else {
+            return Ok(value);
+        };
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        if value.len() <= limit {
+            return Ok(value);
+        }
+
+        match on_exceed {
+            Oversize::Fail => Err(RuleError::TemplateError(format!(
+                "{} exceeds its limit of {} bytes",
+                what(),
+                limit
+            ))),
+            Oversize::Truncate => {
+                let mut end = limit;
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                value.truncate(end);
+                Ok(value)
+            }
+        }
+    }
+
+    fn request_id(context: &ExecutionContext) -> String {
+        context
+            .headers
+            .get("x-request-id")
+            .cloned()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    }
+
     fn evaluate_condition(
         &self,
         response: &Response,
         context: &ExecutionContext,
         request_count: u64,
+        attempt: u64,
     ) -> bool {
         if let Some(condition) = &response.condition {
-            match self.evaluate_expression(condition, context, request_count) {
+            match self.evaluate_expression(condition, context, request_count, attempt) {
                 Ok(result) => result,
                 Err(e) => {
                     tracing::warn!(
@@ -163,27 +894,48 @@ impl ResponseExecutor {
     fn evaluate_expression(
         &self,
         expression: &str,
-        _context: &ExecutionContext,
+        context: &ExecutionContext,
         request_count: u64,
+        attempt: u64,
     ) -> anyhow::Result<bool> {
         // Simple expression evaluation
         // In a real implementation, this would use a proper expression evaluator
         let expr = expression.trim().to_lowercase();
 
-        if expr.contains("request_count") {
-            // Parse simple comparisons like "request_count > 2"
-            let parts: Vec<&str> = expr.split_whitespace().collect();
-            if parts.len() == 3 && parts[0] == "request_count" {
-                if let Ok(value) = parts[2].parse::<u64>() {
-                    match parts[1] {
-                        ">" => return Ok(request_count > value),
-                        "<" => return Ok(request_count < value),
-                        ">=" => return Ok(request_count >= value),
-                        "<=" => return Ok(request_count <= value),
-                        "==" | "=" => return Ok(request_count == value),
-                        "!=" => return Ok(request_count != value),
-                        _ => {}
-                    }
+        // Parse simple comparisons like "request_count > 2", "attempt <= 2",
+        // "body_size > 1mb" or "content_encoding == gzip"
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        if parts.len() == 3 {
+            if parts[0] == "content_encoding" {
+                let encoding = context
+                    .headers
+                    .get("content-encoding")
+                    .map(|e| e.trim().to_lowercase())
+                    .unwrap_or_else(|| "identity".to_string());
+                let expected = parts[2].trim_matches(|c| c == '\'' || c == '"');
+                match parts[1] {
+                    "==" | "=" => return Ok(encoding == expected),
+                    "!=" => return Ok(encoding != expected),
+                    _ => {}
+                }
+            }
+
+            let variable = match parts[0] {
+                "request_count" => Some(request_count),
+                "attempt" => Some(attempt),
+                // The size after any transparent decompression of the request body.
+                "body_size" => Some(context.body.as_ref().map_or(0, |b| b.len() as u64)),
+                _ => None,
+            };
+            if let (Some(variable), Ok(ByteSize(value))) = (variable, parts[2].parse()) {
+                match parts[1] {
+                    ">" => return Ok(variable > value),
+                    "<" => return Ok(variable < value),
+                    ">=" => return Ok(variable >= value),
+                    "<=" => return Ok(variable <= value),
+                    "==" | "=" => return Ok(variable == value),
+                    "!=" => return Ok(variable != value),
+                    _ => {}
                 }
             }
         }
@@ -192,61 +944,476 @@ impl ResponseExecutor {
         Ok(true)
     }
 
+    /// The RNG behind probability selection and delays, so runs can be reproduced:
+    /// seeded from the `X-Molock-Seed` header, which repeats the same choices for
+    /// every request carrying it, or drawn from the endpoint's seeded stream, which
+    /// repeats the same sequence of choices from one run to the next. Otherwise it
+    /// is seeded from entropy.
+    fn rng(&self, endpoint: &Endpoint, context: &ExecutionContext) -> StdRng {
+        let header_seed = context
+            .headers
+            .get("x-molock-seed")
+            .and_then(|seed| seed.trim().parse().ok());
+        if let Some(seed) = header_seed {
+            return StdRng::seed_from_u64(seed);
+        }
+        match self.seeded.get_mut(&endpoint.name) {
+            Some(mut stream) => StdRng::seed_from_u64(stream.gen()),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// The attempt number of the request for the `attempt` condition variable.
+    fn attempt(&self, endpoint: &Endpoint, context: &ExecutionContext) -> u64 {
+        // The loader has already validated the window.
+        let window = endpoint
+            .retry_window
+            .as_deref()
+            .and_then(|w| crate::config::types::parse_duration_str(w).ok())
+            .unwrap_or(DEFAULT_RETRY_WINDOW);
+        let client = format!(
+            "{}:{}",
+            endpoint.name,
+            Self::resolve_state_key(endpoint, context)
+        );
+
+        let request = (
+            context.method.as_str(),
+            context.path.as_str(),
+            context.query.as_str(),
+            context.body.as_deref(),
+        );
+        if self.dry_run {
+            self.attempts
+                .peek(&client, &context.headers, request, window)
+        } else {
+            self.attempts
+                .attempt(&client, &context.headers, request, window)
+        }
+    }
+
+    /// Increments the counter under `key` and returns its new value; in dry-run mode
+    /// returns what that value would be without incrementing.
+    fn next_count(&self, key: &str) -> u64 {
+        if self.dry_run {
+            self.peek_count(key, None)
+        } else {
+            self.state_manager.increment_count(key)
+        }
+    }
+
+    /// [`next_count`](Self::next_count), keeping the counter as it is when it changed
+    /// less than `min_interval` ago.
+    fn next_count_after(&self, key: &str, min_interval: Duration) -> u64 {
+        if self.dry_run {
+            self.peek_count(key, Some(min_interval))
+        } else {
+            self.state_manager.increment_count_after(key, min_interval)
+        }
+    }
+
+    /// What [`next_count_after`](Self::next_count_after) (or [`next_count`](Self::next_count)
+    /// without an interval) would return, without changing the counter.
+    fn peek_count(&self, key: &str, min_interval: Option<Duration>) -> u64 {
+        let count = self.state_manager.get_count(key);
+        match min_interval {
+            Some(interval) if self.state_manager.changed_within(key, interval) => count,
+            _ => count + 1,
+        }
+    }
+
+    /// The value identifying a client for per-client state: the client IP, or the
+    /// header named by `state_key` when the request carries it.
+    fn resolve_state_key(endpoint: &Endpoint, context: &ExecutionContext) -> String {
+        match endpoint.state_key.as_deref().unwrap_or("client_ip") {
+            "client_ip" => context.client_ip.clone(),
+            key => context
+                .headers
+                .get(key)
+                .cloned()
+                .unwrap_or_else(|| context.client_ip.clone()),
+        }
+    }
+
+    fn match_reason(response: &Response) -> SelectionReason {
+        if response.condition.is_some() || !response.matchers.is_empty() {
+            SelectionReason::Condition
+        } else {
+            SelectionReason::Unconditional
+        }
+    }
+
+    /// Picks a response at random, weighted by `probability`. Responses without one
+    /// split the weight left over by the others equally; when none sets a probability
+    /// they all weigh the same. Returns `None` if every weight is zero.
     fn select_by_probability<'a>(
         &self,
         responses: &[&'a Response],
-    ) -> anyhow::Result<&'a Response> {
-        let total_probability: f64 = responses.iter().map(|r| r.probability.unwrap_or(0.0)).sum();
+        rng: &mut impl Rng,
+    ) -> Option<&'a Response> {
+        let weights = Self::weights(responses);
+        let total: f64 = weights.iter().sum();
 
-        if total_probability == 0.0 {
-            anyhow::bail!("No responses with probability specified");
+        if total <= 0.0 {
+            return None;
         }
 
-        let mut rng = rand::thread_rng();
-        let random_value: f64 = rng.gen_range(0.0..total_probability);
+        let random_value: f64 = rng.gen_range(0.0..total);
 
         let mut cumulative = 0.0;
-        for response in responses {
-            let probability = response.probability.unwrap_or(0.0);
-            cumulative += probability;
+        for (response, weight) in responses.iter().zip(&weights) {
+            cumulative += weight;
             if random_value < cumulative {
-                return Ok(response);
+                return Some(response);
             }
         }
 
-        Ok(responses.last().unwrap())
+        responses
+            .iter()
+            .zip(&weights)
+            .rev()
+            .find(|(_, weight)| **weight > 0.0)
+            .map(|(response, _)| *response)
+    }
+
+    fn weights(responses: &[&Response]) -> Vec<f64> {
+        let specified: f64 = responses
+            .iter()
+            .filter_map(|r| r.probability)
+            .map(|p| p.max(0.0))
+            .sum();
+        let unspecified = responses.iter().filter(|r| r.probability.is_none()).count();
+
+        let share = if unspecified == 0 {
+            0.0
+        } else if specified == 0.0 {
+            1.0
+        } else {
+            (1.0 - specified).max(0.0) / unspecified as f64
+        };
+
+        responses
+            .iter()
+            .map(|r| r.probability.map_or(share, |p| p.max(0.0)))
+            .collect()
     }
 
-    fn render_template(
+    /// Renders a response template with Handlebars (see [`template`]), then fills in
+    /// `csv_row` helpers. Templates that are not valid Handlebars still get their
+    /// variables substituted, as before the engine was introduced.
+    pub fn render_template(
         &self,
         template: &str,
         context: &ExecutionContext,
         request_count: u64,
     ) -> String {
-        let mut result = template.to_string();
+        let data = self.template_variables(template, context, request_count, None, &HashMap::new());
+        self.finish_render(
+            template::render(template, &data),
+            template,
+            context,
+            request_count,
+            None,
+        )
+    }
 
-        result = result.replace("{{request_count}}", &request_count.to_string());
-        result = result.replace("{{method}}", &context.method);
-        result = result.replace("{{path}}", &context.path);
-        result = result.replace("{{client_ip}}", &context.client_ip);
-        result = result.replace("{{timestamp}}", &chrono::Utc::now().to_rfc3339());
-        result = result.replace("{{uuid}}", &uuid::Uuid::new_v4().to_string());
-        result = result.replace("{{request_id}}", &uuid::Uuid::new_v4().to_string());
+    /// Renders the label `key` of an endpoint's `metric`th metric from its compiled
+    /// template, with the response's `{{status}}` on top of the request's variables.
+    pub(crate) fn render_metric_label(
+        &self,
+        endpoint: &Endpoint,
+        metric: usize,
+        key: &str,
+        context: &ExecutionContext,
+        status: u16,
+    ) -> String {
+        let source = &endpoint.metrics[metric].labels[key];
+        let mut data = self.template_variables(source, context, 0, None, &HashMap::new());
+        data["status"] = Value::from(status);
+        let name = CompiledTemplates::label(&endpoint.name, metric, key);
+        let rendered = self.templates.render(Some(&name), source, data, usize::MAX);
+        self.finish_render(rendered, source, context, 0, None)
+    }
+
+    /// Fills in the `csv_row` helpers of a rendered template, or substitutes the
+    /// variables of one Handlebars could not render.
+    fn finish_render(
+        &self,
+        rendered: anyhow::Result<String>,
+        template: &str,
+        context: &ExecutionContext,
+        request_count: u64,
+        endpoint_hits: Option<u64>,
+    ) -> String {
+        let rendered = rendered.unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Invalid template, substituting variables only");
+            Self::substitute_variables(template, context, request_count, endpoint_hits)
+        });
+        self.fill_fixtures(&rendered, request_count)
+    }
+
+    /// Everything a template is rendered against: the request's variables, plus the
+    /// endpoint's hit count as `{{endpoint_hits}}` and its `datasets` as
+    /// `{{datasets.<name>}}` when rendering one of its responses.
+    pub(crate) fn template_variables(
+        &self,
+        template: &str,
+        context: &ExecutionContext,
+        request_count: u64,
+        endpoint_hits: Option<u64>,
+        datasets: &HashMap<String, String>,
+    ) -> Value {
+        let mut data = Self::template_data(template, context, request_count);
+        if let Some(hits) = endpoint_hits {
+            data["endpoint_hits"] = Value::from(hits);
+        }
+        if template.contains("captured") {
+            data["captured"] = Value::Object(self.captures.slots());
+        }
+        if template.contains("datasets.") {
+            data["datasets"] = self.datasets.template_data(datasets, template);
+        }
+        data
+    }
+
+    /// Fills in the `csv_row` helpers Handlebars wrote back.
+    pub(crate) fn fill_fixtures(&self, rendered: &str, request_count: u64) -> String {
+        self.fixtures.render(rendered, request_count)
+    }
+
+    /// The variables a template can use. Path parameters sit at the top level and
+    /// shadow the built-in names; the body, form and multipart fields are only decoded
+    /// when used.
+    pub(crate) fn template_data(
+        template: &str,
+        context: &ExecutionContext,
+        request_count: u64,
+    ) -> Value {
+        let mut data = Map::new();
+        let (host, port) = context.host_and_port();
+        let mut query = Map::new();
+        for (key, value) in context.query.split('&').filter_map(|p| p.split_once('=')) {
+            query.entry(key).or_insert_with(|| Value::from(value));
+        }
+        let segments: Vec<&str> = context.path.split('/').filter(|s| !s.is_empty()).collect();
+
+        data.extend([
+            ("request_count".to_string(), Value::from(request_count)),
+            ("method".to_string(), Value::from(context.method.as_str())),
+            ("path".to_string(), Value::from(context.path.as_str())),
+            (
+                "client_ip".to_string(),
+                Value::from(context.client_ip.as_str()),
+            ),
+            (
+                "timestamp".to_string(),
+                Value::from(chrono::Utc::now().to_rfc3339()),
+            ),
+            (
+                "uuid".to_string(),
+                Value::from(uuid::Uuid::new_v4().to_string()),
+            ),
+            (
+                "request_id".to_string(),
+                Value::from(uuid::Uuid::new_v4().to_string()),
+            ),
+            ("url".to_string(), Value::from(context.url())),
+            ("scheme".to_string(), Value::from(context.scheme())),
+            ("host".to_string(), Value::from(host)),
+            ("port".to_string(), Value::from(port)),
+            ("path_segments".to_string(), Value::from(segments)),
+            ("query".to_string(), Value::Object(query)),
+            ("headers".to_string(), serde_json::json!(context.headers)),
+            ("header".to_string(), serde_json::json!(context.headers)),
+        ]);
+
+        if template.contains("cookie.") {
+            let mut cookies = Map::new();
+            for (name, value) in context.cookies() {
+                cookies.entry(name).or_insert(Value::from(value));
+            }
+            data.insert("cookie".to_string(), Value::Object(cookies));
+        }
+
+        if template.contains("body") {
+            data.insert(
+                "body".to_string(),
+                context.json_body().unwrap_or(Value::Null),
+            );
+        }
+
+        if template.contains("form.") {
+            let mut form = Map::new();
+            for (key, value) in context.form_fields() {
+                form.entry(key).or_insert(Value::from(value));
+            }
+            data.insert("form".to_string(), Value::Object(form));
+        }
+
+        if template.contains("multipart.") {
+            let mut multipart = Map::new();
+            for part in context.multipart_parts() {
+                // Metadata a part lacks stays unset, so its placeholder is left as is.
+                multipart.entry(part.name.clone()).or_insert_with(|| {
+                    let mut fields = Map::new();
+                    fields.insert("value".to_string(), Value::from(part.value.as_str()));
+                    fields.insert("size".to_string(), Value::from(part.value.len()));
+                    if let Some(filename) = &part.filename {
+                        fields.insert("filename".to_string(), Value::from(filename.as_str()));
+                    }
+                    if let Some(content_type) = &part.content_type {
+                        fields.insert(
+                            "content_type".to_string(),
+                            Value::from(content_type.as_str()),
+                        );
+                    }
+                    Value::Object(fields)
+                });
+            }
+            data.insert("multipart".to_string(), Value::Object(multipart));
+        }
+
+        // A path parameter named like a built-in (`/files/*path`) is what the
+        // endpoint author means by it.
+        data.extend(
+            context
+                .path_params
+                .iter()
+                .map(|(key, value)| (key.clone(), Value::from(value.as_str()))),
+        );
+
+        Value::Object(data)
+    }
+
+    /// Plain `{{variable}}` replacement, for templates Handlebars cannot parse.
+    fn substitute_variables(
+        template: &str,
+        context: &ExecutionContext,
+        request_count: u64,
+        endpoint_hits: Option<u64>,
+    ) -> String {
+        let mut result = template.to_string();
 
         for (key, value) in &context.path_params {
             result = result.replace(&format!("{{{{{}}}}}", key), value);
         }
 
+        result = result.replace("{{request_count}}", &request_count.to_string());
+        if let Some(hits) = endpoint_hits {
+            result = result.replace("{{endpoint_hits}}", &hits.to_string());
+        }
+        result = result.replace("{{method}}", &context.method);
+        result = result.replace("{{path}}", &context.path);
+        result = result.replace("{{client_ip}}", &context.client_ip);
+        result = result.replace("{{timestamp}}", &chrono::Utc::now().to_rfc3339());
+        result = result.replace("{{uuid}}", &uuid::Uuid::new_v4().to_string());
+        result = result.replace("{{request_id}}", &uuid::Uuid::new_v4().to_string());
+
+        if result.contains("{{url}}")
+            || result.contains("{{scheme}}")
+            || result.contains("{{host}}")
+            || result.contains("{{port}}")
+        {
+            let (host, port) = context.host_and_port();
+            result = result.replace("{{url}}", &context.url());
+            result = result.replace("{{scheme}}", context.scheme());
+            result = result.replace("{{host}}", &host);
+            result = result.replace("{{port}}", &port.to_string());
+        }
+
+        for (index, segment) in context
+            .path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .enumerate()
+        {
+            result = result.replace(&format!("{{{{path_segments.[{}]}}}}", index), segment);
+        }
+
         for param in context.query.split('&') {
             if let Some((key, value)) = param.split_once('=') {
                 result = result.replace(&format!("{{{{query.{}}}}}", key), value);
             }
         }
 
+        if result.contains("{{header.") {
+            result = HEADER_VARIABLE
+                .replace_all(&result, |captures: &regex::Captures<'_>| {
+                    match context.headers.get(&captures[1].to_ascii_lowercase()) {
+                        Some(value) => value.clone(),
+                        None => captures[0].to_string(),
+                    }
+                })
+                .into_owned();
+        }
+
+        if result.contains("{{cookie.") {
+            for (name, value) in context.cookies() {
+                result = result.replace(&format!("{{{{cookie.{}}}}}", name), &value);
+            }
+        }
+
+        if result.contains("{{body") {
+            if let Some(body) = context.json_body() {
+                result = BODY_FIELD
+                    .replace_all(&result, |captures: &regex::Captures<'_>| {
+                        let pointer = captures[1].replace('.', "/");
+                        match body.pointer(&pointer) {
+                            Some(Value::String(text)) => text.clone(),
+                            Some(value) => value.to_string(),
+                            None => captures[0].to_string(),
+                        }
+                    })
+                    .into_owned();
+            }
+        }
+
+        if result.contains("{{form.") {
+            for (key, value) in context.form_fields() {
+                result = result.replace(&format!("{{{{form.{}}}}}", key), &value);
+            }
+        }
+
+        if result.contains("{{multipart.") {
+            for part in context.multipart_parts() {
+                let variable = |suffix: &str| format!("{{{{multipart.{}{}}}}}", part.name, suffix);
+                if let Some(filename) = &part.filename {
+                    result = result.replace(&variable(".filename"), filename);
+                }
+                if let Some(content_type) = &part.content_type {
+                    result = result.replace(&variable(".content_type"), content_type);
+                }
+                result = result.replace(&variable(".size"), &part.value.len().to_string());
+                result = result.replace(&variable(""), &part.value);
+            }
+        }
+
         result
     }
 }
 
+/// Spins a blocking-pool thread for `duration`, occupying a core the way real work
+/// would instead of yielding it like a sleep.
+async fn burn_cpu(duration: Duration) {
+    let burned = tokio::task::spawn_blocking(move || {
+        let start = std::time::Instant::now();
+        let mut state: u64 = 1;
+        while start.elapsed() < duration {
+            for _ in 0..1_000 {
+                state = std::hint::black_box(
+                    state
+                        .wrapping_mul(6_364_136_223_846_793_005)
+                        .wrapping_add(1),
+                );
+            }
+        }
+    })
+    .await;
+    if let Err(e) = burned {
+        tracing::warn!(error = %e, "CPU burn did not complete");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,6 +1428,7 @@ mod tests {
             headers: HashMap::new(),
             client_ip: "127.0.0.1".to_string(),
             path_params: HashMap::new(),
+            body: None,
         }
     }
 
@@ -275,11 +1443,12 @@ mod tests {
                 status: 200,
                 delay: None,
                 body: Some("OK".to_string()),
-                headers: HashMap::new(),
                 condition: None,
                 probability: None,
                 default: false,
+                ..Default::default()
             }],
+            ..Default::default()
         }
     }
 
@@ -293,6 +1462,41 @@ mod tests {
         let result = executor.execute(&endpoint, &context).await.unwrap();
         assert_eq!(result.status, 200);
         assert_eq!(result.body, Some("OK".to_string()));
+        assert_eq!(result.selection.endpoint, "Test");
+        assert_eq!(result.selection.response_index, Some(0));
+        assert_eq!(result.selection.reason, SelectionReason::Unconditional);
+    }
+
+    #[tokio::test]
+    async fn test_execute_selection_reasons() {
+        let state_manager = Arc::new(StateManager::new());
+        let executor = ResponseExecutor::new(state_manager);
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.stateful = true;
+        endpoint.responses[0].condition = Some("request_count > 1".to_string());
+        endpoint.responses.push(Response {
+            status: 503,
+            delay: None,
+            body: None,
+            condition: None,
+            probability: None,
+            default: true,
+            ..Default::default()
+        });
+        endpoint.responses[1].condition = Some("request_count > 99".to_string());
+
+        let context = create_test_context();
+
+        let first = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(first.status, 503);
+        assert_eq!(first.selection.response_index, Some(1));
+        assert_eq!(first.selection.reason, SelectionReason::Default);
+
+        let second = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(second.status, 200);
+        assert_eq!(second.selection.response_index, Some(0));
+        assert_eq!(second.selection.reason, SelectionReason::Condition);
     }
 
     #[tokio::test]
@@ -313,6 +1517,113 @@ mod tests {
         assert!(elapsed >= Duration::from_millis(100));
     }
 
+    #[tokio::test]
+    async fn test_execute_with_delay_expr() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new())).with_dry_run(true);
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0].delay_expr = Some("body.items.length * 10ms".to_string());
+
+        let mut context = create_test_context();
+        context.body = Some(r#"{"items": [1, 2, 3, 4]}"#.to_string());
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.delay, Duration::from_millis(40));
+
+        context.body = None;
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.delay, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_execute_with_delay_profile() {
+        let profile = LatencyProfile::try_from("p100 50ms".to_string()).unwrap();
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()))
+            .with_latency_profiles(HashMap::from([("flat".to_string(), profile)]));
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.delay_profile = Some("flat".to_string());
+        let context = create_test_context();
+
+        let start = tokio::time::Instant::now();
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert!(response.delay <= Duration::from_millis(50));
+        assert!(start.elapsed() >= response.delay);
+
+        // An explicit response delay takes precedence over the profile.
+        endpoint.responses[0].delay = Some(Delay::Fixed("120ms".to_string()));
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.delay, Duration::from_millis(120));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_jitter() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()))
+            .with_jitter(Duration::from_millis(30), Duration::from_millis(40));
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0].delay = Some(Delay::Fixed("50ms".to_string()));
+        let context = create_test_context();
+
+        let start = std::time::Instant::now();
+        executor.execute(&endpoint, &context).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_regions() {
+        let region = |name: &str| Region {
+            name: name.to_string(),
+            header: HashMap::from([("X-Region".to_string(), name.to_string())]),
+            client_ip: Vec::new(),
+            latency: None,
+            jitter: None,
+            fault: None,
+        };
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new())).with_regions(vec![
+            Region {
+                latency: Some(LatencyProfile::try_from("p100 60ms".to_string()).unwrap()),
+                jitter: Some("10-10ms".to_string()),
+                ..region("eu-west")
+            },
+            Region {
+                client_ip: vec!["10.9.0.0/16".to_string()],
+                fault: Some(RegionFault {
+                    rate: 1.0,
+                    status: 503,
+                    body: None,
+                }),
+                ..region("us-east")
+            },
+        ]);
+        let endpoint = create_test_endpoint();
+
+        let mut eu = create_test_context();
+        eu.headers
+            .insert("x-region".to_string(), "eu-west".to_string());
+        let response = executor.execute(&endpoint, &eu).await.unwrap();
+        assert_eq!(response.status, 200);
+        // Up to 60ms sampled from the profile, plus 10ms of jitter.
+        assert!(response.delay >= Duration::from_millis(10));
+        assert!(response.delay <= Duration::from_millis(70));
+
+        // Clients outside every region see the endpoint as configured.
+        let response = executor
+            .execute(&endpoint, &create_test_context())
+            .await
+            .unwrap();
+        assert_eq!(response.delay, Duration::ZERO);
+
+        let mut us = create_test_context();
+        us.client_ip = "10.9.3.4".to_string();
+        let response = executor.execute(&endpoint, &us).await.unwrap();
+        assert_eq!(response.status, 503);
+        assert_eq!(response.selection.reason, SelectionReason::RegionFault);
+        assert_eq!(
+            response.body.as_deref(),
+            Some(r#"{"error": "Region 'us-east' is unavailable"}"#)
+        );
+    }
+
     #[tokio::test]
     async fn test_execute_stateful() {
         let state_manager = Arc::new(StateManager::new());
@@ -337,6 +1648,208 @@ mod tests {
         assert_eq!(state_manager.get_count("127.0.0.1"), 2);
     }
 
+    #[tokio::test]
+    async fn test_unanswered_requests_do_not_count() {
+        let state_manager = Arc::new(StateManager::new());
+        let executor = ResponseExecutor::new(state_manager.clone());
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.stateful = true;
+        endpoint.responses[0].condition = Some("request_count >= 2".to_string());
+        let context = create_test_context();
+
+        for _ in 0..3 {
+            assert!(matches!(
+                executor.execute(&endpoint, &context).await,
+                Err(RuleError::NoResponse { .. })
+            ));
+        }
+        assert_eq!(state_manager.get_count("127.0.0.1"), 0);
+
+        endpoint.responses.push(Response {
+            status: 202,
+            default: true,
+            ..Default::default()
+        });
+        let first = executor.execute(&endpoint, &context).await.unwrap();
+        let second = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!((first.status, second.status), (202, 200));
+        assert_eq!(state_manager.get_count("127.0.0.1"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_cpu_burn() {
+        let mut endpoint = create_test_endpoint();
+        endpoint.cpu_burn = Some("30ms".to_string());
+        let context = create_test_context();
+
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let start = std::time::Instant::now();
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // A dry run skips the burn, so an hour-long one still answers right away.
+        endpoint.cpu_burn = Some("1h".to_string());
+        let dry_run = ResponseExecutor::new(Arc::new(StateManager::new())).with_dry_run(true);
+        tokio::time::timeout(
+            Duration::from_secs(60),
+            dry_run.execute(&endpoint, &context),
+        )
+        .await
+        .expect("dry run burned CPU")
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_datasets() {
+        let dir = tempfile::tempdir().unwrap();
+        let users = dir.path().join("users.csv");
+        std::fs::write(&users, "id,name\n1,Ann\n2,Bob\n").unwrap();
+
+        let mut endpoint = create_test_endpoint();
+        endpoint
+            .datasets
+            .insert("users".to_string(), users.to_str().unwrap().to_string());
+        endpoint.responses[0].body = Some(
+            r#"{{#with (find_row datasets.users "id" id)}}{"name": "{{name}}"}{{else}}{}{{/with}}"#
+                .to_string(),
+        );
+        let mut context = create_test_context();
+        context
+            .path_params
+            .insert("id".to_string(), "2".to_string());
+
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()))
+            .with_datasets(Datasets::new(std::slice::from_ref(&endpoint)));
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.body.as_deref(), Some(r#"{"name": "Bob"}"#));
+
+        context
+            .path_params
+            .insert("id".to_string(), "7".to_string());
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.body.as_deref(), Some("{}"));
+    }
+
+    #[tokio::test]
+    async fn test_path_params_win_over_builtins() {
+        let mut endpoint = create_test_endpoint();
+        endpoint.path = "/files/*path".to_string();
+        endpoint.responses[0].body = Some("{{path}} via {{method}}".to_string());
+        let mut context = create_test_context();
+        context.path = "/files/docs/a.txt".to_string();
+        context
+            .path_params
+            .insert("path".to_string(), "docs/a.txt".to_string());
+
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.body.as_deref(), Some("docs/a.txt via GET"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_body_schema() {
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0].body = None;
+        endpoint.responses[0].body_schema = Some(serde_json::json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": {"id": {"type": "integer", "minimum": 1, "maximum": 5}}
+        }));
+        let context = create_test_context();
+
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        let body: Value = serde_json::from_str(response.body.as_deref().unwrap()).unwrap();
+        assert!((1..=5).contains(&body["id"].as_i64().unwrap()));
+        assert_eq!(
+            response.headers.get("Content-Type").map(String::as_str),
+            Some("application/json")
+        );
+
+        endpoint.responses[0]
+            .headers
+            .insert("Content-Type", "application/vnd.api+json");
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(
+            response.headers.get("Content-Type").map(String::as_str),
+            Some("application/vnd.api+json")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_stateful_with_min_interval() {
+        let state_manager = Arc::new(StateManager::new());
+        let executor = ResponseExecutor::new(state_manager.clone());
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.stateful = true;
+        endpoint.min_interval = Some("100ms".to_string());
+
+        let context = create_test_context();
+        let count = |response: RuleResponse| response.headers.get("X-Request-Count").cloned();
+
+        let first = executor.execute(&endpoint, &context).await.unwrap();
+        let retry = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(count(first).as_deref(), Some("1"));
+        assert_eq!(count(retry).as_deref(), Some("1"));
+
+        let dry_run = ResponseExecutor::new(state_manager.clone()).with_dry_run(true);
+        let peeked = dry_run.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(count(peeked).as_deref(), Some("1"));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let peeked = dry_run.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(count(peeked).as_deref(), Some("2"));
+        let next = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(count(next).as_deref(), Some("2"));
+        assert_eq!(state_manager.get_count("127.0.0.1"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_serialized_requests_complete_in_count_order() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut endpoint = create_test_endpoint();
+        endpoint.stateful = true;
+        endpoint.serialize = true;
+        // The first call is the slowest, so without queueing later ones overtake it.
+        endpoint.responses = vec![
+            Response {
+                status: 503,
+                delay: Some(Delay::Fixed("100ms".to_string())),
+                condition: Some("request_count == 1".to_string()),
+                ..Default::default()
+            },
+            Response {
+                status: 200,
+                default: true,
+                ..Default::default()
+            },
+        ];
+
+        let context = create_test_context();
+        let completed = std::sync::Mutex::new(Vec::new());
+        let requests = (0..3).map(|_| async {
+            let response = executor.execute(&endpoint, &context).await.unwrap();
+            completed.lock().unwrap().push((
+                response.headers.get("X-Request-Count").unwrap().clone(),
+                response.status,
+            ));
+        });
+        futures::future::join_all(requests).await;
+
+        assert_eq!(
+            completed.into_inner().unwrap(),
+            vec![
+                ("1".to_string(), 503),
+                ("2".to_string(), 200),
+                ("3".to_string(), 200)
+            ]
+        );
+    }
+
     #[test]
     fn test_evaluate_condition() {
         let state_manager = Arc::new(StateManager::new());
@@ -346,16 +1859,248 @@ mod tests {
             status: 200,
             delay: None,
             body: None,
-            headers: HashMap::new(),
             condition: Some("request_count > 2".to_string()),
             probability: None,
             default: false,
+            ..Default::default()
         };
 
         let context = create_test_context();
 
-        assert!(!executor.evaluate_condition(&response, &context, 1));
-        assert!(executor.evaluate_condition(&response, &context, 3));
+        assert!(!executor.evaluate_condition(&response, &context, 1, 1));
+        assert!(executor.evaluate_condition(&response, &context, 3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_response_size_limits() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0].body = Some("{{path}}{{path}}{{path}}".to_string());
+        endpoint.responses[0]
+            .headers
+            .insert("X-Echo", "{{path}}{{path}}");
+        endpoint.limits = Some(ResponseLimits {
+            max_body_size: Some(ByteSize(8)),
+            max_header_size: Some(ByteSize(16)),
+            on_exceed: Oversize::Fail,
+        });
+        let context = create_test_context();
+
+        let error = executor.execute(&endpoint, &context).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Rendered body of endpoint 'Test' exceeds its limit of 8 bytes"
+        );
+
+        endpoint.limits.as_mut().unwrap().on_exceed = Oversize::Truncate;
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.body.as_deref(), Some("/test/te"));
+        assert_eq!(response.headers.get("X-Echo").unwrap(), "/test/test");
+
+        endpoint.limits.as_mut().unwrap().max_header_size = Some(ByteSize(4));
+        endpoint.limits.as_mut().unwrap().on_exceed = Oversize::Fail;
+        endpoint.limits.as_mut().unwrap().max_body_size = None;
+        let error = executor.execute(&endpoint, &context).await.unwrap_err();
+        assert!(error.to_string().starts_with("Rendered header 'X-Echo'"));
+
+        // Endpoints without limits of their own fall back to the server-wide ones.
+        let executor = executor.with_limits(ResponseLimits {
+            max_body_size: Some(ByteSize(8)),
+            max_header_size: None,
+            on_exceed: Oversize::Truncate,
+        });
+        endpoint.limits = None;
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.body.as_deref(), Some("/test/te"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_headers_and_revalidation() {
+        use crate::config::CacheSimulation;
+
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.cache = Some(CacheSimulation {
+            ttl: "3600s".to_string(),
+            private: false,
+        });
+        let mut context = create_test_context();
+
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.headers.get("Cache-Control").unwrap(),
+            "public, max-age=3600"
+        );
+        assert!(response.headers.get("Age").is_some());
+        let etag = response.headers.get("ETag").unwrap().clone();
+
+        context
+            .headers
+            .insert("if-none-match".to_string(), etag.clone());
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        // Unless the hour rolled over in between, the ETag is still current.
+        if response.headers.get("ETag") == Some(&etag) {
+            assert_eq!(response.status, 304);
+            assert_eq!(response.body, None);
+        }
+
+        context
+            .headers
+            .insert("if-none-match".to_string(), "\"stale\"".to_string());
+        let response = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.body.is_some());
+    }
+
+    async fn run_five(
+        executor: &ResponseExecutor,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+    ) -> Vec<(u16, Duration)> {
+        let mut outcomes = Vec::new();
+        for _ in 0..5 {
+            let response = executor.execute(endpoint, context).await.unwrap();
+            outcomes.push((response.status, response.delay));
+        }
+        outcomes
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_leaves_state_untouched() {
+        let state_manager = Arc::new(StateManager::new());
+        let executor = ResponseExecutor::new(state_manager.clone()).with_dry_run(true);
+        let mut endpoint = create_test_endpoint();
+        endpoint.stateful = true;
+        endpoint.responses[0].delay = Some(Delay::Fixed("10s".to_string()));
+
+        let context = create_test_context();
+        for _ in 0..2 {
+            let response = executor.execute(&endpoint, &context).await.unwrap();
+            assert_eq!(response.headers.get("X-Request-Count").unwrap(), "1");
+            assert_eq!(response.delay, Duration::from_secs(10));
+        }
+        assert_eq!(state_manager.get_count("127.0.0.1"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_request_renders_same_values() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0].body =
+            Some("{{fake.name}} {{fake.uuid}} {{random_int 1 1000000}}".to_string());
+        let mut context = create_test_context();
+        context
+            .headers
+            .insert("x-molock-seed".to_string(), "7".to_string());
+
+        let first = executor.execute(&endpoint, &context).await.unwrap().body;
+        let second = executor.execute(&endpoint, &context).await.unwrap().body;
+        assert_eq!(first, second);
+
+        endpoint.responses[0].body = None;
+        endpoint.responses[0].body_schema = Some(serde_json::json!({
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {"id": {"type": "integer"}, "name": {"type": "string"}}
+        }));
+        let first = executor.execute(&endpoint, &context).await.unwrap().body;
+        let second = executor.execute(&endpoint, &context).await.unwrap().body;
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_selection_is_reproducible() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses = (0..10)
+            .map(|i| Response {
+                status: 200 + i,
+                delay: Some(Delay::Range("0ms-5ms".to_string())),
+                ..Default::default()
+            })
+            .collect();
+
+        let mut context = create_test_context();
+        context
+            .headers
+            .insert("x-molock-seed".to_string(), "42".to_string());
+
+        let first = run_five(&executor, &endpoint, &context).await;
+        assert!(first.windows(2).all(|pair| pair[0] == pair[1]));
+
+        // An endpoint seed starts one stream per executor: requests differ from one
+        // another, but a new executor replays the same sequence.
+        context.headers.clear();
+        endpoint.seed = Some(42);
+        let seeded = || {
+            ResponseExecutor::new(Arc::new(StateManager::new()))
+                .with_endpoint_seeds(std::slice::from_ref(&endpoint))
+        };
+        let sequence = run_five(&seeded(), &endpoint, &context).await;
+        assert!(sequence.windows(2).any(|pair| pair[0] != pair[1]));
+        assert_eq!(run_five(&seeded(), &endpoint, &context).await, sequence);
+    }
+
+    #[test]
+    fn test_body_size_and_encoding_conditions() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut context = create_test_context();
+        context.body = Some("x".repeat(2048));
+        context
+            .headers
+            .insert("content-encoding".to_string(), "gzip".to_string());
+
+        let holds = |condition: &str, context: &ExecutionContext| {
+            executor
+                .evaluate_expression(condition, context, 0, 1)
+                .unwrap()
+        };
+        assert!(holds("body_size > 1KB", &context));
+        assert!(!holds("body_size > 1MB", &context));
+        assert!(holds("body_size == 2048", &context));
+        assert!(holds("content_encoding == gzip", &context));
+        assert!(holds("content_encoding == 'gzip'", &context));
+        assert!(!holds("content_encoding != gzip", &context));
+
+        context.headers.clear();
+        assert!(holds("content_encoding == identity", &context));
+    }
+
+    #[tokio::test]
+    async fn test_attempt_condition_fails_first_retries() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses = vec![
+            Response {
+                status: 503,
+                condition: Some("attempt <= 2".to_string()),
+                ..Default::default()
+            },
+            Response {
+                status: 200,
+                default: true,
+                ..Default::default()
+            },
+        ];
+
+        let mut context = create_test_context();
+        context.body = Some(r#"{"order": 1}"#.to_string());
+
+        let statuses: Vec<u16> = [
+            executor.execute(&endpoint, &context).await.unwrap().status,
+            executor.execute(&endpoint, &context).await.unwrap().status,
+            executor.execute(&endpoint, &context).await.unwrap().status,
+        ]
+        .into();
+        assert_eq!(statuses, vec![503, 503, 200]);
+
+        // A different body is a new call rather than a retry.
+        context.body = Some(r#"{"order": 2}"#.to_string());
+        assert_eq!(
+            executor.execute(&endpoint, &context).await.unwrap().status,
+            503
+        );
     }
 
     #[test]
@@ -377,6 +2122,82 @@ mod tests {
         assert!(result.contains("127.0.0.1"));
     }
 
+    #[test]
+    fn test_render_template_url_helpers() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.path = "/api/orders/42".to_string();
+        context.query = "expand=items".to_string();
+        context
+            .headers
+            .insert("host".to_string(), "mock.local:9090".to_string());
+
+        let template = "{{scheme}}|{{host}}|{{port}}|{{path_segments.[2]}}|{{url}}";
+        assert_eq!(
+            executor.render_template(template, &context, 0),
+            "http|mock.local|9090|42|http://mock.local:9090/api/orders/42?expand=items"
+        );
+
+        context
+            .headers
+            .insert("host".to_string(), "mock.local".to_string());
+        context
+            .headers
+            .insert("x-forwarded-proto".to_string(), "https".to_string());
+        assert_eq!(
+            executor.render_template("{{url}} {{port}}", &context, 0),
+            "https://mock.local/api/orders/42?expand=items 443"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_renders_header_templates() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses[0]
+            .headers
+            .insert("Location", "{{url}}/{{path_segments.[0]}}");
+
+        let mut context = create_test_context();
+        context
+            .headers
+            .insert("host".to_string(), "localhost:8080".to_string());
+
+        let result = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(
+            result.headers.get("Location").unwrap(),
+            "http://localhost:8080/test/test"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_paginated() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.pagination = Some(crate::config::Pagination {
+            page_size: 2,
+            total: 3,
+            cursor_param: "after".to_string(),
+        });
+        endpoint.responses[0].body = Some("{{page.offset}}:{{next_cursor}}".to_string());
+
+        let mut context = create_test_context();
+        let first = executor.execute(&endpoint, &context).await.unwrap();
+        let (offset, cursor) = first.body.as_deref().unwrap().split_once(':').unwrap();
+        assert_eq!(offset, "0");
+        assert!(!cursor.is_empty());
+
+        context.query = format!("after={}", cursor);
+        let second = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(second.body.as_deref(), Some("2:"));
+
+        context.query = "after=forged".to_string();
+        let invalid = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(invalid.status, 400);
+        assert_eq!(invalid.selection.reason, SelectionReason::InvalidCursor);
+    }
+
     #[test]
     fn test_render_template_empty_query() {
         let state_manager = Arc::new(StateManager::new());
@@ -391,35 +2212,279 @@ mod tests {
         assert_eq!(result, "User {{query.name}}");
     }
 
+    #[test]
+    fn test_render_template_form_fields() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.body = Some("username=jane+doe&email=jane%40example.com&tag=a&tag=b".to_string());
+
+        let template = "{{form.username}} <{{form.email}}> {{form.tag}} {{form.missing}}";
+        assert_eq!(
+            executor.render_template(template, &context, 1),
+            "jane doe <jane@example.com> a {{form.missing}}"
+        );
+    }
+
+    #[test]
+    fn test_render_template_blocks_over_request_body() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.query = "verbose=1".to_string();
+        context.body = Some(r#"{"items": [{"sku": "a-1"}, {"sku": "b-2"}]}"#.to_string());
+
+        let template = "{{#if query.verbose}}{{method}}: {{/if}}\
+                        {{#each body.items}}{{@index}}={{sku}} {{/each}}{{page.number}}";
+        assert_eq!(
+            executor.render_template(template, &context, 1),
+            "GET: 0=a-1 1=b-2 {{page.number}}"
+        );
+
+        // Templates Handlebars rejects still get their plain variables.
+        assert_eq!(
+            executor.render_template("{{#if method}} {{path}}", &context, 1),
+            "{{#if method}} /test"
+        );
+    }
+
+    #[test]
+    fn test_render_template_headers_and_cookies() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.headers = HashMap::from([
+            ("x-api-key".to_string(), "k-123".to_string()),
+            ("cookie".to_string(), "session=abc; theme=dark".to_string()),
+        ]);
+
+        let template = "{{header.x-api-key}} {{header.X-Api-Key}} {{cookie.session}} \
+                        {{cookie.theme}} {{header.x-missing}} {{cookie.missing}}";
+        assert_eq!(
+            executor.render_template(template, &context, 1),
+            "k-123 k-123 abc dark {{header.x-missing}} {{cookie.missing}}"
+        );
+        assert_eq!(
+            executor.render_template(
+                "{{#if}} {{header.X-API-KEY}} {{cookie.session}}",
+                &context,
+                1
+            ),
+            "{{#if}} k-123 abc"
+        );
+    }
+
+    #[test]
+    fn test_render_template_body_fields() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.body =
+            Some(r#"{"user": {"id": 42, "name": "Ada"}, "tags": ["a", "b"]}"#.to_string());
+        assert_eq!(
+            executor.render_template(
+                r#"{"id": {{body.user.id}}, "name": {{json body.user.name}}, "tag": "{{body.tags.[1]}}"}"#,
+                &context,
+                1
+            ),
+            r#"{"id": 42, "name": "Ada", "tag": "b"}"#
+        );
+
+        // Without the Handlebars pass, fields are still looked up one by one.
+        assert_eq!(
+            executor.render_template(
+                "{{#if}} {{body.user.name}} {{body.tags.0}} {{body.user}} {{body.missing}}",
+                &context,
+                1
+            ),
+            r#"{{#if}} Ada a {"id":42,"name":"Ada"} {{body.missing}}"#
+        );
+
+        context.body = Some("not json".to_string());
+        assert_eq!(
+            executor.render_template("{{body}} / {{body.user.id}}", &context, 1),
+            "not json / {{body.user.id}}"
+        );
+
+        context.body = None;
+        assert_eq!(
+            executor.render_template("{{#if}} {{body.user.id}}", &context, 1),
+            "{{#if}} {{body.user.id}}"
+        );
+    }
+
+    #[test]
+    fn test_render_template_multipart_parts() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+
+        let mut context = create_test_context();
+        context.headers.insert(
+            "content-type".to_string(),
+            "multipart/form-data; boundary=b".to_string(),
+        );
+        context.body = Some(
+            "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nTrip\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"a.png\"\r\n\
+             Content-Type: image/png\r\n\r\n12345\r\n--b--\r\n"
+                .to_string(),
+        );
+
+        let template = "{{multipart.title}}: {{multipart.photo.filename}} \
+                        ({{multipart.photo.content_type}}, {{multipart.photo.size}} bytes) \
+                        {{multipart.title.filename}}";
+        assert_eq!(
+            executor.render_template(template, &context, 1),
+            "Trip: a.png (image/png, 5 bytes) {{multipart.title.filename}}"
+        );
+    }
+
     #[test]
     fn test_select_by_probability() {
         let state_manager = Arc::new(StateManager::new());
         let executor = ResponseExecutor::new(state_manager);
 
-        let responses = vec![
+        let responses = [
             Response {
                 status: 200,
                 delay: None,
                 body: None,
-                headers: HashMap::new(),
                 condition: None,
                 probability: Some(0.3),
                 default: false,
+                ..Default::default()
             },
             Response {
                 status: 500,
                 delay: None,
                 body: None,
-                headers: HashMap::new(),
                 condition: None,
                 probability: Some(0.7),
                 default: false,
+                ..Default::default()
             },
         ];
 
         let refs: Vec<&Response> = responses.iter().collect();
-        let selected = executor.select_by_probability(&refs).unwrap();
+        let selected = executor
+            .select_by_probability(&refs, &mut rand::thread_rng())
+            .unwrap();
 
         assert!(selected.status == 200 || selected.status == 500);
     }
+
+    fn response(status: u16, probability: Option<f64>) -> Response {
+        Response {
+            status,
+            delay: None,
+            body: None,
+            condition: None,
+            probability,
+            default: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_weights_for_unspecified_probabilities() {
+        let responses = [response(200, None), response(201, None)];
+        let refs: Vec<&Response> = responses.iter().collect();
+        assert_eq!(ResponseExecutor::weights(&refs), vec![1.0, 1.0]);
+
+        let responses = [
+            response(200, None),
+            response(404, None),
+            response(500, Some(0.2)),
+        ];
+        let refs: Vec<&Response> = responses.iter().collect();
+        let weights = ResponseExecutor::weights(&refs);
+        assert!((weights[0] - 0.4).abs() < 1e-9);
+        assert!((weights[1] - 0.4).abs() < 1e-9);
+        assert_eq!(weights[2], 0.2);
+
+        let responses = [response(200, None), response(500, Some(1.5))];
+        let refs: Vec<&Response> = responses.iter().collect();
+        assert_eq!(ResponseExecutor::weights(&refs), vec![0.0, 1.5]);
+    }
+
+    #[test]
+    fn test_select_by_probability_all_zero() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let responses = [response(200, Some(0.0)), response(500, Some(0.0))];
+        let refs: Vec<&Response> = responses.iter().collect();
+
+        assert!(executor
+            .select_by_probability(&refs, &mut rand::thread_rng())
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_default_is_not_a_candidate() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses.push(Response {
+            default: true,
+            ..response(503, None)
+        });
+
+        let context = create_test_context();
+        for _ in 0..20 {
+            let result = executor.execute(&endpoint, &context).await.unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.selection.reason, SelectionReason::Unconditional);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_selection_strategies() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses = vec![
+            response(200, None),
+            response(201, None),
+            response(202, None),
+        ];
+        let context = create_test_context();
+
+        endpoint.selection = SelectionStrategy::FirstMatch;
+        let result = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(result.status, 200);
+        assert_eq!(result.selection.reason, SelectionReason::Unconditional);
+
+        endpoint.selection = SelectionStrategy::RoundRobin;
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            let result = executor.execute(&endpoint, &context).await.unwrap();
+            assert_eq!(result.selection.reason, SelectionReason::RoundRobin);
+            statuses.push(result.status);
+        }
+        assert_eq!(statuses, vec![200, 201, 202, 200]);
+
+        endpoint.selection = SelectionStrategy::Weighted;
+        let result = executor.execute(&endpoint, &context).await.unwrap();
+        assert_eq!(result.selection.reason, SelectionReason::Probability);
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_per_state_key() {
+        let executor = ResponseExecutor::new(Arc::new(StateManager::new()));
+        let mut endpoint = create_test_endpoint();
+        endpoint.responses = vec![response(200, None), response(503, None)];
+        endpoint.selection = SelectionStrategy::RoundRobin;
+        endpoint.state_key = Some("x-client".to_string());
+
+        let context_for = |client: &str| {
+            let mut context = create_test_context();
+            context
+                .headers
+                .insert("x-client".to_string(), client.to_string());
+            context
+        };
+        let (alice, bob) = (context_for("alice"), context_for("bob"));
+
+        let mut statuses = Vec::new();
+        for context in [&alice, &alice, &bob, &alice, &bob] {
+            statuses.push(executor.execute(&endpoint, context).await.unwrap().status);
+        }
+        assert_eq!(statuses, vec![200, 503, 200, 200, 503]);
+    }
 }
@@ -0,0 +1,178 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Admin-armed failpoints that make parts of Molock itself fail on demand, so
+//! harnesses embedding the mock can rehearse its partial failures deterministically.
+//! Armed through `/__admin/failpoints/{name}`.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Fails the per-client state store behind stateful endpoints and round-robin
+/// selection, answered as a `state_error`.
+pub const STATE_STORE: &str = "state_store";
+/// Fails rendering of response bodies and headers, answered as a `template_error`.
+pub const TEMPLATE_RENDER: &str = "template_render";
+
+/// Every failpoint that can be armed.
+pub const FAILPOINTS: &[&str] = &[STATE_STORE, TEMPLATE_RENDER];
+
+/// How an armed failpoint fails.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FailpointSpec {
+    /// Fail this many times and then disarm; every time until disarmed when omitted.
+    pub count: Option<u64>,
+    /// Error message reported by the failing subsystem.
+    pub message: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct Failpoints {
+    armed: Arc<DashMap<&'static str, FailpointSpec>>,
+}
+
+impl Failpoints {
+    /// Arms `name`, replacing any previous spec. Returns `false` for an unknown name.
+    pub fn arm(&self, name: &str, spec: FailpointSpec) -> bool {
+        match FAILPOINTS.iter().find(|known| **known == name) {
+            Some(known) => {
+                self.armed.insert(known, spec);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disarms `name`, returning whether it was armed.
+    pub fn disarm(&self, name: &str) -> bool {
+        self.armed.remove(name).is_some()
+    }
+
+    pub fn armed(&self) -> BTreeMap<String, FailpointSpec> {
+        self.armed
+            .iter()
+            .map(|entry| (entry.key().to_string(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Passes unless `name` is armed, in which case one failure is used up and its
+    /// message returned.
+    pub fn check(&self, name: &'static str) -> Result<(), String> {
+        // Most checks find nothing armed; skip taking the shard's write lock then.
+        if !self.armed.contains_key(name) {
+            return Ok(());
+        }
+        // Decrementing and removing under one entry lock, so concurrent checks
+        // never use up more failures than were armed.
+        let Entry::Occupied(mut entry) = self.armed.entry(name) else {
+            return Ok(());
+        };
+
+        let spec = entry.get_mut();
+        let message = spec
+            .message
+            .clone()
+            .unwrap_or_else(|| format!("Failpoint '{}' triggered", name));
+        if let Some(count) = spec.count.as_mut() {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                entry.remove();
+            }
+        }
+        tracing::warn!(failpoint = name, "Failpoint triggered");
+        Err(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arm_and_disarm() {
+        let failpoints = Failpoints::default();
+        assert!(failpoints.check(STATE_STORE).is_ok());
+
+        assert!(!failpoints.arm("proxy_upstream", FailpointSpec::default()));
+        assert!(failpoints.arm(
+            STATE_STORE,
+            FailpointSpec {
+                count: None,
+                message: Some("disk full".to_string()),
+            }
+        ));
+        assert_eq!(failpoints.check(STATE_STORE), Err("disk full".to_string()));
+        assert_eq!(failpoints.check(STATE_STORE), Err("disk full".to_string()));
+        assert!(failpoints.check(TEMPLATE_RENDER).is_ok());
+        assert_eq!(failpoints.armed().len(), 1);
+
+        assert!(failpoints.disarm(STATE_STORE));
+        assert!(!failpoints.disarm(STATE_STORE));
+        assert!(failpoints.check(STATE_STORE).is_ok());
+    }
+
+    #[test]
+    fn test_count_disarms_after_use() {
+        let failpoints = Failpoints::default();
+        failpoints.arm(
+            TEMPLATE_RENDER,
+            FailpointSpec {
+                count: Some(2),
+                message: None,
+            },
+        );
+
+        assert_eq!(
+            failpoints.check(TEMPLATE_RENDER),
+            Err("Failpoint 'template_render' triggered".to_string())
+        );
+        assert!(failpoints.check(TEMPLATE_RENDER).is_err());
+        assert!(failpoints.check(TEMPLATE_RENDER).is_ok());
+        assert!(failpoints.armed().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_checks_use_up_exactly_count() {
+        let failpoints = Failpoints::default();
+        failpoints.arm(
+            STATE_STORE,
+            FailpointSpec {
+                count: Some(5),
+                message: None,
+            },
+        );
+
+        let failures: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..10)
+                            .filter(|_| failpoints.check(STATE_STORE).is_err())
+                            .count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(failures, 5);
+        assert!(failpoints.armed().is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! CSV fixtures for data-driven responses. A template such as
+//! `{{csv_row "fixtures/users.csv" request_count "email"}}` renders one column of the
+//! Nth data row of the file, cycling back to the first row past the end; without a
+//! column the whole row renders as a JSON object keyed by the header row.
+
+use crate::config::Endpoint;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+static CSV_ROW: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"\{\{csv_row\s+"([^"]+)"\s+(\w+)(?:\s+"([^"]*)")?\s*\}\}"#).unwrap());
+
+/// A parsed CSV file: its header row and the data rows below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvTable {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let reader = csv::Reader::from_path(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open CSV fixture {:?}: {}", path, e))?;
+        Self::from_reader(reader)
+            .map_err(|e| anyhow::anyhow!("Invalid CSV fixture {:?}: {}", path, e))
+    }
+
+    fn from_reader<R: std::io::Read>(mut reader: csv::Reader<R>) -> anyhow::Result<Self> {
+        let headers = reader.headers()?.iter().map(str::to_string).collect();
+        let rows = reader
+            .records()
+            .map(|record| Ok(record?.iter().map(str::to_string).collect()))
+            .collect::<anyhow::Result<Vec<Vec<String>>>>()?;
+        if rows.is_empty() {
+            anyhow::bail!("no data rows");
+        }
+
+        Ok(Self { headers, rows })
+    }
+
+    /// The `n`th data row (1-based, `0` counting as the first), cycling past the end.
+    pub fn row(&self, n: u64) -> &[String] {
+        let index = n.saturating_sub(1) % self.rows.len() as u64;
+        &self.rows[index as usize]
+    }
+
+    /// One column of the `n`th row, or `None` when the header row has no such column.
+    pub fn cell(&self, n: u64, column: &str) -> Option<&str> {
+        let index = self.headers.iter().position(|h| h == column)?;
+        self.row(n).get(index).map(String::as_str)
+    }
+
+    /// The `n`th row as a JSON object keyed by the header row.
+    pub fn row_json(&self, n: u64) -> String {
+        self.row_value(n).to_string()
+    }
+
+    /// Every data row as a JSON object keyed by the header row.
+    pub fn objects(&self) -> Vec<serde_json::Value> {
+        (1..=self.rows.len() as u64)
+            .map(|n| self.row_value(n))
+            .collect()
+    }
+
+    fn row_value(&self, n: u64) -> serde_json::Value {
+        let row: serde_json::Map<String, serde_json::Value> = self
+            .headers
+            .iter()
+            .cloned()
+            .zip(self.row(n).iter().cloned().map(serde_json::Value::String))
+            .collect();
+        serde_json::Value::Object(row)
+    }
+}
+
+/// The CSV files endpoint responses read, loaded once when the engine is built.
+#[derive(Clone, Default)]
+pub struct CsvFixtures {
+    tables: Arc<HashMap<String, Arc<CsvTable>>>,
+}
+
+impl CsvFixtures {
+    /// Loads the files the `csv_row` helpers of `endpoints`' response bodies and
+    /// headers reference. The loader rejects a config with a file that fails to
+    /// load, so one failing here is only logged.
+    pub fn new(endpoints: &[Endpoint]) -> Self {
+        let mut tables = HashMap::new();
+        let templates = endpoints
+            .iter()
+            .flat_map(|endpoint| &endpoint.responses)
+            .flat_map(|response| {
+                response
+                    .body
+                    .iter()
+                    .chain(response.headers.iter().map(|(_, value)| value))
+            });
+        for path in templates.flat_map(|template| csv_paths(template)) {
+            if tables.contains_key(path) {
+                continue;
+            }
+            match CsvTable::load(path) {
+                Ok(table) => {
+                    tables.insert(path.to_string(), Arc::new(table));
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to load CSV fixture"),
+            }
+        }
+        Self {
+            tables: Arc::new(tables),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Arc<CsvTable>> {
+        self.tables.get(path)
+    }
+
+    /// Replaces every `csv_row` helper in `template`. The row index is
+    /// `request_count` or a number; helpers that cannot be resolved, such as ones
+    /// naming a file no response referenced, are left as is.
+    pub fn render(&self, template: &str, request_count: u64) -> String {
+        if !template.contains("{{csv_row") {
+            return template.to_string();
+        }
+
+        CSV_ROW
+            .replace_all(template, |captures: &Captures| {
+                let helper = captures[0].to_string();
+                let n = match &captures[2] {
+                    "request_count" => request_count,
+                    literal => match literal.parse() {
+                        Ok(n) => n,
+                        Err(_) => {
+                            tracing::warn!(helper = %helper, "Invalid csv_row index");
+                            return helper;
+                        }
+                    },
+                };
+                let Some(table) = self.get(&captures[1]) else {
+                    tracing::warn!(helper = %helper, "CSV fixture is not loaded");
+                    return helper;
+                };
+
+                match captures.get(3) {
+                    Some(column) => table
+                        .cell(n, column.as_str())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| {
+                            tracing::warn!(helper = %helper, "Unknown CSV column");
+                            helper
+                        }),
+                    None => table.row_json(n),
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// `template` with the relative files of its `csv_row` helpers resolved against `base`.
+pub fn resolve_csv_paths(template: &str, base: &Path) -> String {
+    CSV_ROW
+        .replace_all(template, |captures: &Captures| {
+            let (helper, path) = (captures.get(0).unwrap(), captures.get(1).unwrap());
+            let (start, end) = (path.start() - helper.start(), path.end() - helper.start());
+            format!(
+                "{}{}{}",
+                &helper.as_str()[..start],
+                base.join(path.as_str()).display(),
+                &helper.as_str()[end..]
+            )
+        })
+        .into_owned()
+}
+
+/// Files referenced by `csv_row` helpers in `template`, for config validation.
+pub fn csv_paths(template: &str) -> Vec<&str> {
+    CSV_ROW
+        .captures_iter(template)
+        .filter_map(|captures| captures.get(1))
+        .map(|path| path.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(dir: &tempfile::TempDir) -> String {
+        let path = dir.path().join("users.csv");
+        std::fs::write(
+            &path,
+            "id,email\n1,ann@example.com\n2,\"bob, jr@example.com\"\n",
+        )
+        .unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_rows_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let table = CsvTable::load(fixture(&dir)).unwrap();
+
+        assert_eq!(table.cell(0, "email"), Some("ann@example.com"));
+        assert_eq!(table.cell(1, "email"), Some("ann@example.com"));
+        assert_eq!(table.cell(2, "email"), Some("bob, jr@example.com"));
+        assert_eq!(table.cell(3, "id"), Some("1"));
+        assert_eq!(table.cell(1, "name"), None);
+        assert_eq!(
+            table.row_json(2),
+            r#"{"email":"bob, jr@example.com","id":"2"}"#
+        );
+    }
+
+    #[test]
+    fn test_render_helpers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fixture(&dir);
+        let template = format!(
+            r#"{{"user": {{{{csv_row "{0}" request_count}}}}, "email": "{{{{csv_row "{0}" 2 "email"}}}}"}}"#,
+            path
+        );
+        let endpoint = Endpoint {
+            responses: vec![crate::config::Response {
+                body: Some(template.clone()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let fixtures = CsvFixtures::new(&[endpoint]);
+
+        assert_eq!(
+            fixtures.render(&template, 3),
+            r#"{"user": {"email":"ann@example.com","id":"1"}, "email": "bob, jr@example.com"}"#
+        );
+        assert_eq!(csv_paths(&template), vec![path.as_str(), path.as_str()]);
+        assert_eq!(
+            resolve_csv_paths(
+                r#"{{csv_row "users.csv" 1 "id"}} {{csv_row "/abs.csv" 2}}"#,
+                Path::new("teams")
+            ),
+            r#"{{csv_row "teams/users.csv" 1 "id"}} {{csv_row "/abs.csv" 2}}"#
+        );
+
+        let missing = r#"{{csv_row "missing.csv" request_count}}"#;
+        assert_eq!(fixtures.render(missing, 1), missing);
+    }
+
+    #[test]
+    fn test_empty_fixture_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.csv");
+        std::fs::write(&path, "id,email\n").unwrap();
+
+        let error = CsvTable::load(&path).unwrap_err().to_string();
+        assert!(error.contains("no data rows"), "{}", error);
+    }
+}
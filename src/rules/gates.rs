@@ -0,0 +1,101 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Admin-controlled gates that hold requests to endpoints with `gate:` until
+//! `/__admin/gates/{name}/release` is called, so callers can be tested against
+//! responses arriving in a chosen order.
+
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+#[derive(Clone, Default)]
+pub struct Gates {
+    held: Arc<DashMap<String, VecDeque<oneshot::Sender<()>>>>,
+}
+
+impl Gates {
+    /// Waits until the gate releases this request.
+    pub async fn hold(&self, gate: &str) {
+        let (tx, rx) = oneshot::channel();
+        self.held.entry(gate.to_string()).or_default().push_back(tx);
+        // The sender is only dropped without a release when the gate is cleared.
+        let _ = rx.await;
+    }
+
+    /// Number of requests currently held by `gate`.
+    pub fn held(&self, gate: &str) -> usize {
+        self.held
+            .get(gate)
+            .map(|held| held.iter().filter(|tx| !tx.is_closed()).count())
+            .unwrap_or(0)
+    }
+
+    /// Releases up to `count` held requests in arrival order, or all of them
+    /// when `count` is `None`, and returns how many were released.
+    pub fn release(&self, gate: &str, count: Option<usize>) -> usize {
+        let Some(mut held) = self.held.get_mut(gate) else {
+            return 0;
+        };
+
+        let limit = count.unwrap_or(usize::MAX);
+        let mut released = 0;
+        while released < limit {
+            let Some(tx) = held.pop_front() else {
+                break;
+            };
+            // Requests whose client went away no longer count.
+            if tx.send(()).is_ok() {
+                released += 1;
+            }
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_hold_until_released() {
+        let gates = Gates::default();
+
+        let first = tokio::spawn({
+            let gates = gates.clone();
+            async move { gates.hold("orders").await }
+        });
+        let second = tokio::spawn({
+            let gates = gates.clone();
+            async move { gates.hold("orders").await }
+        });
+
+        while gates.held("orders") < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(gates.release("other", None), 0);
+
+        assert_eq!(gates.release("orders", Some(1)), 1);
+        first.await.unwrap();
+        assert!(!second.is_finished());
+
+        assert_eq!(gates.release("orders", None), 1);
+        second.await.unwrap();
+        assert_eq!(gates.held("orders"), 0);
+    }
+}
@@ -0,0 +1,397 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Just enough of GraphQL to tell requests to a single `/graphql` endpoint apart: the
+//! operation a request executes, its type and name, and the fields it selects at the
+//! top level. Documents are tokenized rather than fully parsed, so invalid documents
+//! may still yield an operation.
+
+use crate::config::GraphqlOperationType;
+use crate::rules::ExecutionContext;
+use serde_json::Value;
+
+/// A GraphQL request as sent over HTTP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphqlRequest {
+    pub query: String,
+    pub operation_name: Option<String>,
+    pub variables: Value,
+}
+
+impl GraphqlRequest {
+    /// Reads the request from a JSON body with a `query` member, an
+    /// `application/graphql` body, or the query string of a `GET`. `body` is the
+    /// request body already parsed as JSON, if it is JSON.
+    pub fn from_context(context: &ExecutionContext, body: Option<&Value>) -> Option<Self> {
+        if let Some(body) = body {
+            return Some(Self {
+                query: body.get("query")?.as_str()?.to_string(),
+                operation_name: body
+                    .get("operationName")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                variables: body.get("variables").cloned().unwrap_or(Value::Null),
+            });
+        }
+
+        let is_graphql = context
+            .headers
+            .get("content-type")
+            .is_some_and(|value| value.trim_start().starts_with("application/graphql"));
+        if is_graphql {
+            return Some(Self {
+                query: context.body.clone()?,
+                operation_name: None,
+                variables: Value::Null,
+            });
+        }
+
+        let mut request = Self {
+            query: String::new(),
+            operation_name: None,
+            variables: Value::Null,
+        };
+        for (key, value) in url::form_urlencoded::parse(context.query.as_bytes()) {
+            match key.as_ref() {
+                "query" => request.query = value.into_owned(),
+                "operationName" => request.operation_name = Some(value.into_owned()),
+                "variables" => request.variables = serde_json::from_str(&value).ok()?,
+                _ => {}
+            }
+        }
+        (!request.query.is_empty()).then_some(request)
+    }
+
+    /// The operation the request executes: the one `operationName` names, or the
+    /// only one in the document.
+    pub fn operation(&self) -> Option<Operation> {
+        let mut operations = operations(&self.query);
+        match &self.operation_name {
+            Some(name) => operations
+                .into_iter()
+                .find(|operation| operation.name.as_deref() == Some(name.as_str())),
+            None if operations.len() == 1 => operations.pop(),
+            None => None,
+        }
+    }
+}
+
+/// An operation defined in a GraphQL document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub kind: GraphqlOperationType,
+    pub name: Option<String>,
+    /// The fields of the top-level selection set, by name rather than alias.
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Punct(char),
+    /// `...`, introducing a fragment spread or inline fragment.
+    Spread,
+}
+
+/// The operations in `document`, skipping fragment definitions.
+pub fn operations(document: &str) -> Vec<Operation> {
+    let tokens = tokenize(document);
+    let mut operations = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let (kind, name) = match &tokens[i] {
+            Token::Punct('{') => (Some(GraphqlOperationType::Query), None),
+            Token::Name(keyword) => {
+                let kind = match keyword.as_str() {
+                    "query" => Some(GraphqlOperationType::Query),
+                    "mutation" => Some(GraphqlOperationType::Mutation),
+                    "subscription" => Some(GraphqlOperationType::Subscription),
+                    _ => None,
+                };
+                let name = match tokens.get(i + 1) {
+                    Some(Token::Name(name)) if kind.is_some() => Some(name.clone()),
+                    _ => None,
+                };
+                (kind, name)
+            }
+            _ => (None, None),
+        };
+
+        // Variable definitions and directives come before the selection set, and
+        // default values in them may be object literals.
+        let mut parens = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::Punct('(') => parens += 1,
+                Token::Punct(')') => parens -= 1,
+                Token::Punct('{') if parens == 0 => break,
+                _ => {}
+            }
+            i += 1;
+        }
+        let end = closing_brace(&tokens, i);
+        if let Some(kind) = kind {
+            operations.push(Operation {
+                kind,
+                name,
+                fields: top_level_fields(&tokens[i.min(end)..end]),
+            });
+        }
+        i = end + 1;
+    }
+
+    operations
+}
+
+/// The index of the `}` closing the `{` at `open`, or the end of `tokens`.
+fn closing_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// The field names directly inside `selection`, which starts at its opening `{`.
+fn top_level_fields(selection: &[Token]) -> Vec<String> {
+    let mut fields = Vec::new();
+    let (mut braces, mut parens) = (0, 0);
+    let mut i = 0;
+
+    while i < selection.len() {
+        match &selection[i] {
+            Token::Punct('{') => braces += 1,
+            Token::Punct('}') => braces -= 1,
+            Token::Punct('(') => parens += 1,
+            Token::Punct(')') => parens -= 1,
+            _ if braces != 1 || parens != 0 => {}
+            Token::Spread => {
+                // `...Fragment` or `... on Type`.
+                i += match selection.get(i + 1) {
+                    Some(Token::Name(on)) if on == "on" => 2,
+                    Some(Token::Name(_)) => 1,
+                    _ => 0,
+                };
+            }
+            // A directive such as `@include(if: $full)`.
+            Token::Punct('@') => i += 1,
+            Token::Name(name) => match (selection.get(i + 1), selection.get(i + 2)) {
+                (Some(Token::Punct(':')), Some(Token::Name(field))) => {
+                    fields.push(field.clone());
+                    i += 2;
+                }
+                _ => fields.push(name.clone()),
+            },
+            Token::Punct(_) => {}
+        }
+        i += 1;
+    }
+
+    fields
+}
+
+/// Splits a document into names and punctuation, dropping whitespace, commas,
+/// comments, strings and numbers, none of which matter for finding operations.
+fn tokenize(document: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = document.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => skip_string(&mut chars),
+            '.' if chars.next_if_eq(&'.').is_some() && chars.next_if_eq(&'.').is_some() => {
+                tokens.push(Token::Spread)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                tokens.push(Token::Name(name));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                while chars
+                    .next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-'))
+                    .is_some()
+                {}
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '@' | '$' | '!' | '=' | '|' | '&' => {
+                tokens.push(Token::Punct(c))
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+/// Skips a string whose opening quote was just read, block strings (`"""`) included.
+fn skip_string(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    if chars.next_if_eq(&'"').is_some() {
+        if chars.next_if_eq(&'"').is_none() {
+            // An empty string.
+            return;
+        }
+        let mut quotes = 0;
+        for c in chars.by_ref() {
+            quotes = if c == '"' { quotes + 1 } else { 0 };
+            if quotes == 3 {
+                return;
+            }
+        }
+        return;
+    }
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' | '\n' => return,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn context(query: &str, headers: &[(&str, &str)], body: Option<&str>) -> ExecutionContext {
+        ExecutionContext {
+            method: "POST".to_string(),
+            path: "/graphql".to_string(),
+            query: query.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: body.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let document = r#"
+            # Fetches a user.
+            query GetUser($id: ID!, $filter: Filter = {kind: "a { b"}) @cached(ttl: 60) {
+                user(id: $id) { id name }
+                me: viewer { ...UserFields }
+                ... on Query { ignored }
+                description(text: """block "string" {""")
+            }
+            fragment UserFields on User { email }
+            mutation { createOrder(input: {sku: "x", qty: -1.5e3}) { id } }
+        "#;
+        let operations = operations(document);
+
+        assert_eq!(
+            operations,
+            vec![
+                Operation {
+                    kind: GraphqlOperationType::Query,
+                    name: Some("GetUser".to_string()),
+                    fields: vec![
+                        "user".to_string(),
+                        "viewer".to_string(),
+                        "description".to_string()
+                    ],
+                },
+                Operation {
+                    kind: GraphqlOperationType::Mutation,
+                    name: None,
+                    fields: vec!["createOrder".to_string()],
+                },
+            ]
+        );
+
+        assert_eq!(
+            super::operations("{ hero { name } }"),
+            vec![Operation {
+                kind: GraphqlOperationType::Query,
+                name: None,
+                fields: vec!["hero".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_operation_selection() {
+        let request = |operation_name: Option<&str>| GraphqlRequest {
+            query: "query A { a } mutation B { b }".to_string(),
+            operation_name: operation_name.map(str::to_string),
+            variables: Value::Null,
+        };
+
+        let b = request(Some("B")).operation().unwrap();
+        assert_eq!(b.kind, GraphqlOperationType::Mutation);
+        assert_eq!(b.fields, vec!["b".to_string()]);
+        assert!(request(Some("C")).operation().is_none());
+        // Several operations and no operationName is ambiguous.
+        assert!(request(None).operation().is_none());
+    }
+
+    #[test]
+    fn test_request_sources() {
+        let body = json!({"query": "{ a }", "operationName": "A", "variables": {"id": 1}});
+        let request = GraphqlRequest::from_context(&context("", &[], None), Some(&body)).unwrap();
+        assert_eq!(request.operation_name.as_deref(), Some("A"));
+        assert_eq!(request.variables, json!({"id": 1}));
+
+        let raw = context(
+            "",
+            &[("content-type", "application/graphql")],
+            Some("mutation M { m }"),
+        );
+        let request = GraphqlRequest::from_context(&raw, None).unwrap();
+        assert_eq!(request.operation().unwrap().name.as_deref(), Some("M"));
+
+        let get = context(
+            "query=query%20Q%20%7B%20q%20%7D&variables=%7B%22id%22%3A2%7D",
+            &[],
+            None,
+        );
+        let request = GraphqlRequest::from_context(&get, None).unwrap();
+        assert_eq!(request.query, "query Q { q }");
+        assert_eq!(request.variables, json!({"id": 2}));
+
+        assert!(GraphqlRequest::from_context(&context("", &[], None), None).is_none());
+        assert!(
+            GraphqlRequest::from_context(&context("", &[], None), Some(&json!({"a": 1}))).is_none()
+        );
+    }
+}
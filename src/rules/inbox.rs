@@ -0,0 +1,138 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Request capture for endpoints with `inbox:`, so systems that send webhooks can be
+//! verified through `/__admin/inbox/{name}`.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InboxRequest {
+    pub timestamp: String,
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+/// Filters for inbox retrieval; every field set must match.
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InboxQuery {
+    pub method: Option<String>,
+    pub path: Option<String>,
+    /// Substring the request body must contain.
+    pub body: Option<String>,
+}
+
+impl InboxQuery {
+    fn matches(&self, request: &InboxRequest) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(&request.method))
+            && self.path.as_ref().is_none_or(|p| *p == request.path)
+            && self.body.as_ref().is_none_or(|b| {
+                request
+                    .body
+                    .as_deref()
+                    .is_some_and(|body| body.contains(b.as_str()))
+            })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Inboxes {
+    requests: Arc<DashMap<String, Vec<InboxRequest>>>,
+}
+
+impl Inboxes {
+    pub fn record(&self, inbox: &str, request: InboxRequest) {
+        self.requests
+            .entry(inbox.to_string())
+            .or_default()
+            .push(request);
+    }
+
+    /// Requests received by `inbox` that match `query`, oldest first.
+    pub fn find(&self, inbox: &str, query: &InboxQuery) -> Vec<InboxRequest> {
+        self.requests
+            .get(inbox)
+            .map(|requests| {
+                requests
+                    .iter()
+                    .filter(|r| query.matches(r))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self, inbox: &str) {
+        self.requests.remove(inbox);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: Option<&str>) -> InboxRequest {
+        InboxRequest {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            body: body.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_record_find_and_clear() {
+        let inboxes = Inboxes::default();
+        inboxes.record(
+            "hooks",
+            request("POST", "/hooks/a", Some(r#"{"event":"paid"}"#)),
+        );
+        inboxes.record("hooks", request("PUT", "/hooks/b", None));
+        inboxes.record("other", request("POST", "/other", None));
+
+        assert_eq!(inboxes.find("hooks", &InboxQuery::default()).len(), 2);
+        assert!(inboxes.find("missing", &InboxQuery::default()).is_empty());
+
+        let paid = inboxes.find(
+            "hooks",
+            &InboxQuery {
+                method: Some("post".to_string()),
+                body: Some("paid".to_string()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            paid,
+            vec![request("POST", "/hooks/a", Some(r#"{"event":"paid"}"#))]
+        );
+
+        inboxes.clear("hooks");
+        assert!(inboxes.find("hooks", &InboxQuery::default()).is_empty());
+        assert_eq!(inboxes.find("other", &InboxQuery::default()).len(), 1);
+    }
+}
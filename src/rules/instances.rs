@@ -0,0 +1,141 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Simulated upstream clusters: endpoints with `instances:` spread their requests
+//! over named nodes whose health the admin API toggles, so client-side load
+//! balancers can be tested against nodes that flap.
+
+use crate::config::{Endpoint, EndpointInstance};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// Header naming the instance a client wants, bypassing the rotation.
+pub const INSTANCE_HEADER: &str = "x-molock-instance";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InstanceState {
+    pub name: String,
+    pub weight: u32,
+    pub healthy: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct Instances {
+    /// Health set through the admin API, by endpoint and instance name.
+    health: Arc<DashMap<(String, String), bool>>,
+    turns: Arc<DashMap<String, u64>>,
+}
+
+impl Instances {
+    /// The instance serving a request: the one named by `pinned`, if the endpoint
+    /// has it, otherwise the next one in the weighted rotation.
+    pub fn pick<'a>(
+        &self,
+        endpoint: &'a Endpoint,
+        pinned: Option<&str>,
+    ) -> Option<&'a EndpointInstance> {
+        if let Some(instance) =
+            pinned.and_then(|name| endpoint.instances.iter().find(|i| i.name == name.trim()))
+        {
+            return Some(instance);
+        }
+
+        let total: u64 = endpoint.instances.iter().map(|i| i.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut turn = {
+            let mut turns = self.turns.entry(endpoint.name.clone()).or_insert(0);
+            let turn = *turns;
+            *turns += 1;
+            turn % total
+        };
+        endpoint.instances.iter().find(|instance| {
+            if turn < instance.weight as u64 {
+                return true;
+            }
+            turn -= instance.weight as u64;
+            false
+        })
+    }
+
+    pub fn is_healthy(&self, endpoint: &str, instance: &EndpointInstance) -> bool {
+        self.health
+            .get(&(endpoint.to_string(), instance.name.clone()))
+            .map_or(instance.healthy, |healthy| *healthy)
+    }
+
+    pub fn set_healthy(&self, endpoint: &str, instance: &str, healthy: bool) {
+        self.health
+            .insert((endpoint.to_string(), instance.to_string()), healthy);
+    }
+
+    pub fn state(&self, endpoint: &str, instance: &EndpointInstance) -> InstanceState {
+        InstanceState {
+            name: instance.name.clone(),
+            weight: instance.weight,
+            healthy: self.is_healthy(endpoint, instance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint() -> Endpoint {
+        let instance = |name: &str, weight: u32| EndpointInstance {
+            name: name.to_string(),
+            weight,
+            healthy: true,
+        };
+        Endpoint {
+            name: "Users".to_string(),
+            instances: vec![instance("a", 2), instance("b", 1), instance("c", 0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_weighted_rotation_and_pinning() {
+        let instances = Instances::default();
+        let endpoint = endpoint();
+
+        let picked: Vec<&str> = (0..6)
+            .map(|_| instances.pick(&endpoint, None).unwrap().name.as_str())
+            .collect();
+        assert_eq!(picked, ["a", "a", "b", "a", "a", "b"]);
+
+        // Weight 0 keeps an instance out of the rotation but still addressable.
+        assert_eq!(instances.pick(&endpoint, Some("c")).unwrap().name, "c");
+        assert_eq!(instances.pick(&endpoint, Some("zz")).unwrap().name, "a");
+    }
+
+    #[test]
+    fn test_health_overrides_config() {
+        let instances = Instances::default();
+        let endpoint = endpoint();
+        let b = &endpoint.instances[1];
+
+        assert!(instances.is_healthy("Users", b));
+        instances.set_healthy("Users", "b", false);
+        assert!(!instances.is_healthy("Users", b));
+        assert!(!instances.state("Users", b).healthy);
+        assert!(instances.is_healthy("Other", b));
+    }
+}
@@ -14,9 +14,34 @@
  * limitations under the License.
  */
 
-use crate::config::Endpoint;
+use crate::config::{Endpoint, GraphqlMatcher, JwtMatcher, RequestMatcher};
+use crate::rules::captures::{self, Captures};
+use crate::rules::custom_matchers;
+use crate::rules::graphql::GraphqlRequest;
+use crate::rules::ExecutionContext;
+use anyhow::Context;
+use jsonwebtoken::{DecodingKey, Validation};
 use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use sxd_document::dom::Document;
+use tracing::debug;
+
+/// Marks an endpoint path as a regular expression, e.g. `~/files/(?P<year>\d{4})/\w+\.pdf`.
+/// Named capture groups become path params.
+pub const PATH_REGEX_PREFIX: char = '~';
+
+/// Why no endpoint matched a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchMiss {
+    /// No endpoint answers the path with this method, nor with any other.
+    NotFound,
+    /// The path is configured, but only for these methods.
+    MethodNotAllowed(Vec<String>),
+}
 
 #[derive(Clone)]
 pub struct RuleMatcher {
@@ -30,8 +55,10 @@ impl RuleMatcher {
 
         // Sort endpoints by specificity:
         // 1. Static paths (no : or *)
-        // 2. Paths with parameters (:)
-        // 3. Paths with wildcards (*)
+        // 2. Paths with a constrained parameter (:id<\d+>)
+        // 3. Paths with parameters (:)
+        // 4. Regex paths (~)
+        // 5. Paths with wildcards (*)
         // Among those, longer paths come first.
         endpoints.sort_by(|a, b| {
             let a_score = Self::path_specificity_score(&a.path);
@@ -45,8 +72,10 @@ impl RuleMatcher {
         });
 
         for endpoint in &endpoints {
-            let normalized_path = Self::normalize_path(&endpoint.path);
-            let pattern = Self::compile_path_pattern(&normalized_path);
+            let pattern = match endpoint.path.strip_prefix(PATH_REGEX_PREFIX) {
+                Some(regex) => Self::compile_path_regex(regex),
+                None => Self::compile_path_pattern(&Self::normalize_path(&endpoint.path)),
+            };
             path_patterns.insert(endpoint.path.clone(), pattern);
         }
 
@@ -57,12 +86,34 @@ impl RuleMatcher {
     }
 
     fn path_specificity_score(path: &str) -> u32 {
-        if path.contains('*') {
+        if path.starts_with(PATH_REGEX_PREFIX) {
+            return 2;
+        }
+
+        let tokens = tokenize_path(path);
+        let constrained = |token: &PathToken| {
+            matches!(
+                token,
+                PathToken::Param {
+                    constraint: Some(_),
+                    ..
+                }
+            )
+        };
+        if tokens
+            .iter()
+            .any(|token| matches!(token, PathToken::Wildcard(_)))
+        {
             1
-        } else if path.contains(':') {
-            2
-        } else {
+        } else if tokens.iter().any(constrained) {
+            4
+        } else if tokens
+            .iter()
+            .any(|token| matches!(token, PathToken::Param { .. }))
+        {
             3
+        } else {
+            5
         }
     }
 
@@ -95,19 +146,82 @@ impl RuleMatcher {
     }
 
     pub fn find_match(&self, method: &str, path: &str) -> anyhow::Result<&Endpoint> {
+        match self.matching_endpoints(method, path).next() {
+            Some(endpoint) => Ok(endpoint),
+            None => match self.miss(method, path) {
+                MatchMiss::NotFound => {
+                    anyhow::bail!("No matching endpoint found for {} {}", method, path)
+                }
+                MatchMiss::MethodNotAllowed(allowed) => anyhow::bail!(
+                    "Method {} not allowed for {} (allowed: {})",
+                    method,
+                    path,
+                    allowed.join(", ")
+                ),
+            },
+        }
+    }
+
+    /// Tells an unknown path apart from a known path requested with the wrong method.
+    /// A path that has endpoints for `method` is never a method mismatch, even when
+    /// they all fell through.
+    pub fn miss(&self, method: &str, path: &str) -> MatchMiss {
+        let method = method.to_uppercase();
+        let normalized_request_path = Self::normalize_path(path);
+        let mut allowed: Vec<String> = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| self.matches_path(&endpoint.path, &normalized_request_path))
+            .map(|endpoint| endpoint.method.to_uppercase())
+            .collect();
+        allowed.sort();
+        allowed.dedup();
+
+        if allowed.is_empty() || allowed.contains(&method) {
+            MatchMiss::NotFound
+        } else {
+            MatchMiss::MethodNotAllowed(allowed)
+        }
+    }
+
+    /// Lazily yields every endpoint matching the method and path, most specific first.
+    pub fn matching_endpoints(&self, method: &str, path: &str) -> impl Iterator<Item = &Endpoint> {
+        let method = method.to_uppercase();
         let normalized_request_path = Self::normalize_path(path);
 
-        for endpoint in &self.endpoints {
-            if endpoint.method.to_uppercase() != method.to_uppercase() {
-                continue;
+        // Each evaluation is recorded as an event on the current request span so trace
+        // UIs show the full decision path of the rule engine. The events are at debug
+        // level, which the default `info` filter drops; they need a `log_level` such
+        // as `info,molock::rules=debug`.
+        self.endpoints.iter().filter(move |endpoint| {
+            if endpoint.method.to_uppercase() != method {
+                debug!(
+                    endpoint = %endpoint.name,
+                    matcher.outcome = "failed",
+                    matcher.reason = "method_mismatch",
+                    "Matcher evaluated"
+                );
+                return false;
             }
 
             if self.matches_path(&endpoint.path, &normalized_request_path) {
-                return Ok(endpoint);
+                debug!(
+                    endpoint = %endpoint.name,
+                    matcher.outcome = "matched",
+                    matcher.reason = "path_match",
+                    "Matcher evaluated"
+                );
+                return true;
             }
-        }
 
-        anyhow::bail!("No matching endpoint found for {} {}", method, path)
+            debug!(
+                endpoint = %endpoint.name,
+                matcher.outcome = "failed",
+                matcher.reason = "path_mismatch",
+                "Matcher evaluated"
+            );
+            false
+        })
     }
 
     pub fn extract_path_params(
@@ -120,11 +234,19 @@ impl RuleMatcher {
 
         if let Some(pattern) = self.path_patterns.get(endpoint_path) {
             if let Some(captures) = pattern.captures(&normalized_request_path) {
-                let param_names = Self::extract_param_names(endpoint_path);
+                if endpoint_path.starts_with(PATH_REGEX_PREFIX) {
+                    for name in pattern.capture_names().flatten() {
+                        if let Some(value) = captures.name(name) {
+                            params.insert(name.to_string(), value.as_str().to_string());
+                        }
+                    }
+                    return params;
+                }
 
-                for (i, name) in param_names.iter().enumerate() {
-                    if let Some(value) = captures.get(i + 1) {
-                        params.insert(name.clone(), value.as_str().to_string());
+                let param_names = Self::extract_param_names(endpoint_path);
+                for (i, name) in param_names.into_iter().enumerate() {
+                    if let Some(value) = captures.name(&format!("{}{}", PARAM_GROUP, i)) {
+                        params.insert(name, value.as_str().to_string());
                     }
                 }
             }
@@ -142,68 +264,1139 @@ impl RuleMatcher {
         }
     }
 
+    /// Anchors a `~` path's regex so it has to match the whole request path. An
+    /// invalid regex matches nothing; the loader rejects it up front.
+    fn compile_path_regex(regex: &str) -> Regex {
+        Self::try_compile_path_regex(regex).unwrap_or_else(|_| Regex::new("^$").unwrap())
+    }
+
+    fn try_compile_path_regex(regex: &str) -> Result<Regex, regex::Error> {
+        Regex::new(&format!("^(?:{})$", regex))
+    }
+
     fn compile_path_pattern(path: &str) -> Regex {
+        Self::try_compile_path_pattern(path).unwrap_or_else(|_| Regex::new("^$").unwrap())
+    }
+
+    /// Each parameter is captured by a group named after its position among the
+    /// parameters, so groups inside a `<regex>` constraint do not shift the others.
+    fn try_compile_path_pattern(path: &str) -> anyhow::Result<Regex> {
         let mut pattern = String::new();
-        let mut in_param = false;
-        let _param_name = String::new();
+        let mut params = 0;
 
-        for c in path.chars() {
-            match c {
-                ':' => {
-                    in_param = true;
-                    pattern.push_str("([^/]+)");
+        for token in tokenize_path(path) {
+            match token {
+                PathToken::Literal('/') => pattern.push_str("\\/"),
+                PathToken::Literal(c) => pattern.push(c),
+                PathToken::Wildcard(None) => pattern.push_str(".*"),
+                PathToken::Wildcard(Some(_)) => {
+                    pattern.push_str(&format!("(?P<{}{}>.*)", PARAM_GROUP, params));
+                    params += 1;
                 }
-                '/' => {
-                    if in_param {
-                        in_param = false;
+                PathToken::Param {
+                    name,
+                    constraint,
+                    optional,
+                } => {
+                    // An optional parameter takes the `/` before it along, so
+                    // `/report/:year/:month?` also matches `/report/2024`.
+                    let slash = optional && pattern.ends_with("\\/");
+                    if slash {
+                        pattern.truncate(pattern.len() - 2);
+                        pattern.push_str("(?:\\/");
+                    } else if optional {
+                        pattern.push_str("(?:");
                     }
-                    pattern.push_str("\\/");
-                }
-                '*' => {
-                    pattern.push_str(".*");
-                }
-                _ => {
-                    if !in_param {
-                        pattern.push(c);
+
+                    let constraint = match constraint {
+                        Some(constraint) => {
+                            Regex::new(constraint).with_context(|| {
+                                format!("Invalid constraint for path parameter `{}`", name)
+                            })?;
+                            format!("(?:{})", constraint)
+                        }
+                        None => "[^/]+".to_string(),
+                    };
+                    // Unnamed parameters match but are not extracted.
+                    if name.is_empty() {
+                        pattern.push_str(&format!("(?:{})", constraint));
+                    } else {
+                        pattern.push_str(&format!("(?P<{}{}>{})", PARAM_GROUP, params, constraint));
+                        params += 1;
+                    }
+                    if optional {
+                        pattern.push_str(")?");
                     }
                 }
             }
         }
 
-        Regex::new(&format!("^{}$", pattern)).unwrap_or_else(|_| Regex::new("^$").unwrap())
+        Ok(Regex::new(&format!("^{}$", pattern))?)
     }
 
     fn extract_param_names(path: &str) -> Vec<String> {
-        let mut params = Vec::new();
-        let mut in_param = false;
-        let mut param_name = String::new();
+        tokenize_path(path)
+            .into_iter()
+            .filter_map(|token| match token {
+                PathToken::Param { name, .. } if !name.is_empty() => Some(name.to_string()),
+                PathToken::Wildcard(name) => name.map(str::to_string),
+                _ => None,
+            })
+            .collect()
+    }
+}
 
-        for c in path.chars() {
-            match c {
-                ':' => {
-                    in_param = true;
-                    param_name.clear();
-                }
-                '/' => {
-                    if in_param && !param_name.is_empty() {
-                        params.push(param_name.clone());
+const PARAM_GROUP: &str = "__param";
+
+/// A piece of a non-regex endpoint path.
+#[derive(Debug, PartialEq)]
+enum PathToken<'a> {
+    Literal(char),
+    /// `:name`, optionally constrained as `:name<regex>`, and optional as `:name?`.
+    Param {
+        name: &'a str,
+        constraint: Option<&'a str>,
+        optional: bool,
+    },
+    /// `*`, or `*name` to capture what it matched as the `name` parameter.
+    Wildcard(Option<&'a str>),
+}
+
+/// Splits an endpoint path into literals, parameters and wildcards. A parameter name
+/// runs up to the next `/`, `<` or `?`; a constraint runs up to its balancing `>` and
+/// may be followed by literals, as in `/files/:id<\d+>.json`. A wildcard name is made
+/// of alphanumerics and `_`.
+fn tokenize_path(path: &str) -> Vec<PathToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = path;
+
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            ':' => {
+                let end = rest.find(['/', '<', '?']).unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+                let constraint = match rest.strip_prefix('<') {
+                    Some(inner) => {
+                        let close = closing_angle_bracket(inner).unwrap_or(inner.len());
+                        rest = inner.get(close + 1..).unwrap_or("");
+                        Some(&inner[..close])
+                    }
+                    None => None,
+                };
+                let optional = match rest.strip_prefix('?') {
+                    Some(after) => {
+                        rest = after;
+                        true
+                    }
+                    None => false,
+                };
+                tokens.push(PathToken::Param {
+                    name,
+                    constraint,
+                    optional,
+                });
+            }
+            '*' => {
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+                tokens.push(PathToken::Wildcard((!name.is_empty()).then_some(name)));
+            }
+            c => tokens.push(PathToken::Literal(c)),
+        }
+    }
+
+    tokens
+}
+
+/// Index of the `>` closing a constraint, skipping nested `<...>` pairs such as
+/// those of named groups.
+fn closing_angle_bracket(constraint: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in constraint.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' if depth == 0 => return Some(i),
+            '>' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The request matchers of every endpoint and of its responses, compiled once
+/// when the engine is built.
+#[derive(Clone, Default)]
+pub struct CompiledMatchers {
+    endpoints: Arc<HashMap<String, EndpointMatchers>>,
+}
+
+#[derive(Clone, Default)]
+struct EndpointMatchers {
+    endpoint: Vec<CompiledMatcher>,
+    responses: Vec<Vec<CompiledMatcher>>,
+}
+
+impl CompiledMatchers {
+    pub fn new(endpoints: &[Endpoint]) -> Self {
+        let endpoints = endpoints
+            .iter()
+            .map(|endpoint| {
+                let matchers = EndpointMatchers {
+                    endpoint: CompiledMatcher::compile_all(&endpoint.matchers),
+                    responses: endpoint
+                        .responses
+                        .iter()
+                        .map(|response| CompiledMatcher::compile_all(&response.matchers))
+                        .collect(),
+                };
+                (endpoint.name.clone(), matchers)
+            })
+            .collect();
+        Self {
+            endpoints: Arc::new(endpoints),
+        }
+    }
+
+    /// The endpoint's own `matchers`, compiled now if they were not up front.
+    pub fn endpoint<'a>(&'a self, endpoint: &Endpoint) -> Cow<'a, [CompiledMatcher]> {
+        match self.endpoints.get(&endpoint.name) {
+            Some(matchers) => Cow::Borrowed(&matchers.endpoint),
+            None => Cow::Owned(CompiledMatcher::compile_all(&endpoint.matchers)),
+        }
+    }
+
+    /// The `matchers` of the endpoint's `index`th response, compiled now if they
+    /// were not up front.
+    pub fn response<'a>(&'a self, endpoint: &Endpoint, index: usize) -> Cow<'a, [CompiledMatcher]> {
+        match self
+            .endpoints
+            .get(&endpoint.name)
+            .and_then(|matchers| matchers.responses.get(index))
+        {
+            Some(matchers) => Cow::Borrowed(matchers),
+            None => Cow::Owned(CompiledMatcher::compile_all(
+                &endpoint.responses[index].matchers,
+            )),
+        }
+    }
+}
+
+/// A request matcher with its `~` value patterns, JSONPath condition, XPath and
+/// JWT keys compiled, along with those of the matchers nested in it.
+#[derive(Clone)]
+pub struct CompiledMatcher {
+    matcher: RequestMatcher,
+    /// The anchored regexes of the `~` value patterns, by pattern.
+    patterns: HashMap<String, Regex>,
+    json_path: Option<JsonPathCondition>,
+    /// Whether it or a nested matcher reads the body as JSON.
+    reads_json: bool,
+    xpath: Option<Arc<CompiledXPath>>,
+    /// Whether it or a nested matcher reads the body as XML.
+    reads_xml: bool,
+    jwt_keys: Option<JwtKeys>,
+    /// The compiled `all_of`, `any_of` or `not` matchers.
+    nested: Vec<CompiledMatcher>,
+}
+
+impl CompiledMatcher {
+    /// Compiles `matcher`. Parts that fail to compile never match; the loader
+    /// rejects them up front.
+    pub fn new(matcher: &RequestMatcher) -> Self {
+        let patterns = Self::value_patterns(matcher)
+            .filter_map(|expected| {
+                let regex = expected.strip_prefix(PATH_REGEX_PREFIX)?;
+                let regex = RuleMatcher::try_compile_path_regex(regex).ok()?;
+                Some((expected.clone(), regex))
+            })
+            .collect();
+        let nested = matcher
+            .all_of
+            .iter()
+            .chain(&matcher.any_of)
+            .flatten()
+            .chain(matcher.not.as_deref())
+            .map(Self::new)
+            .collect::<Vec<Self>>();
+        let json_path = matcher
+            .json_path
+            .as_deref()
+            .and_then(|expression| JsonPathCondition::parse(expression).ok());
+        let reads_json = matcher.json_path.is_some()
+            || matcher.graphql.is_some()
+            || nested.iter().any(|nested| nested.reads_json);
+        let xpath = matcher
+            .xpath
+            .as_deref()
+            .and_then(|expression| compile_xpath(expression).ok())
+            .map(|xpath| Arc::new(CompiledXPath(xpath)));
+        let reads_xml = matcher.xpath.is_some() || nested.iter().any(|nested| nested.reads_xml);
+        let jwt_keys = matcher.jwt.as_ref().map(JwtKeys::new);
+        Self {
+            matcher: matcher.clone(),
+            patterns,
+            json_path,
+            reads_json,
+            xpath,
+            reads_xml,
+            jwt_keys,
+            nested,
+        }
+    }
+
+    pub fn compile_all(matchers: &[RequestMatcher]) -> Vec<Self> {
+        matchers.iter().map(Self::new).collect()
+    }
+
+    /// The expected values of `matcher` that [`Self::value_pattern_matches`] compares.
+    fn value_patterns(matcher: &RequestMatcher) -> impl Iterator<Item = &String> {
+        let multipart = matcher.multipart.iter().flat_map(|multipart| {
+            [
+                &multipart.value,
+                &multipart.filename,
+                &multipart.content_type,
+            ]
+            .into_iter()
+            .flatten()
+        });
+        let graphql = matcher.graphql.iter().flat_map(|graphql| {
+            graphql
+                .operation_name
+                .iter()
+                .chain(&graphql.field)
+                .chain(graphql.variables.values())
+        });
+        matcher
+            .path
+            .iter()
+            .chain(matcher.form.iter().flat_map(HashMap::values))
+            .chain(multipart)
+            .chain(matcher.jwt.iter().flat_map(|jwt| jwt.claims.values()))
+            .chain(graphql)
+    }
+}
+
+/// Whether the request satisfies every matcher.
+pub fn matches_all(matchers: &[CompiledMatcher], context: &ExecutionContext) -> bool {
+    evaluate_all(matchers, context, None)
+}
+
+/// [`matches_all`], with `captured` matchers looking their slots up in `captures`
+/// rather than finding every slot empty.
+pub fn matches_all_with(
+    matchers: &[CompiledMatcher],
+    context: &ExecutionContext,
+    captures: &Captures,
+) -> bool {
+    evaluate_all(matchers, context, Some(captures))
+}
+
+fn evaluate_all(
+    matchers: &[CompiledMatcher],
+    context: &ExecutionContext,
+    captures: Option<&Captures>,
+) -> bool {
+    if matchers.is_empty() {
+        return true;
+    }
+
+    let body = if matchers.iter().any(|matcher| matcher.reads_json) {
+        context
+            .body
+            .as_deref()
+            .and_then(|body| serde_json::from_str::<Value>(body).ok())
+    } else {
+        None
+    };
+    let xml = if matchers.iter().any(|matcher| matcher.reads_xml) {
+        context
+            .body
+            .as_deref()
+            .and_then(|body| sxd_document::parser::parse(body).ok())
+    } else {
+        None
+    };
+    let xml = xml.as_ref().map(|package| package.as_document());
+
+    matchers.iter().all(|matcher| {
+        let matched = matcher.matches(context, body.as_ref(), xml.as_ref(), captures);
+        debug!(matcher = ?matcher.matcher, matched, "Request matcher evaluated");
+        matched
+    })
+}
+
+impl CompiledMatcher {
+    fn matches(
+        &self,
+        context: &ExecutionContext,
+        body: Option<&Value>,
+        xml: Option<&Document>,
+        captures: Option<&Captures>,
+    ) -> bool {
+        let matcher = &self.matcher;
+        if matcher.all_of.is_some() {
+            return self
+                .nested
+                .iter()
+                .all(|nested| nested.matches(context, body, xml, captures));
+        }
+
+        if matcher.any_of.is_some() {
+            return self
+                .nested
+                .iter()
+                .any(|nested| nested.matches(context, body, xml, captures));
+        }
+
+        if matcher.not.is_some() {
+            return !self.nested[0].matches(context, body, xml, captures);
+        }
+
+        if let Some(expected) = &matcher.path {
+            return self.value_pattern_matches(expected, &context.path);
+        }
+
+        if matcher.json_path.is_some() {
+            return self
+                .json_path
+                .as_ref()
+                .is_some_and(|condition| body.is_some_and(|body| condition.matches(body)));
+        }
+
+        if let Some(expected) = &matcher.content_type {
+            return context
+                .headers
+                .get("content-type")
+                .is_some_and(|actual| media_type_matches(expected, actual));
+        }
+
+        if matcher.xpath.is_some() {
+            return self.xpath.as_ref().is_some_and(|xpath| {
+                xml.is_some_and(|xml| {
+                    xpath
+                        .0
+                        .evaluate(&sxd_xpath::Context::new(), xml.root())
+                        .is_ok_and(|value| value.boolean())
+                })
+            });
+        }
+
+        if let Some(fields) = &matcher.form {
+            let form = context.form_fields();
+            return fields.iter().all(|(name, expected)| {
+                form.iter()
+                    .filter(|(field, _)| field == name)
+                    .any(|(_, value)| self.value_pattern_matches(expected, value))
+            });
+        }
+
+        if let Some(expected) = &matcher.multipart {
+            return context.multipart_parts().iter().any(|part| {
+                let matches = |expected: &Option<String>, actual: Option<&str>| match expected {
+                    Some(expected) => {
+                        actual.is_some_and(|actual| self.value_pattern_matches(expected, actual))
                     }
-                    in_param = false;
-                    param_name.clear();
+                    None => true,
+                };
+                part.name == expected.field
+                    && matches(&expected.value, Some(&part.value))
+                    && matches(&expected.filename, part.filename.as_deref())
+                    && matches(&expected.content_type, part.content_type.as_deref())
+            });
+        }
+
+        if let Some(bounds) = &matcher.body_size {
+            // The decoded body, like the `body_size` condition, rather than the
+            // compressed or claimed `Content-Length`.
+            let size = context.body.as_ref().map_or(0, |body| body.len() as u64);
+            return bounds.min.is_none_or(|min| size >= min.0)
+                && bounds.max.is_none_or(|max| size <= max.0);
+        }
+
+        if let Some(name) = &matcher.header {
+            let name = name.to_ascii_lowercase();
+            let value = context.headers.get(&name).map(String::as_str);
+            return value_matches(matcher, value.into_iter().collect());
+        }
+
+        if let Some(name) = &matcher.query {
+            let values: Vec<String> = url::form_urlencoded::parse(context.query.as_bytes())
+                .filter(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .collect();
+            return value_matches(matcher, values.iter().map(String::as_str).collect());
+        }
+
+        if let Some(slot) = &matcher.captured {
+            let value =
+                captures.and_then(|captures| captures.text(&captures::slot_name(slot, context)));
+            return value_matches(matcher, value.as_deref().into_iter().collect());
+        }
+
+        if let Some(ranges) = &matcher.client_ip {
+            return client_ip_in(&context.client_ip, ranges);
+        }
+
+        if let (Some(jwt), Some(keys)) = (&matcher.jwt, &self.jwt_keys) {
+            return bearer_claims(keys, context)
+                .is_some_and(|claims| self.jwt_matches(jwt, &claims));
+        }
+
+        if let Some(expected) = &matcher.graphql {
+            return GraphqlRequest::from_context(context, body)
+                .is_some_and(|request| self.graphql_matches(expected, &request));
+        }
+
+        if let Some(custom) = &matcher.custom {
+            return match custom_matchers::get(&custom.kind) {
+                Some(kind) => kind.matches(&custom.config, context),
+                None => {
+                    tracing::warn!(kind = %custom.kind, "Unregistered custom matcher");
+                    false
                 }
-                _ => {
-                    if in_param {
-                        param_name.push(c);
+            };
+        }
+
+        true
+    }
+}
+
+/// Applies a `header`, `query` or `captured` matcher's predicate to the values found for it.
+fn value_matches(matcher: &RequestMatcher, values: Vec<&str>) -> bool {
+    if matcher.absent {
+        return values.is_empty();
+    }
+    if let Some(expected) = &matcher.equals {
+        return values.contains(&expected.as_str());
+    }
+    if let Some(unexpected) = &matcher.not_equals {
+        return !values.contains(&unexpected.as_str());
+    }
+    !values.is_empty()
+}
+
+/// The username and password of an `Authorization: Basic` request header.
+pub fn basic_credentials(context: &ExecutionContext) -> Option<(String, String)> {
+    use base64::Engine;
+
+    let header = context.headers.get("authorization")?.trim();
+    let (scheme, encoded) = header.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let (username, password) = String::from_utf8(decoded)
+        .ok()?
+        .split_once(':')
+        .map(|(username, password)| (username.to_string(), password.to_string()))?;
+    Some((username, password))
+}
+
+/// Parses `addr/prefix`, or a bare address as a single-host range.
+pub fn parse_cidr(range: &str) -> anyhow::Result<(IpAddr, u8)> {
+    let (addr, prefix) = match range.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (range, None),
+    };
+    let addr: IpAddr = addr
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid IP address in `{}`", range))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .with_context(|| format!("Invalid prefix length in `{}`", range))?,
+        None => max,
+    };
+    Ok((addr.to_canonical(), prefix))
+}
+
+/// Whether the client address falls in one of `ranges` (see [`parse_cidr`]).
+pub fn client_ip_in(client_ip: &str, ranges: &[String]) -> bool {
+    let Some(ip) = parse_client_ip(client_ip) else {
+        return false;
+    };
+    ranges
+        .iter()
+        .any(|range| parse_cidr(range).is_ok_and(|(network, prefix)| ip_in(ip, network, prefix)))
+}
+
+/// The client address as an IP, accepting a trailing port as forwarding headers may
+/// carry one. IPv4-mapped IPv6 addresses compare as IPv4.
+fn parse_client_ip(client_ip: &str) -> Option<IpAddr> {
+    let ip = client_ip.parse::<IpAddr>().ok().or_else(|| {
+        client_ip
+            .parse::<std::net::SocketAddr>()
+            .ok()
+            .map(|addr| addr.ip())
+    })?;
+    Some(ip.to_canonical())
+}
+
+fn ip_in(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// The keys a `jwt` matcher verifies signatures against.
+#[derive(Clone)]
+enum JwtKeys {
+    Secret(DecodingKey),
+    /// Each key of the `jwks` with its `kid`; one that fails to build verifies
+    /// nothing, as the loader rejects it up front.
+    Jwks(Vec<(Option<String>, Option<DecodingKey>)>),
+    Unverified,
+}
+
+impl JwtKeys {
+    fn new(jwt: &JwtMatcher) -> Self {
+        if let Some(secret) = &jwt.secret {
+            Self::Secret(DecodingKey::from_secret(secret.as_bytes()))
+        } else if let Some(jwks) = &jwt.jwks {
+            let keys = jwks
+                .keys
+                .iter()
+                .map(|jwk| (jwk.common.key_id.clone(), DecodingKey::from_jwk(jwk).ok()))
+                .collect();
+            Self::Jwks(keys)
+        } else {
+            Self::Unverified
+        }
+    }
+}
+
+/// The claims of the request's `Authorization: Bearer` JWT, provided its signature
+/// verifies against the matcher's `secret` or `jwks` when either is set.
+fn bearer_claims(keys: &JwtKeys, context: &ExecutionContext) -> Option<Value> {
+    let header = context.headers.get("authorization")?.trim();
+    let (scheme, token) = header.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    let token = token.trim();
+    let header = jsonwebtoken::decode_header(token).ok()?;
+
+    // Expiry is matched through `expired` rather than rejected outright.
+    let mut validation = Validation::new(header.alg);
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims.clear();
+    let unverified;
+    let key = match keys {
+        JwtKeys::Secret(key) => key,
+        JwtKeys::Jwks(keys) => {
+            let (_, key) = match &header.kid {
+                Some(kid) => keys.iter().find(|(id, _)| id.as_ref() == Some(kid))?,
+                None => keys.first()?,
+            };
+            key.as_ref()?
+        }
+        JwtKeys::Unverified => {
+            validation.insecure_disable_signature_validation();
+            unverified = DecodingKey::from_secret(&[]);
+            &unverified
+        }
+    };
+
+    match jsonwebtoken::decode::<Value>(token, key, &validation) {
+        Ok(data) => Some(data.claims),
+        Err(e) => {
+            debug!(error = %e, "Bearer token rejected by JWT matcher");
+            None
+        }
+    }
+}
+
+impl CompiledMatcher {
+    fn jwt_matches(&self, jwt: &JwtMatcher, claims: &Value) -> bool {
+        if let Some(expired) = jwt.expired {
+            let exp = claims.get("exp").and_then(Value::as_i64);
+            if exp.is_some_and(|exp| exp <= chrono::Utc::now().timestamp()) != expired {
+                return false;
+            }
+        }
+
+        jwt.claims
+            .iter()
+            .all(|(name, expected)| self.member_matches(claims, name, expected))
+    }
+
+    fn graphql_matches(&self, expected: &GraphqlMatcher, request: &GraphqlRequest) -> bool {
+        let Some(operation) = request.operation() else {
+            return false;
+        };
+        expected
+            .operation_type
+            .is_none_or(|kind| kind == operation.kind)
+            && expected.operation_name.as_ref().is_none_or(|name| {
+                operation
+                    .name
+                    .as_deref()
+                    .is_some_and(|actual| self.value_pattern_matches(name, actual))
+            })
+            && expected.field.as_ref().is_none_or(|field| {
+                operation
+                    .fields
+                    .iter()
+                    .any(|actual| self.value_pattern_matches(field, actual))
+            })
+            && expected
+                .variables
+                .iter()
+                .all(|(name, value)| self.member_matches(&request.variables, name, value))
+    }
+
+    /// Whether the member `name` of `object` (dotted for nested members) matches
+    /// `expected`, scalars compared as text and arrays by any element.
+    fn member_matches(&self, object: &Value, name: &str, expected: &str) -> bool {
+        // Literal names first: namespaced claims like `https://example.com/roles` contain dots.
+        let member = object.get(name).or_else(|| {
+            name.split('.')
+                .try_fold(object, |value, key| value.get(key))
+        });
+        let scalar_matches = |value: &Value| match value {
+            Value::String(text) => self.value_pattern_matches(expected, text),
+            Value::Null | Value::Array(_) | Value::Object(_) => false,
+            other => self.value_pattern_matches(expected, &other.to_string()),
+        };
+        match member {
+            Some(Value::Array(items)) => items.iter().any(scalar_matches),
+            Some(value) => scalar_matches(value),
+            None => false,
+        }
+    }
+
+    /// Compares a path, form field, multipart part, JWT claim or GraphQL name with
+    /// its expected value: literally, or as an anchored regex when `expected` starts
+    /// with `~`.
+    fn value_pattern_matches(&self, expected: &str, value: &str) -> bool {
+        match expected.strip_prefix(PATH_REGEX_PREFIX) {
+            Some(_) => self
+                .patterns
+                .get(expected)
+                .is_some_and(|regex| regex.is_match(value)),
+            None => expected == value,
+        }
+    }
+}
+
+/// A compiled XPath, shared by the threads serving requests.
+#[derive(Debug)]
+struct CompiledXPath(sxd_xpath::XPath);
+
+// SAFETY: an `XPath` owns a tree of boxed expressions holding only names, literals
+// and numbers, with no shared or interior-mutable state, and evaluating it only
+// reads that tree.
+unsafe impl Send for CompiledXPath {}
+unsafe impl Sync for CompiledXPath {}
+
+fn compile_xpath(expression: &str) -> anyhow::Result<sxd_xpath::XPath> {
+    sxd_xpath::Factory::new()
+        .build(expression)
+        .map_err(|e| anyhow::anyhow!("Invalid XPath `{}`: {}", expression, e))?
+        .ok_or_else(|| anyhow::anyhow!("Empty XPath expression"))
+}
+
+/// Whether a `Content-Type` header value has the media type `expected`, which may
+/// end in `/*` to accept any subtype.
+fn media_type_matches(expected: &str, actual: &str) -> bool {
+    let actual = actual.split(';').next().unwrap_or_default().trim();
+    match expected.strip_suffix("/*") {
+        Some(kind) => actual
+            .split_once('/')
+            .is_some_and(|(actual_kind, _)| actual_kind.eq_ignore_ascii_case(kind)),
+        None => actual.eq_ignore_ascii_case(expected.trim()),
+    }
+}
+
+/// Checks that a `~` endpoint path is a valid regex, and the constraints of path
+/// parameters valid regexes, for config validation.
+pub fn validate_path(path: &str) -> anyhow::Result<()> {
+    match path.strip_prefix(PATH_REGEX_PREFIX) {
+        Some(regex) => {
+            RuleMatcher::try_compile_path_regex(regex)?;
+        }
+        None => {
+            RuleMatcher::try_compile_path_pattern(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks a matcher's syntax, for config validation.
+pub fn validate_matcher(matcher: &RequestMatcher) -> anyhow::Result<()> {
+    let kinds = [
+        matcher.json_path.is_some(),
+        matcher.content_type.is_some(),
+        matcher.xpath.is_some(),
+        matcher.form.is_some(),
+        matcher.multipart.is_some(),
+        matcher.body_size.is_some(),
+        matcher.header.is_some(),
+        matcher.query.is_some(),
+        matcher.captured.is_some(),
+        matcher.client_ip.is_some(),
+        matcher.jwt.is_some(),
+        matcher.graphql.is_some(),
+        matcher.custom.is_some(),
+        matcher.path.is_some(),
+        matcher.all_of.is_some(),
+        matcher.any_of.is_some(),
+        matcher.not.is_some(),
+    ];
+    if kinds.iter().filter(|set| **set).count() != 1 {
+        anyhow::bail!(
+            "Matcher must set exactly one of: json_path, content_type, xpath, form, multipart, \
+             body_size, header, query, captured, path, client_ip, jwt, graphql, custom, all_of, \
+             any_of, not"
+        );
+    }
+
+    let predicates = [
+        matcher.equals.is_some(),
+        matcher.not_equals.is_some(),
+        matcher.absent,
+    ];
+    match predicates.iter().filter(|set| **set).count() {
+        0 => {}
+        1 if matcher.header.is_some() || matcher.query.is_some() || matcher.captured.is_some() => {}
+        1 => {
+            anyhow::bail!("equals, not_equals and absent only apply to header, query and captured")
+        }
+        _ => anyhow::bail!("Matcher can set only one of: equals, not_equals, absent"),
+    }
+
+    if let Some(expression) = &matcher.json_path {
+        JsonPathCondition::parse(expression)?;
+    }
+    if let Some(slot) = &matcher.captured {
+        captures::validate_slot(slot)?;
+    }
+    if let Some(content_type) = &matcher.content_type {
+        if !content_type.contains('/') {
+            anyhow::bail!("Invalid media type `{}` in content_type", content_type);
+        }
+    }
+    if let Some(expression) = &matcher.xpath {
+        compile_xpath(expression)?;
+    }
+    if let Some(fields) = &matcher.form {
+        if fields.is_empty() {
+            anyhow::bail!("Form matcher needs at least one field");
+        }
+        for (name, expected) in fields {
+            if let Some(regex) = expected.strip_prefix(PATH_REGEX_PREFIX) {
+                RuleMatcher::try_compile_path_regex(regex)
+                    .with_context(|| format!("Invalid regex for form field `{}`", name))?;
+            }
+        }
+    }
+    for (combinator, matchers) in [("all_of", &matcher.all_of), ("any_of", &matcher.any_of)] {
+        if let Some(matchers) = matchers {
+            if matchers.is_empty() {
+                anyhow::bail!("{} needs at least one matcher", combinator);
+            }
+            for (index, matcher) in matchers.iter().enumerate() {
+                validate_matcher(matcher)
+                    .with_context(|| format!("Invalid matcher {} in {}", index, combinator))?;
+            }
+        }
+    }
+    if let Some(matcher) = &matcher.not {
+        validate_matcher(matcher).context("Invalid matcher in not")?;
+    }
+    if let Some(bounds) = &matcher.body_size {
+        match (bounds.min, bounds.max) {
+            (None, None) => anyhow::bail!("body_size needs min or max"),
+            (Some(min), Some(max)) if min > max => {
+                anyhow::bail!("body_size min is larger than max")
+            }
+            _ => {}
+        }
+    }
+    if let Some(regex) = matcher
+        .path
+        .as_deref()
+        .and_then(|path| path.strip_prefix(PATH_REGEX_PREFIX))
+    {
+        RuleMatcher::try_compile_path_regex(regex).context("Invalid path regex")?;
+    }
+    if let Some(multipart) = &matcher.multipart {
+        let patterns = [
+            &multipart.value,
+            &multipart.filename,
+            &multipart.content_type,
+        ];
+        for expected in patterns.into_iter().flatten() {
+            if let Some(regex) = expected.strip_prefix(PATH_REGEX_PREFIX) {
+                RuleMatcher::try_compile_path_regex(regex).with_context(|| {
+                    format!("Invalid regex for multipart field `{}`", multipart.field)
+                })?;
+            }
+        }
+    }
+    if let Some(ranges) = &matcher.client_ip {
+        if ranges.is_empty() {
+            anyhow::bail!("client_ip matcher needs at least one range");
+        }
+        for range in ranges {
+            parse_cidr(range)?;
+        }
+    }
+    if let Some(jwt) = &matcher.jwt {
+        if jwt.secret.is_some() && jwt.jwks.is_some() {
+            anyhow::bail!("JWT matcher can set only one of: secret, jwks");
+        }
+        for (name, expected) in &jwt.claims {
+            if let Some(regex) = expected.strip_prefix(PATH_REGEX_PREFIX) {
+                RuleMatcher::try_compile_path_regex(regex)
+                    .with_context(|| format!("Invalid regex for JWT claim `{}`", name))?;
+            }
+        }
+        for jwk in jwt.jwks.iter().flat_map(|jwks| &jwks.keys) {
+            DecodingKey::from_jwk(jwk).with_context(|| {
+                format!(
+                    "Invalid JWK `{}`",
+                    jwk.common.key_id.as_deref().unwrap_or("<no kid>")
+                )
+            })?;
+        }
+    }
+    if let Some(graphql) = &matcher.graphql {
+        let names = graphql
+            .operation_name
+            .iter()
+            .map(|name| ("operation_name", name))
+            .chain(graphql.field.iter().map(|field| ("field", field)))
+            .chain(graphql.variables.values().map(|value| ("variables", value)));
+        for (key, expected) in names {
+            if let Some(regex) = expected.strip_prefix(PATH_REGEX_PREFIX) {
+                RuleMatcher::try_compile_path_regex(regex)
+                    .with_context(|| format!("Invalid regex for GraphQL {}", key))?;
+            }
+        }
+    }
+    if let Some(custom) = &matcher.custom {
+        let kind = custom_matchers::get(&custom.kind)
+            .with_context(|| format!("Unknown custom matcher kind `{}`", custom.kind))?;
+        kind.validate(&custom.config)
+            .with_context(|| format!("Invalid config for custom matcher `{}`", custom.kind))?;
+    }
+    Ok(())
+}
+
+/// A JSONPath such as `$.order.items[*].sku`, optionally compared with a literal:
+/// `$.order.type == "express"`. It matches when any node the path selects satisfies
+/// the comparison, or, without one, when the path selects anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPathCondition {
+    path: Vec<PathSegment>,
+    comparison: Option<(Comparison, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl JsonPathCondition {
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let expression = expression.trim();
+        let (path, comparison) = match Self::split_operator(expression) {
+            Some((index, operator, comparison)) => {
+                let literal = expression[index + operator.len()..].trim();
+                (
+                    &expression[..index],
+                    Some((comparison, Self::parse_literal(literal)?)),
+                )
+            }
+            None => (expression, None),
+        };
+
+        Ok(Self {
+            path: Self::parse_path(path.trim())?,
+            comparison,
+        })
+    }
+
+    pub fn matches(&self, document: &Value) -> bool {
+        let nodes = self.select(document);
+        match &self.comparison {
+            None => !nodes.is_empty(),
+            Some((comparison, expected)) => nodes
+                .into_iter()
+                .any(|node| Self::compare(node, *comparison, expected)),
+        }
+    }
+
+    /// Whether the expression compares the selected nodes with a literal.
+    pub fn has_comparison(&self) -> bool {
+        self.comparison.is_some()
+    }
+
+    /// The nodes the path selects, in document order.
+    pub fn select<'a>(&self, document: &'a Value) -> Vec<&'a Value> {
+        let mut nodes = vec![document];
+        for segment in &self.path {
+            nodes = nodes
+                .into_iter()
+                .flat_map(|node| -> Vec<&Value> {
+                    match (segment, node) {
+                        (PathSegment::Key(key), Value::Object(map)) => {
+                            map.get(key).into_iter().collect()
+                        }
+                        (PathSegment::Index(index), Value::Array(items)) => {
+                            items.get(*index).into_iter().collect()
+                        }
+                        (PathSegment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        (PathSegment::Wildcard, Value::Object(map)) => map.values().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        nodes
+    }
+
+    fn compare(actual: &Value, comparison: Comparison, expected: &Value) -> bool {
+        let ordering = match (actual, expected) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+
+        match comparison {
+            Comparison::Eq => ordering.map_or(actual == expected, |o| o.is_eq()),
+            Comparison::Ne => ordering.map_or(actual != expected, |o| o.is_ne()),
+            Comparison::Lt => ordering.is_some_and(|o| o.is_lt()),
+            Comparison::Le => ordering.is_some_and(|o| o.is_le()),
+            Comparison::Gt => ordering.is_some_and(|o| o.is_gt()),
+            Comparison::Ge => ordering.is_some_and(|o| o.is_ge()),
+        }
+    }
+
+    /// Finds the first comparison operator outside brackets and quotes.
+    fn split_operator(expression: &str) -> Option<(usize, &'static str, Comparison)> {
+        const OPERATORS: [(&str, Comparison); 6] = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+
+        let mut quote = None;
+        let mut depth = 0;
+        for (index, c) in expression.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '[') => depth += 1,
+                (None, ']') => depth -= 1,
+                (None, _) if depth == 0 => {
+                    if let Some((operator, comparison)) = OPERATORS
+                        .iter()
+                        .find(|(operator, _)| expression[index..].starts_with(operator))
+                    {
+                        return Some((index, operator, *comparison));
                     }
                 }
+                _ => {}
             }
         }
+        None
+    }
+
+    fn parse_path(path: &str) -> anyhow::Result<Vec<PathSegment>> {
+        let mut rest = path
+            .strip_prefix('$')
+            .ok_or_else(|| anyhow::anyhow!("JSONPath must start with `$`: {}", path))?;
+        let mut segments = Vec::new();
 
-        if in_param && !param_name.is_empty() {
-            params.push(param_name);
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let key = &after[..end];
+                segments.push(match key {
+                    "" => anyhow::bail!("Empty key in JSONPath: {}", path),
+                    "*" => PathSegment::Wildcard,
+                    key => PathSegment::Key(key.to_string()),
+                });
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after
+                    .find(']')
+                    .ok_or_else(|| anyhow::anyhow!("Unclosed `[` in JSONPath: {}", path))?;
+                let inner = after[..end].trim();
+                segments.push(if inner == "*" {
+                    PathSegment::Wildcard
+                } else if let Some(key) = Self::unquote(inner) {
+                    PathSegment::Key(key.to_string())
+                } else {
+                    PathSegment::Index(inner.parse().map_err(|_| {
+                        anyhow::anyhow!("Invalid index `{}` in JSONPath: {}", inner, path)
+                    })?)
+                });
+                rest = &after[end + 1..];
+            } else {
+                anyhow::bail!("Unexpected `{}` in JSONPath: {}", rest, path);
+            }
         }
 
-        params
+        Ok(segments)
+    }
+
+    /// Parses the right-hand side: JSON, a single-quoted string, or a bare word.
+    fn parse_literal(literal: &str) -> anyhow::Result<Value> {
+        if literal.is_empty() {
+            anyhow::bail!("Missing value after comparison operator");
+        }
+        if let Ok(value) = serde_json::from_str(literal) {
+            return Ok(value);
+        }
+        Ok(Value::String(
+            Self::unquote(literal).unwrap_or(literal).to_string(),
+        ))
+    }
+
+    fn unquote(s: &str) -> Option<&str> {
+        ['\'', '"']
+            .iter()
+            .find_map(|q| s.strip_prefix(*q).and_then(|rest| rest.strip_suffix(*q)))
     }
 }
 
@@ -211,7 +1404,12 @@ impl RuleMatcher {
 mod tests {
     use super::*;
     use crate::config::types::Response;
-    use std::collections::HashMap;
+    use crate::config::MultipartMatcher;
+    use serde_json::json;
+
+    fn matches_all(matchers: &[RequestMatcher], context: &ExecutionContext) -> bool {
+        super::matches_all(&CompiledMatcher::compile_all(matchers), context)
+    }
 
     fn create_test_endpoint(method: &str, path: &str) -> Endpoint {
         Endpoint {
@@ -224,11 +1422,12 @@ mod tests {
                 status: 200,
                 delay: None,
                 body: Some("OK".to_string()),
-                headers: HashMap::new(),
                 condition: None,
                 probability: None,
                 default: false,
+                ..Default::default()
             }],
+            ..Default::default()
         }
     }
 
@@ -268,6 +1467,47 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_miss_distinguishes_unknown_path_from_method() {
+        let endpoints = vec![
+            create_test_endpoint("POST", "/api/users"),
+            create_test_endpoint("get", "/api/users/:id"),
+            create_test_endpoint("DELETE", "/api/users/:id"),
+            create_test_endpoint("GET", "/api/users/:id"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+
+        assert_eq!(matcher.miss("GET", "/api/products"), MatchMiss::NotFound);
+        assert_eq!(
+            matcher.miss("PUT", "/api/users/7/"),
+            MatchMiss::MethodNotAllowed(vec!["DELETE".to_string(), "GET".to_string()])
+        );
+        assert_eq!(matcher.miss("get", "/api/users/7"), MatchMiss::NotFound);
+
+        let err = matcher.find_match("GET", "/api/users").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Method GET not allowed for /api/users (allowed: POST)"
+        );
+    }
+
+    #[test]
+    fn test_matching_endpoints_in_specificity_order() {
+        let endpoints = vec![
+            create_test_endpoint("GET", "/api/*"),
+            create_test_endpoint("GET", "/api/:id"),
+            create_test_endpoint("POST", "/api/users"),
+            create_test_endpoint("GET", "/api/users"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+
+        let paths: Vec<&str> = matcher
+            .matching_endpoints("GET", "/api/users")
+            .map(|e| e.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["/api/users", "/api/:id", "/api/*"]);
+    }
+
     #[test]
     fn test_extract_path_params() {
         let endpoints = vec![create_test_endpoint("GET", "/users/:id/posts/:post_id")];
@@ -333,6 +1573,52 @@ mod tests {
         assert_eq!(endpoint.path, "/api/users");
     }
 
+    #[derive(Clone, Default)]
+    struct OutcomeRecorder(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl tracing::field::Visit for OutcomeRecorder {
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            if field.name() == "matcher.reason" {
+                self.0.lock().unwrap().push(value.to_string());
+            }
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for OutcomeRecorder {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            event.record(&mut self.clone());
+        }
+    }
+
+    #[test]
+    fn test_find_match_emits_evaluation_events() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let endpoints = vec![
+            create_test_endpoint("POST", "/api/users"),
+            create_test_endpoint("GET", "/api/orders"),
+            create_test_endpoint("GET", "/api/*"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+
+        let recorder = OutcomeRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            matcher.find_match("GET", "/api/users").unwrap();
+        });
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec!["path_mismatch", "method_mismatch", "path_match"]
+        );
+    }
+
     #[test]
     fn test_find_match_precedence() {
         let endpoints = vec![
@@ -350,4 +1636,955 @@ mod tests {
         let endpoint = matcher.find_match("GET", "/api/123").unwrap();
         assert_eq!(endpoint.path, "/api/:id");
     }
+
+    #[test]
+    fn test_regex_path() {
+        let endpoints = vec![
+            create_test_endpoint("GET", r"~/files/(?P<year>\d{4})/(?P<name>\w+)\.pdf"),
+            create_test_endpoint("GET", "/files/*"),
+            create_test_endpoint("GET", "/files/latest/report.pdf"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+
+        let endpoint = matcher.find_match("GET", "/files/2024/report.pdf").unwrap();
+        assert!(endpoint.path.starts_with('~'));
+        let params = matcher.extract_path_params(&endpoint.path, "/files/2024/report.pdf");
+        assert_eq!(params.get("year"), Some(&"2024".to_string()));
+        assert_eq!(params.get("name"), Some(&"report".to_string()));
+
+        // The regex is anchored, and static paths still win.
+        let endpoint = matcher
+            .find_match("GET", "/files/2024/report.pdf.bak")
+            .unwrap();
+        assert_eq!(endpoint.path, "/files/*");
+        let endpoint = matcher
+            .find_match("GET", "/files/latest/report.pdf")
+            .unwrap();
+        assert_eq!(endpoint.path, "/files/latest/report.pdf");
+    }
+
+    #[test]
+    fn test_validate_path() {
+        assert!(validate_path("/users/:id").is_ok());
+        assert!(validate_path(r"/users/:id<\d+>").is_ok());
+        assert!(validate_path(r"~/files/\d+").is_ok());
+        assert!(validate_path("~/files/(unclosed").is_err());
+        assert_eq!(
+            validate_path("/users/:id<(unclosed>")
+                .unwrap_err()
+                .to_string(),
+            "Invalid constraint for path parameter `id`"
+        );
+    }
+
+    #[test]
+    fn test_optional_and_greedy_path_params() {
+        let endpoints = vec![
+            create_test_endpoint("GET", "/report/:year/:month?"),
+            create_test_endpoint("GET", "/files/*path"),
+            create_test_endpoint("GET", r"/days/:day<\d+>?"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+        let params = |path: &str| {
+            let endpoint = matcher.find_match("GET", path).unwrap();
+            let mut params: Vec<(String, String)> = matcher
+                .extract_path_params(&endpoint.path, path)
+                .into_iter()
+                .collect();
+            params.sort();
+            params
+        };
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+
+        assert_eq!(
+            params("/report/2024/05"),
+            vec![pair("month", "05"), pair("year", "2024")]
+        );
+        assert_eq!(params("/report/2024"), vec![pair("year", "2024")]);
+        assert!(matcher.find_match("GET", "/report").is_err());
+
+        assert_eq!(
+            params("/files/docs/2024/report.pdf"),
+            vec![pair("path", "docs/2024/report.pdf")]
+        );
+        assert_eq!(params("/days"), vec![]);
+        assert_eq!(params("/days/7"), vec![pair("day", "7")]);
+        assert!(matcher.find_match("GET", "/days/seven").is_err());
+
+        assert_eq!(
+            RuleMatcher::extract_param_names("/report/:year/:month?/*rest"),
+            vec!["year".to_string(), "month".to_string(), "rest".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_constrained_path_params() {
+        let endpoints = vec![
+            create_test_endpoint("GET", "/users/:name"),
+            create_test_endpoint("GET", r"/users/:id<\d+>"),
+            create_test_endpoint("GET", r"/files/:year<(19|20)\d{2}>/:slug<[a-z-]+>.json"),
+            create_test_endpoint("GET", r"/tags/:tag<\w*>"),
+        ];
+        let matcher = RuleMatcher::new(endpoints);
+
+        let paths = |path: &str| -> Vec<String> {
+            matcher
+                .matching_endpoints("GET", path)
+                .map(|e| e.path.clone())
+                .collect()
+        };
+        assert_eq!(
+            paths("/users/123"),
+            vec![r"/users/:id<\d+>", "/users/:name"]
+        );
+        assert_eq!(paths("/users/export"), vec!["/users/:name"]);
+        assert!(paths("/files/1899/report.json").is_empty());
+
+        let endpoint = matcher
+            .find_match("GET", "/files/2024/annual-report.json")
+            .unwrap();
+        let params = matcher.extract_path_params(&endpoint.path, "/files/2024/annual-report.json");
+        assert_eq!(params.get("year").map(String::as_str), Some("2024"));
+        assert_eq!(
+            params.get("slug").map(String::as_str),
+            Some("annual-report")
+        );
+
+        // A `*` inside a constraint is not a wildcard.
+        assert_eq!(
+            RuleMatcher::path_specificity_score(r"/tags/:tag<\w*>"),
+            RuleMatcher::path_specificity_score(r"/users/:id<\d+>")
+        );
+        assert_eq!(
+            RuleMatcher::extract_param_names(r"/files/:year<(19|20)\d{2}>/:slug<[a-z-]+>.json"),
+            vec!["year".to_string(), "slug".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_json_path_condition() {
+        let document = serde_json::json!({
+            "order": {
+                "type": "express",
+                "total": 42.5,
+                "items": [{"sku": "A-1"}, {"sku": "B-2"}],
+                "gift wrap": true
+            }
+        });
+        let matches = |expression: &str| {
+            JsonPathCondition::parse(expression)
+                .unwrap()
+                .matches(&document)
+        };
+
+        assert!(matches(r#"$.order.type == "express""#));
+        assert!(matches("$.order.type == 'express'"));
+        assert!(matches("$.order.type != standard"));
+        assert!(!matches(r#"$.order.type == "standard""#));
+        assert!(matches("$.order.total > 40"));
+        assert!(!matches("$.order.total <= 40"));
+        assert!(matches(r#"$.order.items[1].sku == "B-2""#));
+        assert!(matches(r#"$.order.items[*].sku == "A-1""#));
+        assert!(matches("$['order']['gift wrap'] == true"));
+        assert!(matches("$.order.items"));
+        assert!(!matches("$.order.coupon"));
+        assert!(!matches("$.order.type > 5"));
+    }
+
+    #[test]
+    fn test_json_path_parse_errors() {
+        assert!(JsonPathCondition::parse("order.type == 1").is_err());
+        assert!(JsonPathCondition::parse("$.order[").is_err());
+        assert!(JsonPathCondition::parse("$.items[x]").is_err());
+        assert!(JsonPathCondition::parse("$.order.type ==").is_err());
+    }
+
+    #[test]
+    fn test_matches_all_on_request_body() {
+        let context = |body: Option<&str>| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/orders".to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: body.map(str::to_string),
+        };
+        let matchers = vec![RequestMatcher {
+            json_path: Some(r#"$.order.type == "express""#.to_string()),
+            ..Default::default()
+        }];
+
+        assert!(matches_all(&[], &context(None)));
+        assert!(matches_all(
+            &matchers,
+            &context(Some(r#"{"order": {"type": "express"}}"#))
+        ));
+        assert!(!matches_all(
+            &matchers,
+            &context(Some(r#"{"order": {"type": "standard"}}"#))
+        ));
+        assert!(!matches_all(&matchers, &context(Some("not json"))));
+
+        // The condition is parsed once, and only JSON matchers parse the body.
+        let any_of = RequestMatcher {
+            any_of: Some(matchers.clone()),
+            ..Default::default()
+        };
+        let compiled = CompiledMatcher::new(&any_of);
+        assert!(compiled.reads_json);
+        assert!(compiled.nested[0].json_path.is_some());
+        let header = RequestMatcher {
+            header: Some("x-debug".to_string()),
+            ..Default::default()
+        };
+        assert!(!CompiledMatcher::new(&header).reads_json);
+    }
+
+    #[test]
+    fn test_content_type_matcher() {
+        let context = |content_type: &str| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/orders".to_string(),
+            query: String::new(),
+            headers: HashMap::from([("content-type".to_string(), content_type.to_string())]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+        let content_type = |media_type: &str| vec![content_type_matcher(media_type)];
+
+        let json = content_type("application/json");
+        assert!(matches_all(&json, &context("application/json")));
+        assert!(matches_all(
+            &json,
+            &context("Application/JSON; charset=utf-8")
+        ));
+        assert!(!matches_all(&json, &context("application/xml")));
+        assert!(matches_all(&content_type("text/*"), &context("text/xml")));
+        assert!(!matches_all(
+            &content_type("text/*"),
+            &context("application/xml")
+        ));
+
+        let mut no_header = context("");
+        no_header.headers.clear();
+        assert!(!matches_all(&json, &no_header));
+    }
+
+    #[test]
+    fn test_validate_matcher() {
+        assert!(validate_matcher(&content_type_matcher("application/xml")).is_ok());
+        assert!(validate_matcher(&content_type_matcher("xml")).is_err());
+        assert!(validate_matcher(&RequestMatcher::default()).is_err());
+
+        let mut both = content_type_matcher("application/json");
+        both.json_path = Some("$.id".to_string());
+        assert_eq!(
+            validate_matcher(&both).unwrap_err().to_string(),
+            "Matcher must set exactly one of: json_path, content_type, xpath, form, multipart, \
+             body_size, header, query, captured, path, client_ip, jwt, graphql, custom, all_of, \
+             any_of, not"
+        );
+
+        assert!(validate_matcher(&xpath_matcher("count(//item) > 2")).is_ok());
+        assert!(validate_matcher(&xpath_matcher("//item[")).is_err());
+        assert!(validate_matcher(&xpath_matcher("")).is_err());
+    }
+
+    #[test]
+    fn test_xpath_matcher() {
+        let context = |body: &str| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/soap".to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: Some(body.to_string()),
+        };
+        let envelope = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
+            <soap:Body>
+                <GetUser><id>42</id></GetUser>
+            </soap:Body>
+        </soap:Envelope>"#;
+
+        let get_user = [xpath_matcher("//*[local-name()='GetUser']")];
+        assert!(matches_all(&get_user, &context(envelope)));
+        assert!(!matches_all(
+            &[xpath_matcher("//*[local-name()='DeleteUser']")],
+            &context(envelope)
+        ));
+        assert!(matches_all(
+            &[xpath_matcher("//*[local-name()='GetUser']/id = '42'")],
+            &context(envelope)
+        ));
+        assert!(!matches_all(
+            &[xpath_matcher("//*[local-name()='GetUser']/id = '7'")],
+            &context(envelope)
+        ));
+        assert!(matches_all(
+            &[xpath_matcher("count(//items/item) > 1")],
+            &context("<items><item/><item/></items>")
+        ));
+
+        // Bodies that are not XML never match.
+        assert!(!matches_all(&get_user, &context(r#"{"GetUser": {}}"#)));
+        assert!(!matches_all(&get_user, &context("<unclosed>")));
+
+        // The expression compiles once, with the matcher.
+        let compiled = CompiledMatcher::new(&get_user[0]);
+        assert!(compiled.xpath.is_some() && compiled.reads_xml);
+        assert!(!compiled.reads_json);
+    }
+
+    #[test]
+    fn test_body_size_matcher() {
+        let context = |headers: &[(&str, &str)], body: Option<&str>| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/upload".to_string(),
+            query: String::new(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: body.map(str::to_string),
+        };
+        let too_large: Vec<RequestMatcher> =
+            serde_yaml::from_str("[body_size: {min: 1MB}]").unwrap();
+        let small: Vec<RequestMatcher> =
+            serde_yaml::from_str("[body_size: {min: 1, max: 10}]").unwrap();
+
+        let upload = context(&[], Some(&"x".repeat(2 * 1024 * 1024)));
+        assert!(matches_all(&too_large, &upload));
+        assert!(!matches_all(&small, &upload));
+        assert!(matches_all(&small, &context(&[], Some("hello"))));
+        assert!(!matches_all(&small, &context(&[], None)));
+        assert!(!matches_all(&too_large, &context(&[], Some("hello"))));
+
+        // A compressed request is measured decoded, whatever its Content-Length says.
+        let compressed = context(
+            &[("content-encoding", "gzip"), ("content-length", "2097152")],
+            Some("hello"),
+        );
+        assert!(matches_all(&small, &compressed));
+        assert!(!matches_all(&too_large, &compressed));
+
+        assert_eq!(
+            validate_matcher(&serde_yaml::from_str("body_size: {}").unwrap())
+                .unwrap_err()
+                .to_string(),
+            "body_size needs min or max"
+        );
+        assert!(validate_matcher(
+            &serde_yaml::from_str("body_size: {min: 2KB, max: 1KB}").unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_composite_matchers() {
+        let context = |path: &str, headers: &[(&str, &str)], query: &str| ExecutionContext {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: Some("<order><item/></order>".to_string()),
+        };
+        let matchers: Vec<RequestMatcher> = serde_yaml::from_str(
+            r#"
+- any_of:
+    - {header: X-Beta, equals: "1"}
+    - all_of: [{path: "~/v2/.*"}, {not: {query: legacy}}]
+- not:
+    any_of: [{xpath: "count(//item) > 1"}, {path: /v2/internal}]
+"#,
+        )
+        .unwrap();
+
+        assert!(matches_all(
+            &matchers,
+            &context("/v1/users", &[("x-beta", "1")], "")
+        ));
+        assert!(matches_all(&matchers, &context("/v2/users", &[], "")));
+        assert!(!matches_all(
+            &matchers,
+            &context("/v2/users", &[], "legacy=1")
+        ));
+        assert!(!matches_all(&matchers, &context("/v1/users", &[], "")));
+        assert!(!matches_all(&matchers, &context("/v2/internal", &[], "")));
+
+        let mut nested_xpath = context("/v2/users", &[], "");
+        nested_xpath.body = Some("<order><item/><item/></order>".to_string());
+        assert!(!matches_all(&matchers, &nested_xpath));
+
+        for matcher in &matchers {
+            assert!(validate_matcher(matcher).is_ok());
+        }
+        let invalid = |yaml: &str| {
+            format!(
+                "{:#}",
+                validate_matcher(&serde_yaml::from_str(yaml).unwrap()).unwrap_err()
+            )
+        };
+        assert_eq!(invalid("any_of: []"), "any_of needs at least one matcher");
+        assert!(
+            invalid("all_of: [{path: /a}, {not: {path: '~('}}]").starts_with(
+                "Invalid matcher 1 in all_of: Invalid matcher in not: Invalid path regex"
+            )
+        );
+    }
+
+    #[test]
+    fn test_multipart_matcher() {
+        let context = |content_type: &str| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/uploads".to_string(),
+            query: String::new(),
+            headers: HashMap::from([("content-type".to_string(), content_type.to_string())]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: Some(
+                "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nTrip\r\n\
+                 --b\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"a.png\"\r\n\
+                 Content-Type: image/png\r\n\r\n12345\r\n--b--\r\n"
+                    .to_string(),
+            ),
+        };
+        let upload = context("multipart/form-data; boundary=b");
+        let multipart = |field: &str, configure: fn(&mut MultipartMatcher)| {
+            let mut matcher = MultipartMatcher {
+                field: field.to_string(),
+                ..Default::default()
+            };
+            configure(&mut matcher);
+            [RequestMatcher {
+                multipart: Some(matcher),
+                ..Default::default()
+            }]
+        };
+
+        assert!(matches_all(&multipart("title", |_| {}), &upload));
+        assert!(matches_all(
+            &multipart("title", |m| m.value = Some("Trip".to_string())),
+            &upload
+        ));
+        assert!(matches_all(
+            &multipart("photo", |m| {
+                m.filename = Some("~.*\\.png".to_string());
+                m.content_type = Some("image/png".to_string());
+            }),
+            &upload
+        ));
+        assert!(!matches_all(&multipart("video", |_| {}), &upload));
+        assert!(!matches_all(
+            &multipart("title", |m| m.filename = Some("~.*".to_string())),
+            &upload
+        ));
+        assert!(!matches_all(
+            &multipart("photo", |m| m.content_type = Some("image/jpeg".to_string())),
+            &upload
+        ));
+        assert!(!matches_all(
+            &multipart("title", |_| {}),
+            &context("application/x-www-form-urlencoded")
+        ));
+
+        assert_eq!(
+            validate_matcher(&multipart("photo", |m| m.filename = Some("~(".to_string()))[0])
+                .unwrap_err()
+                .to_string(),
+            "Invalid regex for multipart field `photo`"
+        );
+    }
+
+    #[test]
+    fn test_form_matcher() {
+        let context = |body: &str| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/oauth/token".to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: Some(body.to_string()),
+        };
+        let matcher = [form_matcher(&[
+            ("grant_type", "client_credentials"),
+            ("scope", "~read( write)?"),
+        ])];
+
+        assert!(matches_all(
+            &matcher,
+            &context("grant_type=client_credentials&scope=read")
+        ));
+        assert!(matches_all(
+            &matcher,
+            &context("scope=read+write&grant_type=client_credentials")
+        ));
+        assert!(matches_all(
+            &matcher,
+            &context("grant_type=client%5Fcredentials&scope=read%20write")
+        ));
+        // Regexes must match the whole value.
+        assert!(!matches_all(
+            &matcher,
+            &context("grant_type=client_credentials&scope=read+admin")
+        ));
+        assert!(!matches_all(
+            &matcher,
+            &context("grant_type=password&scope=read")
+        ));
+        assert!(!matches_all(
+            &matcher,
+            &context("grant_type=client_credentials")
+        ));
+
+        // Patterns compile with the matcher, nested ones with theirs.
+        let nested = RequestMatcher {
+            not: Some(Box::new(matcher[0].clone())),
+            ..Default::default()
+        };
+        let compiled = CompiledMatcher::new(&nested);
+        assert!(compiled.patterns.is_empty());
+        assert_eq!(
+            compiled.nested[0].patterns.keys().collect::<Vec<_>>(),
+            ["~read( write)?"]
+        );
+
+        assert!(validate_matcher(&matcher[0]).is_ok());
+        assert!(validate_matcher(&form_matcher(&[])).is_err());
+        assert!(validate_matcher(&form_matcher(&[("scope", "~read(")]))
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid regex for form field `scope`"));
+    }
+
+    #[test]
+    fn test_header_and_query_matchers() {
+        let context = |headers: &[(&str, &str)], query: &str| ExecutionContext {
+            method: "GET".to_string(),
+            path: "/users".to_string(),
+            query: query.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+        let header = |name: &str| RequestMatcher {
+            header: Some(name.to_string()),
+            ..Default::default()
+        };
+        let query = |name: &str| RequestMatcher {
+            query: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        let debug_absent = [RequestMatcher {
+            absent: true,
+            ..header("X-Debug")
+        }];
+        assert!(matches_all(&debug_absent, &context(&[], "")));
+        assert!(!matches_all(
+            &debug_absent,
+            &context(&[("x-debug", "1")], "")
+        ));
+        assert!(matches_all(
+            &[header("X-Debug")],
+            &context(&[("x-debug", "1")], "")
+        ));
+        assert!(matches_all(
+            &[RequestMatcher {
+                equals: Some("1".to_string()),
+                ..header("X-Debug")
+            }],
+            &context(&[("x-debug", "1")], "")
+        ));
+
+        let not_v2 = [RequestMatcher {
+            not_equals: Some("2".to_string()),
+            ..query("version")
+        }];
+        assert!(matches_all(&not_v2, &context(&[], "version=1")));
+        assert!(matches_all(&not_v2, &context(&[], "")));
+        assert!(!matches_all(&not_v2, &context(&[], "a=b&version=2")));
+        assert!(matches_all(
+            &[RequestMatcher {
+                equals: Some("a b".to_string()),
+                ..query("name")
+            }],
+            &context(&[], "name=a%20b")
+        ));
+        assert!(!matches_all(&[query("name")], &context(&[], "other=1")));
+
+        assert!(validate_matcher(&not_v2[0]).is_ok());
+        assert_eq!(
+            validate_matcher(&RequestMatcher {
+                absent: true,
+                ..content_type_matcher("application/json")
+            })
+            .unwrap_err()
+            .to_string(),
+            "equals, not_equals and absent only apply to header, query and captured"
+        );
+        assert_eq!(
+            validate_matcher(&RequestMatcher {
+                absent: true,
+                equals: Some("1".to_string()),
+                ..header("X-Debug")
+            })
+            .unwrap_err()
+            .to_string(),
+            "Matcher can set only one of: equals, not_equals, absent"
+        );
+    }
+
+    #[test]
+    fn test_basic_credentials() {
+        let context = |authorization: Option<&str>| ExecutionContext {
+            method: "GET".to_string(),
+            path: "/admin".to_string(),
+            query: String::new(),
+            headers: authorization
+                .map(|value| HashMap::from([("authorization".to_string(), value.to_string())]))
+                .unwrap_or_default(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+
+        // "admin:s3cr:et" — passwords may contain colons.
+        assert_eq!(
+            basic_credentials(&context(Some("Basic YWRtaW46czNjcjpldA=="))),
+            Some(("admin".to_string(), "s3cr:et".to_string()))
+        );
+        assert_eq!(
+            basic_credentials(&context(Some("basic  YWRtaW46czNjcjpldA=="))),
+            Some(("admin".to_string(), "s3cr:et".to_string()))
+        );
+        assert_eq!(basic_credentials(&context(None)), None);
+        assert_eq!(basic_credentials(&context(Some("Bearer abc"))), None);
+        assert_eq!(basic_credentials(&context(Some("Basic !!!"))), None);
+        // "admin" without a colon.
+        assert_eq!(basic_credentials(&context(Some("Basic YWRtaW4="))), None);
+    }
+
+    #[test]
+    fn test_client_ip_matcher() {
+        let context = |client_ip: &str| ExecutionContext {
+            method: "GET".to_string(),
+            path: "/users".to_string(),
+            query: String::new(),
+            headers: HashMap::new(),
+            client_ip: client_ip.to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+        let internal = [RequestMatcher {
+            client_ip: Some(vec![
+                "10.0.0.0/8".to_string(),
+                "192.168.1.20".to_string(),
+                "fd00::/8".to_string(),
+            ]),
+            ..Default::default()
+        }];
+
+        assert!(matches_all(&internal, &context("10.1.2.3")));
+        assert!(matches_all(&internal, &context("10.1.2.3:51234")));
+        assert!(matches_all(&internal, &context("::ffff:10.1.2.3")));
+        assert!(matches_all(&internal, &context("192.168.1.20")));
+        assert!(matches_all(&internal, &context("fd12::1")));
+        assert!(matches_all(&internal, &context("[fd12::1]:8080")));
+        assert!(!matches_all(&internal, &context("192.168.1.21")));
+        assert!(!matches_all(&internal, &context("11.0.0.1")));
+        assert!(!matches_all(&internal, &context("2001:db8::1")));
+        assert!(!matches_all(&internal, &context("unknown")));
+
+        let everyone = [RequestMatcher {
+            client_ip: Some(vec!["0.0.0.0/0".to_string()]),
+            ..Default::default()
+        }];
+        assert!(matches_all(&everyone, &context("203.0.113.9")));
+
+        let invalid = |range: &str| {
+            validate_matcher(&RequestMatcher {
+                client_ip: Some(vec![range.to_string()]),
+                ..Default::default()
+            })
+            .unwrap_err()
+            .to_string()
+        };
+        assert_eq!(
+            invalid("10.0.0.0/33"),
+            "Invalid prefix length in `10.0.0.0/33`"
+        );
+        assert_eq!(invalid("10.0.0/8"), "Invalid IP address in `10.0.0/8`");
+        assert!(validate_matcher(&internal[0]).is_ok());
+    }
+
+    #[test]
+    fn test_graphql_matcher() {
+        use crate::config::GraphqlOperationType;
+
+        let context = |body: Value| ExecutionContext {
+            method: "POST".to_string(),
+            path: "/graphql".to_string(),
+            query: String::new(),
+            headers: HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: Some(body.to_string()),
+        };
+        let graphql = |matcher: GraphqlMatcher| {
+            [RequestMatcher {
+                graphql: Some(matcher),
+                ..Default::default()
+            }]
+        };
+        let get_user = context(json!({
+            "query": "query GetUser($id: ID!) { user(id: $id) { name } }",
+            "variables": {"id": "42", "tags": ["a", "b"], "filter": {"active": true}}
+        }));
+        let create_order = context(json!({
+            "query": "query Q { q } mutation CreateOrder { createOrder { id } }",
+            "operationName": "CreateOrder"
+        }));
+
+        let by_name = graphql(GraphqlMatcher {
+            operation_name: Some("GetUser".to_string()),
+            ..Default::default()
+        });
+        assert!(matches_all(&by_name, &get_user));
+        assert!(!matches_all(&by_name, &create_order));
+
+        let mutation = graphql(GraphqlMatcher {
+            operation_type: Some(GraphqlOperationType::Mutation),
+            field: Some("~create.*".to_string()),
+            ..Default::default()
+        });
+        assert!(matches_all(&mutation, &create_order));
+        assert!(!matches_all(&mutation, &get_user));
+
+        let variables = |pairs: &[(&str, &str)]| {
+            graphql(GraphqlMatcher {
+                variables: pairs
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                ..Default::default()
+            })
+        };
+        assert!(matches_all(
+            &variables(&[("id", "42"), ("tags", "b"), ("filter.active", "true")]),
+            &get_user
+        ));
+        assert!(!matches_all(&variables(&[("id", "7")]), &get_user));
+
+        // Any GraphQL request matches an empty matcher, other requests do not.
+        assert!(matches_all(&graphql(GraphqlMatcher::default()), &get_user));
+        assert!(!matches_all(
+            &graphql(GraphqlMatcher::default()),
+            &context(json!({"name": "not graphql"}))
+        ));
+
+        let invalid = RequestMatcher {
+            graphql: Some(GraphqlMatcher {
+                field: Some("~(".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(format!("{:#}", validate_matcher(&invalid).unwrap_err())
+            .contains("Invalid regex for GraphQL field"));
+    }
+
+    #[test]
+    fn test_jwt_matcher() {
+        use jsonwebtoken::{encode, EncodingKey, Header};
+
+        let token = |claims: Value, kid: Option<&str>, secret: &str| {
+            let header = Header {
+                kid: kid.map(str::to_string),
+                ..Header::default()
+            };
+            encode(
+                &header,
+                &claims,
+                &EncodingKey::from_secret(secret.as_bytes()),
+            )
+            .unwrap()
+        };
+        let context = |token: &str| ExecutionContext {
+            method: "GET".to_string(),
+            path: "/admin".to_string(),
+            query: String::new(),
+            headers: HashMap::from([("authorization".to_string(), format!("Bearer {}", token))]),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::new(),
+            body: None,
+        };
+        let jwt = |matcher: JwtMatcher| {
+            [RequestMatcher {
+                jwt: Some(matcher),
+                ..Default::default()
+            }]
+        };
+        let claims = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        let now = chrono::Utc::now().timestamp();
+
+        let alice = token(
+            json!({
+                "sub": "alice",
+                "scope": "read admin",
+                "roles": ["viewer", "editor"],
+                "realm": {"level": 3},
+                "https://example.com/tenant": "acme",
+                "exp": now + 3600,
+            }),
+            None,
+            "s3cr3t",
+        );
+        let admin = jwt(JwtMatcher {
+            claims: claims(&[
+                ("sub", "alice"),
+                ("scope", "~.*\\badmin\\b.*"),
+                ("roles", "editor"),
+                ("realm.level", "3"),
+                ("https://example.com/tenant", "acme"),
+            ]),
+            ..Default::default()
+        });
+        assert!(matches_all(&admin, &context(&alice)));
+        assert!(!matches_all(&admin, &context("not-a-jwt")));
+        assert!(!matches_all(
+            &admin,
+            &context(&token(
+                json!({"sub": "bob", "scope": "read"}),
+                None,
+                "s3cr3t"
+            ))
+        ));
+        let mut basic = context(&alice);
+        basic
+            .headers
+            .insert("authorization".to_string(), format!("Basic {}", alice));
+        assert!(!matches_all(&admin, &basic));
+
+        // Signatures are only checked when a key is configured.
+        let verified = |secret: &str| {
+            jwt(JwtMatcher {
+                claims: claims(&[("sub", "alice")]),
+                secret: Some(secret.to_string()),
+                ..Default::default()
+            })
+        };
+        assert!(matches_all(&verified("s3cr3t"), &context(&alice)));
+        assert!(!matches_all(&verified("other"), &context(&alice)));
+
+        // "czNjcjN0" is base64url for "s3cr3t".
+        let jwks: jsonwebtoken::jwk::JwkSet = serde_json::from_value(json!({
+            "keys": [{"kty": "oct", "kid": "k1", "alg": "HS256", "k": "czNjcjN0"}]
+        }))
+        .unwrap();
+        let by_kid = jwt(JwtMatcher {
+            jwks: Some(jwks),
+            ..Default::default()
+        });
+        let sub = json!({"sub": "alice"});
+        assert!(matches_all(
+            &by_kid,
+            &context(&token(sub.clone(), Some("k1"), "s3cr3t"))
+        ));
+        assert!(!matches_all(
+            &by_kid,
+            &context(&token(sub.clone(), Some("k2"), "s3cr3t"))
+        ));
+        assert!(!matches_all(
+            &by_kid,
+            &context(&token(sub, Some("k1"), "other"))
+        ));
+        // The keys are built once, with the matcher.
+        let compiled = CompiledMatcher::new(&by_kid[0]);
+        assert!(matches!(
+            compiled.jwt_keys,
+            Some(JwtKeys::Jwks(ref keys)) if keys[0].0.as_deref() == Some("k1") && keys[0].1.is_some()
+        ));
+
+        let expired = |expired: bool| {
+            jwt(JwtMatcher {
+                expired: Some(expired),
+                ..Default::default()
+            })
+        };
+        let stale = token(json!({"sub": "alice", "exp": now - 60}), None, "s3cr3t");
+        assert!(matches_all(&expired(true), &context(&stale)));
+        assert!(!matches_all(&expired(false), &context(&stale)));
+        assert!(matches_all(&expired(false), &context(&alice)));
+        assert!(!matches_all(&expired(true), &context(&alice)));
+
+        let both = RequestMatcher {
+            jwt: Some(JwtMatcher {
+                secret: Some("s3cr3t".to_string()),
+                jwks: Some(jsonwebtoken::jwk::JwkSet { keys: Vec::new() }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_matcher(&both).unwrap_err().to_string(),
+            "JWT matcher can set only one of: secret, jwks"
+        );
+        let bad_regex = RequestMatcher {
+            jwt: Some(JwtMatcher {
+                claims: claims(&[("scope", "~(")]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_matcher(&bad_regex).unwrap_err().to_string(),
+            "Invalid regex for JWT claim `scope`"
+        );
+    }
+
+    fn form_matcher(fields: &[(&str, &str)]) -> RequestMatcher {
+        RequestMatcher {
+            form: Some(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn xpath_matcher(expression: &str) -> RequestMatcher {
+        RequestMatcher {
+            xpath: Some(expression.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn content_type_matcher(media_type: &str) -> RequestMatcher {
+        RequestMatcher {
+            content_type: Some(media_type.to_string()),
+            ..Default::default()
+        }
+    }
 }
@@ -14,30 +14,291 @@
  * limitations under the License.
  */
 
+pub mod attempts;
+pub mod body_match;
+pub mod cache;
+pub mod captures;
+pub mod custom_matchers;
+pub mod datasets;
+pub mod delay_expr;
+pub mod error;
 pub mod executor;
+pub mod failpoints;
+pub mod fixtures;
+pub mod gates;
+pub mod graphql;
+pub mod inbox;
+pub mod instances;
 pub mod matcher;
+pub mod multipart;
+pub mod pagination;
+pub mod registry;
+pub mod schema_body;
+pub mod self_check;
 pub mod state;
+pub mod template;
+pub mod transform;
+pub mod usage;
+pub mod validation;
 
-use crate::config::Endpoint;
+use crate::config::{
+    Config, Endpoint, Fallthrough, Fault, HeaderList, ResponseSigning, ResponseTransform,
+};
+use crate::telemetry::metrics::record_endpoint_metric;
+use captures::Captures;
+use dashmap::DashMap;
+use datasets::Datasets;
+pub use error::RuleError;
 use executor::ResponseExecutor;
-use matcher::RuleMatcher;
+use failpoints::{FailpointSpec, Failpoints};
+use fixtures::CsvFixtures;
+use gates::Gates;
+use inbox::{InboxQuery, InboxRequest, Inboxes};
+use instances::{InstanceState, Instances};
+use matcher::{CompiledMatchers, MatchMiss, RuleMatcher};
 use state::StateManager;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::sync::Arc;
+use template::CompiledTemplates;
+use transform::TransformChain;
+use usage::UsageReport;
+use validation::BodyValidator;
 
 #[derive(Clone)]
 pub struct RuleEngine {
     matcher: RuleMatcher,
+    matchers: CompiledMatchers,
     executor: ResponseExecutor,
+    endpoints: Vec<Endpoint>,
+    hits: Arc<DashMap<String, u64>>,
+    validators: Arc<HashMap<String, BodyValidator>>,
+    transforms: Arc<HashMap<String, Arc<TransformChain>>>,
+    inboxes: Inboxes,
+    gates: Gates,
+    instances: Instances,
+    failpoints: Failpoints,
+    captures: Captures,
+    errors: Arc<DashMap<&'static str, u64>>,
+    dry_run: bool,
 }
 
 impl RuleEngine {
     pub fn new(endpoints: Vec<Endpoint>) -> Self {
         let state_manager = Arc::new(StateManager::new());
         let matcher = RuleMatcher::new(endpoints.clone());
-        let executor = ResponseExecutor::new(state_manager.clone());
+        let failpoints = Failpoints::default();
+        let hits = Arc::new(DashMap::new());
+        let captures = Captures::default();
+        let matchers = CompiledMatchers::new(&endpoints);
+        let executor = ResponseExecutor::new(state_manager.clone())
+            .with_endpoint_seeds(&endpoints)
+            .with_delay_exprs(&endpoints)
+            .with_templates(CompiledTemplates::new(&endpoints))
+            .with_datasets(Datasets::new(&endpoints))
+            .with_fixtures(CsvFixtures::new(&endpoints))
+            .with_matchers(matchers.clone())
+            .with_failpoints(failpoints.clone())
+            .with_hits(hits.clone())
+            .with_captures(captures.clone());
 
-        Self { matcher, executor }
+        let mut validators = HashMap::new();
+        for endpoint in &endpoints {
+            if let Some(validation) = &endpoint.validation {
+                match BodyValidator::new(validation) {
+                    Ok(validator) => {
+                        validators.insert(endpoint.name.clone(), validator);
+                    }
+                    Err(e) => tracing::warn!(
+                        endpoint = %endpoint.name,
+                        error = %e,
+                        "Ignoring invalid body validation"
+                    ),
+                }
+            }
+        }
+
+        let transforms = transform_chains(&endpoints, &[]);
+
+        Self {
+            matcher,
+            matchers,
+            executor,
+            endpoints,
+            hits,
+            validators: Arc::new(validators),
+            transforms: Arc::new(transforms),
+            inboxes: Inboxes::default(),
+            gates: Gates::default(),
+            instances: Instances::default(),
+            failpoints,
+            captures,
+            errors: Arc::new(DashMap::new()),
+            dry_run: false,
+        }
+    }
+
+    /// Builds an engine for the endpoints of `config`, including the settings they
+    /// reference such as latency profiles and the global jitter.
+    pub fn from_config(config: &Config) -> Self {
+        let mut engine = Self::new(config.endpoints.clone());
+        let mut executor = engine
+            .executor
+            .with_latency_profiles(config.latency_profiles.clone())
+            .with_regions(config.regions.clone())
+            .with_limits(config.server.limits.clone())
+            .with_max_computed_delay(config.server.max_computed_delay.as_duration())
+            .with_dry_run(config.server.dry_run);
+        engine.dry_run = config.server.dry_run;
+        engine.transforms = Arc::new(transform_chains(
+            &config.endpoints,
+            &config.server.transform,
+        ));
+
+        // The loader has already validated the range.
+        if let Some(Ok((min, max))) = config
+            .server
+            .jitter
+            .as_deref()
+            .map(crate::config::types::parse_duration_range)
+        {
+            executor = executor.with_jitter(min, max);
+        }
+
+        engine.executor = executor;
+        engine
+    }
+
+    /// Takes over the admin-controlled state of the engine this one replaces on
+    /// reload, so requests held by a gate can still be released, armed failpoints
+    /// stay armed, recorded inbox requests stay readable and instances keep the
+    /// health set for them, and captured slots stay readable. Error counts and the hit
+    /// counts of endpoints that are still configured carry over too.
+    pub fn carry_over(mut self, previous: &RuleEngine) -> Self {
+        for entry in previous.hits.iter() {
+            if self
+                .endpoints
+                .iter()
+                .any(|endpoint| &endpoint.name == entry.key())
+            {
+                self.hits.insert(entry.key().clone(), *entry.value());
+            }
+        }
+        for entry in previous.errors.iter() {
+            self.errors.insert(*entry.key(), *entry.value());
+        }
+        self.gates = previous.gates.clone();
+        self.inboxes = previous.inboxes.clone();
+        self.instances = previous.instances.clone();
+        self.failpoints = previous.failpoints.clone();
+        self.captures = previous.captures.clone();
+        self.executor = self
+            .executor
+            .with_failpoints(previous.failpoints.clone())
+            .with_captures(previous.captures.clone());
+        self
+    }
+
+    /// Per-endpoint hit counts since startup, carried over reloads, independent of
+    /// the stateful per-client counters.
+    pub fn usage(&self) -> UsageReport {
+        UsageReport::new(&self.endpoints, |name| {
+            self.hits.get(name).map(|hits| *hits).unwrap_or(0)
+        })
+    }
+
+    /// Requests recorded by endpoints with `inbox: <name>` that match `query`.
+    pub fn inbox(&self, name: &str, query: &InboxQuery) -> Vec<InboxRequest> {
+        self.inboxes.find(name, query)
+    }
+
+    pub fn clear_inbox(&self, name: &str) {
+        self.inboxes.clear(name);
+    }
+
+    /// Whether the endpoint that would handle the request wants its body as received
+    /// rather than decompressed.
+    pub fn wants_raw_body(&self, method: &str, path: &str) -> bool {
+        self.matcher
+            .find_match(method, path)
+            .is_ok_and(|endpoint| endpoint.raw_body)
+    }
+
+    /// Path pattern of the endpoint that would handle the request.
+    pub fn route(&self, method: &str, path: &str) -> Option<String> {
+        self.matcher
+            .find_match(method, path)
+            .ok()
+            .map(|endpoint| endpoint.path.clone())
+    }
+
+    /// Number of requests currently held by `gate`.
+    pub fn held(&self, gate: &str) -> usize {
+        self.gates.held(gate)
+    }
+
+    /// Releases up to `count` requests held by `gate` (all when `None`).
+    pub fn release_gate(&self, gate: &str, count: Option<usize>) -> usize {
+        self.gates.release(gate, count)
+    }
+
+    /// The instances of every endpoint with `instances:`, by endpoint name.
+    pub fn instances(&self) -> BTreeMap<String, Vec<InstanceState>> {
+        self.endpoints
+            .iter()
+            .filter(|endpoint| !endpoint.instances.is_empty())
+            .map(|endpoint| {
+                let states = endpoint
+                    .instances
+                    .iter()
+                    .map(|instance| self.instances.state(&endpoint.name, instance))
+                    .collect();
+                (endpoint.name.clone(), states)
+            })
+            .collect()
+    }
+
+    /// Marks an instance up or down, returning its new state, or `None` when the
+    /// endpoint has no such instance.
+    pub fn set_instance_health(
+        &self,
+        endpoint: &str,
+        instance: &str,
+        healthy: bool,
+    ) -> Option<InstanceState> {
+        let instance = self
+            .endpoints
+            .iter()
+            .find(|e| e.name == endpoint)?
+            .instances
+            .iter()
+            .find(|i| i.name == instance)?;
+        self.instances
+            .set_healthy(endpoint, &instance.name, healthy);
+        Some(self.instances.state(endpoint, instance))
+    }
+
+    /// Makes the failpoint `name` fail as `spec` says. Returns `false` for an unknown
+    /// failpoint.
+    pub fn arm_failpoint(&self, name: &str, spec: FailpointSpec) -> bool {
+        self.failpoints.arm(name, spec)
+    }
+
+    /// Returns whether the failpoint was armed.
+    pub fn disarm_failpoint(&self, name: &str) -> bool {
+        self.failpoints.disarm(name)
+    }
+
+    pub fn armed_failpoints(&self) -> std::collections::BTreeMap<String, FailpointSpec> {
+        self.failpoints.armed()
+    }
+
+    /// Failed requests since startup, carried over reloads, by [`RuleError::error_type`].
+    pub fn error_counts(&self) -> std::collections::BTreeMap<&'static str, u64> {
+        self.errors
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect()
     }
 
     pub async fn execute(
@@ -46,22 +307,249 @@ impl RuleEngine {
         path: &str,
         query: &str,
         headers: &HashMap<String, String>,
-        _body: Option<&str>,
+        body: Option<&str>,
         client_ip: &str,
-    ) -> anyhow::Result<RuleResponse> {
-        let endpoint = self.matcher.find_match(method, path)?;
+    ) -> Result<RuleResponse, RuleError> {
+        let mut result = self
+            .respond(method, path, query, headers, body, client_ip)
+            .await;
+        match &mut result {
+            Ok(response) => {
+                response.transform = self.transforms.get(&response.selection.endpoint).cloned();
+            }
+            Err(e) => {
+                if !self.dry_run {
+                    *self.errors.entry(e.error_type()).or_insert(0) += 1;
+                }
+            }
+        }
+        result
+    }
 
-        let context = ExecutionContext {
-            method: method.to_string(),
-            path: path.to_string(),
-            query: query.to_string(),
-            headers: headers.clone(),
-            client_ip: client_ip.to_string(),
-            path_params: self.matcher.extract_path_params(&endpoint.path, path),
+    /// Applies the endpoint's custom `metrics` for a request it served.
+    fn update_metrics(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        response: &RuleResponse,
+    ) {
+        if self.dry_run {
+            return;
+        }
+
+        for (index, metric) in endpoint.metrics.iter().enumerate() {
+            let labels = self.metric_labels(endpoint, index, context, response);
+            record_endpoint_metric(metric, &labels);
+        }
+    }
+
+    /// The labels of the endpoint's `index`th metric for a request it served.
+    fn metric_labels(
+        &self,
+        endpoint: &Endpoint,
+        index: usize,
+        context: &ExecutionContext,
+        response: &RuleResponse,
+    ) -> Vec<(String, String)> {
+        let mut labels: Vec<(String, String)> = endpoint.metrics[index]
+            .labels
+            .keys()
+            .map(|key| {
+                let value = self.executor.render_metric_label(
+                    endpoint,
+                    index,
+                    key,
+                    context,
+                    response.status,
+                );
+                (key.clone(), value)
+            })
+            .collect();
+        labels.sort();
+        labels
+    }
+
+    async fn respond(
+        &self,
+        method: &str,
+        path: &str,
+        query: &str,
+        headers: &HashMap<String, String>,
+        body: Option<&str>,
+        client_ip: &str,
+    ) -> Result<RuleResponse, RuleError> {
+        for endpoint in self.matcher.matching_endpoints(method, path) {
+            if let Some(spec) = &endpoint.body_match {
+                if !body_match::matches(spec, body) {
+                    continue;
+                }
+            }
+
+            let context = ExecutionContext {
+                method: method.to_string(),
+                path: path.to_string(),
+                query: query.to_string(),
+                headers: headers.clone(),
+                client_ip: client_ip.to_string(),
+                path_params: self.matcher.extract_path_params(&endpoint.path, path),
+                body: body.map(str::to_string),
+            };
+
+            let matchers = self.matchers.endpoint(endpoint);
+            if !matcher::matches_all_with(&matchers, &context, &self.captures) {
+                continue;
+            }
+
+            if let Some(auth) = &endpoint.basic_auth {
+                let authorized =
+                    matcher::basic_credentials(&context).is_some_and(|(username, password)| {
+                        username == auth.username && password == auth.password
+                    });
+                if !authorized {
+                    let response = self.executor.unauthorized(endpoint, &context, auth);
+                    self.update_metrics(endpoint, &context, &response);
+                    return Ok(response);
+                }
+            }
+
+            if let Some(inbox) = &endpoint.inbox {
+                if !self.dry_run {
+                    self.inboxes.record(
+                        inbox,
+                        InboxRequest {
+                            timestamp: chrono::Utc::now().to_rfc3339(),
+                            method: method.to_string(),
+                            path: path.to_string(),
+                            query: query.to_string(),
+                            headers: headers.clone(),
+                            body: body.map(str::to_string),
+                        },
+                    );
+                }
+
+                if endpoint.responses.is_empty() {
+                    let response = self.executor.accepted(endpoint, &context);
+                    self.update_metrics(endpoint, &context, &response);
+                    return Ok(response);
+                }
+            }
+
+            if let Some(validator) = self.validators.get(&endpoint.name) {
+                let errors = validator.validate(body);
+                if !errors.is_empty() {
+                    let response = self
+                        .executor
+                        .invalid_body(endpoint, &context, validator, &errors);
+                    self.update_metrics(endpoint, &context, &response);
+                    return Ok(response);
+                }
+            }
+
+            let instance = self.instances.pick(
+                endpoint,
+                headers.get(instances::INSTANCE_HEADER).map(String::as_str),
+            );
+            if let Some(instance) = instance {
+                if !self.instances.is_healthy(&endpoint.name, instance) {
+                    let response = self.executor.instance_down(endpoint, &context, instance);
+                    self.update_metrics(endpoint, &context, &response);
+                    return Ok(response);
+                }
+            }
+
+            if let (Some(gate), false) = (&endpoint.gate, self.dry_run) {
+                tracing::debug!(endpoint = %endpoint.name, gate = %gate, "Holding request");
+                self.gates.hold(gate).await;
+            }
+
+            let response = match self.execute_with_timeout(endpoint, &context).await {
+                Ok(response) => response,
+                Err(e @ RuleError::NoResponse { .. }) => match endpoint.fallthrough {
+                    Fallthrough::Default => return Err(e),
+                    Fallthrough::NotFound => self.executor.not_found(endpoint, &context),
+                    Fallthrough::NextEndpoint => {
+                        tracing::debug!(
+                            endpoint = %endpoint.name,
+                            "No response selected, falling through to next endpoint"
+                        );
+                        continue;
+                    }
+                },
+                Err(e) => return Err(e),
+            };
+
+            let mut response = response;
+            if let Some(instance) = instance {
+                response
+                    .headers
+                    .insert("X-Molock-Instance", instance.name.clone());
+            }
+
+            if !self.dry_run {
+                self.captures.record(&endpoint.capture, &context);
+            }
+
+            self.update_metrics(endpoint, &context, &response);
+            return Ok(response);
+        }
+
+        match self.matcher.miss(method, path) {
+            MatchMiss::NotFound => Err(RuleError::NoMatch {
+                method: method.to_string(),
+                path: path.to_string(),
+            }),
+            MatchMiss::MethodNotAllowed(allowed) => Err(RuleError::MethodNotAllowed {
+                method: method.to_string(),
+                path: path.to_string(),
+                allowed,
+            }),
+        }
+    }
+
+    /// Runs the executor, answering from the endpoint's `timeout` if it takes too long.
+    async fn execute_with_timeout(
+        &self,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+    ) -> Result<RuleResponse, RuleError> {
+        let Some(timeout) = &endpoint.timeout else {
+            return self.executor.execute(endpoint, context).await;
         };
+        // Only fires at await points: delays and proxying are cut short, but a
+        // template that is already rendering finishes first.
+        let after = timeout.after.as_duration();
+
+        match tokio::time::timeout(after, self.executor.execute(endpoint, context)).await {
+            Ok(result) => result,
+            Err(_) => Ok(self.executor.timed_out(endpoint, context, timeout, after)),
+        }
+    }
+}
 
-        self.executor.execute(endpoint, &context).await
+/// Each endpoint's `transform` steps followed by the server-wide ones, by endpoint name.
+fn transform_chains(
+    endpoints: &[Endpoint],
+    server: &[ResponseTransform],
+) -> HashMap<String, Arc<TransformChain>> {
+    let mut chains = HashMap::new();
+    for endpoint in endpoints {
+        let steps: Vec<ResponseTransform> =
+            endpoint.transform.iter().chain(server).cloned().collect();
+        if steps.is_empty() {
+            continue;
+        }
+        match TransformChain::new(&steps) {
+            Ok(chain) => {
+                chains.insert(endpoint.name.clone(), Arc::new(chain));
+            }
+            Err(e) => tracing::warn!(
+                endpoint = %endpoint.name,
+                error = %e,
+                "Ignoring invalid response transform"
+            ),
+        }
     }
+    chains
 }
 
 pub struct ExecutionContext {
@@ -71,19 +559,203 @@ pub struct ExecutionContext {
     pub headers: HashMap<String, String>,
     pub client_ip: String,
     pub path_params: HashMap<String, String>,
+    pub body: Option<String>,
+}
+
+impl ExecutionContext {
+    /// The scheme the client used, honoring `X-Forwarded-Proto` from a TLS-terminating proxy.
+    pub fn scheme(&self) -> &str {
+        match self.headers.get("x-forwarded-proto") {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+            _ => "http",
+        }
+    }
+
+    /// Host and port from the `Host` header, defaulting the port from the scheme.
+    pub fn host_and_port(&self) -> (String, u16) {
+        let default_port = if self.scheme() == "https" { 443 } else { 80 };
+        let host = self
+            .headers
+            .get("host")
+            .map(String::as_str)
+            .unwrap_or("localhost");
+
+        // A bracketed IPv6 host without a port ("[::1]") fails the port parse.
+        match host.rsplit_once(':') {
+            Some((name, port)) => match port.parse() {
+                Ok(port) => (name.to_string(), port),
+                Err(_) => (host.to_string(), default_port),
+            },
+            None => (host.to_string(), default_port),
+        }
+    }
+
+    /// The first value of a query string parameter, without decoding.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The `Cookie` header's name/value pairs, in order.
+    pub fn cookies(&self) -> Vec<(String, String)> {
+        self.headers
+            .get("cookie")
+            .map(|cookie| {
+                cookie
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The body parsed as JSON, or as a string when it is not JSON.
+    pub fn json_body(&self) -> Option<serde_json::Value> {
+        let body = self.body.as_deref()?;
+        Some(serde_json::from_str(body).unwrap_or_else(|_| serde_json::Value::from(body)))
+    }
+
+    /// The body decoded as `application/x-www-form-urlencoded` fields, in order.
+    pub fn form_fields(&self) -> Vec<(String, String)> {
+        self.body
+            .as_deref()
+            .map(|body| {
+                url::form_urlencoded::parse(body.trim().as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The parts of a `multipart/form-data` body, in order.
+    pub fn multipart_parts(&self) -> Vec<multipart::Part> {
+        let boundary = self
+            .headers
+            .get("content-type")
+            .and_then(|content_type| multipart::boundary(content_type));
+        match (boundary, self.body.as_deref()) {
+            (Some(boundary), Some(body)) => multipart::parse(boundary, body),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The full URL of the request as the client addressed it.
+    pub fn url(&self) -> String {
+        let host = self
+            .headers
+            .get("host")
+            .map(String::as_str)
+            .unwrap_or("localhost");
+        let mut url = format!("{}://{}{}", self.scheme(), host, self.path);
+        if !self.query.is_empty() {
+            url.push('?');
+            url.push_str(&self.query);
+        }
+        url
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RuleResponse {
     pub status: u16,
     pub body: Option<String>,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderList,
+    pub fault: Option<Fault>,
+    /// The endpoint's and server's `transform` steps, applied once the body is
+    /// encoded for the wire.
+    pub transform: Option<Arc<TransformChain>>,
+    /// The endpoint's `signing`, applied once the body is encoded for the wire.
+    pub signing: Option<ResponseSigning>,
+    pub selection: ResponseSelection,
+    /// Delay applied before the response was produced, including jitter.
+    pub delay: std::time::Duration,
+}
+
+/// Describes which endpoint and response produced a [`RuleResponse`] and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSelection {
+    pub endpoint: String,
+    /// The endpoint's configured path pattern, e.g. `/users/:id`.
+    pub route: String,
+    /// Index into the endpoint's `responses`, or `None` when no configured response was used.
+    pub response_index: Option<usize>,
+    pub reason: SelectionReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionReason {
+    /// The only candidate left after evaluating conditions had a condition that passed.
+    Condition,
+    /// The only candidate left had no condition attached.
+    Unconditional,
+    /// Several candidates passed and one was picked by probability.
+    Probability,
+    /// Several candidates passed and the `round_robin` strategy picked the next one.
+    RoundRobin,
+    /// No candidate passed and the default response was used.
+    Default,
+    /// No candidate passed and the endpoint's `fallthrough` answered instead.
+    Fallthrough,
+    /// The request body failed the endpoint's `validation` schema.
+    InvalidBody,
+    /// The request carried a pagination cursor this instance never issued.
+    InvalidCursor,
+    /// An `inbox` endpoint without responses recorded the request.
+    Inbox,
+    /// The endpoint's `timeout` ran out before a response was ready.
+    Timeout,
+    /// The request lacked the endpoint's `basic_auth` credentials.
+    Unauthorized,
+    /// The client's region simulated an outage through its `fault`.
+    RegionFault,
+    /// The request was routed to an unhealthy instance of the endpoint.
+    InstanceDown,
+}
+
+impl fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            SelectionReason::Condition => "condition",
+            SelectionReason::Unconditional => "unconditional",
+            SelectionReason::Probability => "probability",
+            SelectionReason::RoundRobin => "round_robin",
+            SelectionReason::Default => "default",
+            SelectionReason::Fallthrough => "fallthrough",
+            SelectionReason::InvalidBody => "invalid_body",
+            SelectionReason::InvalidCursor => "invalid_cursor",
+            SelectionReason::Inbox => "inbox",
+            SelectionReason::Timeout => "timeout",
+            SelectionReason::Unauthorized => "unauthorized",
+            SelectionReason::RegionFault => "region_fault",
+            SelectionReason::InstanceDown => "instance_down",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// Formats the selection as used by the `X-Molock-Matched` debug header,
+/// e.g. `endpoint=Orders;response=2;reason=condition`.
+impl fmt::Display for ResponseSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "endpoint={};response=", self.endpoint)?;
+        match self.response_index {
+            Some(index) => write!(f, "{}", index)?,
+            None => f.write_str("none")?,
+        }
+        write!(f, ";reason={}", self.reason)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::types::Response;
+    use crate::config::types::{
+        BasicAuth, BodyMatch, EndpointMetric, EndpointTimeout, RequestMatcher, Response,
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -98,11 +770,12 @@ mod tests {
                 status: 200,
                 delay: None,
                 body: Some("OK".to_string()),
-                headers: HashMap::new(),
                 condition: None,
                 probability: None,
                 default: false,
+                ..Default::default()
             }],
+            ..Default::default()
         }];
 
         let _engine = RuleEngine::new(endpoints);
@@ -117,4 +790,720 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_usage_counts_hits_per_endpoint() {
+        let endpoint = |name: &str, path: &str| Endpoint {
+            name: name.to_string(),
+            method: "GET".to_string(),
+            path: path.to_string(),
+            stateful: false,
+            state_key: None,
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: None,
+                condition: None,
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![endpoint("A", "/a"), endpoint("B", "/b")]);
+
+        for _ in 0..3 {
+            engine
+                .execute("GET", "/a", "", &HashMap::new(), None, "127.0.0.1")
+                .await
+                .unwrap();
+        }
+
+        let report = engine.usage();
+        assert_eq!(report.endpoints[0].hits, 3);
+        assert_eq!(report.endpoints[1].hits, 0);
+        assert_eq!(report.unused()[0].name, "B");
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_hits_in_templates_and_headers() {
+        let mut headers = HeaderList::new();
+        headers.insert("X-Quota-Used", "{{endpoint_hits}}");
+        let engine = RuleEngine::new(vec![Endpoint {
+            name: "Quota".to_string(),
+            method: "GET".to_string(),
+            path: "/quota".to_string(),
+            hit_header: true,
+            responses: vec![Response {
+                status: 200,
+                body: Some("call {{endpoint_hits}}".to_string()),
+                headers,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]);
+
+        for expected in 1..=2 {
+            let response = engine
+                .execute("GET", "/quota", "", &HashMap::new(), None, "10.0.0.1")
+                .await
+                .unwrap();
+            assert_eq!(response.body, Some(format!("call {}", expected)));
+            assert_eq!(
+                response.headers.get("X-Endpoint-Hits").map(String::as_str),
+                Some(expected.to_string().as_str())
+            );
+            assert_eq!(
+                response.headers.get("X-Quota-Used").map(String::as_str),
+                Some(expected.to_string().as_str())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_get_distinct_endpoint_hits() {
+        let engine = RuleEngine::new(vec![Endpoint {
+            name: "Quota".to_string(),
+            method: "GET".to_string(),
+            path: "/quota".to_string(),
+            responses: vec![Response {
+                status: 200,
+                delay: Some(crate::config::types::Delay::Fixed("5ms".to_string())),
+                body: Some("{{endpoint_hits}}".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]);
+
+        let headers = HashMap::new();
+        let requests =
+            (0..8).map(|_| engine.execute("GET", "/quota", "", &headers, None, "10.0.0.1"));
+        let mut hits: Vec<u64> = futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .map(|response| response.unwrap().body.unwrap().parse().unwrap())
+            .collect();
+        hits.sort_unstable();
+        assert_eq!(hits, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_response_selection_display() {
+        let selection = ResponseSelection {
+            endpoint: "Orders".to_string(),
+            route: "/orders".to_string(),
+            response_index: Some(2),
+            reason: SelectionReason::Condition,
+        };
+
+        assert_eq!(
+            selection.to_string(),
+            "endpoint=Orders;response=2;reason=condition"
+        );
+
+        let selection = ResponseSelection {
+            endpoint: "Orders".to_string(),
+            route: "/orders".to_string(),
+            response_index: None,
+            reason: SelectionReason::Fallthrough,
+        };
+
+        assert_eq!(
+            selection.to_string(),
+            "endpoint=Orders;response=none;reason=fallthrough"
+        );
+    }
+
+    fn conditional_endpoint(name: &str, path: &str, fallthrough: Fallthrough) -> Endpoint {
+        Endpoint {
+            name: name.to_string(),
+            method: "GET".to_string(),
+            path: path.to_string(),
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: Some(name.to_string()),
+                condition: Some("request_count > 100".to_string()),
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            fallthrough,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallthrough_modes() {
+        let catch_all = Endpoint {
+            name: "CatchAll".to_string(),
+            method: "GET".to_string(),
+            path: "/items/*".to_string(),
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: Some("CatchAll".to_string()),
+                condition: None,
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![
+            catch_all,
+            conditional_endpoint("Next", "/items/next", Fallthrough::NextEndpoint),
+            conditional_endpoint("NotFound", "/items/missing", Fallthrough::NotFound),
+            conditional_endpoint("Error", "/items/error", Fallthrough::Default),
+        ]);
+        let execute = |path: &'static str| {
+            let engine = engine.clone();
+            async move {
+                engine
+                    .execute("GET", path, "", &HashMap::new(), None, "127.0.0.1")
+                    .await
+            }
+        };
+
+        let response = execute("/items/next").await.unwrap();
+        assert_eq!(response.body.as_deref(), Some("CatchAll"));
+        assert_eq!(response.selection.endpoint, "CatchAll");
+
+        let response = execute("/items/missing").await.unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(response.selection.reason, SelectionReason::Fallthrough);
+
+        assert!(execute("/items/error").await.is_err());
+
+        let usage = engine.usage();
+        let hits = |name: &str| {
+            usage
+                .endpoints
+                .iter()
+                .find(|e| e.name == name)
+                .unwrap()
+                .hits
+        };
+        assert_eq!(hits("CatchAll"), 1);
+        assert_eq!(hits("Next"), 0);
+        assert_eq!(hits("NotFound"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_body_validation() {
+        let endpoint = Endpoint {
+            name: "Signup".to_string(),
+            method: "POST".to_string(),
+            path: "/signup".to_string(),
+            responses: vec![Response {
+                status: 201,
+                ..Default::default()
+            }],
+            validation: Some(crate::config::BodyValidation {
+                schema: serde_json::json!({"type": "object", "required": ["email"]}),
+                status: 422,
+                envelope: r#"{"errors": {{errors}}}"#.to_string(),
+            }),
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![endpoint]);
+        let execute = |body: &'static str| {
+            let engine = engine.clone();
+            async move {
+                engine
+                    .execute(
+                        "POST",
+                        "/signup",
+                        "",
+                        &HashMap::new(),
+                        Some(body),
+                        "127.0.0.1",
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let response = execute(r#"{"email": "a@example.com"}"#).await;
+        assert_eq!(response.status, 201);
+
+        let response = execute("{}").await;
+        assert_eq!(response.status, 422);
+        assert_eq!(response.selection.reason, SelectionReason::InvalidBody);
+        let body: serde_json::Value = serde_json::from_str(&response.body.unwrap()).unwrap();
+        assert_eq!(body["errors"][0]["field"], "/email");
+    }
+
+    #[tokio::test]
+    async fn test_inbox_records_requests() {
+        let engine = RuleEngine::new(vec![Endpoint {
+            name: "Webhooks".to_string(),
+            method: "POST".to_string(),
+            path: "/hooks/*".to_string(),
+            inbox: Some("payments".to_string()),
+            ..Default::default()
+        }]);
+
+        let response = engine
+            .execute(
+                "POST",
+                "/hooks/stripe",
+                "",
+                &HashMap::new(),
+                Some(r#"{"event": "paid"}"#),
+                "127.0.0.1",
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 202);
+        assert_eq!(response.selection.reason, SelectionReason::Inbox);
+
+        let received = engine.inbox("payments", &InboxQuery::default());
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/hooks/stripe");
+        assert_eq!(received[0].body.as_deref(), Some(r#"{"event": "paid"}"#));
+
+        engine.clear_inbox("payments");
+        assert!(engine.inbox("payments", &InboxQuery::default()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gate_holds_until_released() {
+        let engine = Arc::new(RuleEngine::new(vec![Endpoint {
+            name: "Orders".to_string(),
+            method: "POST".to_string(),
+            path: "/orders".to_string(),
+            gate: Some("orders".to_string()),
+            responses: vec![Response {
+                status: 201,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]));
+
+        let request = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                engine
+                    .execute("POST", "/orders", "", &HashMap::new(), None, "127.0.0.1")
+                    .await
+            }
+        });
+
+        while engine.held("orders") == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        assert!(!request.is_finished());
+
+        assert_eq!(engine.release_gate("orders", None), 1);
+        assert_eq!(request.await.unwrap().unwrap().status, 201);
+    }
+
+    #[tokio::test]
+    async fn test_instances_rotate_and_fail_when_down() {
+        let instance = |name: &str| crate::config::EndpointInstance {
+            name: name.to_string(),
+            weight: 1,
+            healthy: true,
+        };
+        let engine = RuleEngine::new(vec![Endpoint {
+            name: "Users".to_string(),
+            method: "GET".to_string(),
+            path: "/users".to_string(),
+            instances: vec![instance("node-a"), instance("node-b")],
+            responses: vec![Response {
+                status: 200,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]);
+        let get = |pinned: Option<&str>| {
+            let headers: HashMap<String, String> = pinned
+                .map(|name| HashMap::from([("x-molock-instance".to_string(), name.to_string())]))
+                .unwrap_or_default();
+            let engine = &engine;
+            async move {
+                engine
+                    .execute("GET", "/users", "", &headers, None, "127.0.0.1")
+                    .await
+                    .unwrap()
+            }
+        };
+        let served_by =
+            |response: &RuleResponse| response.headers.get("X-Molock-Instance").cloned().unwrap();
+
+        assert_eq!(served_by(&get(None).await), "node-a");
+        assert_eq!(served_by(&get(None).await), "node-b");
+
+        let state = engine
+            .set_instance_health("Users", "node-b", false)
+            .unwrap();
+        assert!(!state.healthy);
+        assert!(engine
+            .set_instance_health("Users", "node-z", false)
+            .is_none());
+        assert!(engine
+            .set_instance_health("Orders", "node-b", false)
+            .is_none());
+
+        assert_eq!(get(None).await.status, 200);
+        let down = get(None).await;
+        assert_eq!(down.status, 503);
+        assert_eq!(down.selection.reason, SelectionReason::InstanceDown);
+        assert_eq!(served_by(&down), "node-b");
+
+        // Clients pinning a healthy instance skip the rotation.
+        let pinned = get(Some("node-a")).await;
+        assert_eq!(
+            (pinned.status, served_by(&pinned).as_str()),
+            (200, "node-a")
+        );
+        assert_eq!(get(Some("node-b")).await.status, 503);
+
+        assert_eq!(
+            engine.instances()["Users"]
+                .iter()
+                .map(|state| (state.name.as_str(), state.healthy))
+                .collect::<Vec<_>>(),
+            [("node-a", true), ("node-b", false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth_challenges_missing_or_wrong_credentials() {
+        let engine = RuleEngine::new(vec![Endpoint {
+            name: "Admin".to_string(),
+            method: "GET".to_string(),
+            path: "/admin".to_string(),
+            basic_auth: Some(BasicAuth {
+                username: "admin".to_string(),
+                password: "secret".to_string(),
+                realm: "backoffice".to_string(),
+                status: 401,
+                body: None,
+            }),
+            responses: vec![Response {
+                status: 200,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]);
+        let request = |authorization: Option<&str>| {
+            let headers: HashMap<String, String> = authorization
+                .map(|value| HashMap::from([("authorization".to_string(), value.to_string())]))
+                .unwrap_or_default();
+            let engine = engine.clone();
+            async move {
+                engine
+                    .execute("GET", "/admin", "", &headers, None, "127.0.0.1")
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // admin:secret
+        assert_eq!(request(Some("Basic YWRtaW46c2VjcmV0")).await.status, 200);
+
+        // admin:wrong, and no header at all
+        for authorization in [Some("Basic YWRtaW46d3Jvbmc="), None] {
+            let response = request(authorization).await;
+            assert_eq!(response.status, 401);
+            assert_eq!(response.selection.reason, SelectionReason::Unauthorized);
+            assert_eq!(
+                response.headers.get("WWW-Authenticate"),
+                Some(&r#"Basic realm="backoffice", charset="UTF-8""#.to_string())
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_endpoint_timeout() {
+        use crate::config::types::Delay;
+
+        let endpoint = |delay: &str| Endpoint {
+            name: "Slow".to_string(),
+            method: "GET".to_string(),
+            path: "/slow".to_string(),
+            timeout: Some(EndpointTimeout {
+                after: "2s".to_string().try_into().unwrap(),
+                status: 504,
+                body: None,
+            }),
+            responses: vec![Response {
+                status: 200,
+                delay: Some(Delay::Fixed(delay.to_string())),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let engine = RuleEngine::new(vec![endpoint("10s")]);
+        let response = engine
+            .execute("GET", "/slow", "", &HashMap::new(), None, "127.0.0.1")
+            .await
+            .unwrap();
+        assert_eq!(response.status, 504);
+        assert_eq!(response.selection.reason, SelectionReason::Timeout);
+        assert_eq!(response.delay, std::time::Duration::from_secs(2));
+        assert_eq!(
+            response.body.as_deref(),
+            Some(r#"{"error": "Endpoint timed out after 2s"}"#)
+        );
+
+        let engine = RuleEngine::new(vec![endpoint("1s")]);
+        let response = engine
+            .execute("GET", "/slow", "", &HashMap::new(), None, "127.0.0.1")
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_metric_labels() {
+        let metric = EndpointMetric {
+            name: "orders_created_total".to_string(),
+            kind: crate::config::MetricKind::Counter,
+            increment: 1.0,
+            labels: HashMap::from([
+                ("region".to_string(), "{{query.region}}".to_string()),
+                ("status".to_string(), "{{status}}".to_string()),
+                ("customer".to_string(), "{{id}}".to_string()),
+            ]),
+        };
+        let endpoint = Endpoint {
+            name: "Orders".to_string(),
+            metrics: vec![metric],
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![endpoint.clone()]);
+        let context = ExecutionContext {
+            method: "POST".to_string(),
+            path: "/customers/7/orders".to_string(),
+            query: "region=eu".to_string(),
+            headers: HashMap::new(),
+            client_ip: "127.0.0.1".to_string(),
+            path_params: HashMap::from([("id".to_string(), "7".to_string())]),
+            body: None,
+        };
+        let response = RuleResponse {
+            status: 201,
+            body: None,
+            headers: HeaderList::new(),
+            fault: None,
+            transform: None,
+            signing: None,
+            selection: ResponseSelection {
+                endpoint: "Orders".to_string(),
+                route: "/customers/:id/orders".to_string(),
+                response_index: Some(0),
+                reason: SelectionReason::Unconditional,
+            },
+            delay: std::time::Duration::ZERO,
+        };
+
+        assert_eq!(
+            engine.metric_labels(&endpoint, 0, &context, &response),
+            vec![
+                ("customer".to_string(), "7".to_string()),
+                ("region".to_string(), "eu".to_string()),
+                ("status".to_string(), "201".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_match_selects_endpoint() {
+        let endpoint = |name: &str, status: u16, body_match: Option<BodyMatch>| Endpoint {
+            name: name.to_string(),
+            method: "POST".to_string(),
+            path: "/payments".to_string(),
+            body_match,
+            responses: vec![Response {
+                status,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![
+            endpoint(
+                "Card",
+                201,
+                Some(BodyMatch {
+                    partial: Some(serde_json::json!({"method": "card"})),
+                    ..Default::default()
+                }),
+            ),
+            endpoint(
+                "Refund",
+                202,
+                Some(BodyMatch {
+                    exact: Some(serde_json::json!({"refund": true})),
+                    ..Default::default()
+                }),
+            ),
+        ]);
+        let headers = HashMap::new();
+        let execute = |body: &'static str| {
+            engine.execute("POST", "/payments", "", &headers, Some(body), "127.0.0.1")
+        };
+
+        let card = execute(r#"{"method": "card", "amount": 10}"#)
+            .await
+            .unwrap();
+        assert_eq!(card.selection.endpoint, "Card");
+        let refund = execute(r#"{"refund": true}"#).await.unwrap();
+        assert_eq!(refund.status, 202);
+        assert!(matches!(
+            execute(r#"{"method": "cash"}"#).await,
+            Err(RuleError::NoMatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_json_path_matchers_select_endpoint_and_response() {
+        let json_path = |expression: &str| RequestMatcher {
+            json_path: Some(expression.to_string()),
+            ..Default::default()
+        };
+        let engine = RuleEngine::new(vec![
+            Endpoint {
+                name: "Express".to_string(),
+                method: "POST".to_string(),
+                path: "/orders".to_string(),
+                matchers: vec![json_path(r#"$.order.type == "express""#)],
+                responses: vec![
+                    Response {
+                        status: 202,
+                        matchers: vec![json_path("$.order.total > 100")],
+                        ..Default::default()
+                    },
+                    Response {
+                        status: 201,
+                        default: true,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+            Endpoint {
+                name: "Standard".to_string(),
+                method: "POST".to_string(),
+                path: "/orders".to_string(),
+                responses: vec![Response {
+                    status: 200,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ]);
+        let headers = HashMap::new();
+        let status = |body: &'static str| {
+            let engine = &engine;
+            let headers = &headers;
+            async move {
+                let response = engine
+                    .execute("POST", "/orders", "", headers, Some(body), "127.0.0.1")
+                    .await
+                    .unwrap();
+                (response.selection.endpoint, response.status)
+            }
+        };
+
+        assert_eq!(
+            status(r#"{"order": {"type": "express", "total": 150}}"#).await,
+            ("Express".to_string(), 202)
+        );
+        assert_eq!(
+            status(r#"{"order": {"type": "express", "total": 20}}"#).await,
+            ("Express".to_string(), 201)
+        );
+        assert_eq!(
+            status(r#"{"order": {"type": "standard"}}"#).await,
+            ("Standard".to_string(), 200)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_captured_values_feed_later_endpoints() {
+        let engine = RuleEngine::new(vec![
+            Endpoint {
+                name: "Create order".to_string(),
+                method: "POST".to_string(),
+                path: "/orders".to_string(),
+                capture: vec![
+                    crate::config::Capture {
+                        slot: "order-{{body.id}}".to_string(),
+                        json_path: None,
+                        header: None,
+                        path_param: None,
+                        query: None,
+                    },
+                    crate::config::Capture {
+                        slot: "last_order".to_string(),
+                        json_path: Some("$.id".to_string()),
+                        header: None,
+                        path_param: None,
+                        query: None,
+                    },
+                ],
+                responses: vec![Response {
+                    status: 201,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            Endpoint {
+                name: "Get order".to_string(),
+                method: "GET".to_string(),
+                path: "/orders/:id".to_string(),
+                matchers: vec![RequestMatcher {
+                    captured: Some("order-{{id}}".to_string()),
+                    ..Default::default()
+                }],
+                responses: vec![Response {
+                    status: 200,
+                    body: Some(r#"{{captured "order-" id}}"#.to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            Endpoint {
+                name: "Unknown order".to_string(),
+                method: "GET".to_string(),
+                path: "/orders/:id".to_string(),
+                responses: vec![Response {
+                    status: 404,
+                    body: Some("last: {{captured.last_order}}".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ]);
+        let headers = HashMap::new();
+        let get = |path: &'static str| {
+            let engine = &engine;
+            let headers = &headers;
+            async move {
+                let response = engine
+                    .execute("GET", path, "", headers, None, "127.0.0.1")
+                    .await
+                    .unwrap();
+                (response.status, response.body.unwrap_or_default())
+            }
+        };
+
+        assert_eq!(
+            get("/orders/7").await,
+            (404, "last: {{captured.last_order}}".to_string())
+        );
+
+        let order = r#"{"id":7,"total":12.5}"#;
+        engine
+            .execute("POST", "/orders", "", &headers, Some(order), "127.0.0.1")
+            .await
+            .unwrap();
+
+        assert_eq!(get("/orders/7").await, (200, order.to_string()));
+        assert_eq!(get("/orders/8").await, (404, "last: 7".to_string()));
+    }
 }
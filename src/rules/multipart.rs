@@ -0,0 +1,179 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing of `multipart/form-data` request bodies for the `multipart` matcher and
+//! the `{{multipart.<field>}}` template variables.
+//!
+//! Bodies reach the engine as text, so binary file contents are decoded lossily:
+//! names, filenames and content types are exact, while the `size` of a binary
+//! upload counts its decoded text.
+
+/// One part of a multipart body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub name: String,
+    /// Set for file uploads.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub value: String,
+}
+
+/// The `boundary` of a `multipart/form-data` media type, if it is one.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    let media_type = params.next()?.trim();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+            .filter(|boundary| !boundary.is_empty())
+    })
+}
+
+/// The parts of `body`, delimited by `boundary`. Parts without a `name` in their
+/// `Content-Disposition` are skipped, and parsing stops at the closing delimiter.
+pub fn parse(boundary: &str, body: &str) -> Vec<Part> {
+    let delimiter = format!("--{}", boundary);
+    let mut parts = Vec::new();
+
+    // The preamble before the first delimiter is ignored.
+    for section in body.split(delimiter.as_str()).skip(1) {
+        if section.starts_with("--") {
+            break;
+        }
+        let section = section
+            .strip_prefix("\r\n")
+            .or_else(|| section.strip_prefix('\n'))
+            .unwrap_or(section);
+        let Some((head, value)) = section
+            .split_once("\r\n\r\n")
+            .or_else(|| section.split_once("\n\n"))
+        else {
+            continue;
+        };
+        let value = value
+            .strip_suffix("\r\n")
+            .or_else(|| value.strip_suffix('\n'))
+            .unwrap_or(value);
+
+        let mut name = None;
+        let mut filename = None;
+        let mut content_type = None;
+        for line in head.lines() {
+            let Some((header, header_value)) = line.split_once(':') else {
+                continue;
+            };
+            if header.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in header_value.split(';').skip(1) {
+                    match param.split_once('=') {
+                        Some((key, v)) if key.trim().eq_ignore_ascii_case("name") => {
+                            name = Some(unquote(v));
+                        }
+                        Some((key, v)) if key.trim().eq_ignore_ascii_case("filename") => {
+                            filename = Some(unquote(v));
+                        }
+                        _ => {}
+                    }
+                }
+            } else if header.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(header_value.trim().to_string());
+            }
+        }
+
+        if let Some(name) = name {
+            parts.push(Part {
+                name,
+                filename,
+                content_type,
+                value: value.to_string(),
+            });
+        }
+    }
+
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .map(|v| v.replace("\\\"", "\""))
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Summer trip\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach \\\"1\\\".png\"\r\n\
+        Content-Type: image/png\r\n\
+        \r\n\
+        PNG...\r\n\
+        data\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data\r\n\
+        \r\n\
+        nameless\r\n\
+        --XyZ--\r\n\
+        epilogue";
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(boundary("multipart/form-data; boundary=XyZ"), Some("XyZ"));
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\""),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/form-data"), None);
+        assert_eq!(boundary("multipart/mixed; boundary=XyZ"), None);
+        assert_eq!(boundary("application/json"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let parts = parse("XyZ", BODY);
+
+        assert_eq!(
+            parts,
+            vec![
+                Part {
+                    name: "title".to_string(),
+                    filename: None,
+                    content_type: None,
+                    value: "Summer trip".to_string(),
+                },
+                Part {
+                    name: "photo".to_string(),
+                    filename: Some("beach \"1\".png".to_string()),
+                    content_type: Some("image/png".to_string()),
+                    value: "PNG...\r\ndata".to_string(),
+                },
+            ]
+        );
+        assert!(parse("other", BODY).is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Opaque pagination cursors for endpoints with `pagination:`.
+//!
+//! Cursors are issued once per endpoint and offset and remembered, so every client
+//! paging through a list sees the same cursors and only cursors this instance handed
+//! out are accepted.
+
+use crate::config::Pagination;
+use dashmap::DashMap;
+use std::sync::Arc;
+
+/// The page a request asked for, exposed to templates as `{{page.*}}` and
+/// `{{next_cursor}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub offset: u64,
+    pub size: u64,
+    pub number: u64,
+    pub total: u64,
+    pub next_cursor: Option<String>,
+}
+
+impl Page {
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{{page.offset}}", &self.offset.to_string())
+            .replace("{{page.size}}", &self.size.to_string())
+            .replace("{{page.number}}", &self.number.to_string())
+            .replace("{{page.total}}", &self.total.to_string())
+            .replace("{{page.has_more}}", &self.next_cursor.is_some().to_string())
+            .replace("{{next_cursor}}", self.next_cursor.as_deref().unwrap_or(""))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CursorStore {
+    by_position: Arc<DashMap<(String, u64), String>>,
+    by_cursor: Arc<DashMap<String, (String, u64)>>,
+}
+
+impl CursorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the page for `cursor` (the first page when `None`). Returns `None` for
+    /// a cursor that was not issued for this endpoint.
+    pub fn page(
+        &self,
+        endpoint: &str,
+        pagination: &Pagination,
+        cursor: Option<&str>,
+    ) -> Option<Page> {
+        let offset = match cursor {
+            None | Some("") => 0,
+            Some(cursor) => {
+                let (owner, offset) = self.by_cursor.get(cursor)?.value().clone();
+                if owner != endpoint {
+                    return None;
+                }
+                offset
+            }
+        };
+
+        let page_size = pagination.page_size.max(1);
+        let size = page_size.min(pagination.total.saturating_sub(offset));
+        let next_offset = offset + page_size;
+        let next_cursor =
+            (next_offset < pagination.total).then(|| self.issue(endpoint, next_offset));
+
+        Some(Page {
+            offset,
+            size,
+            number: offset / page_size + 1,
+            total: pagination.total,
+            next_cursor,
+        })
+    }
+
+    fn issue(&self, endpoint: &str, offset: u64) -> String {
+        self.by_position
+            .entry((endpoint.to_string(), offset))
+            .or_insert_with(|| {
+                let cursor = uuid::Uuid::new_v4().simple().to_string();
+                self.by_cursor
+                    .insert(cursor.clone(), (endpoint.to_string(), offset));
+                cursor
+            })
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pagination() -> Pagination {
+        Pagination {
+            page_size: 10,
+            total: 25,
+            cursor_param: "cursor".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_iterate_pages() {
+        let store = CursorStore::new();
+
+        let first = store.page("Items", &pagination(), None).unwrap();
+        assert_eq!((first.offset, first.size, first.number), (0, 10, 1));
+        let cursor = first.next_cursor.unwrap();
+
+        let second = store.page("Items", &pagination(), Some(&cursor)).unwrap();
+        assert_eq!((second.offset, second.size, second.number), (10, 10, 2));
+
+        let third = store
+            .page("Items", &pagination(), second.next_cursor.as_deref())
+            .unwrap();
+        assert_eq!((third.offset, third.size, third.number), (20, 5, 3));
+        assert!(third.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_cursors_are_stable() {
+        let store = CursorStore::new();
+        let a = store.page("Items", &pagination(), None).unwrap();
+        let b = store.page("Items", &pagination(), None).unwrap();
+        assert_eq!(a.next_cursor, b.next_cursor);
+    }
+
+    #[test]
+    fn test_unknown_or_foreign_cursor_rejected() {
+        let store = CursorStore::new();
+        assert!(store.page("Items", &pagination(), Some("bogus")).is_none());
+
+        let cursor = store
+            .page("Other", &pagination(), None)
+            .unwrap()
+            .next_cursor
+            .unwrap();
+        assert!(store.page("Items", &pagination(), Some(&cursor)).is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let page = Page {
+            offset: 10,
+            size: 10,
+            number: 2,
+            total: 25,
+            next_cursor: Some("abc".to_string()),
+        };
+        assert_eq!(
+            page.render("{{page.number}}/{{page.total}} {{next_cursor}} {{page.has_more}}"),
+            "2/25 abc true"
+        );
+    }
+}
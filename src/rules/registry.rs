@@ -0,0 +1,57 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Named extension points embedders register before loading a configuration, such
+//! as [`crate::rules::custom_matchers`] and custom transform steps.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// Entries by name, usable as a `static`.
+pub struct Registry<T: ?Sized> {
+    entries: RwLock<BTreeMap<String, Arc<T>>>,
+}
+
+impl<T: ?Sized> Registry<T> {
+    pub const fn new() -> Self {
+        Self {
+            entries: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Registers `entry` under `name`, replacing any entry registered before.
+    pub fn insert(&self, name: impl Into<String>, entry: Arc<T>) {
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.into(), entry);
+    }
+
+    /// The entry registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<Arc<T>> {
+        self.entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+}
+
+impl<T: ?Sized> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
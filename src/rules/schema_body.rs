@@ -0,0 +1,653 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Response bodies synthesized from a JSON Schema set as `body_schema` on a response,
+//! a fresh random instance per request:
+//!
+//! ```yaml
+//! body_schema:
+//!   type: object
+//!   required: [id, email]
+//!   properties:
+//!     id: {type: integer, minimum: 1}
+//!     email: {type: string, format: email}
+//!     tags: {type: array, items: {enum: [new, vip]}, maxItems: 3}
+//! ```
+//!
+//! A `$ref` points inside the schema (`#/$defs/Address`) or, with a file before the
+//! `#`, into a JSON or YAML document such as an OpenAPI spec
+//! (`specs/api.yaml#/components/schemas/User`), whose own local `$ref`s resolve within
+//! that document. Every declared property is generated until objects nest
+//! [`MAX_DEPTH`] levels deep, below which only required ones are. `pattern` is not
+//! honored, so constrained strings are better described with `enum` or `const`.
+
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use dashmap::DashMap;
+use fake::faker::{internet, lorem};
+use fake::Fake;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Nesting depth past which optional properties are left out and arrays get their
+/// minimum number of items, so recursive schemas such as trees stay finite.
+pub const MAX_DEPTH: usize = 6;
+
+/// Nesting depth at which generation gives up on a schema that keeps requiring itself.
+const DEPTH_LIMIT: usize = 64;
+
+/// Keywords whose values are data rather than subschemas.
+const DATA_KEYWORDS: &[&str] = &["const", "enum", "default", "example", "examples"];
+
+/// Documents referenced by `$ref`, loaded on first use and kept for the life of the
+/// engine.
+#[derive(Clone, Default)]
+pub struct SchemaDocuments {
+    documents: Arc<DashMap<String, Arc<Value>>>,
+}
+
+impl SchemaDocuments {
+    pub fn get(&self, path: &str) -> anyhow::Result<Arc<Value>> {
+        if let Some(document) = self.documents.get(path) {
+            return Ok(document.clone());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read schema document {:?}", path))?;
+        // YAML is a superset of JSON, so this reads both.
+        let document: Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("Invalid schema document {:?}", path))?;
+        let document = Arc::new(document);
+        self.documents.insert(path.to_string(), document.clone());
+        Ok(document)
+    }
+
+    /// Generates a random instance of `schema`, drawing from `rng` so a seeded
+    /// request generates the same instance every time.
+    pub fn generate(&self, schema: &Value, rng: &mut impl Rng) -> anyhow::Result<Value> {
+        self.value(schema, schema, 0, rng)
+    }
+
+    /// Resolves every `$ref` reachable from `schema` and generates one instance, so
+    /// broken references and contradictory bounds are reported at load time.
+    pub fn check(&self, schema: &Value) -> anyhow::Result<()> {
+        self.check_refs(schema, schema, "", &mut HashSet::new())?;
+        self.generate(schema, &mut rand::thread_rng()).map(drop)
+    }
+
+    /// Walks `schema` inside the document `root`, read from the file `base` (empty for
+    /// the inline schema), following each `$ref` once.
+    fn check_refs(
+        &self,
+        schema: &Value,
+        root: &Value,
+        base: &str,
+        seen: &mut HashSet<String>,
+    ) -> anyhow::Result<()> {
+        match schema {
+            Value::Object(object) => {
+                if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+                    let base = match split_ref(reference).0 {
+                        "" => base,
+                        path => path,
+                    };
+                    if seen.insert(format!("{}#{}", base, split_ref(reference).1)) {
+                        let external = self.external(reference)?;
+                        let document = external.as_deref().unwrap_or(root);
+                        self.check_refs(target(document, reference)?, document, base, seen)?;
+                    }
+                }
+                for (keyword, value) in object {
+                    if !DATA_KEYWORDS.contains(&keyword.as_str()) {
+                        self.check_refs(value, root, base, seen)?;
+                    }
+                }
+                Ok(())
+            }
+            Value::Array(items) => items
+                .iter()
+                .try_for_each(|item| self.check_refs(item, root, base, seen)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The file `reference` points into, or `None` for a reference within the
+    /// current document.
+    fn external(&self, reference: &str) -> anyhow::Result<Option<Arc<Value>>> {
+        match split_ref(reference).0 {
+            "" => Ok(None),
+            path => self.get(path).map(Some),
+        }
+    }
+
+    fn value(
+        &self,
+        schema: &Value,
+        root: &Value,
+        depth: usize,
+        rng: &mut impl Rng,
+    ) -> anyhow::Result<Value> {
+        if depth >= DEPTH_LIMIT {
+            anyhow::bail!("Schema nests more than {} levels deep", DEPTH_LIMIT);
+        }
+        let object = match schema {
+            Value::Object(object) => object,
+            Value::Bool(true) => return Ok(Value::from(word(rng))),
+            Value::Bool(false) => anyhow::bail!("Schema `false` has no valid instance"),
+            other => anyhow::bail!("Invalid schema: {}", other),
+        };
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            let external = self.external(reference)?;
+            let document = external.as_deref().unwrap_or(root);
+            return self.value(target(document, reference)?, document, depth + 1, rng);
+        }
+        if let Some(constant) = object.get("const") {
+            return Ok(constant.clone());
+        }
+        if let Some(options) = object.get("enum").and_then(Value::as_array) {
+            return options
+                .choose(rng)
+                .cloned()
+                .context("Schema has an empty enum");
+        }
+
+        // What the schema declares itself comes first, so its own properties win over
+        // those the combinators next to it contribute.
+        let mut parts = Vec::new();
+        if let Some(kind) = schema_type(object, rng) {
+            parts.push(self.typed(kind, object, root, depth, rng)?);
+        }
+        if let Some(all) = object.get("allOf").and_then(Value::as_array) {
+            for schema in all {
+                parts.push(self.value(schema, root, depth + 1, rng)?);
+            }
+        }
+        for keyword in ["oneOf", "anyOf"] {
+            if let Some(options) = object.get(keyword).and_then(Value::as_array) {
+                let schema = options
+                    .choose(rng)
+                    .with_context(|| format!("Schema has an empty {}", keyword))?;
+                parts.push(self.value(schema, root, depth + 1, rng)?);
+            }
+        }
+        if parts.is_empty() {
+            parts.push(Value::from(word(rng)));
+        }
+
+        Ok(merge(parts))
+    }
+
+    fn typed(
+        &self,
+        kind: &str,
+        object: &Map<String, Value>,
+        root: &Value,
+        depth: usize,
+        rng: &mut impl Rng,
+    ) -> anyhow::Result<Value> {
+        Ok(match kind {
+            "object" => {
+                let required: Vec<&str> = object
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map(|names| names.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+                let mut instance = Map::new();
+                if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+                    for (name, schema) in properties {
+                        if depth < MAX_DEPTH || required.contains(&name.as_str()) {
+                            instance
+                                .insert(name.clone(), self.value(schema, root, depth + 1, rng)?);
+                        }
+                    }
+                }
+                // Required names without a property schema get a placeholder, unless a
+                // combinator may declare them.
+                let combined = ["allOf", "oneOf", "anyOf"]
+                    .iter()
+                    .any(|keyword| object.contains_key(*keyword));
+                for name in required {
+                    if !combined && !instance.contains_key(name) {
+                        instance.insert(name.to_string(), Value::from(word(rng)));
+                    }
+                }
+                Value::Object(instance)
+            }
+            "array" => {
+                let min = usize_keyword(object, "minItems").unwrap_or(1);
+                let max = usize_keyword(object, "maxItems").unwrap_or(min.max(1) + 2);
+                if min > max {
+                    anyhow::bail!("Schema has minItems {} above maxItems {}", min, max);
+                }
+                let count = if depth < MAX_DEPTH {
+                    rng.gen_range(min..=max)
+                } else {
+                    min
+                };
+                let unique = object.get("uniqueItems") == Some(&Value::Bool(true));
+                let mut items = Vec::with_capacity(count);
+                match object.get("items") {
+                    // A draft 4 tuple: one schema per position.
+                    Some(Value::Array(schemas)) => {
+                        for schema in schemas {
+                            items.push(self.value(schema, root, depth + 1, rng)?);
+                        }
+                    }
+                    items_schema => {
+                        let schema = items_schema.unwrap_or(&Value::Bool(true));
+                        // Duplicates are retried a few times, as small enums may not
+                        // have enough distinct values.
+                        let mut attempts = count * 4;
+                        while items.len() < count && attempts > 0 {
+                            attempts -= 1;
+                            let item = self.value(schema, root, depth + 1, rng)?;
+                            if !unique || !items.contains(&item) {
+                                items.push(item);
+                            }
+                        }
+                    }
+                }
+                Value::Array(items)
+            }
+            "string" => Value::from(string(object, rng)),
+            "integer" => {
+                let (min, max) = bounds(object, 1.0)?;
+                let (min, max) = (min.ceil() as i64, max.floor() as i64);
+                let step = object
+                    .get("multipleOf")
+                    .and_then(Value::as_f64)
+                    .map_or(1, |step| (step as i64).max(1));
+                let (low, high) = (div_ceil(min, step), max.div_euclid(step));
+                if low > high {
+                    anyhow::bail!("Schema bounds leave no integer to generate");
+                }
+                Value::from(rng.gen_range(low..=high) * step)
+            }
+            "number" => {
+                let (min, max) = bounds(object, 0.01)?;
+                let number = match object.get("multipleOf").and_then(Value::as_f64) {
+                    Some(step) if step > 0.0 => {
+                        let (low, high) = ((min / step).ceil() as i64, (max / step).floor() as i64);
+                        if low > high {
+                            anyhow::bail!("Schema bounds leave no number to generate");
+                        }
+                        rng.gen_range(low..=high) as f64 * step
+                    }
+                    _ => {
+                        let number = (rng.gen_range(min..=max) * 100.0).round() / 100.0;
+                        if (min..=max).contains(&number) {
+                            number
+                        } else {
+                            (min + max) / 2.0
+                        }
+                    }
+                };
+                Value::from(number)
+            }
+            "boolean" => Value::from(rng.gen::<bool>()),
+            _ => Value::Null,
+        })
+    }
+}
+
+/// Splits a `$ref` into its document path, empty for a local reference, and the
+/// JSON pointer after the `#`.
+fn split_ref(reference: &str) -> (&str, &str) {
+    reference.split_once('#').unwrap_or((reference, ""))
+}
+
+/// The schema `reference` points to inside `document`.
+fn target<'a>(document: &'a Value, reference: &str) -> anyhow::Result<&'a Value> {
+    document
+        .pointer(split_ref(reference).1)
+        .with_context(|| format!("Unresolved $ref `{}`", reference))
+}
+
+/// The schema's `type`, one of them at random when it lists several (`null` only when
+/// it is the sole choice), or one inferred from the keywords present.
+fn schema_type(object: &Map<String, Value>, rng: &mut impl Rng) -> Option<&'static str> {
+    const TYPES: &[&str] = &[
+        "object", "array", "string", "integer", "number", "boolean", "null",
+    ];
+    let named = |name: &str| TYPES.iter().copied().find(|kind| *kind == name);
+
+    match object.get("type") {
+        Some(Value::String(kind)) => named(kind),
+        Some(Value::Array(kinds)) => {
+            let kinds: Vec<&str> = kinds.iter().filter_map(Value::as_str).collect();
+            let non_null: Vec<&str> = kinds.iter().copied().filter(|k| *k != "null").collect();
+            non_null
+                .choose(rng)
+                .or(kinds.first())
+                .and_then(|kind| named(kind))
+        }
+        _ => {
+            let has = |keywords: &[&str]| keywords.iter().any(|k| object.contains_key(*k));
+            if has(&["properties", "required", "additionalProperties"]) {
+                Some("object")
+            } else if has(&["items", "minItems", "maxItems"]) {
+                Some("array")
+            } else if has(&["minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum"]) {
+                Some("number")
+            } else if has(&["format", "minLength", "maxLength", "pattern"]) {
+                Some("string")
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Inclusive bounds from `minimum`/`maximum` and their exclusive forms, which are
+/// narrowed by `step`. A missing bound is taken 1000 from the other one.
+fn bounds(object: &Map<String, Value>, step: f64) -> anyhow::Result<(f64, f64)> {
+    let number = |keyword: &str| object.get(keyword).and_then(Value::as_f64);
+    // Draft 4 spells exclusive bounds as booleans next to minimum/maximum.
+    let flag = |keyword: &str| object.get(keyword) == Some(&Value::Bool(true));
+
+    let min = match (number("minimum"), number("exclusiveMinimum")) {
+        (Some(min), _) if flag("exclusiveMinimum") => Some(min + step),
+        (min, None) => min,
+        (None, Some(exclusive)) => Some(exclusive + step),
+        (Some(min), Some(exclusive)) => Some(min.max(exclusive + step)),
+    };
+    let max = match (number("maximum"), number("exclusiveMaximum")) {
+        (Some(max), _) if flag("exclusiveMaximum") => Some(max - step),
+        (max, None) => max,
+        (None, Some(exclusive)) => Some(exclusive - step),
+        (Some(max), Some(exclusive)) => Some(max.min(exclusive - step)),
+    };
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) => (min, max),
+        (Some(min), None) => (min, min + 1000.0),
+        (None, Some(max)) => (max - 1000.0, max),
+        (None, None) => (1.0, 1000.0),
+    };
+    if min > max {
+        anyhow::bail!(
+            "Schema has a minimum of {} above its maximum of {}",
+            min,
+            max
+        );
+    }
+    Ok((min, max))
+}
+
+fn usize_keyword(object: &Map<String, Value>, keyword: &str) -> Option<usize> {
+    object
+        .get(keyword)
+        .and_then(Value::as_u64)
+        .map(|n| n as usize)
+}
+
+fn div_ceil(a: i64, b: i64) -> i64 {
+    -((-a).div_euclid(b))
+}
+
+fn word(rng: &mut impl Rng) -> String {
+    lorem::en::Word().fake_with_rng(rng)
+}
+
+fn string(object: &Map<String, Value>, rng: &mut impl Rng) -> String {
+    let format = object.get("format").and_then(Value::as_str).unwrap_or("");
+    let now = Utc::now() - Duration::days(rng.gen_range(0..365));
+    match format {
+        "date-time" => return now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        "date" => return now.format("%Y-%m-%d").to_string(),
+        "time" => return now.format("%H:%M:%SZ").to_string(),
+        "email" => return internet::en::SafeEmail().fake_with_rng(rng),
+        "uuid" => {
+            return uuid::Builder::from_random_bytes(rng.gen())
+                .into_uuid()
+                .to_string()
+        }
+        "uri" | "url" => return format!("https://example.com/{}", word(rng)),
+        "hostname" => return format!("{}.example.com", word(rng)),
+        "ipv4" => return internet::en::IPv4().fake_with_rng(rng),
+        "ipv6" => return internet::en::IPv6().fake_with_rng(rng),
+        _ => {}
+    }
+
+    let min = usize_keyword(object, "minLength");
+    let max = usize_keyword(object, "maxLength");
+    if min.is_none() && max.is_none() {
+        return word(rng);
+    }
+    let min = min.unwrap_or(0);
+    let max = max.unwrap_or(min + 20).max(min);
+    let length = rng.gen_range(min..=max);
+    let mut text = String::new();
+    while text.len() < length {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(&word(rng));
+    }
+    text.truncate(length);
+    // A word cut right after a space would end the value in whitespace.
+    if text.ends_with(' ') {
+        text.pop();
+        text.push('x');
+    }
+    text
+}
+
+/// Combines the parts generated for one schema: objects are merged, keeping the
+/// first value of each key, and otherwise the last part wins.
+fn merge(parts: Vec<Value>) -> Value {
+    parts
+        .into_iter()
+        .reduce(|merged, part| match (merged, part) {
+            (Value::Object(mut merged), Value::Object(part)) => {
+                for (key, value) in part {
+                    merged.entry(key).or_insert(value);
+                }
+                Value::Object(merged)
+            }
+            (_, part) => part,
+        })
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn assert_valid(schema: &Value, instance: &Value) {
+        let validator = jsonschema::validator_for(schema).unwrap();
+        let errors: Vec<String> = validator
+            .iter_errors(instance)
+            .map(|e| e.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{} is invalid: {:?}", instance, errors);
+    }
+
+    #[test]
+    fn test_generates_valid_instances() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "email", "status"],
+            "properties": {
+                "id": {"type": "integer", "minimum": 1, "maximum": 10},
+                "email": {"type": "string", "format": "email"},
+                "status": {"enum": ["active", "disabled"]},
+                "score": {"type": "number", "exclusiveMinimum": 0, "maximum": 1},
+                "even": {"type": "integer", "multipleOf": 2, "minimum": 3, "maximum": 9},
+                "code": {"type": "string", "minLength": 3, "maxLength": 5},
+                "created": {"type": "string", "format": "date-time"},
+                "tags": {
+                    "type": "array",
+                    "items": {"enum": ["a", "b", "c"]},
+                    "minItems": 2,
+                    "maxItems": 3,
+                    "uniqueItems": true
+                },
+                "kind": {"const": "user"},
+                "note": {"type": ["string", "null"]},
+                "address": {"$ref": "#/$defs/Address"}
+            },
+            "$defs": {
+                "Address": {
+                    "type": "object",
+                    "required": ["city"],
+                    "properties": {"city": {"type": "string"}}
+                }
+            }
+        });
+        let documents = SchemaDocuments::default();
+
+        for _ in 0..50 {
+            let instance = documents
+                .generate(&schema, &mut rand::thread_rng())
+                .unwrap();
+            assert_valid(&schema, &instance);
+            assert_eq!(instance["kind"], "user");
+            assert!(instance["address"]["city"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible() {
+        use rand::SeedableRng;
+
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "email", "uuid", "words"],
+            "properties": {
+                "id": {"type": "integer"},
+                "email": {"type": "string", "format": "email"},
+                "uuid": {"type": "string", "format": "uuid"},
+                "words": {"type": "array", "items": {"type": "string"}}
+            }
+        });
+        let documents = SchemaDocuments::default();
+        let generate = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            documents.generate(&schema, &mut rng).unwrap()
+        };
+
+        assert_eq!(generate(7), generate(7));
+        assert_ne!(generate(7), generate(8));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = json!({
+            "allOf": [
+                {"type": "object", "required": ["id"], "properties": {"id": {"type": "integer"}}},
+                {"properties": {"name": {"type": "string"}}}
+            ],
+            "properties": {"extra": {"type": "boolean"}},
+            "oneOf": [{"required": ["id"]}]
+        });
+        let instance = SchemaDocuments::default()
+            .generate(&schema, &mut rand::thread_rng())
+            .unwrap();
+
+        assert!(instance["id"].is_i64());
+        assert!(instance["name"].is_string());
+        assert!(instance["extra"].is_boolean());
+    }
+
+    #[test]
+    fn test_recursive_schema_terminates() {
+        let schema = json!({
+            "$ref": "#/$defs/Node",
+            "$defs": {
+                "Node": {
+                    "type": "object",
+                    "required": ["value"],
+                    "properties": {
+                        "value": {"type": "integer"},
+                        "children": {"type": "array", "items": {"$ref": "#/$defs/Node"}}
+                    }
+                }
+            }
+        });
+        let instance = SchemaDocuments::default()
+            .generate(&schema, &mut rand::thread_rng())
+            .unwrap();
+        assert_valid(&schema, &instance);
+
+        let endless = json!({
+            "$ref": "#/$defs/Node",
+            "$defs": {"Node": {"required": ["next"], "properties": {"next": {"$ref": "#/$defs/Node"}}}}
+        });
+        assert!(SchemaDocuments::default()
+            .generate(&endless, &mut rand::thread_rng())
+            .is_err());
+    }
+
+    #[test]
+    fn test_openapi_component_reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let spec = dir.path().join("api.yaml");
+        std::fs::write(
+            &spec,
+            r##"
+openapi: 3.0.3
+components:
+  schemas:
+    User:
+      type: object
+      required: [id, address]
+      properties:
+        id: {type: string, format: uuid}
+        address: {$ref: "#/components/schemas/Address"}
+    Address:
+      type: object
+      required: [zip]
+      properties:
+        zip: {type: string, minLength: 5, maxLength: 5}
+"##,
+        )
+        .unwrap();
+        let reference = format!("{}#/components/schemas/User", spec.display());
+        let schema = json!({"$ref": reference});
+        let documents = SchemaDocuments::default();
+
+        documents.check(&schema).unwrap();
+        let instance = documents
+            .generate(&schema, &mut rand::thread_rng())
+            .unwrap();
+        assert!(uuid::Uuid::parse_str(instance["id"].as_str().unwrap()).is_ok());
+        assert_eq!(instance["address"]["zip"].as_str().unwrap().len(), 5);
+
+        let missing = json!({"$ref": format!("{}#/components/schemas/Order", spec.display())});
+        let error = documents.check(&missing).unwrap_err().to_string();
+        assert!(error.contains("Unresolved $ref"), "{}", error);
+    }
+
+    #[test]
+    fn test_check_rejects_broken_schemas() {
+        let documents = SchemaDocuments::default();
+
+        // An unresolved reference is found even in a branch generation may not take.
+        let schema = json!({"oneOf": [{"type": "string"}, {"$ref": "#/$defs/Missing"}]});
+        assert!(documents.check(&schema).is_err());
+
+        let schema = json!({"type": "integer", "minimum": 5, "maximum": 1});
+        assert!(documents.check(&schema).is_err());
+
+        assert!(documents.check(&json!({"enum": []})).is_err());
+        assert!(documents
+            .check(&json!({"$ref": "missing.yaml#/User"}))
+            .is_err());
+    }
+}
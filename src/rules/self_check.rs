@@ -0,0 +1,290 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Preflight check run by `--self-check`: every endpoint answers one synthetic request
+//! through a dry-run executor, so template, fixture and condition errors surface
+//! before a test suite starts rather than on its first request.
+
+use crate::config::Endpoint;
+use crate::rules::error::RuleError;
+use crate::rules::matcher::PATH_REGEX_PREFIX;
+use crate::rules::template;
+use crate::rules::{ExecutionContext, RuleEngine};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{[^}]*\}\}").unwrap());
+
+/// Outcome of checking one endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointCheck {
+    pub endpoint: String,
+    /// Status of the response the synthetic request got, if any.
+    pub status: Option<u16>,
+    /// Problems that would fail real requests.
+    pub errors: Vec<String>,
+    /// Things worth a look that may be fine for real requests, such as placeholders
+    /// that only a request with the right query or body fills in.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SelfCheckReport {
+    pub endpoints: Vec<EndpointCheck>,
+}
+
+impl SelfCheckReport {
+    pub fn passed(&self) -> bool {
+        self.endpoints.iter().all(|check| check.errors.is_empty())
+    }
+
+    pub fn failures(&self) -> usize {
+        self.endpoints
+            .iter()
+            .filter(|check| !check.errors.is_empty())
+            .count()
+    }
+}
+
+impl fmt::Display for SelfCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.endpoints {
+            let outcome = match (check.errors.is_empty(), check.status) {
+                (false, _) => "FAILED".to_string(),
+                (true, Some(status)) => format!("ok ({})", status),
+                (true, None) => "ok".to_string(),
+            };
+            writeln!(f, "  {:<40} {}", check.endpoint, outcome)?;
+            for error in &check.errors {
+                writeln!(f, "    error: {}", error)?;
+            }
+            for warning in &check.warnings {
+                writeln!(f, "    warning: {}", warning)?;
+            }
+        }
+        write!(
+            f,
+            "Self-check: {} of {} endpoints ready",
+            self.endpoints.len() - self.failures(),
+            self.endpoints.len()
+        )
+    }
+}
+
+impl RuleEngine {
+    /// Runs the preflight check without touching state, inboxes, gates or delays.
+    pub async fn self_check(&self) -> SelfCheckReport {
+        let executor = self.executor.clone().with_dry_run(true);
+        let mut report = SelfCheckReport::default();
+
+        for endpoint in &self.endpoints {
+            let path = sample_path(&endpoint.path);
+            let context = ExecutionContext {
+                method: endpoint.method.to_uppercase(),
+                path_params: self.matcher.extract_path_params(&endpoint.path, &path),
+                path,
+                query: String::new(),
+                headers: HashMap::new(),
+                client_ip: "127.0.0.1".to_string(),
+                body: None,
+            };
+            let mut check = EndpointCheck {
+                endpoint: endpoint.name.clone(),
+                status: None,
+                errors: Vec::new(),
+                warnings: Vec::new(),
+            };
+
+            for (index, response) in endpoint.responses.iter().enumerate() {
+                if let Some(Err(e)) = response.delay.as_ref().map(|d| d.parse_duration()) {
+                    check
+                        .errors
+                        .push(format!("responses[{}]: invalid delay: {}", index, e));
+                }
+                let templates = response
+                    .body
+                    .iter()
+                    .chain(response.headers.iter().map(|(_, value)| value));
+                for template in templates {
+                    let data = executor.template_variables(
+                        template,
+                        &context,
+                        1,
+                        Some(1),
+                        &endpoint.datasets,
+                    );
+                    let rendered = match template::render(template, &data) {
+                        Ok(rendered) => executor.fill_fixtures(&rendered, 1),
+                        Err(e) => {
+                            check
+                                .errors
+                                .push(format!("responses[{}]: invalid template: {:#}", index, e));
+                            continue;
+                        }
+                    };
+                    for placeholder in PLACEHOLDER.find_iter(&rendered) {
+                        check.warnings.push(format!(
+                            "responses[{}]: {} is not filled in by a bare request",
+                            index,
+                            placeholder.as_str()
+                        ));
+                    }
+                }
+            }
+
+            if !endpoint.responses.is_empty() {
+                self.check_execution(&executor, endpoint, &context, &mut check)
+                    .await;
+            }
+            report.endpoints.push(check);
+        }
+
+        report
+    }
+
+    async fn check_execution(
+        &self,
+        executor: &crate::rules::executor::ResponseExecutor,
+        endpoint: &Endpoint,
+        context: &ExecutionContext,
+        check: &mut EndpointCheck,
+    ) {
+        match executor.execute(endpoint, context).await {
+            Ok(response) => check.status = Some(response.status),
+            Err(e @ RuleError::NoResponse { .. }) => check.warnings.push(e.to_string()),
+            Err(e) => check.errors.push(e.to_string()),
+        }
+    }
+}
+
+/// A request path served by `pattern`, with params and wildcards filled in. Regex
+/// paths are used as written and may not match themselves.
+fn sample_path(pattern: &str) -> String {
+    if let Some(regex) = pattern.strip_prefix(PATH_REGEX_PREFIX) {
+        return regex.to_string();
+    }
+
+    pattern
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with(':') {
+                "1"
+            } else if segment == "*" {
+                "self-check"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::{Delay, Response};
+    use crate::config::HeaderList;
+
+    fn endpoint(name: &str, path: &str, responses: Vec<Response>) -> Endpoint {
+        Endpoint {
+            name: name.to_string(),
+            method: "GET".to_string(),
+            path: path.to_string(),
+            responses,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sample_path() {
+        assert_eq!(sample_path("/users/:id/orders"), "/users/1/orders");
+        assert_eq!(sample_path("/static/*"), "/static/self-check");
+        assert_eq!(sample_path("/health"), "/health");
+    }
+
+    #[tokio::test]
+    async fn test_self_check_reports_errors_and_warnings() {
+        let mut headers = HeaderList::new();
+        headers.insert("X-Filter", "{{query.filter}}");
+        let engine = RuleEngine::new(vec![
+            endpoint(
+                "User",
+                "/users/:id",
+                vec![Response {
+                    status: 200,
+                    body: Some(r#"{"id": "{{id}}"}"#.to_string()),
+                    delay: Some(Delay::Fixed("10s".to_string())),
+                    headers,
+                    ..Default::default()
+                }],
+            ),
+            endpoint(
+                "Broken",
+                "/broken",
+                vec![Response {
+                    status: 200,
+                    delay: Some(Delay::Fixed("soon".to_string())),
+                    body: Some("{{#each items}}unclosed".to_string()),
+                    ..Default::default()
+                }],
+            ),
+            endpoint(
+                "Third Call",
+                "/third",
+                vec![Response {
+                    status: 200,
+                    condition: Some("request_count > 2".to_string()),
+                    ..Default::default()
+                }],
+            ),
+        ]);
+
+        let started = std::time::Instant::now();
+        let report = engine.self_check().await;
+        // Delays are not slept through.
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        let user = &report.endpoints[0];
+        assert_eq!(user.status, Some(200));
+        assert!(user.errors.is_empty());
+        assert_eq!(
+            user.warnings,
+            vec!["responses[0]: {{query.filter}} is not filled in by a bare request"]
+        );
+
+        let broken = &report.endpoints[1];
+        assert_eq!(broken.errors.len(), 2);
+        assert!(broken.errors[0].starts_with("responses[0]: invalid delay"));
+        assert!(broken.errors[1].starts_with("responses[0]: invalid template"));
+
+        let third = &report.endpoints[2];
+        assert_eq!(third.status, None);
+        assert!(third.errors.is_empty());
+        assert_eq!(
+            third.warnings,
+            vec!["No matching response and no default response found for endpoint 'Third Call'"]
+        );
+
+        assert!(!report.passed());
+        assert_eq!(report.failures(), 1);
+        assert!(report
+            .to_string()
+            .ends_with("Self-check: 2 of 3 endpoints ready"));
+        assert_eq!(engine.usage().endpoints[0].hits, 0);
+    }
+}
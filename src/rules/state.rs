@@ -17,10 +17,12 @@
 use dashmap::DashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedMutexGuard};
 
 #[derive(Clone)]
 pub struct StateManager {
     counters: Arc<DashMap<String, CounterState>>,
+    locks: Arc<DashMap<String, Arc<Mutex<()>>>>,
     ttl: Duration,
 }
 
@@ -37,6 +39,7 @@ impl StateManager {
     pub fn with_ttl(ttl: Duration) -> Self {
         Self {
             counters: Arc::new(DashMap::new()),
+            locks: Arc::new(DashMap::new()),
             ttl,
         }
     }
@@ -57,13 +60,55 @@ impl StateManager {
         entry.count
     }
 
+    /// Increments the counter unless it last changed less than `min_interval` ago, and
+    /// returns its value either way.
+    pub fn increment_count_after(&self, key: &str, min_interval: Duration) -> u64 {
+        self.cleanup_expired();
+
+        let mut entry = self
+            .counters
+            .entry(key.to_string())
+            .or_insert_with(|| CounterState {
+                count: 0,
+                last_updated: Instant::now(),
+            });
+
+        if entry.count > 0 && entry.last_updated.elapsed() < min_interval {
+            return entry.count;
+        }
+        entry.count += 1;
+        entry.last_updated = Instant::now();
+        entry.count
+    }
+
+    /// Whether the counter under `key` changed less than `window` ago.
+    pub fn changed_within(&self, key: &str, window: Duration) -> bool {
+        self.counters
+            .get(key)
+            .is_some_and(|entry| entry.count > 0 && entry.last_updated.elapsed() < window)
+    }
+
     pub fn get_count(&self, key: &str) -> u64 {
         self.cleanup_expired();
 
         self.counters.get(key).map(|entry| entry.count).unwrap_or(0)
     }
 
+    /// Waits for exclusive use of `key`; callers queue in arrival order and hold it
+    /// until the guard is dropped.
+    pub async fn lock(&self, key: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .locks
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone();
+        lock.lock_owned().await
+    }
+
     pub fn cleanup_expired(&self) {
+        // Locks nobody holds or waits for are recreated on demand.
+        self.locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+
         let now = Instant::now();
         let expired_keys: Vec<String> = self
             .counters
@@ -102,6 +147,23 @@ mod tests {
         assert_eq!(manager.get_count("test"), 2);
     }
 
+    #[test]
+    fn test_increment_count_after_min_interval() {
+        let manager = StateManager::new();
+        let interval = Duration::from_millis(50);
+
+        assert!(!manager.changed_within("test", interval));
+        assert_eq!(manager.increment_count_after("test", interval), 1);
+        assert!(manager.changed_within("test", interval));
+        assert_eq!(manager.increment_count_after("test", interval), 1);
+
+        thread::sleep(interval);
+        assert!(!manager.changed_within("test", interval));
+        assert_eq!(manager.increment_count_after("test", interval), 2);
+        assert_eq!(manager.increment_count_after("test", interval), 2);
+        assert_eq!(manager.get_count("test"), 2);
+    }
+
     #[test]
     fn test_multiple_keys() {
         let manager = StateManager::new();
@@ -114,6 +176,23 @@ mod tests {
         assert_eq!(manager.get_count("key2"), 1);
     }
 
+    #[tokio::test]
+    async fn test_lock_is_per_key() {
+        let manager = StateManager::new();
+
+        let held = manager.lock("client-a").await;
+        // Another key is not blocked by it.
+        let _other = manager.lock("client-b").await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), manager.lock("client-a"))
+                .await
+                .is_err()
+        );
+
+        drop(held);
+        let _again = manager.lock("client-a").await;
+    }
+
     #[test]
     fn test_cleanup_expired() {
         let manager = StateManager::with_ttl(Duration::from_millis(100));
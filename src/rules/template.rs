@@ -0,0 +1,763 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Response templates rendered with Handlebars, so bodies and headers get
+//! conditionals, loops and helpers on top of the plain `{{variable}}` substitution:
+//!
+//! ```text
+//! {"items": [{{#each body.items}}{{#unless @first}},{{/unless}}{"id": {{json id}}}{{/each}}]}
+//! ```
+//!
+//! `fake.*` helpers generate realistic data, fresh on every render: `{{fake.name}}`,
+//! `{{fake.first_name}}`, `{{fake.last_name}}`, `{{fake.email}}`, `{{fake.username}}`,
+//! `{{fake.phone}}`, `{{fake.company}}`, `{{fake.city}}`, `{{fake.street}}`,
+//! `{{fake.country}}`, `{{fake.word}}`, `{{fake.sentence}}`, `{{fake.uuid}}`,
+//! `{{fake.bool}}` and `{{fake.int 1 100}}` (inclusive bounds, 0 to 100 by default).
+//!
+//! `{{captured "order-" id}}` writes the slot an endpoint `capture`d under the name
+//! its parameters spell, strings as they are and other values as JSON.
+//!
+//! `{{random_int 100 999}}` (inclusive), `{{random_float}}` (0 to 1, or
+//! `{{random_float 1 10 precision=2}}`) and `{{one_of "a" "b" "c"}}` vary a value
+//! on every render. Both kinds of helper draw from the request's RNG, so a seeded
+//! request renders the same values (see [`with_rng`]).
+//!
+//! `{{#with (find_row datasets.users "id" id)}}{{name}}{{else}}unknown{{/with}}`
+//! looks up the first row of an endpoint's dataset whose column equals a value, and
+//! `{{#each (filter_rows datasets.users "role" "admin")}}` iterates all such rows;
+//! values compare as text, so the path parameter `"42"` finds the number `42`.
+//!
+//! Output is not escaped, since most mock bodies are JSON rather than HTML; the
+//! `json` helper renders a value as JSON (quoting and escaping strings) and `html`
+//! HTML-escapes it. Expressions Handlebars cannot resolve are written back exactly
+//! as they appear, so later passes (`csv_row`, `page.*`) and the self-check still
+//! see them.
+
+use crate::config::Endpoint;
+use fake::faker::{address, company, internet, lorem, name, phone_number};
+use fake::Fake;
+use handlebars::{
+    handlebars_helper, no_escape, Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender,
+    Output, RenderContext, RenderError, ScopedJson, Template,
+};
+use once_cell::sync::Lazy;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore};
+use regex::Regex;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+static ENGINE: Lazy<Handlebars<'static>> = Lazy::new(registry);
+
+/// A Handlebars registry with the helpers every template can use.
+fn registry() -> Handlebars<'static> {
+    let mut engine = Handlebars::new();
+    engine.register_escape_fn(no_escape);
+    engine.register_helper("json", Box::new(json));
+    engine.register_helper("html", Box::new(html));
+    engine.register_helper("captured", Box::new(captured));
+    engine.register_helper("helperMissing", Box::new(write_back));
+    for (helper, generate) in FAKERS.iter().chain(RANDOM).chain(ROWS) {
+        engine.register_helper(helper, Box::new(GeneratorHelper(*generate)));
+    }
+    engine
+}
+
+/// The response body and header templates and the metric label templates of an
+/// engine's endpoints, compiled once when the engine is built instead of on every
+/// request, and named by endpoint and index (see [`CompiledTemplates::body`],
+/// [`CompiledTemplates::header`] and [`CompiledTemplates::label`]).
+#[derive(Clone)]
+pub struct CompiledTemplates {
+    registry: Arc<Handlebars<'static>>,
+}
+
+impl Default for CompiledTemplates {
+    fn default() -> Self {
+        Self {
+            registry: Arc::new(registry()),
+        }
+    }
+}
+
+impl CompiledTemplates {
+    /// Compiles the templates of `endpoints`. Plain text needs no compiling, and a
+    /// template Handlebars cannot parse is left to [`render`] to report.
+    pub fn new(endpoints: &[Endpoint]) -> Self {
+        let mut registry = registry();
+        for endpoint in endpoints {
+            let responses = endpoint
+                .responses
+                .iter()
+                .enumerate()
+                .flat_map(|(index, response)| {
+                    let headers =
+                        response
+                            .headers
+                            .iter()
+                            .enumerate()
+                            .map(move |(header, (_, value))| {
+                                (Self::header(&endpoint.name, index, header), value)
+                            });
+                    response
+                        .body
+                        .iter()
+                        .map(move |body| (Self::body(&endpoint.name, index), body))
+                        .chain(headers)
+                });
+            let labels = endpoint
+                .metrics
+                .iter()
+                .enumerate()
+                .flat_map(|(index, metric)| {
+                    metric
+                        .labels
+                        .iter()
+                        .map(move |(key, source)| (Self::label(&endpoint.name, index, key), source))
+                });
+            for (name, source) in responses.chain(labels) {
+                if !source.contains("{{") {
+                    continue;
+                }
+                match compile(source) {
+                    Ok(template) => registry.register_template(&name, template),
+                    Err(e) => tracing::warn!(
+                        endpoint = %endpoint.name,
+                        template = %name,
+                        error = %e,
+                        "Invalid response template"
+                    ),
+                }
+            }
+        }
+        Self {
+            registry: Arc::new(registry),
+        }
+    }
+
+    /// The name of the body template of an endpoint's response.
+    pub fn body(endpoint: &str, response: usize) -> String {
+        format!("{}#{}#body", endpoint, response)
+    }
+
+    /// The name of the template of the `header`th header of an endpoint's response.
+    pub fn header(endpoint: &str, response: usize, header: usize) -> String {
+        format!("{}#{}#header#{}", endpoint, response, header)
+    }
+
+    /// The name of the template of the label `key` of an endpoint's `metric`th metric.
+    pub fn label(endpoint: &str, metric: usize, key: &str) -> String {
+        format!("{}#metric#{}#label#{}", endpoint, metric, key)
+    }
+
+    /// Renders the template compiled as `name`, or `source` like [`render_capped`]
+    /// when it was not compiled, stopping at `cap` bytes. `data` moves into the
+    /// render context rather than being copied into it.
+    pub fn render(
+        &self,
+        name: Option<&str>,
+        source: &str,
+        data: Value,
+        cap: usize,
+    ) -> anyhow::Result<String> {
+        let context = Context::from(data);
+        match name.filter(|name| self.registry.has_template(name)) {
+            Some(name) => write_capped(cap, |output| {
+                self.registry
+                    .render_with_context_to_write(name, &context, output)
+            }),
+            None => render_in_context(source, &context, cap),
+        }
+    }
+}
+
+type Generator = fn(&Helper<'_>, &mut dyn RngCore) -> Value;
+
+static FAKERS: &[(&str, Generator)] = &[
+    ("fake_name", |_, rng| {
+        Value::from(name::en::Name().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_first_name", |_, rng| {
+        Value::from(name::en::FirstName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_last_name", |_, rng| {
+        Value::from(name::en::LastName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_email", |_, rng| {
+        Value::from(internet::en::SafeEmail().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_username", |_, rng| {
+        Value::from(internet::en::Username().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_phone", |_, rng| {
+        Value::from(phone_number::en::PhoneNumber().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_company", |_, rng| {
+        Value::from(company::en::CompanyName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_city", |_, rng| {
+        Value::from(address::en::CityName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_street", |_, rng| {
+        Value::from(address::en::StreetName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_country", |_, rng| {
+        Value::from(address::en::CountryName().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_word", |_, rng| {
+        Value::from(lorem::en::Word().fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_sentence", |_, rng| {
+        Value::from(lorem::en::Sentence(4..10).fake_with_rng::<String, _>(rng))
+    }),
+    ("fake_uuid", |_, rng| {
+        Value::from(
+            uuid::Builder::from_random_bytes(rng.gen())
+                .into_uuid()
+                .to_string(),
+        )
+    }),
+    ("fake_bool", |_, rng| Value::from(rng.gen::<bool>())),
+    ("fake_int", fake_int),
+];
+
+static RANDOM: &[(&str, Generator)] = &[
+    ("random_int", fake_int),
+    ("random_float", random_float),
+    ("one_of", one_of),
+];
+
+static ROWS: &[(&str, Generator)] = &[("find_row", find_row), ("filter_rows", filter_rows)];
+
+/// A `fake.*`, random value or row lookup helper. It returns a value rather than writing output,
+/// so it also works as a subexpression such as `{{json (fake.name)}}`.
+struct GeneratorHelper(Generator);
+
+impl HelperDef for GeneratorHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        Ok(ScopedJson::Derived(draw(|rng| (self.0)(h, rng))))
+    }
+}
+
+thread_local! {
+    /// The RNG of the request whose templates are rendering, see [`with_rng`].
+    static REQUEST_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Runs `render` with the `fake.*` and random helpers drawing from `rng` instead of
+/// the thread's RNG, so a seeded request renders the same values every time.
+pub fn with_rng<T>(rng: &mut StdRng, render: impl FnOnce() -> T) -> T {
+    let _restore = RestoreRng {
+        previous: REQUEST_RNG.replace(Some(rng.clone())),
+        rng,
+    };
+    render()
+}
+
+/// Hands the advanced RNG back to [`with_rng`]'s caller, even if rendering panics.
+struct RestoreRng<'a> {
+    rng: &'a mut StdRng,
+    previous: Option<StdRng>,
+}
+
+impl Drop for RestoreRng<'_> {
+    fn drop(&mut self) {
+        if let Some(advanced) = REQUEST_RNG.replace(self.previous.take()) {
+            *self.rng = advanced;
+        }
+    }
+}
+
+fn draw<T>(generate: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    REQUEST_RNG.with_borrow_mut(|rng| match rng {
+        Some(rng) => generate(rng),
+        None => generate(&mut rand::thread_rng()),
+    })
+}
+
+/// `{{fake.int min max}}` and `{{random_int min max}}`, inclusive; bounds given the
+/// wrong way round are swapped.
+fn fake_int(h: &Helper<'_>, rng: &mut dyn RngCore) -> Value {
+    let bound = |index: usize, default: i64| {
+        h.param(index)
+            .and_then(|param| param.value().as_i64())
+            .unwrap_or(default)
+    };
+    let (min, max) = (bound(0, 0), bound(1, 100));
+    Value::from(rng.gen_range(min.min(max)..=min.max(max)))
+}
+
+/// `{{random_float min max}}`, from `min` up to but excluding `max` (0 to 1 by
+/// default), rounded to `precision=<digits>` when given.
+fn random_float(h: &Helper<'_>, rng: &mut dyn RngCore) -> Value {
+    let bound = |index: usize, default: f64| {
+        h.param(index)
+            .and_then(|param| param.value().as_f64())
+            .unwrap_or(default)
+    };
+    let (min, max) = (bound(0, 0.0), bound(1, 1.0));
+    let (min, max) = (min.min(max), min.max(max));
+    let value = if min < max {
+        rng.gen_range(min..max)
+    } else {
+        min
+    };
+    match h.hash_get("precision").and_then(|p| p.value().as_u64()) {
+        Some(digits) => {
+            let scale = 10f64.powi(digits.min(15) as i32);
+            Value::from((value * scale).round() / scale)
+        }
+        None => Value::from(value),
+    }
+}
+
+/// `{{one_of "a" "b" "c"}}`, one of the parameters picked at random.
+fn one_of(h: &Helper<'_>, rng: &mut dyn RngCore) -> Value {
+    let params = h.params();
+    if params.is_empty() {
+        return Value::Null;
+    }
+    params[rng.gen_range(0..params.len())].value().clone()
+}
+
+/// `{{find_row rows "column" value}}`, the first row whose column is `value`, or
+/// `null` when none is.
+fn find_row(h: &Helper<'_>, _: &mut dyn RngCore) -> Value {
+    matching_rows(h).next().cloned().unwrap_or(Value::Null)
+}
+
+/// `{{filter_rows rows "column" value}}`, every row whose column is `value`.
+fn filter_rows(h: &Helper<'_>, _: &mut dyn RngCore) -> Value {
+    Value::Array(matching_rows(h).cloned().collect())
+}
+
+fn matching_rows<'a>(h: &'a Helper<'_>) -> impl Iterator<Item = &'a Value> + 'a {
+    let rows = h
+        .param(0)
+        .and_then(|param| param.value().as_array())
+        .map_or(&[][..], Vec::as_slice);
+    let column = h
+        .param(1)
+        .and_then(|param| param.value().as_str())
+        .unwrap_or_default();
+    let wanted = h.param(2).map(|param| param.value().render());
+    rows.iter().filter(move |row| {
+        row.get(column)
+            .is_some_and(|value| Some(value.render()) == wanted)
+    })
+}
+
+/// `{{multipart.<name>}}` is the part's value, while `{{multipart.<name>.filename}}`
+/// and friends are its metadata; the value lives at `multipart.<name>.value`.
+static MULTIPART_VALUE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*multipart\.([^\s.}]+)\s*\}\}").unwrap());
+
+/// Header names are case-insensitive, while the `header` variables are lowercase.
+static HEADER_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\{\{[^}]*?\bheader\.)([\w-]+)").unwrap());
+
+/// Handlebars only takes parameters after a plain helper name, so `fake.int 1 100`
+/// is rendered by the `fake_int` helper.
+static FAKE_HELPER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\{\{~?\s*|\(\s*)fake\.(\w+)").unwrap());
+
+handlebars_helper!(json: |value: Json| serde_json::to_string(value).unwrap_or_default());
+handlebars_helper!(html: |value: Json| handlebars::html_escape(&value.render()));
+
+/// `{{captured "order-" id}}` writes the captured slot named by its parameters joined
+/// together: a string as it is, anything else as JSON, and nothing for an empty slot.
+fn captured(
+    h: &Helper<'_>,
+    _: &Handlebars<'_>,
+    ctx: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let slot: String = h
+        .params()
+        .iter()
+        .map(|param| param.value().render())
+        .collect();
+    match ctx
+        .data()
+        .get("captured")
+        .and_then(|slots| slots.get(&slot))
+    {
+        Some(Value::String(text)) => out.write(text)?,
+        Some(value) => out.write(&value.to_string())?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// Renders `template` against `data`. Fails only for templates that are not valid
+/// Handlebars, such as an unclosed block.
+pub fn render(template: &str, data: &Value) -> anyhow::Result<String> {
+    render_capped(template, data, usize::MAX)
+}
+
+/// Renders like [`render`], but stops writing once the output reaches `cap` bytes,
+/// so a runaway loop cannot build an unbounded response. The output is cut at
+/// `cap` (on a character boundary); callers tell a cut output by rendering with a
+/// cap one byte above their limit.
+pub fn render_capped(template: &str, data: &Value, cap: usize) -> anyhow::Result<String> {
+    if !template.contains("{{") {
+        return Ok(template.to_string());
+    }
+    render_in_context(template, &Context::wraps(data)?, cap)
+}
+
+/// Renders `template` against an already built context, stopping at `cap` bytes.
+fn render_in_context(template: &str, context: &Context, cap: usize) -> anyhow::Result<String> {
+    if !template.contains("{{") {
+        return Ok(template.to_string());
+    }
+    let template = normalize(template);
+    write_capped(cap, |output| {
+        ENGINE.render_template_with_context_to_write(&template, context, output)
+    })
+}
+
+/// Runs `render` against a writer that takes `cap` bytes, keeping what it wrote.
+fn write_capped(
+    cap: usize,
+    render: impl FnOnce(&mut CappedWriter) -> Result<(), RenderError>,
+) -> anyhow::Result<String> {
+    let mut output = CappedWriter {
+        bytes: Vec::new(),
+        cap,
+        full: false,
+    };
+    match render(&mut output) {
+        Ok(()) => {}
+        Err(_) if output.full => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(match String::from_utf8(output.bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).unwrap_or_default()
+        }
+    })
+}
+
+/// Parses `template`, failing the way [`render`] would for invalid Handlebars.
+pub fn compile(template: &str) -> anyhow::Result<Template> {
+    Ok(Template::compile(&normalize(template))?)
+}
+
+/// Rewrites the shorthands Handlebars has no syntax for into plain expressions.
+fn normalize(template: &str) -> String {
+    let template = MULTIPART_VALUE.replace_all(template, "{{multipart.$1.value}}");
+    let template = FAKE_HELPER.replace_all(&template, "${1}fake_$2");
+    HEADER_NAME
+        .replace_all(&template, |captures: &regex::Captures<'_>| {
+            format!("{}{}", &captures[1], captures[2].to_ascii_lowercase())
+        })
+        .into_owned()
+}
+
+/// Collects rendered output up to `cap` bytes, then fails the render.
+struct CappedWriter {
+    bytes: Vec<u8>,
+    cap: usize,
+    full: bool,
+}
+
+impl std::io::Write for CappedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let room = self.cap - self.bytes.len();
+        if data.len() > room {
+            self.bytes.extend_from_slice(&data[..room]);
+            self.full = true;
+            return Err(std::io::Error::other("template output reached its cap"));
+        }
+        self.bytes.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes an unresolved expression back as `{{name param key=value}}`.
+fn write_back(
+    h: &Helper<'_>,
+    _: &Handlebars<'_>,
+    _: &Context,
+    _: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let mut expression = h.name().to_string();
+    let literal = |param: &handlebars::PathAndJson<'_>| match param.relative_path() {
+        Some(path) => path.clone(),
+        None => serde_json::to_string(param.value()).unwrap_or_default(),
+    };
+    for param in h.params() {
+        expression.push(' ');
+        expression.push_str(&literal(param));
+    }
+    for (key, param) in h.hash() {
+        expression.push_str(&format!(" {}={}", key, literal(param)));
+    }
+    out.write(&format!("{{{{{}}}}}", expression))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_variables_blocks_and_helpers() {
+        let data = json!({
+            "method": "POST",
+            "query": {"verbose": "true"},
+            "body": {"items": [{"id": 1, "name": "a\"b"}, {"id": 2, "name": "<c>"}]},
+        });
+
+        assert_eq!(render("{{method}}", &data).unwrap(), "POST");
+        assert_eq!(
+            render(r#"{{#if query.verbose}}verbose{{else}}terse{{/if}}"#, &data).unwrap(),
+            "verbose"
+        );
+        assert_eq!(
+            render(
+                "[{{#each body.items}}{{#unless @first}},{{/unless}}{{json name}}{{/each}}]",
+                &data
+            )
+            .unwrap(),
+            r#"["a\"b","<c>"]"#
+        );
+        assert_eq!(
+            render(
+                "{{body.items.[1].name}} {{html body.items.[1].name}}",
+                &data
+            )
+            .unwrap(),
+            "<c> &lt;c&gt;"
+        );
+        assert_eq!(
+            render("{{#if (eq method \"POST\")}}created{{/if}}", &data).unwrap(),
+            "created"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_expressions_are_written_back() {
+        let data = json!({"request_count": 3});
+        for template in [
+            "{{page.number}}/{{next_cursor}}",
+            r#"{{csv_row "fixtures/users.csv" request_count "email"}}"#,
+            "{{query.filter}}",
+        ] {
+            assert_eq!(render(template, &data).unwrap(), template);
+        }
+    }
+
+    #[test]
+    fn test_multipart_value_and_metadata() {
+        let data = json!({"multipart": {"photo": {"value": "bytes", "filename": "cat.png"}}});
+        assert_eq!(
+            render("{{multipart.photo}} {{multipart.photo.filename}}", &data).unwrap(),
+            "bytes cat.png"
+        );
+    }
+
+    #[test]
+    fn test_compiled_templates() {
+        use crate::config::{HeaderList, Response};
+
+        let mut headers = HeaderList::new();
+        headers.insert("X-Plain", "plain");
+        headers.insert("X-Name", "{{header.X-Name}}");
+        let endpoint = Endpoint {
+            name: "Users".to_string(),
+            responses: vec![Response {
+                body: Some("{{multipart.file}} {{fake.int 3 3}}".to_string()),
+                headers,
+                ..Default::default()
+            }],
+            metrics: vec![crate::config::EndpointMetric {
+                name: "users_total".to_string(),
+                kind: Default::default(),
+                increment: 1.0,
+                labels: [("code".to_string(), "{{status}}".to_string())].into(),
+            }],
+            ..Default::default()
+        };
+        let templates = CompiledTemplates::new(&[endpoint]);
+        let data = json!({"multipart": {"file": {"value": "a.txt"}}, "header": {"x-name": "ann"}});
+
+        let body = CompiledTemplates::body("Users", 0);
+        assert!(templates.registry.has_template(&body));
+        assert_eq!(
+            templates
+                .render(Some(&body), "", data.clone(), usize::MAX)
+                .unwrap(),
+            "a.txt 3"
+        );
+        let label = CompiledTemplates::label("Users", 0, "code");
+        assert_eq!(
+            templates
+                .render(Some(&label), "", json!({"status": 201}), usize::MAX)
+                .unwrap(),
+            "201"
+        );
+        let header = CompiledTemplates::header("Users", 0, 1);
+        assert_eq!(
+            templates
+                .render(Some(&header), "", data.clone(), 2)
+                .unwrap(),
+            "an"
+        );
+
+        // Plain text is not compiled and renders as it is.
+        let plain = CompiledTemplates::header("Users", 0, 0);
+        assert!(!templates.registry.has_template(&plain));
+        assert_eq!(
+            templates
+                .render(Some(&plain), "plain", data.clone(), usize::MAX)
+                .unwrap(),
+            "plain"
+        );
+    }
+
+    #[test]
+    fn test_render_capped_stops_at_cap() {
+        let data = json!({"items": vec![1; 10_000]});
+        let template = "{{#each items}}é{{/each}}";
+        assert_eq!(render_capped(template, &data, 5).unwrap(), "éé");
+        assert!(render_capped("{{#each items}}", &data, 5).is_err());
+        assert_eq!(
+            render_capped("short {{items.0}}", &data, 100).unwrap(),
+            "short 1"
+        );
+    }
+
+    #[test]
+    fn test_fake_helpers() {
+        let data = json!({});
+        for helper in FAKERS.iter().map(|(name, _)| name.replacen('_', ".", 1)) {
+            let rendered = render(&format!("{{{{{}}}}}", helper), &data).unwrap();
+            assert!(!rendered.is_empty(), "{} rendered nothing", helper);
+            assert!(!rendered.contains("{{"), "{} was not resolved", helper);
+        }
+
+        assert!(render("{{fake.email}}", &data).unwrap().contains('@'));
+        for _ in 0..50 {
+            let n: i64 = render("{{fake.int 5 7}}", &data).unwrap().parse().unwrap();
+            assert!((5..=7).contains(&n));
+            let n: i64 = render("{{fake.int 9 7}}", &data).unwrap().parse().unwrap();
+            assert!((7..=9).contains(&n));
+        }
+        assert_eq!(
+            render(
+                "{{#each items}}{{fake.bool}}|{{/each}}",
+                &json!({"items": [1, 2]})
+            )
+            .unwrap()
+            .matches('|')
+            .count(),
+            2
+        );
+        let n: i64 = render("{{#if true}}{{json (fake.int 2 2)}}{{/if}}", &data)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn test_random_helpers() {
+        let data = json!({"fallback": "d"});
+        for _ in 0..50 {
+            let n: i64 = render("{{random_int 100 999}}", &data)
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((100..=999).contains(&n));
+
+            let x: f64 = render("{{random_float}}", &data).unwrap().parse().unwrap();
+            assert!((0.0..1.0).contains(&x));
+            let x: f64 = render("{{random_float 10 1 precision=2}}", &data)
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!((1.0..=10.0).contains(&x));
+            assert_eq!((x * 100.0).round() / 100.0, x);
+
+            let choice = render(r#"{{one_of "a" "b" fallback}}"#, &data).unwrap();
+            assert!(["a", "b", "d"].contains(&choice.as_str()), "{}", choice);
+        }
+
+        assert_eq!(render("{{random_float 2.5 2.5}}", &data).unwrap(), "2.5");
+        assert_eq!(render("{{one_of}}", &data).unwrap(), "");
+        assert_eq!(render(r#"{{json (one_of "x")}}"#, &data).unwrap(), r#""x""#);
+    }
+
+    #[test]
+    fn test_row_helpers() {
+        let data = json!({
+            "id": "2",
+            "users": [
+                {"id": 1, "name": "Ann", "role": "admin"},
+                {"id": 2, "name": "Bob", "role": "user"},
+                {"id": 3, "name": "Cy", "role": "admin"}
+            ]
+        });
+        let find = r#"{{#with (find_row users "id" id)}}{{name}}{{else}}none{{/with}}"#;
+
+        assert_eq!(render(find, &data).unwrap(), "Bob");
+        assert_eq!(
+            render(find, &json!({"id": "9", "users": data["users"]})).unwrap(),
+            "none"
+        );
+        assert_eq!(
+            render(
+                r#"{{#each (filter_rows users "role" "admin")}}{{name}};{{/each}}"#,
+                &data
+            )
+            .unwrap(),
+            "Ann;Cy;"
+        );
+        assert_eq!(
+            render(r#"{{json (find_row missing "id" 1)}}"#, &data).unwrap(),
+            "null"
+        );
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        assert!(render("{{#if method}}unclosed", &json!({})).is_err());
+        assert_eq!(
+            render(r#"{"plain": true}"#, &json!({})).unwrap(),
+            r#"{"plain": true}"#
+        );
+    }
+}
@@ -0,0 +1,480 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Response transformer chains: cross-cutting mutations of the encoded response,
+//! configured with `transform` on an endpoint or the server and applied after the
+//! executor has produced the response, before `signing` and faults.
+//!
+//! Embedders add their own kinds with [`register`], used as
+//! `transform: [{custom: {kind: <kind>, config: ...}}]`.
+
+use crate::config::{BodyRewrite, CanonicalJson, ContentCoding, HeaderList, ResponseTransform};
+use crate::rules::registry::Registry;
+use anyhow::Context;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+/// A response as it is about to be sent, with its body already encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WireResponse {
+    pub status: u16,
+    pub headers: HeaderList,
+    pub body: Option<Vec<u8>>,
+}
+
+/// One step of a transform chain.
+pub trait ResponseTransformer: Send + Sync {
+    /// Mutates `response` to the request that carried `request_headers` (lowercase names).
+    fn transform(&self, request_headers: &HashMap<String, String>, response: &mut WireResponse);
+}
+
+/// Plain functions and closures work as transformers.
+impl<F> ResponseTransformer for F
+where
+    F: Fn(&HashMap<String, String>, &mut WireResponse) + Send + Sync,
+{
+    fn transform(&self, request_headers: &HashMap<String, String>, response: &mut WireResponse) {
+        self(request_headers, response)
+    }
+}
+
+/// Builds a transformer from the `config` of one `custom` step, rejecting invalid ones.
+pub type TransformerFactory =
+    dyn Fn(&Value) -> anyhow::Result<Box<dyn ResponseTransformer>> + Send + Sync;
+
+static REGISTRY: Registry<TransformerFactory> = Registry::new();
+
+/// Makes `factory` available as `custom: {kind: <kind>}`, replacing any factory
+/// registered under the same kind. Register before the configuration is loaded.
+pub fn register<F>(kind: impl Into<String>, factory: F)
+where
+    F: Fn(&Value) -> anyhow::Result<Box<dyn ResponseTransformer>> + Send + Sync + 'static,
+{
+    REGISTRY.insert(kind, Arc::new(factory));
+}
+
+fn factory(kind: &str) -> Option<Arc<TransformerFactory>> {
+    REGISTRY.get(kind)
+}
+
+/// Transformers applied in order.
+#[derive(Default)]
+pub struct TransformChain {
+    steps: Vec<Box<dyn ResponseTransformer>>,
+}
+
+impl TransformChain {
+    /// Builds the chain for configured steps, failing on the first invalid one.
+    pub fn new(config: &[ResponseTransform]) -> anyhow::Result<Self> {
+        let mut chain = Self::default();
+        for (index, step) in config.iter().enumerate() {
+            chain.steps.push(
+                build_step(step).with_context(|| format!("Invalid transform step {}", index))?,
+            );
+        }
+        Ok(chain)
+    }
+
+    /// Appends a transformer built in code.
+    pub fn push(&mut self, step: impl ResponseTransformer + 'static) {
+        self.steps.push(Box::new(step));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn apply(&self, request_headers: &HashMap<String, String>, response: &mut WireResponse) {
+        for step in &self.steps {
+            step.transform(request_headers, response);
+        }
+    }
+}
+
+impl fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransformChain")
+            .field("steps", &self.steps.len())
+            .finish()
+    }
+}
+
+fn build_step(step: &ResponseTransform) -> anyhow::Result<Box<dyn ResponseTransformer>> {
+    let kinds = [
+        step.headers.is_some(),
+        step.replace.is_some(),
+        step.canonical_json.is_some(),
+        step.compress.is_some(),
+        step.custom.is_some(),
+    ];
+    if kinds.iter().filter(|set| **set).count() != 1 {
+        anyhow::bail!(
+            "Transform step must set exactly one of: headers, replace, canonical_json, compress, \
+             custom"
+        );
+    }
+
+    if let Some(headers) = &step.headers {
+        return Ok(Box::new(InjectHeaders(headers.clone())));
+    }
+    if let Some(rewrite) = &step.replace {
+        return Ok(Box::new(RewriteBody::new(rewrite)?));
+    }
+    if let Some(canonical) = step.canonical_json {
+        return Ok(Box::new(Canonicalize(canonical)));
+    }
+    if let Some(codings) = &step.compress {
+        if codings.is_empty() {
+            anyhow::bail!("`compress` needs at least one coding");
+        }
+        return Ok(Box::new(Compress(codings.clone())));
+    }
+
+    let custom = step.custom.as_ref().expect("one kind is set");
+    let factory = factory(&custom.kind)
+        .with_context(|| format!("Unknown custom transformer kind `{}`", custom.kind))?;
+    factory(&custom.config)
+        .with_context(|| format!("Invalid config for custom transformer `{}`", custom.kind))
+}
+
+struct InjectHeaders(HeaderList);
+
+impl ResponseTransformer for InjectHeaders {
+    fn transform(&self, _: &HashMap<String, String>, response: &mut WireResponse) {
+        // The first value of each name replaces the response's; repeats are appended.
+        let mut seen = HashSet::new();
+        for (name, value) in self.0.iter() {
+            if seen.insert(name.to_ascii_lowercase()) {
+                response.headers.insert(name.clone(), value.clone());
+            } else {
+                response.headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+struct RewriteBody {
+    pattern: Regex,
+    with: String,
+}
+
+impl RewriteBody {
+    fn new(config: &BodyRewrite) -> anyhow::Result<Self> {
+        let pattern = Regex::new(&config.pattern)
+            .with_context(|| format!("Invalid replace pattern: {}", config.pattern))?;
+        Ok(Self {
+            pattern,
+            with: config.with.clone(),
+        })
+    }
+}
+
+impl ResponseTransformer for RewriteBody {
+    fn transform(&self, _: &HashMap<String, String>, response: &mut WireResponse) {
+        // Binary bodies are left alone.
+        let Some(text) = response
+            .body
+            .as_deref()
+            .and_then(|body| std::str::from_utf8(body).ok())
+        else {
+            return;
+        };
+        let rewritten = self.pattern.replace_all(text, self.with.as_str());
+        response.body = Some(rewritten.into_owned().into_bytes());
+    }
+}
+
+struct Canonicalize(CanonicalJson);
+
+impl ResponseTransformer for Canonicalize {
+    fn transform(&self, _: &HashMap<String, String>, response: &mut WireResponse) {
+        // Bodies that are not JSON, or already compressed, are left alone.
+        if response.headers.get("Content-Encoding").is_some() {
+            return;
+        }
+        let Some(value) = response
+            .body
+            .as_deref()
+            .and_then(|body| serde_json::from_slice::<Value>(body).ok())
+        else {
+            return;
+        };
+
+        let value = canonical(value, self.0.sort_arrays);
+        let body = if self.0.pretty {
+            serde_json::to_vec_pretty(&value)
+        } else {
+            serde_json::to_vec(&value)
+        };
+        response.body = Some(body.expect("a JSON value serializes"));
+    }
+}
+
+/// `value` with object keys sorted and, with `sort_arrays`, array elements too.
+fn canonical(value: Value, sort_arrays: bool) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map
+                .into_iter()
+                .map(|(key, value)| (key, canonical(value, sort_arrays)))
+                .collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(items) => {
+            let mut items: Vec<Value> = items
+                .into_iter()
+                .map(|item| canonical(item, sort_arrays))
+                .collect();
+            if sort_arrays {
+                items.sort_by_cached_key(Value::to_string);
+            }
+            Value::Array(items)
+        }
+        value => value,
+    }
+}
+
+struct Compress(Vec<ContentCoding>);
+
+impl ResponseTransformer for Compress {
+    fn transform(&self, request_headers: &HashMap<String, String>, response: &mut WireResponse) {
+        let Some(body) = response.body.as_deref().filter(|body| !body.is_empty()) else {
+            return;
+        };
+        if response.headers.get("Content-Encoding").is_some() {
+            return;
+        }
+        response.headers.append("Vary", "Accept-Encoding");
+
+        let accepted = request_headers
+            .get("accept-encoding")
+            .map(String::as_str)
+            .unwrap_or_default();
+        let Some(coding) = self
+            .0
+            .iter()
+            .copied()
+            .find(|coding| accepts(accepted, coding.as_str()))
+        else {
+            return;
+        };
+
+        response.body = Some(compress(coding, body));
+        response
+            .headers
+            .insert("Content-Encoding", coding.as_str().to_string());
+    }
+}
+
+/// Whether an `Accept-Encoding` value admits `coding`, explicitly or through `*`,
+/// with a non-zero quality.
+fn accepts(accept_encoding: &str, coding: &str) -> bool {
+    accept_encoding.split(',').any(|item| {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let refused = params.any(|param| {
+            param
+                .trim()
+                .strip_prefix("q=")
+                .is_some_and(|q| q.trim().parse::<f32>().is_ok_and(|q| q == 0.0))
+        });
+        (name.eq_ignore_ascii_case(coding) || name == "*") && !refused
+    })
+}
+
+fn compress(coding: ContentCoding, body: &[u8]) -> Vec<u8> {
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+
+    // Writing to a Vec cannot fail.
+    match coding {
+        ContentCoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).expect("in-memory write");
+            encoder.finish().expect("in-memory write")
+        }
+        ContentCoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).expect("in-memory write");
+            encoder.finish().expect("in-memory write")
+        }
+        ContentCoding::Br => {
+            let mut compressed = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(body).expect("in-memory write");
+            }
+            compressed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::decompress::decode_body;
+
+    fn response(body: &str) -> WireResponse {
+        let mut headers = HeaderList::new();
+        headers.insert("Content-Type", "application/json");
+        WireResponse {
+            status: 200,
+            headers,
+            body: Some(body.as_bytes().to_vec()),
+        }
+    }
+
+    fn chain(yaml: &str) -> anyhow::Result<TransformChain> {
+        TransformChain::new(&serde_yaml::from_str::<Vec<ResponseTransform>>(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_headers_and_replace() {
+        let chain = chain(
+            r#"
+- headers: {Content-Type: text/plain, Set-Cookie: [a=1, b=2]}
+- replace: {pattern: '"secret":"(\w)\w*"', with: '"secret":"$1***"'}
+"#,
+        )
+        .unwrap();
+
+        let mut response = response(r#"{"secret":"hunter2"}"#);
+        chain.apply(&HashMap::new(), &mut response);
+
+        assert_eq!(response.body.as_deref(), Some(&br#"{"secret":"h***"}"#[..]));
+        assert_eq!(
+            response.headers.get("content-type").map(String::as_str),
+            Some("text/plain")
+        );
+        assert_eq!(response.headers.get_all("Set-Cookie").count(), 2);
+        assert_eq!(response.headers.len(), 3);
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let body = r#"{"b": [3, {"z": 1, "a": 2}, 1], "a": "x"}"#;
+        let canonicalized = |yaml: &str, body: &str| {
+            let mut response = response(body);
+            chain(yaml).unwrap().apply(&HashMap::new(), &mut response);
+            String::from_utf8(response.body.unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            canonicalized("- canonical_json: {}", body),
+            r#"{"a":"x","b":[3,{"a":2,"z":1},1]}"#
+        );
+        assert_eq!(
+            canonicalized("- canonical_json: {sort_arrays: true}", body),
+            r#"{"a":"x","b":[1,3,{"a":2,"z":1}]}"#
+        );
+        assert_eq!(
+            canonicalized("- canonical_json: {pretty: true}", r#"{"b": [1], "a": {}}"#),
+            "{\n  \"a\": {},\n  \"b\": [\n    1\n  ]\n}"
+        );
+        assert_eq!(
+            canonicalized("- canonical_json: {}", "not {json}"),
+            "not {json}"
+        );
+    }
+
+    #[test]
+    fn test_compress_negotiates_coding() {
+        let chain = chain("- compress: [br, gzip]").unwrap();
+        let accept =
+            |value: &str| HashMap::from([("accept-encoding".to_string(), value.to_string())]);
+        let body = "hello ".repeat(100);
+
+        let mut gzipped = response(&body);
+        chain.apply(&accept("gzip, br;q=0"), &mut gzipped);
+        assert_eq!(
+            gzipped.headers.get("Content-Encoding").map(String::as_str),
+            Some("gzip")
+        );
+        assert_eq!(
+            gzipped.headers.get("Vary").map(String::as_str),
+            Some("Accept-Encoding")
+        );
+        let decoded = decode_body("gzip", gzipped.body.as_deref().unwrap(), 1 << 20)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded, body.as_bytes());
+
+        let mut brotli = response(&body);
+        chain.apply(&accept("*"), &mut brotli);
+        assert_eq!(
+            brotli.headers.get("Content-Encoding").map(String::as_str),
+            Some("br")
+        );
+        let decoded = decode_body("br", brotli.body.as_deref().unwrap(), 1 << 20)
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded, body.as_bytes());
+
+        let mut identity = response(&body);
+        chain.apply(&accept("deflate"), &mut identity);
+        assert!(identity.headers.get("Content-Encoding").is_none());
+        assert_eq!(identity.body.as_deref(), Some(body.as_bytes()));
+    }
+
+    #[test]
+    fn test_custom_transformers() {
+        register("transform_test_status", |config: &Value| {
+            let status = config["status"]
+                .as_u64()
+                .context("`status` must be a number")? as u16;
+            Ok(Box::new(
+                move |_: &HashMap<String, String>, response: &mut WireResponse| {
+                    response.status = status;
+                },
+            ) as Box<dyn ResponseTransformer>)
+        });
+
+        let chain =
+            chain("- custom: {kind: transform_test_status, config: {status: 418}}").unwrap();
+        let mut response = response("{}");
+        chain.apply(&HashMap::new(), &mut response);
+        assert_eq!(response.status, 418);
+
+        let mut coded = TransformChain::default();
+        coded.push(|_: &HashMap<String, String>, response: &mut WireResponse| {
+            response.body = None;
+        });
+        coded.apply(&HashMap::new(), &mut response);
+        assert_eq!(response.body, None);
+
+        let invalid = |yaml: &str| format!("{:#}", super::tests::chain(yaml).unwrap_err());
+        assert_eq!(
+            invalid("- custom: {kind: transform_test_status, config: {}}"),
+            "Invalid transform step 0: Invalid config for custom transformer \
+             `transform_test_status`: `status` must be a number"
+        );
+        assert_eq!(
+            invalid("- custom: {kind: transform_test_unknown}"),
+            "Invalid transform step 0: Unknown custom transformer kind `transform_test_unknown`"
+        );
+        assert_eq!(
+            invalid("- {headers: {X-A: b}, compress: [gzip]}"),
+            "Invalid transform step 0: Transform step must set exactly one of: headers, \
+             replace, canonical_json, compress, custom"
+        );
+        assert!(invalid("- replace: {pattern: '('}").contains("Invalid replace pattern: ("));
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::config::Endpoint;
+use crate::journal::JournalEntry;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EndpointUsage {
+    pub name: String,
+    pub method: String,
+    pub path: String,
+    pub hits: u64,
+}
+
+/// Per-endpoint hit counts for every configured endpoint, including those never hit.
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub endpoints: Vec<EndpointUsage>,
+}
+
+impl UsageReport {
+    pub fn new<F>(endpoints: &[Endpoint], hits: F) -> Self
+    where
+        F: Fn(&str) -> u64,
+    {
+        let endpoints = endpoints
+            .iter()
+            .map(|endpoint| EndpointUsage {
+                name: endpoint.name.clone(),
+                method: endpoint.method.to_uppercase(),
+                path: endpoint.path.clone(),
+                hits: hits(&endpoint.name),
+            })
+            .collect();
+
+        Self { endpoints }
+    }
+
+    pub fn from_journal(endpoints: &[Endpoint], entries: &[JournalEntry]) -> Self {
+        let mut hits: HashMap<&str, u64> = HashMap::new();
+        for name in entries.iter().filter_map(|e| e.endpoint.as_deref()) {
+            *hits.entry(name).or_insert(0) += 1;
+        }
+
+        Self::new(endpoints, |name| hits.get(name).copied().unwrap_or(0))
+    }
+
+    /// Endpoints that were never hit, in configuration order.
+    pub fn unused(&self) -> Vec<&EndpointUsage> {
+        self.endpoints.iter().filter(|e| e.hits == 0).collect()
+    }
+
+    /// Percentage of configured endpoints hit at least once. An empty configuration
+    /// is considered fully covered.
+    pub fn coverage_percent(&self) -> f64 {
+        if self.endpoints.is_empty() {
+            return 100.0;
+        }
+
+        let hit = self.endpoints.len() - self.unused().len();
+        hit as f64 * 100.0 / self.endpoints.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::Response;
+
+    fn endpoint(name: &str, method: &str, path: &str) -> Endpoint {
+        Endpoint {
+            name: name.to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            stateful: false,
+            state_key: None,
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: None,
+                condition: None,
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn journal_entry(endpoint: Option<&str>) -> JournalEntry {
+        JournalEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            endpoint: endpoint.map(str::to_string),
+            status: 200,
+            latency_ms: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_from_journal() {
+        let endpoints = vec![
+            endpoint("Users", "get", "/users"),
+            endpoint("Orders", "POST", "/orders"),
+        ];
+        let entries = vec![
+            journal_entry(Some("Users")),
+            journal_entry(Some("Users")),
+            journal_entry(None),
+        ];
+
+        let report = UsageReport::from_journal(&endpoints, &entries);
+        assert_eq!(report.endpoints[0].hits, 2);
+        assert_eq!(report.endpoints[0].method, "GET");
+        assert_eq!(report.endpoints[1].hits, 0);
+
+        let unused = report.unused();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].name, "Orders");
+    }
+
+    #[test]
+    fn test_coverage_percent() {
+        let endpoints = vec![
+            endpoint("A", "GET", "/a"),
+            endpoint("B", "GET", "/b"),
+            endpoint("C", "GET", "/c"),
+            endpoint("D", "GET", "/d"),
+        ];
+        let entries = vec![journal_entry(Some("A")), journal_entry(Some("C"))];
+
+        let report = UsageReport::from_journal(&endpoints, &entries);
+        assert_eq!(report.coverage_percent(), 50.0);
+
+        let report = UsageReport::from_journal(&[], &entries);
+        assert_eq!(report.coverage_percent(), 100.0);
+    }
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::config::BodyValidation;
+use jsonschema::error::ValidationErrorKind;
+use jsonschema::Validator;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// JSON pointer to the offending field, empty for the body itself.
+    pub field: String,
+    pub message: String,
+}
+
+/// A compiled request body schema for one endpoint.
+#[derive(Clone)]
+pub struct BodyValidator {
+    validator: Arc<Validator>,
+    config: BodyValidation,
+}
+
+impl BodyValidator {
+    pub fn new(config: &BodyValidation) -> anyhow::Result<Self> {
+        let validator = jsonschema::validator_for(&config.schema)
+            .map_err(|e| anyhow::anyhow!("Invalid validation schema: {}", e))?;
+
+        Ok(Self {
+            validator: Arc::new(validator),
+            config: config.clone(),
+        })
+    }
+
+    /// Returns the field errors for `body`, or an empty list if it is valid. A missing
+    /// body is validated as JSON `null`.
+    pub fn validate(&self, body: Option<&str>) -> Vec<FieldError> {
+        let instance = match body {
+            Some(body) => match serde_json::from_str(body) {
+                Ok(instance) => instance,
+                Err(e) => {
+                    return vec![FieldError {
+                        field: String::new(),
+                        message: format!("Request body is not valid JSON: {}", e),
+                    }]
+                }
+            },
+            None => serde_json::Value::Null,
+        };
+
+        self.validator
+            .iter_errors(&instance)
+            .map(|error| {
+                let mut field = error.instance_path.to_string();
+                // Report a missing property at its own path rather than its parent's.
+                if let ValidationErrorKind::Required { property } = &error.kind {
+                    if let Some(property) = property.as_str() {
+                        field = format!("{}/{}", field, property);
+                    }
+                }
+                FieldError {
+                    field,
+                    message: error.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn status(&self) -> u16 {
+        self.config.status
+    }
+
+    /// Renders the configured error envelope for `errors`.
+    pub fn render_errors(&self, errors: &[FieldError]) -> String {
+        let json = serde_json::to_string(errors).unwrap_or_else(|_| "[]".to_string());
+        self.config
+            .envelope
+            .replace("{{errors}}", &json)
+            .replace("{{error_count}}", &errors.len().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validator(envelope: &str) -> BodyValidator {
+        BodyValidator::new(&BodyValidation {
+            schema: json!({
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": {"type": "string"},
+                    "age": {"type": "integer", "minimum": 0}
+                }
+            }),
+            status: 400,
+            envelope: envelope.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_valid_body() {
+        let validator = validator("{{errors}}");
+        assert!(validator
+            .validate(Some(r#"{"name": "Ann", "age": 3}"#))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_field_errors() {
+        let validator = validator("{{errors}}");
+        let errors = validator.validate(Some(r#"{"age": -1}"#));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+
+        assert_eq!(errors.len(), 2);
+        assert!(fields.contains(&"/name"));
+        assert!(fields.contains(&"/age"));
+    }
+
+    #[test]
+    fn test_invalid_json_and_missing_body() {
+        let validator = validator("{{errors}}");
+
+        let errors = validator.validate(Some("{not json"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("not valid JSON"));
+
+        assert_eq!(validator.validate(None).len(), 1);
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let validator = validator(r#"{"count": {{error_count}}, "details": {{errors}}}"#);
+        let errors = vec![FieldError {
+            field: "/name".to_string(),
+            message: "required".to_string(),
+        }];
+
+        let rendered: serde_json::Value =
+            serde_json::from_str(&validator.render_errors(&errors)).unwrap();
+        assert_eq!(
+            rendered,
+            json!({"count": 1, "details": [{"field": "/name", "message": "required"}]})
+        );
+        assert_eq!(validator.status(), 400);
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        let result = BodyValidator::new(&BodyValidation {
+            schema: json!({"type": 12}),
+            status: 422,
+            envelope: "{{errors}}".to_string(),
+        });
+        assert!(result.is_err());
+    }
+}
@@ -15,22 +15,46 @@
  */
 
 use crate::config::Config;
+use crate::journal::Journal;
 use crate::rules::RuleEngine;
+use crate::server::banner::ServerInfo;
 use crate::server::openapi::ApiDoc;
+use crate::server::reload::Reloader;
+use crate::smtp::Mailbox;
 use crate::telemetry::tracer::tracing_middleware;
 use actix_web::dev::Server;
 use actix_web::http::header;
+use actix_web::http::KeepAlive;
 use actix_web::web;
 use actix_web::App;
 use actix_web::HttpResponse;
 use actix_web::HttpServer;
 use actix_web::Responder;
+use anyhow::Context;
+use arc_swap::ArcSwap;
 use std::sync::Arc;
 use tracing::info;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::{SwaggerUi, Url};
 
 pub async fn run_server(config: Config, rule_engine: Arc<RuleEngine>) -> anyhow::Result<Server> {
+    serve(config, Arc::new(ArcSwap::from(rule_engine)), None).await
+}
+
+/// Like [`run_server`], serving from the reloader's engine so reloads take effect
+/// and their status shows up in `/health`.
+pub async fn run_server_with_reload(
+    config: Config,
+    reloader: Arc<Reloader>,
+) -> anyhow::Result<Server> {
+    serve(config, reloader.engine(), Some(reloader)).await
+}
+
+async fn serve(
+    config: Config,
+    rule_engine: Arc<ArcSwap<RuleEngine>>,
+    reloader: Option<Arc<Reloader>>,
+) -> anyhow::Result<Server> {
     let server_config = config.server.clone();
     let addr = format!("{}:{}", server_config.host, server_config.port);
 
@@ -38,28 +62,109 @@ pub async fn run_server(config: Config, rule_engine: Arc<RuleEngine>) -> anyhow:
     info!("Server workers: {}", server_config.workers);
     info!("Max request size: {} bytes", server_config.max_request_size);
 
+    let journal = match &server_config.journal {
+        Some(path) => {
+            info!("Writing request journal to {}", path);
+            Some(Arc::new(Journal::open(path)?))
+        }
+        None => None,
+    };
+
+    let mailbox = Arc::new(Mailbox::default());
+    if let Some(smtp) = &config.smtp {
+        start_smtp(smtp, mailbox.clone(), journal.clone()).await?;
+    }
+
+    // Binding up front lets the banner and `/health` report the actual addresses.
+    let listener =
+        std::net::TcpListener::bind(&addr).with_context(|| format!("Failed to bind {}", addr))?;
+    let info = Arc::new(ServerInfo::new(&config, &[listener.local_addr()?]));
+    println!("{}", info.banner());
+
     let openapi = ApiDoc::openapi();
     let swagger_urls = vec![(Url::new("Molock API", "/api-docs/openapi.json"), openapi)];
 
     let server = HttpServer::new(move || {
-        let app_state = web::Data::new(AppState {
-            _config: config.clone(),
-            rule_engine: rule_engine.clone(),
-        });
+        crate::telemetry::runtime::register_worker_runtime();
+        let mut app_state = AppState::with_engine(config.clone(), rule_engine.clone());
+        app_state.reloader = reloader.clone();
+        app_state.journal = journal.clone();
+        app_state.mailbox = mailbox.clone();
+        app_state.info = info.clone();
+        let app_state = web::Data::new(app_state);
 
         App::new()
+            .wrap(crate::server::connection::connection_semantics(
+                config.server.connection,
+            ))
+            .wrap(crate::server::crash::catch_panics())
             .wrap(tracing_middleware())
             .app_data(app_state.clone())
             .app_data(web::JsonConfig::default().limit(config.server.max_request_size))
             .service(web::resource("/health").to(crate::server::health_handler))
+            .service(web::resource("/ready").to(crate::server::ready_handler))
             .service(web::resource("/metrics").to(crate::server::metrics_handler))
+            .service(web::resource("/__admin/usage").to(crate::server::usage_handler))
+            .service(web::resource("/__admin/config").to(crate::server::config_handler))
+            .service(
+                web::resource("/__admin/emails")
+                    .route(web::get().to(crate::server::emails_handler))
+                    .route(web::delete().to(crate::server::clear_emails_handler)),
+            )
+            .service(
+                web::resource("/__admin/inbox/{name}")
+                    .route(web::get().to(crate::server::inbox_handler))
+                    .route(web::delete().to(crate::server::clear_inbox_handler)),
+            )
+            .service(
+                web::resource("/__admin/inbox/{name}/verify")
+                    .route(web::get().to(crate::server::verify_inbox_handler)),
+            )
+            .service(
+                web::resource("/__admin/gates/{name}/release")
+                    .route(web::post().to(crate::server::release_gate_handler)),
+            )
+            .service(
+                web::resource("/__admin/instances")
+                    .route(web::get().to(crate::server::instances_handler)),
+            )
+            .service(
+                web::resource("/__admin/instances/{endpoint}/{instance}")
+                    .route(web::put().to(crate::server::set_instance_health_handler)),
+            )
+            .service(
+                web::resource("/__admin/reload/last")
+                    .route(web::get().to(crate::server::last_reload_handler)),
+            )
+            .service(
+                web::resource("/__admin/failpoints")
+                    .route(web::get().to(crate::server::failpoints_handler)),
+            )
+            .service(
+                web::resource("/__admin/failpoints/{name}")
+                    .route(web::put().to(crate::server::arm_failpoint_handler))
+                    .route(web::delete().to(crate::server::disarm_failpoint_handler)),
+            )
+            .service(
+                web::resource("/__admin/pprof/cpu")
+                    .route(web::get().to(crate::server::profiling::cpu_profile_handler)),
+            )
+            .service(
+                web::resource("/__admin/pprof/heap")
+                    .route(web::get().to(crate::server::profiling::heap_profile_handler)),
+            )
             .service(SwaggerUi::new("/swagger-ui/{_:.*}").urls(swagger_urls.clone()))
             .service(web::resource("/api-docs/openapi.json").to(openapi_json_handler))
             .default_service(web::to(crate::server::request_handler))
     })
-    .workers(server_config.workers)
-    .bind(addr)?
-    .run();
+    .workers(server_config.workers);
+
+    let server = if server_config.connection.keep_alive {
+        server
+    } else {
+        server.keep_alive(KeepAlive::Disabled)
+    };
+    let server = server.listen(listener)?.run();
 
     Ok(server)
 }
@@ -74,43 +179,109 @@ async fn openapi_json_handler() -> impl Responder {
 
 #[derive(Clone)]
 pub struct AppState {
-    pub _config: Config,
-    pub rule_engine: Arc<RuleEngine>,
+    pub config: Config,
+    pub rule_engine: Arc<ArcSwap<RuleEngine>>,
+    /// Set when hot reload is enabled.
+    pub reloader: Option<Arc<Reloader>>,
+    pub journal: Option<Arc<Journal>>,
+    pub mailbox: Arc<Mailbox>,
+    pub info: Arc<ServerInfo>,
+}
+
+impl AppState {
+    pub fn new(config: Config, rule_engine: Arc<RuleEngine>) -> Self {
+        Self::with_engine(config, Arc::new(ArcSwap::from(rule_engine)))
+    }
+
+    /// Serves from a shared engine handle that a [`Reloader`] may swap.
+    pub fn with_engine(config: Config, rule_engine: Arc<ArcSwap<RuleEngine>>) -> Self {
+        Self {
+            config,
+            rule_engine,
+            reloader: None,
+            journal: None,
+            mailbox: Arc::new(Mailbox::default()),
+            info: Arc::new(ServerInfo::default()),
+        }
+    }
+}
+
+#[cfg(feature = "smtp")]
+async fn start_smtp(
+    config: &crate::config::SmtpConfig,
+    mailbox: Arc<Mailbox>,
+    journal: Option<Arc<Journal>>,
+) -> anyhow::Result<()> {
+    crate::smtp::listener::start(config, mailbox, journal).await
+}
+
+#[cfg(not(feature = "smtp"))]
+async fn start_smtp(
+    _config: &crate::config::SmtpConfig,
+    _mailbox: Arc<Mailbox>,
+    _journal: Option<Arc<Journal>>,
+) -> anyhow::Result<()> {
+    tracing::warn!("`smtp` is configured but the smtp feature is not enabled");
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::types::{Endpoint, Response};
-    use std::collections::HashMap;
 
     #[test]
     fn test_app_state() {
-        let mut config = Config::default();
-        config.endpoints = vec![Endpoint {
-            name: "Test".to_string(),
-            method: "GET".to_string(),
-            path: "/test".to_string(),
-            stateful: false,
-            state_key: None,
-            responses: vec![Response {
-                status: 200,
-                delay: None,
-                body: Some("OK".to_string()),
-                headers: HashMap::new(),
-                condition: None,
-                probability: None,
-                default: false,
+        let config = Config {
+            endpoints: vec![Endpoint {
+                name: "Test".to_string(),
+                method: "GET".to_string(),
+                path: "/test".to_string(),
+                stateful: false,
+                state_key: None,
+                responses: vec![Response {
+                    status: 200,
+                    delay: None,
+                    body: Some("OK".to_string()),
+                    condition: None,
+                    probability: None,
+                    default: false,
+                    ..Default::default()
+                }],
+                ..Default::default()
             }],
-        }];
+            ..Default::default()
+        };
 
         let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
-        let app_state = AppState {
-            _config: config.clone(),
-            rule_engine: rule_engine.clone(),
-        };
+        let app_state = AppState::new(config.clone(), rule_engine.clone());
 
-        assert_eq!(app_state._config.endpoints.len(), 1);
-        assert_eq!(app_state._config.endpoints[0].name, "Test");
+        assert_eq!(app_state.config.endpoints.len(), 1);
+        assert_eq!(app_state.config.endpoints[0].name, "Test");
+    }
+
+    #[test]
+    fn test_openapi_documents_admin_api() {
+        let openapi = ApiDoc::openapi();
+        for path in [
+            "/__admin/usage",
+            "/__admin/config",
+            "/__admin/emails",
+            "/__admin/inbox/{name}",
+            "/__admin/inbox/{name}/verify",
+            "/__admin/gates/{name}/release",
+            "/__admin/instances",
+            "/__admin/instances/{endpoint}/{instance}",
+            "/__admin/failpoints",
+            "/__admin/failpoints/{name}",
+            "/__admin/pprof/cpu",
+            "/__admin/pprof/heap",
+        ] {
+            assert!(
+                openapi.paths.paths.contains_key(path),
+                "{} undocumented",
+                path
+            );
+        }
     }
 }
@@ -0,0 +1,143 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! What this instance is serving, printed at startup and reported by `/health` so
+//! CI logs show which mock definition answered a run.
+
+use crate::config::Config;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ServerInfo {
+    /// SHA-256 of the configuration file, when the config was loaded from one.
+    pub config_sha256: Option<String>,
+    pub endpoints: BTreeMap<String, usize>,
+    pub features: Vec<&'static str>,
+    pub urls: Vec<String>,
+}
+
+impl ServerInfo {
+    pub fn new(config: &Config, addrs: &[SocketAddr]) -> Self {
+        let mut endpoints = BTreeMap::new();
+        for endpoint in &config.endpoints {
+            *endpoints.entry(endpoint.method.to_uppercase()).or_insert(0) += 1;
+        }
+
+        Self {
+            config_sha256: config.sha256.clone(),
+            endpoints,
+            features: enabled_features(),
+            urls: addrs
+                .iter()
+                .map(|addr| format!("http://{}", addr))
+                .collect(),
+        }
+    }
+
+    pub fn banner(&self) -> String {
+        let endpoints = self
+            .endpoints
+            .iter()
+            .map(|(method, count)| format!("{} {}", count, method))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let features = if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(", ")
+        };
+
+        let mut banner = format!(
+            "Molock {}\n  config sha256: {}\n  endpoints:     {} ({})\n  features:      {}\n",
+            env!("CARGO_PKG_VERSION"),
+            self.config_sha256.as_deref().unwrap_or("n/a"),
+            self.endpoints.values().sum::<usize>(),
+            if endpoints.is_empty() {
+                "none"
+            } else {
+                &endpoints
+            },
+            features,
+        );
+        for url in &self.urls {
+            banner.push_str(&format!("  listening on:  {}\n", url));
+        }
+        banner
+    }
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("otel", cfg!(feature = "otel")),
+        ("hot-reload", cfg!(feature = "hot-reload")),
+        ("broker", cfg!(feature = "broker")),
+        ("smtp", cfg!(feature = "smtp")),
+        ("profiling", cfg!(feature = "profiling")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigLoader;
+
+    #[test]
+    fn test_server_info() {
+        let config = ConfigLoader::parse_str(
+            r#"
+server:
+  port: 8080
+telemetry:
+  enabled: false
+endpoints:
+  - name: "List"
+    method: get
+    path: "/items"
+    responses:
+      - status: 200
+  - name: "Show"
+    method: GET
+    path: "/items/:id"
+    responses:
+      - status: 200
+  - name: "Create"
+    method: POST
+    path: "/items"
+    responses:
+      - status: 201
+"#,
+        )
+        .unwrap();
+
+        let info = ServerInfo::new(&config, &["127.0.0.1:8080".parse().unwrap()]);
+        assert_eq!(info.config_sha256.as_ref().map(String::len), Some(64));
+        assert_eq!(
+            info.endpoints,
+            BTreeMap::from([("GET".to_string(), 2), ("POST".to_string(), 1)])
+        );
+        assert_eq!(info.urls, vec!["http://127.0.0.1:8080"]);
+
+        let banner = info.banner();
+        assert!(banner.contains("endpoints:     3 (2 GET, 1 POST)"));
+        assert!(banner.contains("listening on:  http://127.0.0.1:8080"));
+    }
+}
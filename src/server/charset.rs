@@ -0,0 +1,84 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Transcoding of rendered (UTF-8) response bodies into the charset declared by the
+//! response's `Content-Type`.
+
+use crate::config::HeaderList;
+use encoding_rs::{Encoding, UTF_8};
+
+/// Encodes `body` in the charset named by the `Content-Type` header, falling back to
+/// UTF-8 when none is declared or the label is unknown. Characters the target charset
+/// cannot represent are written as HTML numeric character references.
+pub fn encode_body(headers: &HeaderList, body: String) -> Vec<u8> {
+    let encoding = match headers.content_charset() {
+        Some(label) => match Encoding::for_label(label.as_bytes()) {
+            Some(encoding) => encoding,
+            None => {
+                tracing::warn!(charset = %label, "Unknown response charset, sending UTF-8");
+                UTF_8
+            }
+        },
+        None => UTF_8,
+    };
+
+    if encoding == UTF_8 {
+        return body.into_bytes();
+    }
+
+    let (encoded, _, had_unmappable) = encoding.encode(&body);
+    if had_unmappable {
+        tracing::debug!(
+            charset = encoding.name(),
+            "Response body contains characters outside the charset"
+        );
+    }
+    encoded.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(content_type: &str) -> HeaderList {
+        [("Content-Type", content_type)].into_iter().collect()
+    }
+
+    #[test]
+    fn test_encode_utf8_by_default() {
+        let body = "café".to_string();
+        assert_eq!(
+            encode_body(&HeaderList::new(), body.clone()),
+            body.as_bytes()
+        );
+        assert_eq!(
+            encode_body(&headers("text/plain; charset=nonsense"), body.clone()),
+            body.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_encode_latin1() {
+        let encoded = encode_body(&headers("text/plain; charset=iso-8859-1"), "café".into());
+        assert_eq!(encoded, b"caf\xe9");
+    }
+
+    #[test]
+    fn test_encode_shift_jis() {
+        let encoded = encode_body(&headers("text/plain; charset=shift_jis"), "日本".into());
+        assert_eq!(encoded, b"\x93\xfa\x96\x7b");
+    }
+}
@@ -0,0 +1,184 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `server.connection` toggles that responses carry: `force_close` and the
+//! HTTP/1.0 connection semantics of `http10`. Disabling keep-alive altogether is a
+//! listener setting applied in [`crate::server::app`].
+
+use crate::config::ConnectionOptions;
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::CONNECTION;
+use actix_web::http::ConnectionType;
+use futures::future::LocalBoxFuture;
+use std::future::ready;
+use std::rc::Rc;
+use std::task::{Context as TaskContext, Poll};
+
+pub fn connection_semantics(options: ConnectionOptions) -> ConnectionSemantics {
+    ConnectionSemantics(options)
+}
+
+pub struct ConnectionSemantics(ConnectionOptions);
+
+impl<S, B> Transform<S, ServiceRequest> for ConnectionSemantics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ConnectionSemanticsService<S>;
+    type InitError = ();
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ConnectionSemanticsService {
+            service: Rc::new(service),
+            options: self.0,
+        }))
+    }
+}
+
+pub struct ConnectionSemanticsService<S> {
+    service: Rc<S>,
+    options: ConnectionOptions,
+}
+
+impl<S, B> Service<ServiceRequest> for ConnectionSemanticsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let options = self.options;
+        let keep_alive_requested = req
+            .headers()
+            .get(CONNECTION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| {
+                value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("keep-alive"))
+            });
+        let response = self.service.call(req);
+
+        Box::pin(async move {
+            let mut response = response.await?;
+            // Without chunking, only closing the connection can end a body of
+            // unknown length.
+            let streaming = matches!(response.response().body().size(), BodySize::Stream);
+            let head = response.response_mut().head_mut();
+            if options.http10 {
+                head.no_chunking(true);
+                if !keep_alive_requested || streaming {
+                    head.set_connection_type(ConnectionType::Close);
+                }
+            }
+            if options.force_close {
+                head.set_connection_type(ConnectionType::Close);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn head(options: ConnectionOptions, connection: Option<&str>) -> (ConnectionType, bool) {
+        let app = test::init_service(
+            App::new()
+                .wrap(connection_semantics(options))
+                .default_service(web::to(HttpResponse::Ok)),
+        )
+        .await;
+        let mut request = test::TestRequest::get().uri("/");
+        if let Some(connection) = connection {
+            request = request.insert_header((CONNECTION, connection));
+        }
+        let response = test::call_service(&app, request.to_request()).await;
+        let head = response.response().head();
+        (head.connection_type(), head.chunked())
+    }
+
+    #[actix_web::test]
+    async fn test_connection_semantics() {
+        let defaults = ConnectionOptions::default();
+        assert_eq!(
+            head(defaults, None).await,
+            (ConnectionType::KeepAlive, true)
+        );
+
+        let force_close = ConnectionOptions {
+            force_close: true,
+            ..defaults
+        };
+        assert_eq!(
+            head(force_close, Some("keep-alive")).await,
+            (ConnectionType::Close, true)
+        );
+
+        let http10 = ConnectionOptions {
+            http10: true,
+            ..defaults
+        };
+        assert_eq!(head(http10, None).await, (ConnectionType::Close, false));
+        assert_eq!(
+            head(http10, Some("Keep-Alive")).await,
+            (ConnectionType::KeepAlive, false)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_http10_closes_after_streamed_bodies() {
+        let options = ConnectionOptions {
+            http10: true,
+            ..ConnectionOptions::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(connection_semantics(options))
+                .default_service(web::to(|| async {
+                    HttpResponse::Ok().streaming(futures::stream::once(async {
+                        Ok::<_, actix_web::Error>(web::Bytes::from_static(b"chunk"))
+                    }))
+                })),
+        )
+        .await;
+        let request = test::TestRequest::get()
+            .uri("/")
+            .insert_header((CONNECTION, "keep-alive"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(
+            response.response().head().connection_type(),
+            ConnectionType::Close
+        );
+    }
+}
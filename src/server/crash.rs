@@ -0,0 +1,350 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Turns panicking request handlers into `500` responses instead of dead workers.
+//!
+//! A panic hook remembers the message and location of the latest panic on each thread;
+//! the [`CatchPanic`] middleware catches the unwind, answers with a crash ID, counts it
+//! in `molock_panics_total` and writes a crash report (config hash, route, recent
+//! journal entries) as `<crash_dir>/<crash id>.json`.
+
+use crate::journal::JournalEntry;
+use crate::server::app::AppState;
+use crate::telemetry::metrics::record_panic;
+use crate::telemetry::routes::route_label;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::{web, HttpResponse};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::cell::RefCell;
+use std::future::ready;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Once;
+use std::task::{Context as TaskContext, Poll};
+
+/// Response header carrying the crash ID of a panicked request.
+pub const CRASH_ID_HEADER: &str = "X-Molock-Crash-Id";
+
+/// What went wrong, written to disk for every caught panic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub crash_id: String,
+    pub timestamp: String,
+    pub version: String,
+    pub method: String,
+    pub path: String,
+    pub route: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub config_sha256: Option<String>,
+    /// The latest journal entries before the crash, if a journal is configured.
+    pub recent_requests: Vec<JournalEntry>,
+}
+
+struct PanicDetails {
+    message: String,
+    location: Option<String>,
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<PanicDetails>> = const { RefCell::new(None) };
+}
+
+/// Installs the panic hook once per process, keeping the previous hook's output.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let details = PanicDetails {
+                message: payload_message(info.payload()),
+                location: info.location().map(|l| l.to_string()),
+            };
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(details));
+            previous(info);
+        }));
+    });
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+pub fn catch_panics() -> CatchPanic {
+    install_panic_hook();
+    CatchPanic
+}
+
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = CatchPanicService<S>;
+    type InitError = ();
+    type Future = std::future::Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CatchPanicService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        // The request itself is gone once the handler unwinds, so note what a
+        // report needs up front.
+        let request = CrashedRequest {
+            method: req.method().to_string(),
+            path: req.path().to_string(),
+            pattern: req.match_pattern(),
+            state: req.app_data::<web::Data<AppState>>().cloned(),
+        };
+
+        Box::pin(async move {
+            let result = AssertUnwindSafe(async move { service.call(req).await })
+                .catch_unwind()
+                .await;
+            match result {
+                Ok(response) => response,
+                Err(payload) => {
+                    let response = crash_response(request, payload);
+                    Err(InternalError::from_response("handler panicked", response).into())
+                }
+            }
+        })
+    }
+}
+
+struct CrashedRequest {
+    method: String,
+    path: String,
+    /// The admin route pattern, if any; mock endpoints are looked up in the engine.
+    pattern: Option<String>,
+    state: Option<web::Data<AppState>>,
+}
+
+fn crash_response(req: CrashedRequest, payload: Box<dyn Any + Send>) -> HttpResponse {
+    let details = LAST_PANIC
+        .with(|last| last.borrow_mut().take())
+        .unwrap_or_else(|| PanicDetails {
+            message: payload_message(payload.as_ref()),
+            location: None,
+        });
+    let state = req.state.as_ref();
+    let route = route_label(
+        req.pattern
+            .or_else(|| state.and_then(|s| s.rule_engine.load().route(&req.method, &req.path)))
+            .as_deref(),
+    );
+
+    let report = CrashReport {
+        crash_id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        method: req.method,
+        path: req.path,
+        route,
+        message: details.message,
+        location: details.location,
+        config_sha256: state.and_then(config_sha256),
+        recent_requests: state
+            .and_then(|s| s.journal.as_ref())
+            .map(|journal| {
+                journal.flush();
+                journal.recent()
+            })
+            .unwrap_or_default(),
+    };
+
+    record_panic(&report.route);
+    let written = write_report(&crash_dir(state), &report);
+    tracing::error!(
+        crash_id = %report.crash_id,
+        method = %report.method,
+        route = %report.route,
+        panic.message = %report.message,
+        panic.location = report.location.as_deref().unwrap_or("unknown"),
+        report = written.as_ref().map(|p| p.display().to_string()).unwrap_or_default(),
+        "Request handler panicked"
+    );
+
+    HttpResponse::InternalServerError()
+        .insert_header((CRASH_ID_HEADER, report.crash_id.clone()))
+        .json(serde_json::json!({
+            "error": "Internal server error",
+            "crash_id": report.crash_id
+        }))
+}
+
+fn config_sha256(state: &web::Data<AppState>) -> Option<String> {
+    match &state.reloader {
+        Some(reloader) => reloader.status().config_sha256,
+        None => state.config.sha256.clone(),
+    }
+}
+
+fn crash_dir(state: Option<&web::Data<AppState>>) -> PathBuf {
+    state
+        .and_then(|s| s.config.server.crash_dir.as_ref())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("molock-crashes"))
+}
+
+fn write_report(dir: &std::path::Path, report: &CrashReport) -> Option<PathBuf> {
+    let path = dir.join(format!("{}.json", report.crash_id));
+    let written = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&path, serde_json::to_vec_pretty(report)?));
+    match written {
+        Ok(()) => Some(path),
+        Err(e) => {
+            tracing::warn!(error = %e, dir = %dir.display(), "Failed to write crash report");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::ServerConfig;
+    use crate::config::Config;
+    use crate::journal::Journal;
+    use crate::rules::RuleEngine;
+    use actix_web::{test as actix_test, App};
+    use std::sync::Arc;
+
+    async fn panicking_handler() -> HttpResponse {
+        panic!("template helper exploded");
+    }
+
+    #[actix_web::test]
+    async fn test_panic_becomes_crash_response_and_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            server: ServerConfig {
+                crash_dir: Some(dir.path().join("crashes").display().to_string()),
+                ..Default::default()
+            },
+            sha256: Some("abc123".to_string()),
+            ..Default::default()
+        };
+        let journal = Arc::new(Journal::open(dir.path().join("journal.jsonl")).unwrap());
+        journal.record(JournalEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            method: "GET".to_string(),
+            path: "/ok".to_string(),
+            endpoint: None,
+            status: 200,
+            latency_ms: 1.0,
+        });
+        let mut state = AppState::new(config, Arc::new(RuleEngine::new(vec![])));
+        state.journal = Some(journal);
+
+        let app = actix_test::init_service(
+            App::new()
+                .wrap(catch_panics())
+                .app_data(web::Data::new(state))
+                .route("/boom/{id}", web::get().to(panicking_handler))
+                .route("/ok", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let err = actix_test::try_call_service(
+            &app,
+            actix_test::TestRequest::get().uri("/boom/1").to_request(),
+        )
+        .await
+        .unwrap_err();
+        let resp = err.error_response();
+        assert_eq!(resp.status(), 500);
+        let crash_id = resp
+            .headers()
+            .get(CRASH_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body: serde_json::Value =
+            serde_json::from_slice(&actix_web::body::to_bytes(resp.into_body()).await.unwrap())
+                .unwrap();
+        assert_eq!(body["crash_id"], crash_id);
+
+        let report: CrashReport = serde_json::from_slice(
+            &std::fs::read(
+                dir.path()
+                    .join("crashes")
+                    .join(format!("{}.json", crash_id)),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(report.path, "/boom/1");
+        assert_eq!(report.route, "/boom/{id}");
+        assert_eq!(report.message, "template helper exploded");
+        assert!(report.location.unwrap().contains("crash.rs"));
+        assert_eq!(report.config_sha256.as_deref(), Some("abc123"));
+        assert_eq!(report.recent_requests.len(), 1);
+
+        // The worker survives and keeps serving.
+        let resp =
+            actix_test::call_service(&app, actix_test::TestRequest::get().uri("/ok").to_request())
+                .await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[test]
+    fn test_payload_message() {
+        assert_eq!(payload_message(&"static"), "static");
+        assert_eq!(payload_message(&"owned".to_string()), "owned");
+        assert_eq!(payload_message(&42), "Box<dyn Any>");
+    }
+}
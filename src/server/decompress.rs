@@ -0,0 +1,94 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Decoding of compressed request bodies, so matchers and templates see the
+//! payload the client meant rather than its `Content-Encoding`.
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// Decodes `body` according to a `Content-Encoding` header value.
+///
+/// Returns `Ok(None)` for `identity` and for encodings Molock does not know, which
+/// are passed through untouched. Output beyond `limit` bytes is an error so that a
+/// small compressed upload cannot expand without bound.
+pub fn decode_body(encoding: &str, body: &[u8], limit: usize) -> anyhow::Result<Option<Vec<u8>>> {
+    let reader: Box<dyn Read + '_> = match encoding.trim().to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => Box::new(GzDecoder::new(body)),
+        "deflate" => Box::new(ZlibDecoder::new(body)),
+        "br" => Box::new(brotli::Decompressor::new(body, 4096)),
+        _ => return Ok(None),
+    };
+
+    let mut decoded = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| anyhow::anyhow!("Invalid {} request body: {}", encoding, e))?;
+    if decoded.len() > limit {
+        anyhow::bail!("Decoded request body exceeds {} bytes", limit);
+    }
+    Ok(Some(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    const PAYLOAD: &[u8] = br#"{"event": "order.created"}"#;
+
+    #[test]
+    fn test_decode_gzip_deflate_and_brotli() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(PAYLOAD).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut deflate = ZlibEncoder::new(Vec::new(), Compression::default());
+        deflate.write_all(PAYLOAD).unwrap();
+        let deflate = deflate.finish().unwrap();
+
+        let mut br = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut br, 4096, 5, 22);
+            writer.write_all(PAYLOAD).unwrap();
+        }
+
+        for (encoding, body) in [("gzip", gzip), ("deflate", deflate), ("br", br)] {
+            let decoded = decode_body(encoding, &body, 1024).unwrap();
+            assert_eq!(decoded.as_deref(), Some(PAYLOAD), "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_identity_unknown_and_invalid() {
+        assert_eq!(decode_body("identity", PAYLOAD, 1024).unwrap(), None);
+        assert_eq!(decode_body("zstd", PAYLOAD, 1024).unwrap(), None);
+        assert!(decode_body("gzip", PAYLOAD, 1024).is_err());
+    }
+
+    #[test]
+    fn test_decoded_size_limit() {
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&[b'a'; 4096]).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        assert!(decode_body("gzip", &gzip, 1024).is_err());
+        assert!(decode_body("gzip", &gzip, 4096).is_ok());
+    }
+}
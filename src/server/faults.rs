@@ -0,0 +1,200 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Deliberately broken responses configured through `fault:` on a response.
+
+use crate::config::Fault;
+use actix_web::body::BodyStream;
+use actix_web::http::{header, ConnectionType};
+use actix_web::web::Bytes;
+use actix_web::{HttpResponse, HttpResponseBuilder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::convert::Infallible;
+use std::io::Write;
+
+/// Applies `fault` to the response being built and finishes it with the body to send.
+pub fn apply_fault(
+    fault: Fault,
+    builder: &mut HttpResponseBuilder,
+    body: Option<Vec<u8>>,
+) -> HttpResponse {
+    let body = body.unwrap_or_default();
+
+    match fault {
+        Fault::GzipPlain => {
+            builder.insert_header((header::CONTENT_ENCODING, "gzip"));
+            builder.body(body)
+        }
+        Fault::GzipTruncated => {
+            builder.insert_header((header::CONTENT_ENCODING, "gzip"));
+            let mut compressed = gzip(&body);
+            compressed.truncate(compressed.len() / 2);
+            builder.body(compressed)
+        }
+        Fault::ContentLengthShort => {
+            builder.insert_header((header::CONTENT_LENGTH, body.len() / 2));
+            unframed(builder, body)
+        }
+        Fault::ContentLengthLong => {
+            builder.insert_header((header::CONTENT_LENGTH, body.len() * 2 + 1));
+            unframed(builder, body)
+        }
+        Fault::ChunkedWithLength => {
+            builder.insert_header((header::CONTENT_LENGTH, body.len()));
+            builder.insert_header((header::TRANSFER_ENCODING, "chunked"));
+            unframed(builder, chunked(&body))
+        }
+        Fault::Unframed => unframed(builder, body),
+    }
+}
+
+/// Sends `body` as is, with only the framing headers already on `builder`.
+///
+/// actix computes `Content-Length` and chunking itself for bodies of known size, so the body
+/// goes out as a stream with chunking disabled, and the connection is closed afterwards since
+/// the client cannot tell where the next response would start.
+fn unframed(builder: &mut HttpResponseBuilder, body: Vec<u8>) -> HttpResponse {
+    let chunk = Bytes::from(body);
+    let mut response = builder.body(BodyStream::new(futures::stream::once(async move {
+        Ok::<_, Infallible>(chunk)
+    })));
+    let head = response.head_mut();
+    head.no_chunking(true);
+    head.set_connection_type(ConnectionType::Close);
+    response
+}
+
+/// Encodes `data` as a single chunk followed by the last chunk.
+fn chunked(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len() + 16);
+    if !data.is_empty() {
+        encoded.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+        encoded.extend_from_slice(data);
+        encoded.extend_from_slice(b"\r\n");
+    }
+    encoded.extend_from_slice(b"0\r\n\r\n");
+    encoded
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail.
+    encoder.write_all(data).expect("in-memory write");
+    encoder.finish().expect("in-memory write")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::{to_bytes, MessageBody};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    async fn body_of(response: HttpResponse) -> Vec<u8> {
+        to_bytes(response.into_body()).await.unwrap().to_vec()
+    }
+
+    #[actix_web::test]
+    async fn test_gzip_plain() {
+        let response = apply_fault(
+            Fault::GzipPlain,
+            &mut HttpResponse::Ok(),
+            Some(b"hello".to_vec()),
+        );
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_ENCODING).unwrap(),
+            "gzip"
+        );
+        assert_eq!(body_of(response).await, b"hello");
+    }
+
+    #[actix_web::test]
+    async fn test_gzip_truncated() {
+        let payload = "a fairly long body that compresses into several bytes".repeat(4);
+        let response = apply_fault(
+            Fault::GzipTruncated,
+            &mut HttpResponse::Ok(),
+            Some(payload.clone().into_bytes()),
+        );
+        let body = body_of(response).await;
+
+        assert!(!body.is_empty());
+        assert!(body.len() < gzip(payload.as_bytes()).len());
+
+        let mut decoded = String::new();
+        assert!(GzDecoder::new(body.as_slice())
+            .read_to_string(&mut decoded)
+            .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_content_length_mismatch() {
+        for (fault, declared) in [
+            (Fault::ContentLengthShort, "5"),
+            (Fault::ContentLengthLong, "21"),
+        ] {
+            let response =
+                apply_fault(fault, &mut HttpResponse::Ok(), Some(b"0123456789".to_vec()));
+
+            assert_eq!(
+                response.headers().get(header::CONTENT_LENGTH).unwrap(),
+                declared
+            );
+            assert!(!response.head().chunked());
+            assert_eq!(response.head().connection_type(), ConnectionType::Close);
+            assert_eq!(body_of(response).await, b"0123456789");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_chunked_with_length() {
+        let response = apply_fault(
+            Fault::ChunkedWithLength,
+            &mut HttpResponse::Ok(),
+            Some(b"hello world".to_vec()),
+        );
+
+        assert_eq!(
+            response.headers().get(header::CONTENT_LENGTH).unwrap(),
+            "11"
+        );
+        assert_eq!(
+            response.headers().get(header::TRANSFER_ENCODING).unwrap(),
+            "chunked"
+        );
+        assert_eq!(body_of(response).await, b"b\r\nhello world\r\n0\r\n\r\n");
+        assert_eq!(chunked(b""), b"0\r\n\r\n");
+    }
+
+    #[actix_web::test]
+    async fn test_unframed() {
+        let response = apply_fault(
+            Fault::Unframed,
+            &mut HttpResponse::Ok(),
+            Some(b"hi".to_vec()),
+        );
+
+        assert!(response.headers().get(header::CONTENT_LENGTH).is_none());
+        assert!(response.headers().get(header::TRANSFER_ENCODING).is_none());
+        assert!(matches!(
+            response.body().size(),
+            actix_web::body::BodySize::Stream
+        ));
+        assert_eq!(body_of(response).await, b"hi");
+    }
+}
@@ -15,8 +15,24 @@
  */
 
 pub mod app;
+pub mod banner;
+pub mod charset;
+pub mod connection;
+pub mod crash;
+pub mod decompress;
+pub mod faults;
 pub mod handlers;
 pub mod openapi;
+pub mod profiling;
+pub mod reload;
+pub mod signing;
+pub mod strict;
+pub mod summary;
 
-pub use app::run_server;
-pub use handlers::{health_handler, metrics_handler, request_handler};
+pub use app::{run_server, run_server_with_reload};
+pub use handlers::{
+    arm_failpoint_handler, clear_emails_handler, clear_inbox_handler, config_handler,
+    disarm_failpoint_handler, emails_handler, failpoints_handler, health_handler, inbox_handler,
+    instances_handler, last_reload_handler, metrics_handler, ready_handler, release_gate_handler,
+    request_handler, set_instance_health_handler, usage_handler, verify_inbox_handler,
+};
@@ -14,7 +14,17 @@
  * limitations under the License.
  */
 
-use serde::Serialize;
+use crate::rules::failpoints::FailpointSpec;
+use crate::rules::inbox::InboxRequest;
+use crate::rules::instances::InstanceState;
+use crate::rules::usage::EndpointUsage;
+use crate::server::handlers::InstanceHealth;
+use crate::server::profiling::HeapSnapshot;
+use crate::server::reload::{ConfigDiff, LastReload, ReloadStatus};
+use crate::smtp::CapturedEmail;
+use crate::telemetry::{TelemetryState, TelemetryStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::OpenApi;
 use utoipa::ToSchema;
 
@@ -35,18 +45,53 @@ use utoipa::ToSchema;
     ),
     paths(
         super::handlers::health_handler,
+        super::handlers::ready_handler,
         super::handlers::metrics_handler,
+        super::handlers::usage_handler,
+        super::handlers::config_handler,
+        super::handlers::emails_handler,
+        super::handlers::clear_emails_handler,
+        super::handlers::inbox_handler,
+        super::handlers::clear_inbox_handler,
+        super::handlers::verify_inbox_handler,
+        super::handlers::release_gate_handler,
+        super::handlers::instances_handler,
+        super::handlers::set_instance_health_handler,
+        super::handlers::last_reload_handler,
+        super::handlers::failpoints_handler,
+        super::handlers::arm_failpoint_handler,
+        super::handlers::disarm_failpoint_handler,
+        super::profiling::cpu_profile_handler,
+        super::profiling::heap_profile_handler,
         request_handler_path
     ),
     components(
         schemas(
             HealthResponse,
+            ReadyResponse,
+            TelemetryStatus,
+            TelemetryState,
             MetricsResponse,
-            ErrorResponse
+            ErrorResponse,
+            UsageResponse,
+            EndpointUsage,
+            CapturedEmail,
+            InboxRequest,
+            InboxVerification,
+            GateReleaseResponse,
+            InstanceState,
+            InstanceHealth,
+            FailpointsResponse,
+            FailpointSpec,
+            ReloadStatus,
+            LastReload,
+            ConfigDiff,
+            HeapSnapshot
         )
     ),
     tags(
         (name = "System", description = "System endpoints"),
+        (name = "Admin", description = "Inspect and control a running mock under `/__admin`"),
         (name = "Mock", description = "Mock endpoint handlers")
     )
 )]
@@ -64,7 +109,7 @@ pub struct ApiDoc;
 #[allow(dead_code)]
 pub fn request_handler_path() {}
 
-#[derive(ToSchema, Serialize)]
+#[derive(Debug, ToSchema, Serialize, Deserialize)]
 pub struct HealthResponse {
     #[schema(example = "healthy")]
     pub status: String,
@@ -72,6 +117,24 @@ pub struct HealthResponse {
     pub service: String,
     #[schema(example = "2026-01-01T00:00:00Z")]
     pub timestamp: String,
+    /// SHA-256 of the configuration file being served.
+    pub config_sha256: Option<String>,
+    /// Number of configured endpoints per HTTP method.
+    pub endpoints: BTreeMap<String, usize>,
+    /// Optional features compiled into this build.
+    pub features: Vec<String>,
+    /// Addresses the server listens on.
+    pub urls: Vec<String>,
+    /// Reload outcomes, present when hot reload is enabled.
+    pub reload: Option<ReloadStatus>,
+}
+
+#[derive(Debug, ToSchema, Serialize, Deserialize)]
+pub struct ReadyResponse {
+    #[schema(example = "ready")]
+    pub status: String,
+    /// Progress of the telemetry set up in the background.
+    pub telemetry: TelemetryStatus,
 }
 
 #[derive(ToSchema, Serialize)]
@@ -87,3 +150,34 @@ pub struct ErrorResponse {
     #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
     pub request_id: String,
 }
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize)]
+pub struct UsageResponse {
+    pub endpoints: Vec<EndpointUsage>,
+    /// Names of the endpoints never hit, in configuration order.
+    pub unused: Vec<String>,
+    /// Failed requests by error type.
+    pub errors: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize)]
+pub struct InboxVerification {
+    pub matched: bool,
+    pub expected: Option<usize>,
+    pub actual: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize)]
+pub struct GateReleaseResponse {
+    pub released: usize,
+    /// Requests still held after the release.
+    pub held: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, ToSchema, Serialize, Deserialize)]
+pub struct FailpointsResponse {
+    /// Failpoints that can be armed.
+    pub available: Vec<String>,
+    /// Currently armed failpoints by name.
+    pub armed: BTreeMap<String, FailpointSpec>,
+}
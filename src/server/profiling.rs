@@ -0,0 +1,328 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! On-demand profiling of the Molock process itself, behind the `profiling` feature.
+//!
+//! `GET /__admin/pprof/cpu` samples all threads with pprof-rs for a few seconds and
+//! answers with a flamegraph SVG. `GET /__admin/pprof/heap` reports allocation
+//! counters kept by a thin wrapper around the system allocator. Without the feature
+//! both endpoints answer `501 Not Implemented`.
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_SECONDS: u64 = 10;
+const MAX_SECONDS: u64 = 60;
+const DEFAULT_FREQUENCY: i32 = 99;
+const MAX_FREQUENCY: i32 = 1000;
+
+/// Query parameters of a CPU profile.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct CpuProfileQuery {
+    /// How long to sample, in seconds (default 10, at most 60).
+    pub seconds: Option<u64>,
+    /// Samples per second (default 99, at most 1000).
+    pub frequency: Option<i32>,
+}
+
+impl CpuProfileQuery {
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(
+            self.seconds
+                .unwrap_or(DEFAULT_SECONDS)
+                .clamp(1, MAX_SECONDS),
+        )
+    }
+
+    pub fn frequency(&self) -> i32 {
+        self.frequency
+            .unwrap_or(DEFAULT_FREQUENCY)
+            .clamp(1, MAX_FREQUENCY)
+    }
+}
+
+/// Allocation counters since the process started.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HeapSnapshot {
+    /// Bytes currently allocated.
+    pub allocated_bytes: u64,
+    /// Highest `allocated_bytes` seen so far.
+    pub peak_allocated_bytes: u64,
+    /// Number of allocations made.
+    pub allocations: u64,
+    /// Number of allocations freed.
+    pub deallocations: u64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/__admin/pprof/cpu",
+    tag = "Admin",
+    params(CpuProfileQuery),
+    responses(
+        (status = 200, description = "Flamegraph of the sampled CPU time", content_type = "image/svg+xml"),
+        (status = 204, description = "No samples were taken because the process was idle"),
+        (status = 409, description = "Another CPU profile is already running"),
+        (status = 501, description = "Built without the profiling feature")
+    )
+)]
+pub async fn cpu_profile_handler(query: web::Query<CpuProfileQuery>) -> impl Responder {
+    cpu_profile(&query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/__admin/pprof/heap",
+    tag = "Admin",
+    responses(
+        (status = 200, description = "Allocation counters", body = HeapSnapshot),
+        (status = 501, description = "Built without the profiling feature")
+    )
+)]
+pub async fn heap_profile_handler() -> impl Responder {
+    match heap_snapshot() {
+        Some(snapshot) => HttpResponse::Ok().json(snapshot),
+        None => not_enabled(),
+    }
+}
+
+#[cfg(feature = "profiling")]
+async fn cpu_profile(query: &CpuProfileQuery) -> HttpResponse {
+    let guard = match pprof::ProfilerGuardBuilder::default()
+        .frequency(query.frequency())
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+    {
+        Ok(guard) => guard,
+        Err(e) => {
+            return HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Could not start CPU profiler: {}", e)
+            }))
+        }
+    };
+
+    tracing::info!(
+        seconds = query.duration().as_secs(),
+        "Capturing CPU profile"
+    );
+    tokio::time::sleep(query.duration()).await;
+
+    let mut svg = Vec::new();
+    let rendered = guard.report().build().and_then(|report| {
+        if report.data.is_empty() {
+            return Ok(false);
+        }
+        report.flamegraph(&mut svg).map(|()| true)
+    });
+    match rendered {
+        Ok(true) => HttpResponse::Ok().content_type("image/svg+xml").body(svg),
+        // An idle process is never sampled, so there is nothing to draw.
+        Ok(false) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Could not render CPU profile: {}", e)
+        })),
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+async fn cpu_profile(_query: &CpuProfileQuery) -> HttpResponse {
+    not_enabled()
+}
+
+#[cfg(feature = "profiling")]
+fn heap_snapshot() -> Option<HeapSnapshot> {
+    Some(allocator::snapshot())
+}
+
+#[cfg(not(feature = "profiling"))]
+fn heap_snapshot() -> Option<HeapSnapshot> {
+    None
+}
+
+#[allow(dead_code)]
+fn not_enabled() -> HttpResponse {
+    HttpResponse::NotImplemented().json(serde_json::json!({
+        "error": "Molock was built without the profiling feature"
+    }))
+}
+
+#[cfg(feature = "profiling")]
+mod allocator {
+    use super::HeapSnapshot;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+    static PEAK: AtomicU64 = AtomicU64::new(0);
+    static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+    /// The system allocator, counting what passes through it.
+    struct CountingAllocator;
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    fn record_alloc(size: usize) {
+        let now = ALLOCATED.fetch_add(size as u64, Ordering::Relaxed) + size as u64;
+        PEAK.fetch_max(now, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(size: usize) {
+        ALLOCATED.fetch_sub(size as u64, Ordering::Relaxed);
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            record_dealloc(layout.size());
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            let new_ptr = System.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                ALLOCATED.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+                let now = ALLOCATED.fetch_add(new_size as u64, Ordering::Relaxed) + new_size as u64;
+                PEAK.fetch_max(now, Ordering::Relaxed);
+            }
+            new_ptr
+        }
+    }
+
+    pub fn snapshot() -> HeapSnapshot {
+        HeapSnapshot {
+            allocated_bytes: ALLOCATED.load(Ordering::Relaxed),
+            peak_allocated_bytes: PEAK.load(Ordering::Relaxed),
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test as actix_test, App};
+
+    #[test]
+    fn test_cpu_profile_query_defaults_and_clamps() {
+        let query = CpuProfileQuery::default();
+        assert_eq!(query.duration().as_secs(), DEFAULT_SECONDS);
+        assert_eq!(query.frequency(), DEFAULT_FREQUENCY);
+
+        let query = CpuProfileQuery {
+            seconds: Some(3600),
+            frequency: Some(0),
+        };
+        assert_eq!(query.duration().as_secs(), MAX_SECONDS);
+        assert_eq!(query.frequency(), 1);
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    #[actix_web::test]
+    async fn test_profiling_disabled_answers_not_implemented() {
+        let app = actix_test::init_service(
+            App::new()
+                .route("/__admin/pprof/cpu", web::get().to(cpu_profile_handler))
+                .route("/__admin/pprof/heap", web::get().to(heap_profile_handler)),
+        )
+        .await;
+
+        for uri in ["/__admin/pprof/cpu", "/__admin/pprof/heap"] {
+            let resp = actix_test::call_service(
+                &app,
+                actix_test::TestRequest::get().uri(uri).to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), 501, "{}", uri);
+        }
+    }
+
+    #[cfg(feature = "profiling")]
+    #[actix_web::test]
+    async fn test_heap_snapshot_counts_allocations() {
+        let before = allocator::snapshot();
+        let buffer = vec![0u8; 1 << 20];
+        let during = allocator::snapshot();
+        drop(buffer);
+
+        assert!(during.allocations > before.allocations);
+        assert!(during.peak_allocated_bytes >= 1 << 20);
+
+        let app = actix_test::init_service(
+            App::new().route("/__admin/pprof/heap", web::get().to(heap_profile_handler)),
+        )
+        .await;
+        let resp = actix_test::call_service(
+            &app,
+            actix_test::TestRequest::get()
+                .uri("/__admin/pprof/heap")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), 200);
+        let snapshot: HeapSnapshot = actix_test::read_body_json(resp).await;
+        assert!(snapshot.allocations > 0);
+    }
+
+    #[cfg(feature = "profiling")]
+    #[actix_web::test]
+    async fn test_cpu_profile_renders_flamegraph() {
+        let app = actix_test::init_service(
+            App::new().route("/__admin/pprof/cpu", web::get().to(cpu_profile_handler)),
+        )
+        .await;
+        let request = actix_test::TestRequest::get()
+            .uri("/__admin/pprof/cpu?seconds=1&frequency=1000")
+            .to_request();
+
+        // Burn CPU only once the profiler is running, so there is something to sample.
+        let (resp, _) = tokio::join!(actix_test::call_service(&app, request), async {
+            tokio::task::spawn_blocking(|| {
+                let start = std::time::Instant::now();
+                let mut x = 0u64;
+                while start.elapsed() < std::time::Duration::from_millis(900) {
+                    x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+                }
+                x
+            })
+            .await
+        });
+
+        assert_eq!(resp.status(), 200);
+        let body = actix_test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains("<svg"));
+    }
+}
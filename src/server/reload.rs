@@ -0,0 +1,525 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Configuration reload in two phases: the new configuration is parsed, validated and
+//! turned into a `RuleEngine` away from the request path, and only a fully built engine
+//! is swapped in. A failed reload keeps the previous engine serving and is reported by
+//! `/health` and the `molock_config_reload_total` metric. Each successful reload logs
+//! which endpoints it added, removed or changed, also served at `/__admin/reload/last`.
+
+use crate::config::{Config, ConfigLoader};
+use crate::rules::{template, RuleEngine};
+use crate::telemetry::metrics::record_config_reload;
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+use utoipa::ToSchema;
+
+/// Outcome of the reloads since startup, as reported by `/health`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ReloadStatus {
+    /// Reloads that replaced the serving configuration.
+    pub reloads: u64,
+    /// Reloads rejected while the previous configuration kept serving.
+    pub failures: u64,
+    /// SHA-256 of the configuration file currently being served.
+    pub config_sha256: Option<String>,
+    /// Why the most recent reload was rejected; cleared by the next successful one.
+    pub last_error: Option<String>,
+    /// When the most recent reload was attempted (RFC 3339).
+    pub last_attempt: Option<String>,
+}
+
+/// Endpoints a reload added, removed or changed, by name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ConfigDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ConfigDiff {
+    pub fn between(previous: &Config, next: &Config) -> Self {
+        let previous = endpoints_by_name(previous);
+        let next = endpoints_by_name(next);
+        let mut diff = Self::default();
+
+        for (name, endpoints) in &next {
+            match previous.get(name) {
+                None => diff.added.push(name.to_string()),
+                Some(before) if before != endpoints => diff.changed.push(name.to_string()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = previous
+            .keys()
+            .filter(|name| !next.contains_key(*name))
+            .map(|name| name.to_string())
+            .collect();
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Endpoint names are unique (the loader rejects duplicates), so they identify
+/// an endpoint across reloads.
+fn endpoints_by_name(config: &Config) -> BTreeMap<&str, serde_json::Value> {
+    config
+        .endpoints
+        .iter()
+        .map(|endpoint| {
+            let value = serde_json::to_value(endpoint).unwrap_or_default();
+            (endpoint.name.as_str(), value)
+        })
+        .collect()
+}
+
+/// The most recent reload that replaced the serving configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LastReload {
+    /// When the configuration was swapped in (RFC 3339).
+    pub at: String,
+    /// SHA-256 of the configuration file swapped in.
+    pub config_sha256: Option<String>,
+    /// What changed compared with the configuration it replaced.
+    pub diff: ConfigDiff,
+}
+
+pub struct Reloader {
+    config_path: PathBuf,
+    dry_run: bool,
+    engine: Arc<ArcSwap<RuleEngine>>,
+    config: ArcSwap<Config>,
+    status: Mutex<ReloadStatus>,
+    last: Mutex<Option<LastReload>>,
+}
+
+impl Reloader {
+    pub fn new(config_path: PathBuf, config: Config, engine: Arc<RuleEngine>) -> Self {
+        let status = ReloadStatus {
+            config_sha256: config.sha256.clone(),
+            ..Default::default()
+        };
+
+        Self {
+            config_path,
+            dry_run: config.server.dry_run,
+            engine: Arc::new(ArcSwap::from(engine)),
+            config: ArcSwap::from_pointee(config),
+            status: Mutex::new(status),
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The engine handle requests are served from.
+    pub fn engine(&self) -> Arc<ArcSwap<RuleEngine>> {
+        self.engine.clone()
+    }
+
+    /// The configuration the current engine was built from.
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    pub fn status(&self) -> ReloadStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// The most recent successful reload, if there has been one.
+    pub fn last_reload(&self) -> Option<LastReload> {
+        self.last.lock().unwrap().clone()
+    }
+
+    /// Re-reads the configuration file and swaps in a new engine built from it. On
+    /// error the serving engine is left untouched and the failure is recorded.
+    pub async fn reload(&self) -> anyhow::Result<()> {
+        let path = self.config_path.clone();
+        let dry_run = self.dry_run;
+        let previous = self.engine.load_full();
+        let built = tokio::task::spawn_blocking(move || build(&path, dry_run, &previous))
+            .await
+            .map_err(|e| anyhow::anyhow!("Building the new configuration panicked: {}", e))
+            .and_then(|built| built);
+
+        let mut status = self.status.lock().unwrap();
+        status.last_attempt = Some(chrono::Utc::now().to_rfc3339());
+        record_config_reload(built.is_ok());
+
+        match built {
+            Ok((config, engine)) => {
+                let diff = ConfigDiff::between(&self.config.load(), &config);
+                info!(
+                    added = ?diff.added,
+                    removed = ?diff.removed,
+                    changed = ?diff.changed,
+                    "Reloaded configuration"
+                );
+                *self.last.lock().unwrap() = Some(LastReload {
+                    at: status.last_attempt.clone().unwrap_or_default(),
+                    config_sha256: config.sha256.clone(),
+                    diff,
+                });
+
+                status.reloads += 1;
+                status.config_sha256 = config.sha256.clone();
+                status.last_error = None;
+                self.config.store(Arc::new(config));
+                self.engine.store(Arc::new(engine));
+                Ok(())
+            }
+            Err(e) => {
+                status.failures += 1;
+                status.last_error = Some(format!("{:#}", e));
+                Err(e)
+            }
+        }
+    }
+}
+
+fn build(
+    path: &Path,
+    dry_run: bool,
+    previous: &RuleEngine,
+) -> anyhow::Result<(Config, RuleEngine)> {
+    let mut config = ConfigLoader::from_file(path)
+        .with_context(|| format!("Failed to load config from {:?}", path))?;
+    config.server.dry_run = dry_run;
+    check_templates(&config)?;
+    let engine = RuleEngine::from_config(&config).carry_over(previous);
+
+    Ok((config, engine))
+}
+
+/// A template Handlebars cannot parse would only get its variables substituted, so
+/// a reload introducing one is rejected instead of served.
+fn check_templates(config: &Config) -> anyhow::Result<()> {
+    for endpoint in &config.endpoints {
+        for (index, response) in endpoint.responses.iter().enumerate() {
+            let templates = response
+                .body
+                .iter()
+                .chain(response.headers.iter().map(|(_, value)| value));
+            for source in templates {
+                template::compile(source).with_context(|| {
+                    format!(
+                        "Endpoint '{}' responses[{}] has an invalid template",
+                        endpoint.name, index
+                    )
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const VALID: &str = r#"
+server:
+  port: 8080
+  workers: 1
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Ping"
+    method: GET
+    path: "/ping"
+    responses:
+      - status: 200
+        body: "pong"
+"#;
+
+    fn updated() -> String {
+        VALID.replace("body: \"pong\"", "body: \"pong v2\"")
+    }
+
+    async fn ping(reloader: &Reloader) -> Option<String> {
+        let engine = reloader.engine().load_full();
+        engine
+            .execute("GET", "/ping", "", &HashMap::new(), None, "127.0.0.1")
+            .await
+            .unwrap()
+            .body
+    }
+
+    fn reloader(dir: &tempfile::TempDir) -> Reloader {
+        let path = dir.path().join("molock.yaml");
+        std::fs::write(&path, VALID).unwrap();
+        let config = ConfigLoader::from_file(&path).unwrap();
+        let engine = Arc::new(RuleEngine::from_config(&config));
+        Reloader::new(path, config, engine)
+    }
+
+    #[tokio::test]
+    async fn test_reload_swaps_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(dir.path().join("molock.yaml"), updated()).unwrap();
+
+        reloader.reload().await.unwrap();
+
+        assert_eq!(ping(&reloader).await.as_deref(), Some("pong v2"));
+        let status = reloader.status();
+        assert_eq!(status.reloads, 1);
+        assert_eq!(status.failures, 0);
+        assert_eq!(status.config_sha256, reloader.config().sha256);
+    }
+
+    #[tokio::test]
+    async fn test_reload_records_endpoint_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        assert_eq!(reloader.last_reload(), None);
+
+        let added = r#"
+  - name: "Health"
+    method: GET
+    path: "/up"
+    responses:
+      - status: 204
+"#;
+        std::fs::write(dir.path().join("molock.yaml"), updated() + added).unwrap();
+        reloader.reload().await.unwrap();
+
+        let last = reloader.last_reload().unwrap();
+        assert_eq!(last.config_sha256, reloader.config().sha256);
+        assert_eq!(last.diff.added, vec!["Health"]);
+        assert_eq!(last.diff.changed, vec!["Ping"]);
+        assert!(last.diff.removed.is_empty());
+
+        let renamed = updated().replace("name: \"Ping\"", "name: \"Pong\"");
+        std::fs::write(dir.path().join("molock.yaml"), renamed).unwrap();
+        reloader.reload().await.unwrap();
+
+        let diff = reloader.last_reload().unwrap().diff;
+        assert_eq!(diff.added, vec!["Pong"]);
+        assert_eq!(diff.removed, vec!["Health", "Ping"]);
+        assert!(diff.changed.is_empty());
+
+        // A rejected reload leaves the last successful one in place.
+        std::fs::write(dir.path().join("molock.yaml"), "endpoints: [").unwrap();
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(reloader.last_reload().unwrap().diff, diff);
+    }
+
+    #[test]
+    fn test_unchanged_config_has_empty_diff() {
+        let config = ConfigLoader::parse_str(VALID).unwrap();
+        assert!(ConfigDiff::between(&config, &config).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_keeps_previous_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        let sha = reloader.status().config_sha256;
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            VALID.replace("status: 200", "status: 999"),
+        )
+        .unwrap();
+
+        assert!(reloader.reload().await.is_err());
+
+        assert_eq!(ping(&reloader).await.as_deref(), Some("pong"));
+        let status = reloader.status();
+        assert_eq!(status.reloads, 0);
+        assert_eq!(status.failures, 1);
+        assert_eq!(status.config_sha256, sha);
+        assert!(status
+            .last_error
+            .unwrap()
+            .contains("Invalid HTTP status code: 999"));
+
+        std::fs::write(dir.path().join("molock.yaml"), updated()).unwrap();
+        reloader.reload().await.unwrap();
+        assert_eq!(reloader.status().last_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_reload_rejects_invalid_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            VALID.replace(r#"body: "pong""#, r#"body: "{{#each items}}pong""#),
+        )
+        .unwrap();
+
+        assert!(reloader.reload().await.is_err());
+
+        assert_eq!(ping(&reloader).await.as_deref(), Some("pong"));
+        assert!(reloader
+            .status()
+            .last_error
+            .unwrap()
+            .contains("Endpoint 'Ping' responses[0] has an invalid template"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_inbox_requests() {
+        use crate::rules::inbox::InboxQuery;
+
+        let inbox = VALID.replace("    responses:", "    inbox: pings\n    responses:");
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(dir.path().join("molock.yaml"), &inbox).unwrap();
+        reloader.reload().await.unwrap();
+        ping(&reloader).await;
+
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            inbox.replace("pong", "pong v2"),
+        )
+        .unwrap();
+        reloader.reload().await.unwrap();
+
+        let engine = reloader.engine().load_full();
+        assert_eq!(engine.inbox("pings", &InboxQuery::default()).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        ping(&reloader).await;
+        ping(&reloader).await;
+        let engine = reloader.engine().load_full();
+        assert!(engine
+            .execute("GET", "/missing", "", &HashMap::new(), None, "127.0.0.1")
+            .await
+            .is_err());
+
+        std::fs::write(dir.path().join("molock.yaml"), updated()).unwrap();
+        reloader.reload().await.unwrap();
+        ping(&reloader).await;
+
+        let engine = reloader.engine().load_full();
+        assert_eq!(engine.usage().endpoints[0].hits, 3);
+        assert_eq!(engine.error_counts().values().sum::<u64>(), 1);
+
+        // A renamed endpoint starts from zero.
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            VALID.replace("name: \"Ping\"", "name: \"Pong\""),
+        )
+        .unwrap();
+        reloader.reload().await.unwrap();
+        assert_eq!(reloader.engine().load().usage().endpoints[0].hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_captures() {
+        let capturing = VALID.replace(
+            "        body: \"pong\"",
+            "        body: '{{captured \"tenant\"}}'",
+        );
+        let capturing = capturing.replace(
+            "    responses:",
+            "    capture: [{slot: tenant, header: X-Tenant}]\n    responses:",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(dir.path().join("molock.yaml"), &capturing).unwrap();
+        reloader.reload().await.unwrap();
+        let headers = HashMap::from([("x-tenant".to_string(), "acme".to_string())]);
+        let engine = reloader.engine().load_full();
+        engine
+            .execute("GET", "/ping", "", &headers, None, "127.0.0.1")
+            .await
+            .unwrap();
+
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            capturing.replace("{{captured", "v2 {{captured"),
+        )
+        .unwrap();
+        reloader.reload().await.unwrap();
+
+        assert_eq!(ping(&reloader).await.as_deref(), Some("v2 acme"));
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_instance_health() {
+        let clustered = VALID.replace(
+            "    responses:",
+            "    instances: [{name: a}, {name: b}]\n    responses:",
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(dir.path().join("molock.yaml"), &clustered).unwrap();
+        reloader.reload().await.unwrap();
+        let engine = reloader.engine().load_full();
+        assert!(engine.set_instance_health("Ping", "b", false).is_some());
+
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            clustered.replace("pong", "pong v2"),
+        )
+        .unwrap();
+        reloader.reload().await.unwrap();
+
+        let instances = reloader.engine().load().instances();
+        let health: Vec<bool> = instances["Ping"].iter().map(|i| i.healthy).collect();
+        assert_eq!(health, vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_held_requests_releasable() {
+        let gated = VALID.replace("    responses:", "    gate: ping\n    responses:");
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = reloader(&dir);
+        std::fs::write(dir.path().join("molock.yaml"), &gated).unwrap();
+        reloader.reload().await.unwrap();
+
+        let engine = reloader.engine().load_full();
+        engine.arm_failpoint(crate::rules::failpoints::STATE_STORE, Default::default());
+        let held = tokio::spawn(async move {
+            engine
+                .execute("GET", "/ping", "", &HashMap::new(), None, "127.0.0.1")
+                .await
+        });
+        while reloader.engine().load().held("ping") == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+
+        std::fs::write(
+            dir.path().join("molock.yaml"),
+            gated.replace("pong", "pong v2"),
+        )
+        .unwrap();
+        reloader.reload().await.unwrap();
+
+        let engine = reloader.engine().load_full();
+        assert_eq!(engine.held("ping"), 1);
+        assert!(engine.armed_failpoints().contains_key("state_store"));
+        assert_eq!(engine.release_gate("ping", None), 1);
+        assert_eq!(held.await.unwrap().unwrap().status, 200);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checksum and signature headers over encoded response bodies, configured per
+//! endpoint with `signing`.
+
+use crate::config::SignatureEncoding;
+use crate::config::{DigestAlgorithm, HmacAlgorithm, HmacSignature, ResponseSigning};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, KeyInit, Mac};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+
+/// The headers `signing` asks for, computed over `body` exactly as it is sent.
+pub fn signature_headers(signing: &ResponseSigning, body: &[u8]) -> Vec<(String, String)> {
+    let mut headers = Vec::new();
+
+    if signing.content_md5 {
+        headers.push((
+            "Content-MD5".to_string(),
+            STANDARD.encode(Md5::digest(body)),
+        ));
+    }
+
+    if let Some(algorithm) = signing.digest {
+        let digest = match algorithm {
+            DigestAlgorithm::Sha256 => format!("SHA-256={}", STANDARD.encode(Sha256::digest(body))),
+            DigestAlgorithm::Sha512 => format!("SHA-512={}", STANDARD.encode(Sha512::digest(body))),
+        };
+        headers.push(("Digest".to_string(), digest));
+    }
+
+    if let Some(hmac) = &signing.hmac {
+        headers.push((hmac.header.clone(), hmac_signature(hmac, body)));
+    }
+
+    headers
+}
+
+fn hmac_signature(config: &HmacSignature, body: &[u8]) -> String {
+    let secret = config.secret.as_bytes();
+    // HMAC accepts keys of any length, so creating the MAC cannot fail.
+    let signature = match config.algorithm {
+        HmacAlgorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC takes any key");
+            mac.update(body);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(secret).expect("HMAC takes any key");
+            mac.update(body);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+
+    let encoded = match config.encoding {
+        SignatureEncoding::Hex => signature.iter().map(|b| format!("{:02x}", b)).collect(),
+        SignatureEncoding::Base64 => STANDARD.encode(signature),
+    };
+    format!("{}{}", config.prefix, encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn test_checksums() {
+        let signing = ResponseSigning {
+            content_md5: true,
+            digest: Some(DigestAlgorithm::Sha256),
+            hmac: None,
+        };
+
+        let headers = signature_headers(&signing, b"hello");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            header(&headers, "Content-MD5"),
+            Some("XUFAKrxLKna5cZ2REBfFkg==")
+        );
+        assert_eq!(
+            header(&headers, "Digest"),
+            Some("SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=")
+        );
+    }
+
+    #[test]
+    fn test_hmac_signature() {
+        // RFC 4231 test case 2.
+        let mut hmac = HmacSignature {
+            header: "X-Signature".to_string(),
+            secret: "Jefe".to_string(),
+            algorithm: HmacAlgorithm::Sha256,
+            encoding: SignatureEncoding::Hex,
+            prefix: "sha256=".to_string(),
+        };
+        let body = b"what do ya want for nothing?";
+        assert_eq!(
+            hmac_signature(&hmac, body),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        hmac.encoding = SignatureEncoding::Base64;
+        hmac.prefix.clear();
+        assert_eq!(
+            hmac_signature(&hmac, body),
+            "W9zBRr9gdU5qBCQmCJV1x1oAPwidJzmDnexYuWTsOEM="
+        );
+
+        let signing = ResponseSigning {
+            hmac: Some(hmac),
+            ..Default::default()
+        };
+        assert!(header(&signature_headers(&signing, body), "X-Signature").is_some());
+    }
+}
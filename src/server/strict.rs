@@ -0,0 +1,80 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Strict mode (`server.strict`): requests no endpoint matches are answered with a
+//! distinct status, logged as errors and counted, and the count is checked at
+//! shutdown so CI runs that hit unmocked endpoints can fail.
+
+use crate::config::StrictMode;
+use crate::telemetry::metrics::record_strict_unmatched;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static UNMATCHED: AtomicU64 = AtomicU64::new(0);
+
+/// Records an unmatched request served in strict mode.
+pub fn record_unmatched(method: &str, path: &str) {
+    let total = UNMATCHED.fetch_add(1, Ordering::Relaxed) + 1;
+    record_strict_unmatched(method);
+    tracing::error!(
+        method = %method,
+        path = %path,
+        unmatched_total = total,
+        "STRICT MODE: request matched no configured endpoint"
+    );
+}
+
+/// Unmatched requests served in strict mode since startup.
+pub fn unmatched_count() -> u64 {
+    UNMATCHED.load(Ordering::Relaxed)
+}
+
+/// The exit code the process should end with, if strict mode asks for one and any
+/// request went unmatched.
+pub fn exit_code(strict: &StrictMode) -> Option<i32> {
+    match strict.exit_code {
+        Some(code) if strict.enabled && unmatched_count() > 0 => Some(code),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_after_unmatched_request() {
+        let strict = StrictMode {
+            enabled: true,
+            exit_code: Some(3),
+            ..StrictMode::default()
+        };
+        let before = unmatched_count();
+        record_unmatched("GET", "/nobody/mocked/this");
+        assert!(unmatched_count() > before);
+
+        assert_eq!(exit_code(&strict), Some(3));
+        let no_code = StrictMode {
+            exit_code: None,
+            ..strict.clone()
+        };
+        assert_eq!(exit_code(&no_code), None);
+        let disabled = StrictMode {
+            enabled: false,
+            ..strict
+        };
+        assert_eq!(exit_code(&disabled), None);
+    }
+}
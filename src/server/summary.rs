@@ -0,0 +1,241 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The run summary written on shutdown with `--report <file>`: what the mock saw of
+//! a test run, as a JSON artifact for CI pipelines.
+
+use crate::rules::usage::EndpointUsage;
+use crate::rules::{RuleEngine, RuleError};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Latencies are kept in 1ms buckets up to this bound; slower requests share the
+/// last bucket, which keeps memory flat for long runs.
+const MAX_LATENCY_MS: usize = 60_000;
+
+static RUN: Lazy<Mutex<RunStats>> = Lazy::new(|| Mutex::new(RunStats::new()));
+/// Set when a report was requested; until then requests are not recorded, so
+/// servers without `--report` never take the lock.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct RunStats {
+    started_at: chrono::DateTime<chrono::Utc>,
+    total: u64,
+    unmatched: u64,
+    errors: BTreeMap<String, u64>,
+    faults: BTreeMap<String, u64>,
+    latency_buckets: Vec<u64>,
+}
+
+impl RunStats {
+    fn new() -> Self {
+        Self {
+            started_at: chrono::Utc::now(),
+            total: 0,
+            unmatched: 0,
+            errors: BTreeMap::new(),
+            faults: BTreeMap::new(),
+            latency_buckets: vec![0; MAX_LATENCY_MS + 1],
+        }
+    }
+
+    /// The smallest latency at or below which `quantile` of requests completed.
+    fn percentile(&self, quantile: f64) -> u64 {
+        let rank = (self.total as f64 * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (ms, count) in self.latency_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return ms as u64;
+            }
+        }
+        0
+    }
+}
+
+/// Starts recording requests for the run summary, from now on.
+pub fn enable() {
+    Lazy::force(&RUN);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Counts a served request, with the error it failed with if any.
+pub fn record_request(latency_ms: f64, error: Option<&RuleError>) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut run = RUN.lock().unwrap();
+    run.total += 1;
+    let bucket = (latency_ms.max(0.0) as usize).min(MAX_LATENCY_MS);
+    run.latency_buckets[bucket] += 1;
+    if let Some(error) = error {
+        if error.is_unmatched() {
+            run.unmatched += 1;
+        }
+        *run.errors
+            .entry(error.error_type().to_string())
+            .or_insert(0) += 1;
+    }
+}
+
+/// Counts a response served with an injected `fault`.
+pub fn record_fault(fault: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    *RUN.lock()
+        .unwrap()
+        .faults
+        .entry(fault.to_string())
+        .or_insert(0) += 1;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub started_at: String,
+    pub finished_at: String,
+    pub total_requests: u64,
+    pub unmatched_requests: u64,
+    /// Hits of every endpoint in the configuration served last, including unused ones,
+    /// counted over the whole run since reloads carry them over.
+    pub endpoints: Vec<EndpointUsage>,
+    /// Failed requests by error type.
+    pub errors: BTreeMap<String, u64>,
+    /// Responses served with an injected fault, by fault.
+    pub faults: BTreeMap<String, u64>,
+    pub latency: LatencySummary,
+}
+
+impl RunSummary {
+    /// Summarizes the run so far, taking endpoint hits from `engine`.
+    pub fn collect(engine: &RuleEngine) -> Self {
+        let run = RUN.lock().unwrap();
+        Self {
+            started_at: run.started_at.to_rfc3339(),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+            total_requests: run.total,
+            unmatched_requests: run.unmatched,
+            endpoints: engine.usage().endpoints,
+            errors: run.errors.clone(),
+            faults: run.faults.clone(),
+            latency: LatencySummary {
+                p50_ms: run.percentile(0.50),
+                p95_ms: run.percentile(0.95),
+                p99_ms: run.percentile(0.99),
+            },
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut run = RunStats::new();
+        for ms in 1..=100 {
+            run.total += 1;
+            run.latency_buckets[ms] += 1;
+        }
+        assert_eq!(run.percentile(0.50), 50);
+        assert_eq!(run.percentile(0.95), 95);
+        assert_eq!(run.percentile(0.99), 99);
+
+        assert_eq!(RunStats::new().percentile(0.5), 0);
+    }
+
+    #[test]
+    fn test_summary_collects_recorded_requests() {
+        let engine = RuleEngine::new(Vec::new());
+        enable();
+        record_request(
+            12.0,
+            Some(&RuleError::NoMatch {
+                method: "GET".to_string(),
+                path: "/nope".to_string(),
+            }),
+        );
+        record_request(3.0, None);
+        record_fault("gzip_truncated");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        RunSummary::collect(&engine).write(&path).unwrap();
+
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(report["total_requests"].as_u64().unwrap() >= 2);
+        assert!(report["unmatched_requests"].as_u64().unwrap() >= 1);
+        assert!(report["errors"]["no_match"].as_u64().unwrap() >= 1);
+        assert!(report["faults"]["gzip_truncated"].as_u64().unwrap() >= 1);
+        assert!(report["latency"]["p95_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_summary_counts_hits_across_reloads() {
+        use crate::config::ConfigLoader;
+        use crate::server::reload::Reloader;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let config = r#"
+endpoints:
+  - name: Ping
+    method: GET
+    path: /ping
+    responses: [{status: 200, body: pong}]
+"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("molock.yaml");
+        std::fs::write(&path, config).unwrap();
+        let loaded = ConfigLoader::from_file(&path).unwrap();
+        let engine = Arc::new(RuleEngine::from_config(&loaded));
+        let reloader = Reloader::new(path.clone(), loaded, engine);
+        let ping = || async {
+            let engine = reloader.engine().load_full();
+            engine
+                .execute("GET", "/ping", "", &HashMap::new(), None, "127.0.0.1")
+                .await
+                .unwrap();
+        };
+
+        ping().await;
+        std::fs::write(&path, config.replace("pong", "pong v2")).unwrap();
+        reloader.reload().await.unwrap();
+        ping().await;
+
+        let summary = RunSummary::collect(&reloader.engine().load());
+        assert_eq!(summary.endpoints[0].hits, 2);
+    }
+}
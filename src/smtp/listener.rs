@@ -0,0 +1,184 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A minimal SMTP server accepting every message (no auth, no TLS, no relaying).
+
+use crate::config::SmtpConfig;
+use crate::journal::{Journal, JournalEntry};
+use crate::smtp::{CapturedEmail, Mailbox};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// Binds the listener and accepts mail in the background.
+pub async fn start(
+    config: &SmtpConfig,
+    mailbox: Arc<Mailbox>,
+    journal: Option<Arc<Journal>>,
+) -> anyhow::Result<()> {
+    let addr = format!("{}:{}", config.host, config.port);
+    let listener = TcpListener::bind(&addr).await?;
+    info!("Starting SMTP listener on {}", addr);
+
+    tokio::spawn(accept_loop(listener, mailbox, journal));
+    Ok(())
+}
+
+async fn accept_loop(listener: TcpListener, mailbox: Arc<Mailbox>, journal: Option<Arc<Journal>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let mailbox = mailbox.clone();
+                let journal = journal.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = session(stream, &mailbox, journal.as_deref()).await {
+                        debug!(error = %e, "SMTP session ended");
+                    }
+                });
+            }
+            Err(e) => warn!(error = %e, "Failed to accept SMTP connection"),
+        }
+    }
+}
+
+async fn session(
+    stream: TcpStream,
+    mailbox: &Mailbox,
+    journal: Option<&Journal>,
+) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer.write_all(b"220 molock ESMTP\r\n").await?;
+
+    let mut from = String::new();
+    let mut to = Vec::new();
+
+    while let Some(line) = lines.next_line().await? {
+        let upper = line.to_ascii_uppercase();
+        let reply: &[u8] = if upper.starts_with("EHLO") || upper.starts_with("HELO") {
+            b"250 molock\r\n"
+        } else if upper.starts_with("MAIL FROM:") {
+            from = address(&line["MAIL FROM:".len()..]);
+            to.clear();
+            b"250 OK\r\n"
+        } else if upper.starts_with("RCPT TO:") {
+            to.push(address(&line["RCPT TO:".len()..]));
+            b"250 OK\r\n"
+        } else if upper == "DATA" {
+            writer
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await?;
+
+            let mut data = Vec::new();
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                // Undo dot-stuffing (RFC 5321 section 4.5.2).
+                data.push(line.strip_prefix('.').map(str::to_string).unwrap_or(line));
+            }
+
+            let email =
+                CapturedEmail::new(from.clone(), std::mem::take(&mut to), data.join("\r\n"));
+            info!(from = %email.from, to = ?email.to, subject = ?email.subject, "Email captured");
+            if let Some(journal) = journal {
+                journal.record(JournalEntry {
+                    timestamp: email.received_at.clone(),
+                    method: "SMTP".to_string(),
+                    path: email.to.join(","),
+                    endpoint: None,
+                    status: 250,
+                    latency_ms: 0.0,
+                });
+            }
+            mailbox.store(email);
+            b"250 OK: queued\r\n"
+        } else if upper == "RSET" {
+            from.clear();
+            to.clear();
+            b"250 OK\r\n"
+        } else if upper == "NOOP" {
+            b"250 OK\r\n"
+        } else if upper == "QUIT" {
+            writer.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else {
+            b"502 Command not implemented\r\n"
+        };
+
+        writer.write_all(reply).await?;
+    }
+
+    Ok(())
+}
+
+/// Extracts `a@b` from `<a@b>` or `<a@b> SIZE=123`.
+fn address(argument: &str) -> String {
+    let argument = argument.trim();
+    match (argument.find('<'), argument.find('>')) {
+        (Some(start), Some(end)) if start < end => argument[start + 1..end].to_string(),
+        _ => argument
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smtp::EmailQuery;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn test_address() {
+        assert_eq!(address(" <ann@example.com> SIZE=10"), "ann@example.com");
+        assert_eq!(address("bob@example.com"), "bob@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_capture_email() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mailbox = Arc::new(Mailbox::default());
+        tokio::spawn(accept_loop(listener, mailbox.clone(), None));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(
+                b"EHLO test\r\nMAIL FROM:<app@example.com>\r\nRCPT TO:<ann@example.com>\r\n\
+                  DATA\r\nSubject: Hi\r\n\r\n..leading dot\r\n.\r\nQUIT\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut transcript = String::new();
+        client.read_to_string(&mut transcript).await.unwrap();
+        assert!(transcript.starts_with("220 molock"));
+        assert!(transcript.contains("250 OK: queued"));
+        assert!(transcript.ends_with("221 Bye\r\n"));
+
+        let emails = mailbox.search(&EmailQuery::default());
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].from, "app@example.com");
+        assert_eq!(emails[0].to, vec!["ann@example.com"]);
+        assert_eq!(emails[0].subject.as_deref(), Some("Hi"));
+        assert_eq!(emails[0].data, "Subject: Hi\r\n\r\n.leading dot");
+    }
+}
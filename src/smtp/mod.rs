@@ -0,0 +1,165 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Email capture for applications under test that send mail.
+//!
+//! With the `smtp` feature and an `smtp:` config block, Molock accepts mail over SMTP
+//! and keeps every message in a [`Mailbox`], searchable through `/__admin/emails`.
+
+#[cfg(feature = "smtp")]
+pub mod listener;
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CapturedEmail {
+    pub id: String,
+    pub received_at: String,
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    /// The raw message as sent in the DATA phase, headers included.
+    pub data: String,
+}
+
+impl CapturedEmail {
+    pub fn new(from: String, to: Vec<String>, data: String) -> Self {
+        let subject = data
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("subject")
+                    .then(|| value.trim().to_string())
+            });
+
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            received_at: chrono::Utc::now().to_rfc3339(),
+            from,
+            to,
+            subject,
+            data,
+        }
+    }
+}
+
+/// Filters for [`Mailbox::search`]; every field set must match, case-insensitively.
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EmailQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    /// Free text searched in the whole message.
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Mailbox {
+    emails: Mutex<Vec<CapturedEmail>>,
+}
+
+impl Mailbox {
+    pub fn store(&self, email: CapturedEmail) {
+        self.emails
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(email);
+    }
+
+    pub fn search(&self, query: &EmailQuery) -> Vec<CapturedEmail> {
+        let contains = |haystack: &str, needle: &Option<String>| {
+            needle
+                .as_ref()
+                .is_none_or(|n| haystack.to_lowercase().contains(&n.to_lowercase()))
+        };
+
+        self.emails
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|email| {
+                contains(&email.from, &query.from)
+                    && contains(&email.to.join(","), &query.to)
+                    && contains(email.subject.as_deref().unwrap_or(""), &query.subject)
+                    && contains(&email.data, &query.q)
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.emails
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(from: &str, to: &str, subject: &str) -> CapturedEmail {
+        CapturedEmail::new(
+            from.to_string(),
+            vec![to.to_string()],
+            format!("Subject: {}\r\nFrom: {}\r\n\r\nHello {}", subject, from, to),
+        )
+    }
+
+    #[test]
+    fn test_subject_parsed_from_headers() {
+        let email = email("app@example.com", "ann@example.com", "Welcome!");
+        assert_eq!(email.subject.as_deref(), Some("Welcome!"));
+
+        let no_subject = CapturedEmail::new(String::new(), vec![], "\r\nSubject: body".into());
+        assert_eq!(no_subject.subject, None);
+    }
+
+    #[test]
+    fn test_search() {
+        let mailbox = Mailbox::default();
+        mailbox.store(email("app@example.com", "ann@example.com", "Welcome"));
+        mailbox.store(email(
+            "app@example.com",
+            "bob@example.com",
+            "Password reset",
+        ));
+
+        assert_eq!(mailbox.search(&EmailQuery::default()).len(), 2);
+
+        let found = mailbox.search(&EmailQuery {
+            to: Some("BOB@".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].subject.as_deref(), Some("Password reset"));
+
+        let found = mailbox.search(&EmailQuery {
+            q: Some("hello ann".to_string()),
+            subject: Some("welcome".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(found.len(), 1);
+
+        mailbox.clear();
+        assert!(mailbox.search(&EmailQuery::default()).is_empty());
+    }
+}
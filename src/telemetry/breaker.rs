@@ -0,0 +1,377 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Circuit breaker around the OTLP exporters. After `failure_threshold` failed exports
+//! in a row the breaker opens: exports are skipped without touching the network and
+//! spans are buffered up to `buffered_spans`, until every `probe_interval_seconds` a
+//! single export probes whether the collector is back. Traces and metrics share one
+//! breaker since they go to the same collector.
+
+use crate::config::ExportBreakerConfig;
+use once_cell::sync::OnceCell;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::error::OTelSdkResult;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::metrics::{
+    data::ResourceMetrics, exporter::PushMetricExporter, Temporality,
+};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::{SpanData, SpanExporter};
+#[cfg(feature = "otel")]
+use std::collections::VecDeque;
+
+static SHARED: OnceCell<Arc<CircuitBreaker>> = OnceCell::new();
+
+/// The breaker the exporters of this process share, created from the first
+/// configuration it is asked for.
+pub fn shared(config: &ExportBreakerConfig) -> Arc<CircuitBreaker> {
+    SHARED
+        .get_or_init(|| Arc::new(CircuitBreaker::new(config)))
+        .clone()
+}
+
+/// The shared breaker, if exporters were set up.
+pub fn current() -> Option<Arc<CircuitBreaker>> {
+    SHARED.get().cloned()
+}
+
+/// Whether an export may go out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permit {
+    Closed,
+    /// The breaker is open and this export probes the collector.
+    Probe,
+    Denied,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    probe_interval: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &ExportBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            probe_interval: Duration::from_secs(config.probe_interval_seconds),
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+
+    pub fn permit(&self) -> Permit {
+        self.permit_at(Instant::now())
+    }
+
+    fn permit_at(&self, now: Instant) -> Permit {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            None => Permit::Closed,
+            Some(until) if now >= until && !state.probing => {
+                state.probing = true;
+                Permit::Probe
+            }
+            Some(_) => Permit::Denied,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            info!("OpenTelemetry collector reachable again, resuming exports");
+        }
+        *state = BreakerState::default();
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now())
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.probing = false;
+        if state.open_until.is_some() || state.failures >= self.failure_threshold {
+            if state.open_until.is_none() {
+                warn!(
+                    failures = state.failures,
+                    probe_in = ?self.probe_interval,
+                    "OpenTelemetry collector keeps failing, pausing exports"
+                );
+            }
+            state.open_until = Some(now + self.probe_interval);
+        }
+    }
+
+    /// Opens the breaker right away, e.g. when the collector is unreachable at startup.
+    pub fn trip(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.max(self.failure_threshold);
+        state.probing = false;
+        state.open_until = Some(Instant::now() + self.probe_interval);
+    }
+
+    #[cfg(feature = "otel")]
+    fn record(&self, result: &OTelSdkResult) {
+        match result {
+            Ok(()) => self.record_success(),
+            Err(e) => {
+                tracing::debug!(error = %e, "OpenTelemetry export failed");
+                self.record_failure();
+            }
+        }
+    }
+}
+
+/// A span exporter that stops calling `inner` while the breaker is open.
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub struct BreakerSpanExporter<E> {
+    inner: E,
+    breaker: Arc<CircuitBreaker>,
+    buffer: Mutex<VecDeque<SpanData>>,
+    capacity: usize,
+}
+
+#[cfg(feature = "otel")]
+impl<E> BreakerSpanExporter<E> {
+    pub fn new(inner: E, breaker: Arc<CircuitBreaker>, capacity: usize) -> Self {
+        Self {
+            inner,
+            breaker,
+            buffer: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// Keeps the newest `capacity` spans.
+    fn hold(&self, spans: Vec<SpanData>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend(spans);
+        let excess = buffer.len().saturating_sub(self.capacity);
+        buffer.drain(..excess);
+    }
+}
+
+#[cfg(feature = "otel")]
+impl<E: SpanExporter> SpanExporter for BreakerSpanExporter<E> {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        match self.breaker.permit() {
+            Permit::Denied => {
+                self.hold(batch);
+                Ok(())
+            }
+            Permit::Closed => {
+                let result = self.inner.export(batch).await;
+                self.breaker.record(&result);
+                result
+            }
+            // Buffered spans go out with the probe and are kept if it fails.
+            Permit::Probe => {
+                let mut spans: Vec<SpanData> = self.buffer.lock().unwrap().drain(..).collect();
+                spans.extend(batch);
+                let result = self.inner.export(spans.clone()).await;
+                self.breaker.record(&result);
+                if result.is_err() {
+                    self.hold(spans);
+                }
+                result
+            }
+        }
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &opentelemetry_sdk::Resource) {
+        self.inner.set_resource(resource)
+    }
+}
+
+/// A metric exporter that skips exports while the breaker is open. Nothing is
+/// buffered: cumulative metrics catch up with the next export that goes out.
+#[cfg(feature = "otel")]
+pub struct BreakerMetricExporter<E> {
+    inner: E,
+    breaker: Arc<CircuitBreaker>,
+}
+
+#[cfg(feature = "otel")]
+impl<E> BreakerMetricExporter<E> {
+    pub fn new(inner: E, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { inner, breaker }
+    }
+}
+
+#[cfg(feature = "otel")]
+impl<E: PushMetricExporter> PushMetricExporter for BreakerMetricExporter<E> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        if self.breaker.permit() == Permit::Denied {
+            return Ok(());
+        }
+        let result = self.inner.export(metrics).await;
+        self.breaker.record(&result);
+        result
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.inner.temporality()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanContext, SpanId, SpanKind, Status};
+    use opentelemetry::InstrumentationScope;
+    use opentelemetry_sdk::error::OTelSdkError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(&ExportBreakerConfig {
+            failure_threshold,
+            probe_interval_seconds: 30,
+            buffered_spans: 2,
+        })
+    }
+
+    #[test]
+    fn test_breaker_opens_and_probes() {
+        let breaker = breaker(2);
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.permit_at(now), Permit::Closed);
+        breaker.record_failure_at(now);
+        assert!(breaker.is_open());
+        assert_eq!(breaker.permit_at(now), Permit::Denied);
+
+        // One probe at a time once the interval elapsed.
+        let later = now + Duration::from_secs(30);
+        assert_eq!(breaker.permit_at(later), Permit::Probe);
+        assert_eq!(breaker.permit_at(later), Permit::Denied);
+
+        // A failed probe waits another interval.
+        breaker.record_failure_at(later);
+        assert_eq!(breaker.permit_at(later), Permit::Denied);
+        let probe = later + Duration::from_secs(30);
+        assert_eq!(breaker.permit_at(probe), Permit::Probe);
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.permit_at(probe), Permit::Closed);
+    }
+
+    #[test]
+    fn test_trip_opens_immediately() {
+        let breaker = breaker(3);
+        breaker.trip();
+        assert_eq!(breaker.permit(), Permit::Denied);
+    }
+
+    #[derive(Debug, Default)]
+    struct Collector {
+        down: Arc<AtomicBool>,
+        received: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SpanExporter for Collector {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(OTelSdkError::InternalFailure(
+                    "connection refused".to_string(),
+                ));
+            }
+            let mut received = self.received.lock().unwrap();
+            received.extend(batch.into_iter().map(|span| span.name.into_owned()));
+            Ok(())
+        }
+    }
+
+    fn span(name: &'static str) -> SpanData {
+        SpanData {
+            span_context: SpanContext::empty_context(),
+            parent_span_id: SpanId::INVALID,
+            parent_span_is_remote: false,
+            span_kind: SpanKind::Server,
+            name: name.into(),
+            start_time: std::time::SystemTime::now(),
+            end_time: std::time::SystemTime::now(),
+            attributes: Vec::new(),
+            dropped_attributes_count: 0,
+            events: Default::default(),
+            links: Default::default(),
+            status: Status::Unset,
+            instrumentation_scope: InstrumentationScope::builder("test").build(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_span_exporter_buffers_while_open() {
+        let collector = Collector::default();
+        let down = collector.down.clone();
+        let received = collector.received.clone();
+        let breaker = Arc::new(breaker(1));
+        let exporter = BreakerSpanExporter::new(collector, breaker.clone(), 2);
+
+        down.store(true, Ordering::SeqCst);
+        assert!(exporter.export(vec![span("lost")]).await.is_err());
+        assert!(breaker.is_open());
+
+        // Skipped without reaching the collector; only the newest two are kept.
+        for name in ["a", "b", "c"] {
+            assert!(exporter.export(vec![span(name)]).await.is_ok());
+        }
+        assert_eq!(exporter.buffer.lock().unwrap().len(), 2);
+
+        down.store(false, Ordering::SeqCst);
+        breaker.state.lock().unwrap().open_until = Some(Instant::now());
+        exporter.export(vec![span("d")]).await.unwrap();
+
+        assert!(!breaker.is_open());
+        assert_eq!(*received.lock().unwrap(), vec!["b", "c", "d"]);
+        assert!(exporter.buffer.lock().unwrap().is_empty());
+    }
+}
@@ -14,12 +14,18 @@
  * limitations under the License.
  */
 
-use crate::config::TelemetryConfig;
+use crate::config::{EndpointMetric, Fault, MetricKind, TelemetryConfig};
 use crate::telemetry::attributes;
+#[cfg(feature = "otel")]
+use crate::telemetry::breaker;
+use crate::telemetry::statsd;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
 use tracing::{error, info, warn};
 
+/// `error.type` recorded for requests whose client went away before the response.
+pub const CLIENT_DISCONNECTED: &str = "client_disconnected";
+
 #[cfg(feature = "otel")]
 pub async fn init_metrics(config: &TelemetryConfig) -> anyhow::Result<()> {
     if !config.enabled {
@@ -112,6 +118,8 @@ pub async fn init_metrics(config: &TelemetryConfig) -> anyhow::Result<()> {
         error!("Failed to build OpenTelemetry metric exporter: {}", e);
         anyhow::anyhow!("OpenTelemetry metric exporter build failed: {}", e)
     })?;
+    let exporter =
+        breaker::BreakerMetricExporter::new(exporter, breaker::shared(&config.export_breaker));
 
     // Create meter provider with the exporter
     // Wrap exporter in a PeriodicReader for regular export
@@ -149,6 +157,14 @@ pub async fn init_metrics(config: &TelemetryConfig) -> anyhow::Result<()> {
 
 #[cfg(feature = "otel")]
 pub fn record_request(method: &str, path: &str, status: u16) {
+    statsd::count(
+        "requests",
+        &[
+            ("method", method),
+            ("route", path),
+            ("status", &status.to_string()),
+        ],
+    );
     use opentelemetry::global;
 
     let meter = global::meter("molock");
@@ -188,6 +204,14 @@ pub fn record_request(method: &str, path: &str, status: u16) {
 
 #[cfg(feature = "otel")]
 pub fn record_error(method: &str, path: &str, error_type: &str) {
+    statsd::count(
+        "errors",
+        &[
+            ("method", method),
+            ("route", path),
+            ("error_type", error_type),
+        ],
+    );
     use opentelemetry::global;
 
     let meter = global::meter("molock");
@@ -225,6 +249,11 @@ pub fn record_error(method: &str, path: &str, error_type: &str) {
 
 #[cfg(feature = "otel")]
 pub fn record_latency(method: &str, path: &str, latency_ms: f64) {
+    statsd::timing(
+        "latency",
+        latency_ms,
+        &[("method", method), ("route", path)],
+    );
     use opentelemetry::global;
 
     let meter = global::meter("molock");
@@ -267,8 +296,142 @@ pub fn record_latency(method: &str, path: &str, latency_ms: f64) {
     );
 }
 
+/// Applies an endpoint's custom metric, with its labels already rendered.
+#[cfg(feature = "otel")]
+pub fn record_endpoint_metric(metric: &EndpointMetric, labels: &[(String, String)]) {
+    use opentelemetry::global;
+
+    let meter = global::meter("molock");
+    let attributes: Vec<KeyValue> = labels
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect();
+
+    match metric.kind {
+        MetricKind::Counter => meter
+            .f64_counter(metric.name.clone())
+            .build()
+            .add(metric.increment, &attributes),
+        MetricKind::Gauge => meter
+            .f64_up_down_counter(metric.name.clone())
+            .build()
+            .add(metric.increment, &attributes),
+    }
+
+    tracing::debug!(
+        metric = %metric.name,
+        increment = metric.increment,
+        ?labels,
+        "Endpoint metric updated"
+    );
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_endpoint_metric(metric: &EndpointMetric, labels: &[(String, String)]) {
+    tracing::debug!(
+        metric = %metric.name,
+        increment = metric.increment,
+        ?labels,
+        "Endpoint metric updated"
+    );
+}
+
+/// Counts a response served with an injected `fault`.
+#[cfg(feature = "otel")]
+pub fn record_fault(path: &str, fault: Fault) {
+    use opentelemetry::global;
+
+    statsd::count("faults", &[("route", path), ("fault", fault.name())]);
+    let meter = global::meter("molock");
+    let counter = meter
+        .u64_counter("molock_faults_total")
+        .with_description("Responses served with an injected fault")
+        .build();
+    counter.add(
+        1,
+        &[
+            attributes::kv::http_route(path),
+            KeyValue::new("fault", fault.name()),
+        ],
+    );
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_fault(path: &str, fault: Fault) {
+    statsd::count("faults", &[("route", path), ("fault", fault.name())]);
+    tracing::debug!(path = %path, fault = fault.name(), "Fault injected");
+}
+
+/// Counts a request whose handler panicked.
+#[cfg(feature = "otel")]
+pub fn record_panic(path: &str) {
+    use opentelemetry::global;
+
+    statsd::count("panics", &[("route", path)]);
+    let meter = global::meter("molock");
+    let counter = meter
+        .u64_counter("molock_panics_total")
+        .with_description("Requests whose handler panicked")
+        .build();
+    counter.add(1, &[attributes::kv::http_route(path)]);
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_panic(path: &str) {
+    statsd::count("panics", &[("route", path)]);
+    tracing::debug!(path = %path, "Panic recorded");
+}
+
+/// Counts a request that matched no endpoint while `server.strict` is on.
+#[cfg(feature = "otel")]
+pub fn record_strict_unmatched(method: &str) {
+    use opentelemetry::global;
+
+    statsd::count("strict_unmatched", &[("method", method)]);
+    let meter = global::meter("molock");
+    let counter = meter
+        .u64_counter("molock_strict_unmatched_total")
+        .with_description("Requests no endpoint matched while strict mode was on")
+        .build();
+    counter.add(1, &[attributes::kv::http_method(method)]);
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_strict_unmatched(method: &str) {
+    statsd::count("strict_unmatched", &[("method", method)]);
+    tracing::debug!(method = %method, "Strict mode violation recorded");
+}
+
+/// Counts a configuration reload attempt by outcome (`success` or `failure`).
+#[cfg(feature = "otel")]
+pub fn record_config_reload(success: bool) {
+    use opentelemetry::global;
+
+    let meter = global::meter("molock");
+    let counter = meter
+        .u64_counter("molock_config_reload_total")
+        .with_description("Configuration reload attempts by result")
+        .build();
+
+    let result = if success { "success" } else { "failure" };
+    counter.add(1, &[KeyValue::new("result", result)]);
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn record_config_reload(success: bool) {
+    tracing::debug!(success, "Configuration reload recorded");
+}
+
 #[cfg(not(feature = "otel"))]
 pub fn record_request(method: &str, path: &str, status: u16) {
+    statsd::count(
+        "requests",
+        &[
+            ("method", method),
+            ("route", path),
+            ("status", &status.to_string()),
+        ],
+    );
     info!(
         method = %method,
         path = %path,
@@ -279,6 +442,14 @@ pub fn record_request(method: &str, path: &str, status: u16) {
 
 #[cfg(not(feature = "otel"))]
 pub fn record_error(method: &str, path: &str, error_type: &str) {
+    statsd::count(
+        "errors",
+        &[
+            ("method", method),
+            ("route", path),
+            ("error_type", error_type),
+        ],
+    );
     tracing::error!(
         method = %method,
         path = %path,
@@ -289,6 +460,11 @@ pub fn record_error(method: &str, path: &str, error_type: &str) {
 
 #[cfg(not(feature = "otel"))]
 pub fn record_latency(method: &str, path: &str, latency_ms: f64) {
+    statsd::timing(
+        "latency",
+        latency_ms,
+        &[("method", method), ("route", path)],
+    );
     tracing::debug!(
         method = %method,
         path = %path,
@@ -316,6 +492,9 @@ mod tests {
             timeout_seconds: 30,
             export_batch_size: 512,
             export_timeout_millis: 30000,
+            max_route_labels: 100,
+            statsd: None,
+            export_breaker: Default::default(),
         };
 
         let result = init_metrics(&config).await;
@@ -327,6 +506,9 @@ mod tests {
         record_request("GET", "/test", 200);
         record_error("GET", "/test", "timeout");
         record_latency("GET", "/test", 100.0);
+        record_config_reload(true);
+        record_fault("/test", Fault::GzipTruncated);
+        record_config_reload(false);
     }
 
     #[test]
@@ -15,8 +15,13 @@
  */
 
 pub mod attributes;
+pub mod breaker;
 pub mod metrics;
 pub mod otel_direct;
+pub mod pretty;
+pub mod routes;
+pub mod runtime;
+pub mod statsd;
 pub mod tracer;
 
 pub use metrics::init_metrics;
@@ -24,8 +29,54 @@ pub use tracer::init_tracing;
 
 use crate::config::TelemetryConfig;
 use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
 use std::time::Duration;
 use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryState {
+    Disabled,
+    /// Exporters are being built in the background while the server already serves.
+    Initializing,
+    Ready,
+    /// Building the exporters failed; the server keeps serving without exporting.
+    Failed,
+}
+
+/// Progress of the background telemetry initialization, as reported by `/ready`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TelemetryStatus {
+    pub state: TelemetryState,
+    /// Why initialization failed.
+    pub error: Option<String>,
+    /// Whether the collector passed the connectivity check; unknown until it ran.
+    pub collector_reachable: Option<bool>,
+    /// False while the export breaker is open and exports are skipped.
+    pub exporting: bool,
+}
+
+static STATUS: Mutex<TelemetryStatus> = Mutex::new(TelemetryStatus {
+    state: TelemetryState::Disabled,
+    error: None,
+    collector_reachable: None,
+    exporting: false,
+});
+
+fn set_status(state: TelemetryState, error: Option<String>) {
+    let mut status = STATUS.lock().unwrap();
+    status.state = state;
+    status.error = error;
+}
+
+pub fn status() -> TelemetryStatus {
+    let mut status = STATUS.lock().unwrap().clone();
+    status.exporting = status.state == TelemetryState::Ready
+        && breaker::current().is_none_or(|breaker| !breaker.is_open());
+    status
+}
 
 /// Check if telemetry debug mode is enabled via environment variable
 pub fn is_debug_enabled() -> bool {
@@ -109,27 +160,33 @@ async fn test_connectivity(endpoint: &str, protocol: &str) -> anyhow::Result<()>
     }
 }
 
-/// Test connectivity with retry logic
-async fn test_connectivity_with_retry(endpoint: &str, protocol: &str) -> anyhow::Result<()> {
-    let max_retries = 3;
+/// Test connectivity with retry logic, until the connection succeeds or the failures
+/// open the export breaker.
+async fn test_connectivity_with_retry(
+    endpoint: &str,
+    protocol: &str,
+    breaker: &breaker::CircuitBreaker,
+) -> anyhow::Result<()> {
     let mut retry_delay = Duration::from_secs(1);
 
-    for attempt in 1..=max_retries {
+    for attempt in 1.. {
         info!(
-            "Connectivity test attempt {}/{} to {} endpoint",
-            attempt, max_retries, protocol
+            "Connectivity test attempt {} to {} endpoint",
+            attempt, protocol
         );
 
         match test_connectivity(endpoint, protocol).await {
             Ok(_) => {
                 info!("Connectivity test passed on attempt {}", attempt);
+                breaker.record_success();
                 return Ok(());
             }
-            Err(e) if attempt == max_retries => {
-                error!("All connectivity attempts failed: {}", e);
-                return Err(e);
-            }
             Err(e) => {
+                breaker.record_failure();
+                if breaker.is_open() {
+                    error!("All connectivity attempts failed: {}", e);
+                    return Err(e);
+                }
                 warn!("Connectivity attempt {} failed: {}", attempt, e);
                 warn!("Retrying in {:?}...", retry_delay);
                 tokio::time::sleep(retry_delay).await;
@@ -141,12 +198,23 @@ async fn test_connectivity_with_retry(endpoint: &str, protocol: &str) -> anyhow:
     unreachable!()
 }
 
+/// Installs logging and returns; exporters are built and the collector is checked
+/// in a background task, so a slow or absent collector never delays startup. Its
+/// progress is reported by [`status`].
 pub async fn init_telemetry(config: &TelemetryConfig) -> anyhow::Result<()> {
+    routes::set_max_route_labels(config.max_route_labels);
+
+    if let Some(statsd) = &config.statsd {
+        statsd::init(statsd)?;
+    }
+
     if !config.enabled {
+        set_status(TelemetryState::Disabled, None);
         info!("Telemetry is disabled");
         return Ok(());
     }
 
+    tracer::install_subscriber(config);
     info!(
         "Initializing telemetry with service name: {}",
         config.service_name
@@ -155,41 +223,64 @@ pub async fn init_telemetry(config: &TelemetryConfig) -> anyhow::Result<()> {
     // Debug logging
     debug_log("Starting telemetry initialization", config);
 
-    // Test connectivity before initialization
-    info!("Testing connectivity to OpenTelemetry collector...");
-    match test_connectivity_with_retry(&config.endpoint, &config.protocol).await {
-        Ok(_) => info!("Connectivity test passed"),
-        Err(e) => {
-            error!("Connectivity test failed: {}", e);
-            error!("OpenTelemetry collector is unreachable. Telemetry data will not be exported.");
-            error!(
-                "Check if OpenTelemetry collector is running at: {}",
-                config.endpoint
-            );
-            // Log error but continue - telemetry will be initialized but may not work
-            // This allows the application to start even if observability is unavailable
+    set_status(TelemetryState::Initializing, None);
+    let config = config.clone();
+    tokio::spawn(async move {
+        match start_exporters(&config).await {
+            Ok(()) => {
+                set_status(TelemetryState::Ready, None);
+                info!("Telemetry initialized successfully");
+                debug_log("Telemetry initialization completed successfully", &config);
+            }
+            Err(e) => {
+                error!("Telemetry initialization failed, not exporting: {:#}", e);
+                set_status(TelemetryState::Failed, Some(format!("{:#}", e)));
+            }
         }
-    }
+    });
 
-    // Add a small delay to avoid race conditions
-    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    Ok(())
+}
+
+async fn start_exporters(config: &TelemetryConfig) -> anyhow::Result<()> {
+    // Failed connectivity checks open the export breaker, which then skips exports
+    // until the collector answers a probe.
+    info!("Testing connectivity to OpenTelemetry collector...");
+    let breaker = breaker::shared(&config.export_breaker);
+    let endpoint = config.endpoint.clone();
+    let protocol = config.protocol.clone();
+    tokio::spawn(async move {
+        let result = test_connectivity_with_retry(&endpoint, &protocol, &breaker).await;
+        STATUS.lock().unwrap().collector_reachable = Some(result.is_ok());
+        match result {
+            Ok(_) => info!("Connectivity test passed"),
+            Err(e) => {
+                error!("Connectivity test failed: {}", e);
+                error!(
+                    "OpenTelemetry collector is unreachable. Telemetry data will not be exported until it is."
+                );
+                error!(
+                    "Check if OpenTelemetry collector is running at: {}",
+                    endpoint
+                );
+            }
+        }
+    });
 
-    // Initialize tracing first (which includes logging)
     info!("Starting tracing initialization...");
     init_tracing(config)
         .await
         .context("Failed to initialize tracing")?;
     info!("Tracing initialized, starting metrics...");
 
-    // Another small delay between tracing and metrics
+    // A small delay between tracing and metrics
     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
     init_metrics(config)
         .await
         .context("Failed to initialize metrics")?;
+    runtime::register_runtime_metrics();
 
-    info!("Telemetry initialized successfully");
-    debug_log("Telemetry initialization completed successfully", config);
     Ok(())
 }
 
@@ -221,10 +312,15 @@ mod tests {
             timeout_seconds: 30,
             export_batch_size: 512,
             export_timeout_millis: 30000,
+            max_route_labels: 100,
+            statsd: None,
+            export_breaker: Default::default(),
         };
 
         let result = init_telemetry(&config).await;
         assert!(result.is_ok());
+        assert_eq!(status().state, TelemetryState::Disabled);
+        assert!(!status().exporting);
     }
 
     #[test]
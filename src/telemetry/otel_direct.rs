@@ -44,11 +44,12 @@ fn get_tracer() -> Option<SdkTracer> {
 /// The `parent_cx` parameter allows linking this span to an upstream trace extracted
 /// from incoming request headers (W3C `traceparent`/`tracestate`). Pass
 /// `&Context::current()` when no parent context is available.
+///
+/// `http.route` is only known once routing has run; set it with [`set_http_route`].
 pub fn create_http_server_span(
     name: String,
     method: String,
     target: String,
-    route: String,
     parent_cx: &Context,
 ) -> Option<Span> {
     let tracer = get_tracer()?;
@@ -59,13 +60,44 @@ pub fn create_http_server_span(
         .with_attributes(vec![
             attributes::kv::http_method(&method),
             attributes::kv::http_target(&target),
-            attributes::kv::http_route(&route),
         ])
         .start_with_context(&tracer, parent_cx);
 
     Some(span)
 }
 
+/// A server span still waiting for its response. If it is dropped before
+/// [`finish`](Self::finish) — the request future was cancelled because the client
+/// disconnected — the span ends with a `client_disconnected` error.
+pub struct PendingServerSpan(Option<Span>);
+
+impl PendingServerSpan {
+    pub fn new(span: Span) -> Self {
+        Self(Some(span))
+    }
+
+    pub fn finish(mut self) -> Span {
+        self.0.take().expect("span is only taken once")
+    }
+}
+
+impl Drop for PendingServerSpan {
+    fn drop(&mut self) {
+        if let Some(mut span) = self.0.take() {
+            span.set_attribute(attributes::kv::error_type(
+                crate::telemetry::metrics::CLIENT_DISCONNECTED,
+            ));
+            span.set_status(Status::error("Client disconnected"));
+            span.end();
+        }
+    }
+}
+
+/// Set the matched route pattern (`http.route`) on a span using direct OpenTelemetry API
+pub fn set_http_route(span: &mut Span, route: &str) {
+    span.set_attribute(attributes::kv::http_route(route));
+}
+
 /// Set HTTP response status code on a span using direct OpenTelemetry API
 pub fn set_http_response_status_code(span: &mut Span, status: u16) {
     // Set the correct semantic convention: http.response.status_code
@@ -99,7 +131,7 @@ mod tests {
         let _guard = TEST_LOCK.lock().unwrap();
 
         let original_provider = {
-            let provider = TRACER_PROVIDER.write().unwrap();
+            let provider = TRACER_PROVIDER.read().unwrap();
             provider.clone()
         };
 
@@ -112,7 +144,6 @@ mod tests {
             "test-span".to_string(),
             "GET".to_string(),
             "/test".to_string(),
-            "/test".to_string(),
             &cx,
         );
         assert!(span.is_none());
@@ -179,7 +210,6 @@ mod tests {
             "http.request".to_string(),
             "GET".to_string(),
             "/api/users".to_string(),
-            "/api/users".to_string(),
             &cx,
         );
 
@@ -216,7 +246,6 @@ mod tests {
             "http.request".to_string(),
             "GET".to_string(),
             "/test".to_string(),
-            "/test".to_string(),
             &cx,
         );
         assert!(span.is_some());
@@ -258,7 +287,6 @@ mod tests {
             "http.request".to_string(),
             "GET".to_string(),
             "/api/resource".to_string(),
-            "/api/resource".to_string(),
             &parent_cx,
         );
 
@@ -356,7 +384,6 @@ mod tests {
                 "http.request".to_string(),
                 method.to_string(),
                 "/api/test".to_string(),
-                "/api/test".to_string(),
                 &cx,
             );
 
@@ -395,7 +422,6 @@ mod tests {
                 "http.request".to_string(),
                 "GET".to_string(),
                 path.to_string(),
-                path.to_string(),
                 &cx,
             );
 
@@ -425,7 +451,6 @@ mod tests {
             "http.request".to_string(),
             "POST".to_string(),
             "/api/users".to_string(),
-            "/api/users".to_string(),
             &cx,
         )
         .unwrap();
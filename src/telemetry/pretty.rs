@@ -0,0 +1,226 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! `log_format: pretty`: colorized single-line output for local development, with
+//! one summary line per served request.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const CYAN: &str = "\x1b[36m";
+
+/// The fmt layer for the pretty format. The metrics module's per-request log lines
+/// are left out since the request summary already carries them.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .event_format(PrettyFormat)
+        .with_filter(filter_fn(|metadata| {
+            !(metadata.target().starts_with("molock::telemetry::metrics")
+                && *metadata.level() >= Level::INFO)
+        }))
+}
+
+pub struct PrettyFormat;
+
+impl<S, N> FormatEvent<S, N> for PrettyFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let time = chrono::Local::now().format("%H:%M:%S%.3f");
+        let line = if fields.is_request_summary() {
+            fields.request_line(writer.has_ansi_escapes())
+        } else {
+            fields.event_line(event.metadata().level(), writer.has_ansi_escapes())
+        };
+        writeln!(
+            writer,
+            "{} {}",
+            paint(&time.to_string(), DIM, writer.has_ansi_escapes()),
+            line
+        )
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    values: BTreeMap<&'static str, String>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.values.insert(field.name(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.values.insert(field.name(), format!("{:?}", value));
+        }
+    }
+}
+
+impl Fields {
+    fn is_request_summary(&self) -> bool {
+        self.message == "Request completed"
+            && ["method", "path", "status", "latency_ms"]
+                .iter()
+                .all(|name| self.values.contains_key(name))
+    }
+
+    fn get(&self, name: &str) -> &str {
+        self.values.get(name).map(String::as_str).unwrap_or("-")
+    }
+
+    /// `GET    /orders/7 → Get Order  200  12ms (delay 10ms)`
+    fn request_line(&self, ansi: bool) -> String {
+        let status = self.get("status");
+        let color = match status.chars().next() {
+            Some('2') => GREEN,
+            Some('3') => CYAN,
+            Some('4') => YELLOW,
+            _ => RED,
+        };
+        let latency = self
+            .get("latency_ms")
+            .parse::<f64>()
+            .map(|ms| format!("{:.0}ms", ms))
+            .unwrap_or_else(|_| "-".to_string());
+
+        let mut line = format!(
+            "{} {} {} {}  {}  {}",
+            paint(&format!("{:<6}", self.get("method")), BOLD, ansi),
+            self.get("path"),
+            paint("→", DIM, ansi),
+            paint(self.get("endpoint"), BLUE, ansi),
+            paint(status, color, ansi),
+            latency,
+        );
+        match self.values.get("delay_ms") {
+            Some(delay) if delay != "0" => {
+                line.push_str(&paint(&format!(" (delay {}ms)", delay), DIM, ansi))
+            }
+            _ => {}
+        }
+        line
+    }
+
+    fn event_line(&self, level: &Level, ansi: bool) -> String {
+        let color = match *level {
+            Level::ERROR => RED,
+            Level::WARN => YELLOW,
+            Level::INFO => GREEN,
+            _ => DIM,
+        };
+        let mut line = format!(
+            "{} {}",
+            paint(&format!("{:<5}", level), color, ansi),
+            self.message
+        );
+        for (name, value) in &self.values {
+            line.push_str(&paint(&format!(" {}={}", name, value), DIM, ansi));
+        }
+        line
+    }
+}
+
+fn paint(text: &str, color: &str, ansi: bool) -> String {
+    if ansi {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(message: &str, values: &[(&'static str, &str)]) -> Fields {
+        Fields {
+            message: message.to_string(),
+            values: values.iter().map(|(k, v)| (*k, v.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_request_line() {
+        let summary = fields(
+            "Request completed",
+            &[
+                ("method", "GET"),
+                ("path", "/orders/7"),
+                ("endpoint", "Get Order"),
+                ("status", "200"),
+                ("latency_ms", "12.4"),
+                ("delay_ms", "10"),
+            ],
+        );
+        assert!(summary.is_request_summary());
+        assert_eq!(
+            summary.request_line(false),
+            "GET    /orders/7 → Get Order  200  12ms (delay 10ms)"
+        );
+        assert_eq!(
+            summary.request_line(true),
+            format!(
+                "{BOLD}GET   {RESET} /orders/7 {DIM}→{RESET} {BLUE}Get Order{RESET}  {GREEN}200{RESET}  12ms{DIM} (delay 10ms){RESET}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_event_line() {
+        let event = fields("Starting server", &[("port", "8080")]);
+        assert!(!event.is_request_summary());
+        assert_eq!(
+            event.event_line(&Level::INFO, false),
+            "INFO  Starting server port=8080"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Route labels for metrics and spans. Requests are labelled by the configured
+//! endpoint path pattern (`/users/:id`) rather than the raw path, so clients sending
+//! many unique URLs cannot blow up label cardinality.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Label for requests no endpoint or admin route matched.
+pub const UNMATCHED: &str = "unmatched";
+/// Label for matched routes once `max_route_labels` distinct routes have been seen.
+pub const OVERFLOW: &str = "other";
+
+pub const DEFAULT_MAX_ROUTE_LABELS: usize = 100;
+
+static ROUTE_LABELS: RouteLabels = RouteLabels::new(DEFAULT_MAX_ROUTE_LABELS);
+
+/// The route pattern a response was served by, attached to the `HttpResponse` so the
+/// tracing middleware can label its span after the handler ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedRoute(pub String);
+
+/// Hands out route labels, up to a maximum number of distinct ones.
+#[derive(Debug)]
+pub struct RouteLabels {
+    max: AtomicUsize,
+    seen: Mutex<BTreeSet<String>>,
+}
+
+impl RouteLabels {
+    pub const fn new(max: usize) -> Self {
+        Self {
+            max: AtomicUsize::new(max),
+            seen: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max, Ordering::Relaxed);
+    }
+
+    /// Returns the label to record for a request served by `route`, if any.
+    pub fn label(&self, route: Option<&str>) -> String {
+        let Some(route) = route else {
+            return UNMATCHED.to_string();
+        };
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        if seen.contains(route) {
+            return route.to_string();
+        }
+        if seen.len() >= self.max.load(Ordering::Relaxed) {
+            return OVERFLOW.to_string();
+        }
+        seen.insert(route.to_string());
+        route.to_string()
+    }
+}
+
+/// Caps the number of distinct route labels handed out.
+pub fn set_max_route_labels(max: usize) {
+    ROUTE_LABELS.set_max(max);
+}
+
+/// Returns the label to record for a request served by `route`, if any.
+pub fn route_label(route: Option<&str>) -> String {
+    ROUTE_LABELS.label(route)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_label() {
+        let labels = RouteLabels::new(3);
+        assert_eq!(labels.label(None), UNMATCHED);
+        assert_eq!(labels.label(Some("/users/:id")), "/users/:id");
+        assert_eq!(labels.label(Some("/orders")), "/orders");
+        assert_eq!(labels.label(Some("/orders/:id")), "/orders/:id");
+
+        assert_eq!(labels.label(Some("/one-too-many")), OVERFLOW);
+        assert_eq!(labels.label(Some("/users/:id")), "/users/:id");
+
+        labels.set_max(4);
+        assert_eq!(labels.label(Some("/one-too-many")), "/one-too-many");
+    }
+}
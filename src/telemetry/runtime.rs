@@ -0,0 +1,137 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Saturation metrics for load testing: the tokio runtimes of the HTTP workers and
+//! the number of requests being served at once.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use tokio::runtime::Handle;
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static WORKER_RUNTIMES: Mutex<Vec<(ThreadId, Handle)>> = Mutex::new(Vec::new());
+
+/// Remembers the runtime of the calling HTTP worker thread. Called from the app
+/// factory, which runs once on each worker.
+pub fn register_worker_runtime() {
+    let Ok(handle) = Handle::try_current() else {
+        return;
+    };
+    let thread = std::thread::current().id();
+    let mut runtimes = WORKER_RUNTIMES.lock().unwrap_or_else(|e| e.into_inner());
+    runtimes.retain(|(id, _)| *id != thread);
+    runtimes.push((thread, handle));
+}
+
+/// Counts a request as in flight until dropped.
+pub struct InFlightRequest(());
+
+impl InFlightRequest {
+    pub fn start() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RuntimeSnapshot {
+    /// HTTP worker runtimes seen so far.
+    pub workers: usize,
+    /// Tasks alive across the worker runtimes.
+    pub alive_tasks: usize,
+    /// Tasks scheduled but not yet polled, summed over the worker runtimes.
+    pub queue_depth: usize,
+    pub in_flight_requests: usize,
+}
+
+pub fn snapshot() -> RuntimeSnapshot {
+    let runtimes = WORKER_RUNTIMES.lock().unwrap_or_else(|e| e.into_inner());
+    runtimes.iter().fold(
+        RuntimeSnapshot {
+            in_flight_requests: IN_FLIGHT.load(Ordering::Relaxed),
+            ..Default::default()
+        },
+        |mut snapshot, (_, handle)| {
+            let metrics = handle.metrics();
+            snapshot.workers += 1;
+            snapshot.alive_tasks += metrics.num_alive_tasks();
+            snapshot.queue_depth += metrics.global_queue_depth();
+            snapshot
+        },
+    )
+}
+
+/// Exports the [`snapshot`] values as observable gauges under `molock.runtime.*`.
+#[cfg(feature = "otel")]
+pub fn register_runtime_metrics() {
+    let meter = opentelemetry::global::meter("molock");
+    let gauge =
+        |name: &'static str, description: &'static str, read: fn(RuntimeSnapshot) -> usize| {
+            meter
+                .u64_observable_gauge(name)
+                .with_description(description)
+                .with_callback(move |observer| observer.observe(read(snapshot()) as u64, &[]))
+                .build()
+        };
+
+    // The instruments stay registered with the meter provider once built.
+    gauge("molock.runtime.workers", "HTTP worker runtimes", |s| {
+        s.workers
+    });
+    gauge(
+        "molock.runtime.alive_tasks",
+        "Tasks alive across the HTTP worker runtimes",
+        |s| s.alive_tasks,
+    );
+    gauge(
+        "molock.runtime.queue_depth",
+        "Tasks waiting to be polled on the HTTP worker runtimes",
+        |s| s.queue_depth,
+    );
+    gauge(
+        "molock.server.in_flight_requests",
+        "Requests currently being served",
+        |s| s.in_flight_requests,
+    );
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn register_runtime_metrics() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_counts_workers_and_requests() {
+        register_worker_runtime();
+        register_worker_runtime();
+
+        let request = InFlightRequest::start();
+        let snapshot = snapshot();
+        assert!(snapshot.workers >= 1);
+        assert!(snapshot.in_flight_requests >= 1);
+        drop(request);
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright 2026 Molock Team
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! StatsD/DogStatsD emitter for CI infrastructure that only scrapes StatsD. Metrics
+//! are sent as fire-and-forget UDP datagrams, so an absent agent costs nothing.
+
+use crate::config::StatsdConfig;
+use anyhow::Context;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::OnceLock;
+
+static CLIENT: OnceLock<StatsdClient> = OnceLock::new();
+
+#[derive(Debug)]
+struct StatsdClient {
+    socket: UdpSocket,
+    prefix: String,
+    dogstatsd: bool,
+}
+
+impl StatsdClient {
+    /// A socket connected to the configured agent, bound to the unspecified address
+    /// of the agent's family so IPv6 agents work too.
+    fn connect(config: &StatsdConfig) -> anyhow::Result<Self> {
+        let agent = config
+            .address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .with_context(|| format!("Invalid StatsD address: {}", config.address))?;
+        let local = match agent {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local).context("Failed to bind StatsD socket")?;
+        socket
+            .connect(agent)
+            .with_context(|| format!("Failed to connect to StatsD agent {}", agent))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            prefix: config.prefix.clone(),
+            dogstatsd: config.dogstatsd,
+        })
+    }
+
+    fn send(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let line = format_line(&self.prefix, self.dogstatsd, name, value, kind, tags);
+        if let Err(e) = self.socket.send(line.as_bytes()) {
+            tracing::debug!(error = %e, "Failed to send StatsD metric");
+        }
+    }
+}
+
+/// Starts emitting to the configured agent. Only the first call takes effect.
+pub fn init(config: &StatsdConfig) -> anyhow::Result<()> {
+    let client = StatsdClient::connect(config)?;
+    if CLIENT.set(client).is_ok() {
+        tracing::info!(address = %config.address, "Emitting StatsD metrics");
+    }
+    Ok(())
+}
+
+pub fn count(name: &str, tags: &[(&str, &str)]) {
+    send(name, "1", "c", tags);
+}
+
+pub fn timing(name: &str, ms: f64, tags: &[(&str, &str)]) {
+    send(name, &format!("{}", ms), "ms", tags);
+}
+
+fn send(name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+    if let Some(client) = CLIENT.get() {
+        client.send(name, value, kind, tags);
+    }
+}
+
+/// One metric line. DogStatsD gets tags (`name:1|c|#route:/users/:id`); plain StatsD
+/// has none, so their values become name segments (`name._users__id:1|c`).
+fn format_line(
+    prefix: &str,
+    dogstatsd: bool,
+    name: &str,
+    value: &str,
+    kind: &str,
+    tags: &[(&str, &str)],
+) -> String {
+    let mut metric = if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    };
+
+    if dogstatsd {
+        let mut line = format!("{}:{}|{}", metric, value, kind);
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|(key, value)| format!("{}:{}", key, value.replace([',', '|', '#'], "_")))
+                .collect();
+            line.push_str("|#");
+            line.push_str(&tags.join(","));
+        }
+        return line;
+    }
+
+    for (_, value) in tags {
+        metric.push('.');
+        metric.extend(value.chars().map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        }));
+    }
+    format!("{}:{}|{}", metric, value, kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dogstatsd_line() {
+        assert_eq!(
+            format_line(
+                "molock",
+                true,
+                "requests",
+                "1",
+                "c",
+                &[
+                    ("method", "GET"),
+                    ("route", "/users/:id"),
+                    ("status", "200")
+                ]
+            ),
+            "molock.requests:1|c|#method:GET,route:/users/:id,status:200"
+        );
+        assert_eq!(
+            format_line("", true, "latency", "12.5", "ms", &[]),
+            "latency:12.5|ms"
+        );
+    }
+
+    #[test]
+    fn test_statsd_line_folds_tags_into_name() {
+        assert_eq!(
+            format_line(
+                "molock",
+                false,
+                "requests",
+                "1",
+                "c",
+                &[
+                    ("method", "GET"),
+                    ("route", "/users/:id"),
+                    ("status", "200")
+                ]
+            ),
+            "molock.requests.GET._users__id.200:1|c"
+        );
+    }
+
+    #[test]
+    fn test_emits_over_udp() {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        agent
+            .set_read_timeout(Some(std::time::Duration::from_secs(2)))
+            .unwrap();
+        let client = StatsdClient::connect(&StatsdConfig {
+            address: agent.local_addr().unwrap().to_string(),
+            prefix: "test".to_string(),
+            dogstatsd: true,
+        })
+        .unwrap();
+
+        client.send("statsd_probe", "1", "c", &[("kind", "udp")]);
+
+        let mut buf = [0; 512];
+        let len = agent.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"test.statsd_probe:1|c|#kind:udp");
+    }
+
+    #[test]
+    fn test_binds_to_agent_address_family() {
+        // Skipped where the host has no IPv6 loopback.
+        let Ok(agent) = UdpSocket::bind("[::1]:0") else {
+            return;
+        };
+        let client = StatsdClient::connect(&StatsdConfig {
+            address: agent.local_addr().unwrap().to_string(),
+            prefix: String::new(),
+            dogstatsd: false,
+        })
+        .unwrap();
+        assert!(client.socket.local_addr().unwrap().is_ipv6());
+    }
+}
@@ -16,21 +16,38 @@
 
 use crate::config::TelemetryConfig;
 use crate::telemetry::attributes;
+#[cfg(feature = "otel")]
+use crate::telemetry::breaker;
 use crate::telemetry::otel_direct;
+use crate::telemetry::routes::{self, MatchedRoute};
 use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
 use futures::future::LocalBoxFuture;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
 
+#[cfg(feature = "otel")]
+use once_cell::sync::OnceCell;
+#[cfg(feature = "otel")]
+use std::any::TypeId;
 use std::future::ready;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::task::{Context as TaskContext, Poll};
+#[cfg(feature = "otel")]
+use tracing::span::{Attributes, Id, Record};
+#[cfg(feature = "otel")]
+use tracing::Event;
 use tracing::{error, info, warn};
 use tracing_subscriber::layer::SubscriberExt;
+#[cfg(feature = "otel")]
+use tracing_subscriber::layer::{Context as LayerContext, Layer, Layered};
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Registry;
 
+/// Set once Molock installed its own subscriber, rather than finding one set.
+static SUBSCRIBER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
 /// Adapts actix-web's `HeaderMap` to the `opentelemetry::propagation::Extractor`
 /// trait so that W3C `traceparent`/`tracestate` headers can be extracted from
 /// incoming requests.  `opentelemetry_http::HeaderExtractor` expects `http::HeaderMap`
@@ -49,6 +66,105 @@ impl opentelemetry::propagation::Extractor for ActixHeaderExtractor<'_> {
     }
 }
 
+/// The subscriber the OpenTelemetry layer sits on.
+#[cfg(feature = "otel")]
+type OtelSubscriber = Layered<tracing_subscriber::EnvFilter, Registry>;
+
+#[cfg(feature = "otel")]
+type OtelLayer =
+    tracing_opentelemetry::OpenTelemetryLayer<OtelSubscriber, opentelemetry::global::BoxedTracer>;
+
+/// Filled in by [`init_tracing`] once the exporter is built, possibly after the
+/// subscriber started logging.
+#[cfg(feature = "otel")]
+static OTEL_LAYER: OnceCell<OtelLayer> = OnceCell::new();
+
+/// Forwards to the OpenTelemetry layer once it is set, so logging can start before
+/// the exporter is built. Spans opened earlier are simply not exported.
+#[cfg(feature = "otel")]
+struct LateOtelLayer;
+
+#[cfg(feature = "otel")]
+impl Layer<OtelSubscriber> for LateOtelLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_new_span(attrs, id, ctx);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_record(id, values, ctx);
+        }
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_follows_from(id, follows, ctx);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_enter(id, ctx);
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_exit(id, ctx);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, OtelSubscriber>) {
+        if let Some(layer) = OTEL_LAYER.get() {
+            layer.on_close(id, ctx);
+        }
+    }
+
+    // Lets `OpenTelemetrySpanExt::set_parent` reach the layer. The pointers stay valid
+    // since the layer lives in a static that is never replaced.
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        OTEL_LAYER.get().and_then(|layer| layer.downcast_raw(id))
+    }
+}
+
+/// Installs the log subscriber, ready to export spans once [`init_tracing`] built
+/// the exporter. Does nothing when telemetry is disabled or a subscriber is set.
+#[cfg(feature = "otel")]
+pub fn install_subscriber(config: &TelemetryConfig) {
+    use tracing::dispatcher::has_been_set;
+    if !config.enabled || has_been_set() {
+        return;
+    }
+
+    let subscriber = Registry::default()
+        .with(tracing_subscriber::EnvFilter::new(&config.log_level))
+        .with(LateOtelLayer);
+
+    let installed = match config.log_format.as_str() {
+        "json" => subscriber
+            .with(tracing_subscriber::fmt::layer().json())
+            .try_init(),
+        "pretty" => subscriber
+            .with(crate::telemetry::pretty::layer())
+            .try_init(),
+        _ => subscriber.with(tracing_subscriber::fmt::layer()).try_init(),
+    };
+    if installed.is_ok() {
+        SUBSCRIBER_INSTALLED.store(true, Ordering::SeqCst);
+    }
+}
+
 #[cfg(feature = "otel")]
 pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
     if !config.enabled {
@@ -70,9 +186,8 @@ pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
         );
     }
 
-    // Check if a subscriber is already set
-    use tracing::dispatcher::has_been_set;
-    if has_been_set() {
+    install_subscriber(config);
+    if !SUBSCRIBER_INSTALLED.load(Ordering::SeqCst) || OTEL_LAYER.get().is_some() {
         info!("A tracing subscriber is already set, skipping initialization");
         return Ok(());
     }
@@ -149,6 +264,11 @@ pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
         error!("Failed to build OpenTelemetry span exporter: {}", e);
         anyhow::anyhow!("OpenTelemetry span exporter build failed: {}", e)
     })?;
+    let exporter = breaker::BreakerSpanExporter::new(
+        exporter,
+        breaker::shared(&config.export_breaker),
+        config.export_breaker.buffered_spans,
+    );
 
     // Create tracer provider with the exporter
     let tracer_provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
@@ -174,52 +294,47 @@ pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
     // Initialize direct OpenTelemetry tracer for precise attribute control
     otel_direct::init_direct_tracer(Arc::new(tracer_provider));
 
-    // Initialize tracing subscriber with OpenTelemetry layer
-    let telemetry_layer = tracing_opentelemetry::layer().with_tracer(tracer);
-
-    let subscriber = Registry::default()
-        .with(tracing_subscriber::EnvFilter::new(&config.log_level))
-        .with(telemetry_layer);
-
-    if config.log_format == "json" {
-        let _ = subscriber
-            .with(tracing_subscriber::fmt::layer().json())
-            .try_init();
-    } else {
-        let _ = subscriber.with(tracing_subscriber::fmt::layer()).try_init();
-    }
+    // Start exporting spans through the subscriber installed up front.
+    let _ = OTEL_LAYER.set(tracing_opentelemetry::layer().with_tracer(tracer));
 
     info!("OpenTelemetry tracing initialized successfully");
     Ok(())
 }
 
+/// Installs the log subscriber. Does nothing when telemetry is disabled or a
+/// subscriber is set.
 #[cfg(not(feature = "otel"))]
-pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
-    if !config.enabled {
-        info!("Tracing is disabled");
-        return Ok(());
-    }
-
-    info!("Starting basic tracing initialization (OpenTelemetry feature not enabled)");
-
-    // Check if a subscriber is already set
+pub fn install_subscriber(config: &TelemetryConfig) {
     use tracing::dispatcher::has_been_set;
-    if has_been_set() {
-        info!("A tracing subscriber is already set, skipping initialization");
-        return Ok(());
+    if !config.enabled || has_been_set() {
+        return;
     }
 
     let subscriber =
         Registry::default().with(tracing_subscriber::EnvFilter::new(&config.log_level));
 
-    if config.log_format == "json" {
-        let _ = subscriber
+    let installed = match config.log_format.as_str() {
+        "json" => subscriber
             .with(tracing_subscriber::fmt::layer().json())
-            .try_init();
-    } else {
-        let _ = subscriber.with(tracing_subscriber::fmt::layer()).try_init();
+            .try_init(),
+        "pretty" => subscriber
+            .with(crate::telemetry::pretty::layer())
+            .try_init(),
+        _ => subscriber.with(tracing_subscriber::fmt::layer()).try_init(),
+    };
+    if installed.is_ok() {
+        SUBSCRIBER_INSTALLED.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+pub async fn init_tracing(config: &TelemetryConfig) -> anyhow::Result<()> {
+    if !config.enabled {
+        info!("Tracing is disabled");
+        return Ok(());
     }
 
+    install_subscriber(config);
     info!("Basic tracing initialized successfully");
     Ok(())
 }
@@ -289,7 +404,6 @@ where
                 "http.request".to_string(),
                 method.clone(),
                 path.clone(),
-                path.clone(),
                 &parent_cx,
             ) {
                 Some(span) => {
@@ -307,7 +421,7 @@ where
                         "http.request",
                         http.method = %method,
                         http.target = %path,
-                        http.route = %path,
+                        http.route = tracing::field::Empty,
                         span.kind = "server",
                     );
 
@@ -322,6 +436,7 @@ where
                     let response = service.call(req).await?;
                     let status = response.status().as_u16();
 
+                    span.record(attributes::http::ROUTE, matched_route(&response));
                     span.record(attributes::http::RESPONSE_STATUS_CODE, status);
 
                     if (200..300).contains(&status) {
@@ -338,13 +453,22 @@ where
                 }
             };
 
-            let response = service.call(req).await?;
+            // Dropped together with this future when the client disconnects.
+            let pending = otel_direct::PendingServerSpan::new(direct_span);
+            let response = match service.call(req).await {
+                Ok(response) => response,
+                Err(e) => {
+                    drop(pending.finish());
+                    return Err(e);
+                }
+            };
 
             let status = response.status().as_u16();
 
             // Set HTTP response status code using direct OpenTelemetry API.
             // This ensures the correct semantic convention name is used.
-            let mut direct_span_mut = direct_span;
+            let mut direct_span_mut = pending.finish();
+            otel_direct::set_http_route(&mut direct_span_mut, &matched_route(&response));
             tracing::debug!(
                 "[TELEMETRY DEBUG] Setting HTTP response status code: {}",
                 status
@@ -370,6 +494,15 @@ where
     }
 }
 
+/// The route to label a request's span with: the endpoint pattern the handler
+/// attached, else the pattern of the admin route that served it.
+fn matched_route<B>(response: &ServiceResponse<B>) -> String {
+    match response.response().extensions().get::<MatchedRoute>() {
+        Some(MatchedRoute(route)) => route.clone(),
+        None => routes::route_label(response.request().match_pattern().as_deref()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -520,6 +653,9 @@ mod tests {
             timeout_seconds: 30,
             export_batch_size: 512,
             export_timeout_millis: 30000,
+            max_route_labels: 100,
+            statsd: None,
+            export_breaker: Default::default(),
         };
 
         let result = init_tracing(&config).await;
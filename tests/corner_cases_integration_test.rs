@@ -1,42 +1,41 @@
 use actix_web::{test, web, App};
-use molock::config::types::{Config, Endpoint, Response, ServerConfig};
+use molock::config::types::{Config, Endpoint, Fault, HeaderList, Response, ServerConfig};
 use molock::rules::RuleEngine;
 use molock::server::app::AppState;
-use std::collections::HashMap;
 use std::sync::Arc;
 
 #[actix_web::test]
 async fn test_integration_path_normalization() {
-    let mut config = Config::default();
-    config.server = ServerConfig {
-        host: "127.0.0.1".to_string(),
-        port: 8080,
-        workers: 1,
-        max_request_size: 1024 * 1024,
-    };
-
-    config.endpoints = vec![Endpoint {
-        name: "Test".to_string(),
-        method: "GET".to_string(),
-        path: "/api/users".to_string(),
-        stateful: false,
-        state_key: None,
-        responses: vec![Response {
-            status: 200,
-            delay: None,
-            body: Some("OK".to_string()),
-            headers: HashMap::new(),
-            condition: None,
-            probability: None,
-            default: false,
+    let config = Config {
+        server: ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            workers: 1,
+            max_request_size: 1024 * 1024,
+            ..Default::default()
+        },
+        endpoints: vec![Endpoint {
+            name: "Test".to_string(),
+            method: "GET".to_string(),
+            path: "/api/users".to_string(),
+            stateful: false,
+            state_key: None,
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: Some("OK".to_string()),
+                condition: None,
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            ..Default::default()
         }],
-    }];
+        ..Default::default()
+    };
 
     let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
-    let app_state = web::Data::new(AppState {
-        _config: config.clone(),
-        rule_engine: rule_engine.clone(),
-    });
+    let app_state = web::Data::new(AppState::new(config.clone(), rule_engine.clone()));
 
     let app = test::init_service(
         App::new()
@@ -58,47 +57,48 @@ async fn test_integration_path_normalization() {
 
 #[actix_web::test]
 async fn test_integration_precedence() {
-    let mut config = Config::default();
-    config.endpoints = vec![
-        Endpoint {
-            name: "Wildcard".to_string(),
-            method: "GET".to_string(),
-            path: "/api/*".to_string(),
-            stateful: false,
-            state_key: None,
-            responses: vec![Response {
-                status: 200,
-                delay: None,
-                body: Some("Wildcard".to_string()),
-                headers: HashMap::new(),
-                condition: None,
-                probability: None,
-                default: false,
-            }],
-        },
-        Endpoint {
-            name: "Static".to_string(),
-            method: "GET".to_string(),
-            path: "/api/users".to_string(),
-            stateful: false,
-            state_key: None,
-            responses: vec![Response {
-                status: 200,
-                delay: None,
-                body: Some("Static".to_string()),
-                headers: HashMap::new(),
-                condition: None,
-                probability: None,
-                default: false,
-            }],
-        },
-    ];
+    let config = Config {
+        endpoints: vec![
+            Endpoint {
+                name: "Wildcard".to_string(),
+                method: "GET".to_string(),
+                path: "/api/*".to_string(),
+                stateful: false,
+                state_key: None,
+                responses: vec![Response {
+                    status: 200,
+                    delay: None,
+                    body: Some("Wildcard".to_string()),
+                    condition: None,
+                    probability: None,
+                    default: false,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            Endpoint {
+                name: "Static".to_string(),
+                method: "GET".to_string(),
+                path: "/api/users".to_string(),
+                stateful: false,
+                state_key: None,
+                responses: vec![Response {
+                    status: 200,
+                    delay: None,
+                    body: Some("Static".to_string()),
+                    condition: None,
+                    probability: None,
+                    default: false,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
 
     let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
-    let app_state = web::Data::new(AppState {
-        _config: config.clone(),
-        rule_engine,
-    });
+    let app_state = web::Data::new(AppState::new(config.clone(), rule_engine));
 
     let app = test::init_service(
         App::new()
@@ -120,10 +120,7 @@ async fn test_integration_precedence() {
 async fn test_integration_invalid_utf8_body() {
     let config = Config::default();
     let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
-    let app_state = web::Data::new(AppState {
-        _config: config,
-        rule_engine,
-    });
+    let app_state = web::Data::new(AppState::new(config, rule_engine));
 
     let app = test::init_service(
         App::new()
@@ -141,3 +138,440 @@ async fn test_integration_invalid_utf8_body() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), 400);
 }
+
+#[actix_web::test]
+async fn test_integration_debug_matched_header() {
+    let mut config = Config {
+        endpoints: vec![Endpoint {
+            name: "Orders".to_string(),
+            method: "GET".to_string(),
+            path: "/orders".to_string(),
+            stateful: false,
+            state_key: None,
+            responses: vec![Response {
+                status: 200,
+                delay: None,
+                body: Some("OK".to_string()),
+                condition: None,
+                probability: None,
+                default: false,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Header is opt-in
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config.clone(), rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/orders").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.headers().get("X-Molock-Matched").is_none());
+
+    config.server.debug_headers = true;
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/orders").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("X-Molock-Matched").unwrap(),
+        "endpoint=Orders;response=0;reason=unconditional"
+    );
+}
+
+#[actix_web::test]
+async fn test_integration_gzip_plain_fault() {
+    let config = Config {
+        endpoints: vec![Endpoint {
+            name: "Broken".to_string(),
+            method: "GET".to_string(),
+            path: "/broken".to_string(),
+            responses: vec![Response {
+                status: 200,
+                body: Some("not gzip".to_string()),
+                fault: Some(Fault::GzipPlain),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/broken").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("Content-Encoding").unwrap(), "gzip");
+    assert_eq!(test::read_body(resp).await, "not gzip");
+}
+
+#[actix_web::test]
+async fn test_integration_repeated_headers() {
+    let headers: HeaderList = [
+        ("Set-Cookie", "session=abc; Path=/"),
+        ("Set-Cookie", "theme=dark"),
+        ("Vary", "Accept"),
+    ]
+    .into_iter()
+    .collect();
+    let config = Config {
+        endpoints: vec![Endpoint {
+            name: "Login".to_string(),
+            method: "POST".to_string(),
+            path: "/login".to_string(),
+            responses: vec![Response {
+                status: 200,
+                headers,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let req = test::TestRequest::post().uri("/login").to_request();
+    let resp = test::call_service(&app, req).await;
+    let cookies: Vec<_> = resp.headers().get_all("Set-Cookie").collect();
+    assert_eq!(cookies, vec!["session=abc; Path=/", "theme=dark"]);
+    assert_eq!(resp.headers().get("Vary").unwrap(), "Accept");
+}
+
+#[actix_web::test]
+async fn test_integration_compressed_upload_conditions() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let payload = r#"{"items": [1, 2, 3]}"#;
+    let config = Config {
+        endpoints: vec![Endpoint {
+            name: "Upload".to_string(),
+            method: "POST".to_string(),
+            path: "/upload".to_string(),
+            responses: vec![
+                Response {
+                    status: 201,
+                    condition: Some("content_encoding == gzip".to_string()),
+                    ..Default::default()
+                },
+                Response {
+                    status: 413,
+                    condition: Some("body_size > 1KB".to_string()),
+                    ..Default::default()
+                },
+                Response {
+                    status: 200,
+                    default: true,
+                    ..Default::default()
+                },
+            ],
+            selection: molock::config::SelectionStrategy::FirstMatch,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload.as_bytes()).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/upload")
+        .insert_header(("Content-Encoding", "gzip"))
+        .set_payload(encoder.finish().unwrap())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+    let req = test::TestRequest::post()
+        .uri("/upload")
+        .set_payload("x".repeat(2048))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 413);
+
+    let req = test::TestRequest::post()
+        .uri("/upload")
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_integration_compressed_upload_body_size_matcher() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let config = Config {
+        endpoints: vec![Endpoint {
+            name: "Upload".to_string(),
+            method: "POST".to_string(),
+            path: "/upload".to_string(),
+            responses: vec![
+                Response {
+                    status: 413,
+                    matchers: serde_yaml::from_str("[body_size: {min: 1KB}]").unwrap(),
+                    ..Default::default()
+                },
+                Response {
+                    status: 200,
+                    default: true,
+                    ..Default::default()
+                },
+            ],
+            selection: molock::config::SelectionStrategy::FirstMatch,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    // 4KB of one byte compresses to well under 1KB; the decoded size is matched.
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all("x".repeat(4096).as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(compressed.len() < 1024);
+    let req = test::TestRequest::post()
+        .uri("/upload")
+        .insert_header(("Content-Encoding", "gzip"))
+        .set_payload(compressed)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 413);
+
+    let req = test::TestRequest::post()
+        .uri("/upload")
+        .set_payload("small")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn test_integration_request_decompression_opt_out() {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let payload = r#"{"id": 42, "x": 1}"#;
+    let endpoint = |name: &str, path: &str, raw_body: bool| Endpoint {
+        name: name.to_string(),
+        method: "POST".to_string(),
+        path: path.to_string(),
+        raw_body,
+        responses: vec![
+            Response {
+                status: 201,
+                condition: Some(format!("body_size == {}", payload.len())),
+                ..Default::default()
+            },
+            Response {
+                status: 200,
+                default: true,
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    let config = Config {
+        endpoints: vec![
+            endpoint("Decoded", "/decoded", false),
+            endpoint("Raw", "/raw", true),
+        ],
+        ..Default::default()
+    };
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState::new(config, rule_engine)))
+            .default_service(web::to(molock::server::request_handler)),
+    )
+    .await;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let request = |path: &str, body: Vec<u8>| {
+        test::TestRequest::post()
+            .uri(path)
+            .insert_header(("Content-Encoding", "deflate"))
+            .set_payload(body)
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, request("/decoded", compressed.clone())).await;
+    assert_eq!(resp.status(), 201);
+
+    let resp = test::call_service(&app, request("/raw", compressed)).await;
+    assert_eq!(resp.status(), 200);
+
+    let resp = test::call_service(&app, request("/decoded", b"not deflate".to_vec())).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[actix_web::test]
+async fn test_integration_client_disconnect_cancels_delay() {
+    use molock::config::types::Delay;
+    use molock::telemetry::runtime::snapshot;
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncWriteExt;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = Config {
+        server: ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            workers: 1,
+            ..Default::default()
+        },
+        endpoints: vec![Endpoint {
+            name: "Slow".to_string(),
+            method: "GET".to_string(),
+            path: "/slow".to_string(),
+            responses: vec![Response {
+                status: 200,
+                delay: Some(Delay::Fixed("10s".to_string())),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    config.telemetry.enabled = false;
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let server = molock::server::run_server(config, rule_engine)
+        .await
+        .unwrap();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let wait_for = |done: fn() -> bool| async move {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    wait_for(|| snapshot().in_flight_requests > 0).await;
+
+    // Abort the connection; a mere half-close keeps the request running.
+    let started = Instant::now();
+    stream.set_zero_linger().unwrap();
+    drop(stream);
+    wait_for(|| snapshot().in_flight_requests == 0).await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    handle.stop(false).await;
+}
+
+#[actix_web::test]
+async fn test_integration_content_length_short_fault() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = Config {
+        server: ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            workers: 1,
+            ..Default::default()
+        },
+        endpoints: vec![Endpoint {
+            name: "Short".to_string(),
+            method: "GET".to_string(),
+            path: "/short".to_string(),
+            responses: vec![Response {
+                status: 200,
+                body: Some("0123456789".to_string()),
+                fault: Some(Fault::ContentLengthShort),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    config.telemetry.enabled = false;
+
+    let rule_engine = Arc::new(RuleEngine::new(config.endpoints.clone()));
+    let server = molock::server::run_server(config, rule_engine)
+        .await
+        .unwrap();
+    let handle = server.handle();
+    actix_web::rt::spawn(server);
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET /short HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    // The whole body goes out after a header claiming half of it, then the server closes.
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let response = String::from_utf8(response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains("content-length: 5\r\n"));
+    assert!(!head.contains("transfer-encoding"));
+    assert!(head.contains("connection: close"));
+    assert_eq!(body, "0123456789");
+
+    handle.stop(false).await;
+}
@@ -0,0 +1,82 @@
+use molock::config::ConfigLoader;
+use molock::rules::custom_matchers::{self, Matcher};
+use molock::rules::{ExecutionContext, RuleEngine};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Matches requests whose `X-Tenant` header names the configured tenant `id`.
+struct Tenant;
+
+impl Matcher for Tenant {
+    fn validate(&self, config: &Value) -> anyhow::Result<()> {
+        match config.get("id") {
+            Some(Value::String(_)) => Ok(()),
+            _ => anyhow::bail!("`id` must be a string"),
+        }
+    }
+
+    fn matches(&self, config: &Value, context: &ExecutionContext) -> bool {
+        context.headers.get("x-tenant").map(String::as_str) == config["id"].as_str()
+    }
+}
+
+const CONFIG: &str = r#"
+server:
+  port: 8080
+
+telemetry:
+  enabled: false
+
+endpoints:
+  - name: "Acme Orders"
+    method: GET
+    path: "/orders"
+    matchers:
+      - custom: {kind: tenant, config: {id: acme}}
+    responses:
+      - status: 200
+        body: "acme"
+
+  - name: "Orders"
+    method: GET
+    path: "/orders"
+    responses:
+      - status: 200
+        body: "everyone"
+"#;
+
+#[tokio::test]
+async fn test_registered_matcher_selects_endpoint() {
+    custom_matchers::register("tenant", Tenant);
+
+    let config = ConfigLoader::parse_str(CONFIG).unwrap();
+    let engine = RuleEngine::from_config(&config);
+
+    let tenant = |id: &str| HashMap::from([("x-tenant".to_string(), id.to_string())]);
+    let acme = engine
+        .execute("GET", "/orders", "", &tenant("acme"), None, "127.0.0.1")
+        .await
+        .unwrap();
+    assert_eq!(acme.body.as_deref(), Some("acme"));
+
+    let other = engine
+        .execute("GET", "/orders", "", &tenant("globex"), None, "127.0.0.1")
+        .await
+        .unwrap();
+    assert_eq!(other.body.as_deref(), Some("everyone"));
+
+    let invalid = CONFIG.replace("config: {id: acme}", "config: {id: 42}");
+    assert!(
+        format!("{:#}", ConfigLoader::parse_str(&invalid).unwrap_err())
+            .contains("Invalid config for custom matcher `tenant`: `id` must be a string")
+    );
+}
+
+#[test]
+fn test_unknown_kind_is_rejected() {
+    let config = CONFIG.replace("kind: tenant", "kind: not_registered");
+    assert!(
+        format!("{:#}", ConfigLoader::parse_str(&config).unwrap_err())
+            .contains("Unknown custom matcher kind `not_registered`")
+    );
+}